@@ -0,0 +1,49 @@
+// benches/render.rs
+// Criterion benchmarks on the `--bench-scene` scenes: a frame of every fixed
+// view of each tier, at the same size and with the same sun, textures and
+// default settings as `raytracer-render --bench-scene`, so the numbers stay
+// comparable across changes. `cargo bench -- small` runs one tier.
+use criterion::{criterion_group, criterion_main, Criterion};
+use raylib::prelude::*;
+use raytracer::bench::{bench_light, load_bench_textures};
+use raytracer::camera::Camera;
+use raytracer::config::AssetsConfig;
+use raytracer::render::{render, RenderStats};
+use raytracer::settings::RenderSettings;
+use raytracer::shadow_map::update_sun_shadow;
+use raytracer::worldgen::{benchmark_scene, BenchTier};
+
+const WIDTH: i32 = 640;
+const HEIGHT: i32 = 480;
+
+const TIERS: [BenchTier; 4] = [BenchTier::Small, BenchTier::Medium, BenchTier::Large, BenchTier::Mirrors];
+
+fn render_tiers(c: &mut Criterion) {
+    let settings = RenderSettings::default();
+    let texture_manager = load_bench_textures(&AssetsConfig::default());
+    let mut depth_buffer = vec![f32::INFINITY; (WIDTH * HEIGHT) as usize];
+    let mut sample_counts = vec![1; (WIDTH * HEIGHT) as usize];
+    let mut stats = RenderStats::default();
+    for tier in TIERS {
+        let mut scene = benchmark_scene(tier);
+        scene.set_sah_buckets(settings.bvh_sah_buckets);
+        let light = bench_light(&scene);
+        update_sun_shadow(&mut scene, &light, &settings);
+
+        let mut group = c.benchmark_group(tier.name());
+        // A large-tier frame takes long enough that ten are plenty
+        group.sample_size(10);
+        for bookmark in &scene.bookmarks {
+            let camera = Camera::new(bookmark.eye, bookmark.center, Vector3::new(0.0, 1.0, 0.0));
+            group.bench_function(&bookmark.name, |b| {
+                b.iter(|| {
+                    render(WIDTH, HEIGHT, &scene, &camera, &light, &texture_manager, &settings, &mut depth_buffer, &mut sample_counts, &mut stats)
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, render_tiers);
+criterion_main!(benches);
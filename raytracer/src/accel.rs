@@ -0,0 +1,639 @@
+// accel.rs
+// Traversal helpers built on top of the `bvh` crate's node array, and a
+// parallel build of that array. Rays are `TracerRay`s, made once per ray with
+// their inverse direction, and tested against the crate's AABBs here; the
+// crate's own ray type and the nalgebra vectors it wants never come into it.
+use std::ops::ControlFlow;
+
+use bvh::aabb::{Bounded, AABB};
+use bvh::bounding_hierarchy::BHShape;
+use bvh::bvh::{BVHNode, BVH};
+use nalgebra::Point3;
+use raylib::prelude::*;
+use rayon::prelude::*;
+
+use crate::profile::{self, Counter};
+
+/// Side of the square pixel block traced as one packet.
+pub const PACKET_SIZE: usize = 4;
+/// Lane masks are stored in a `u32`, so a packet holds at most 32 rays.
+pub const MAX_PACKET_RAYS: usize = 32;
+
+/// A ray as the traversal and the cube test use it: the inverse direction
+/// and its signs are worked out once, when the ray is made, instead of on
+/// every box and cube it meets.
+#[derive(Debug, Clone, Copy)]
+pub struct TracerRay {
+    /// Where the ray starts.
+    pub origin: Vector3,
+    /// Its direction, as given; not normalized here.
+    pub dir: Vector3,
+    /// `1 / dir` per axis; infinite on an axis the ray is parallel to.
+    pub inv_dir: Vector3,
+    /// Per axis, 1 when `inv_dir` is negative: which side of a box the ray
+    /// enters by.
+    pub sign: [usize; 3],
+}
+
+impl TracerRay {
+    /// Ray from `origin` along `dir`.
+    pub fn new(origin: Vector3, dir: Vector3) -> Self {
+        let inv_dir = Vector3::new(1.0 / dir.x, 1.0 / dir.y, 1.0 / dir.z);
+        let sign = [inv_dir.x, inv_dir.y, inv_dir.z].map(|inv| (inv < 0.0) as usize);
+        TracerRay { origin, dir, inv_dir, sign }
+    }
+
+    /// Whether the ray reaches `aabb` in front of its origin. The same slab
+    /// test as the `bvh` crate's `Ray::intersects_aabb`, one axis at a time
+    /// with an early out; the crate normalizes the direction first, which
+    /// only scales the distances. A ray lying in the plane of a face gives
+    /// 0 * inf = NaN on that axis; `min`/`max` drop it, so that axis does not
+    /// restrict the ray, the same as in `CubeBounds::slab`.
+    #[inline]
+    pub fn intersects_aabb(&self, aabb: &AABB) -> bool {
+        let bounds = [&aabb.min, &aabb.max];
+        let mut t_min = (bounds[self.sign[0]].x - self.origin.x) * self.inv_dir.x;
+        let mut t_max = (bounds[1 - self.sign[0]].x - self.origin.x) * self.inv_dir.x;
+
+        let y_min = (bounds[self.sign[1]].y - self.origin.y) * self.inv_dir.y;
+        let y_max = (bounds[1 - self.sign[1]].y - self.origin.y) * self.inv_dir.y;
+        if t_min > y_max || y_min > t_max {
+            return false;
+        }
+        t_min = t_min.max(y_min);
+        t_max = t_max.min(y_max);
+
+        let z_min = (bounds[self.sign[2]].z - self.origin.z) * self.inv_dir.z;
+        let z_max = (bounds[1 - self.sign[2]].z - self.origin.z) * self.inv_dir.z;
+        if t_min > z_max || z_min > t_max {
+            return false;
+        }
+        t_max.min(z_max) > 0.0
+    }
+}
+
+/// Conservative interval bounds of a bundle of rays that share an origin.
+/// Used to reject a node for the whole packet before testing every lane.
+struct PacketBounds {
+    origin: [f32; 3],
+    inv_lo: [f32; 3],
+    inv_hi: [f32; 3],
+    // false when the direction interval straddles zero on that axis
+    bounded: [bool; 3],
+}
+
+impl PacketBounds {
+    fn new(origin: &Vector3, directions: &[Vector3]) -> Self {
+        let mut lo = [f32::INFINITY; 3];
+        let mut hi = [f32::NEG_INFINITY; 3];
+        for d in directions {
+            for (axis, value) in [d.x, d.y, d.z].into_iter().enumerate() {
+                lo[axis] = lo[axis].min(value);
+                hi[axis] = hi[axis].max(value);
+            }
+        }
+
+        let mut bounds = PacketBounds {
+            origin: [origin.x, origin.y, origin.z],
+            inv_lo: [0.0; 3],
+            inv_hi: [0.0; 3],
+            bounded: [false; 3],
+        };
+        for axis in 0..3 {
+            if lo[axis] > 0.0 || hi[axis] < 0.0 {
+                // Same sign on the whole interval: 1/x is monotonic
+                bounds.inv_lo[axis] = 1.0 / hi[axis];
+                bounds.inv_hi[axis] = 1.0 / lo[axis];
+                bounds.bounded[axis] = true;
+            }
+        }
+        bounds
+    }
+
+    /// False only if no ray of the packet can hit `aabb` in front of the origin.
+    fn may_hit(&self, aabb: &AABB) -> bool {
+        let min = [aabb.min.x, aabb.min.y, aabb.min.z];
+        let max = [aabb.max.x, aabb.max.y, aabb.max.z];
+        let mut t_near = 0.0f32;
+        let mut t_far = f32::INFINITY;
+        for axis in 0..3 {
+            if !self.bounded[axis] {
+                continue;
+            }
+            let a = min[axis] - self.origin[axis];
+            let b = max[axis] - self.origin[axis];
+            let t = [
+                a * self.inv_lo[axis],
+                a * self.inv_hi[axis],
+                b * self.inv_lo[axis],
+                b * self.inv_hi[axis],
+            ];
+            let t_min = t.iter().copied().fold(f32::INFINITY, f32::min);
+            let t_max = t.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            t_near = t_near.max(t_min);
+            t_far = t_far.min(t_max);
+        }
+        t_near <= t_far
+    }
+}
+
+fn packet_mask(
+    rays: &[TracerRay],
+    bounds: &PacketBounds,
+    #[cfg(feature = "simd")] lanes: &crate::simd::PacketLanes,
+    aabb: &AABB,
+    active: u32,
+) -> u32 {
+    if !bounds.may_hit(aabb) {
+        return 0;
+    }
+    // The vector test settles every lane but the few left to the loop below
+    #[cfg(feature = "simd")]
+    let (mut mask, active) = (lanes.hit_mask(aabb, active), active & lanes.scalar_lanes());
+    #[cfg(not(feature = "simd"))]
+    let mut mask = 0;
+    for (lane, ray) in rays.iter().enumerate() {
+        let bit = 1u32 << lane;
+        if active & bit != 0 && ray.intersects_aabb(aabb) {
+            mask |= bit;
+        }
+    }
+    mask
+}
+
+/// Traverses the BVH once for a packet of rays sharing `origin`.
+///
+/// Fills `candidates` with `(shape_index, lane_mask)` pairs: bit `i` of the
+/// mask is set when ray `i` reached that leaf. Leaves come out in the same
+/// left-first order as `BVH::traverse`, and the per-lane tests are the same
+/// AABB tests, so each lane sees exactly the candidates a single-ray
+/// traversal would produce. With the `simd` feature the per-lane tests run
+/// eight lanes at a time; see `simd`.
+pub fn traverse_packet(
+    bvh: &BVH,
+    origin: &Vector3,
+    directions: &[Vector3],
+    candidates: &mut Vec<(usize, u32)>,
+) {
+    candidates.clear();
+    if bvh.nodes.is_empty() || directions.is_empty() {
+        return;
+    }
+    assert!(directions.len() <= MAX_PACKET_RAYS, "packet too large");
+
+    let rays: Vec<TracerRay> = directions.iter().map(|d| TracerRay::new(*origin, *d)).collect();
+    let bounds = PacketBounds::new(origin, directions);
+    #[cfg(feature = "simd")]
+    let lanes = crate::simd::PacketLanes::new(origin, directions);
+    let all_lanes = if directions.len() == MAX_PACKET_RAYS {
+        u32::MAX
+    } else {
+        (1u32 << directions.len()) - 1
+    };
+
+    let mut stack: Vec<(usize, u32)> = vec![(0, all_lanes)];
+    while let Some((index, mask)) = stack.pop() {
+        profile::count(Counter::NodeVisits, 1);
+        match &bvh.nodes[index] {
+            BVHNode::Leaf { shape_index, .. } => candidates.push((*shape_index, mask)),
+            BVHNode::Node {
+                child_l_index,
+                child_l_aabb,
+                child_r_index,
+                child_r_aabb,
+                ..
+            } => {
+                // Right is pushed first so the left subtree is visited first
+                let right = packet_mask(
+                    &rays,
+                    &bounds,
+                    #[cfg(feature = "simd")]
+                    &lanes,
+                    child_r_aabb,
+                    mask,
+                );
+                if right != 0 {
+                    stack.push((*child_r_index, right));
+                }
+                let left = packet_mask(
+                    &rays,
+                    &bounds,
+                    #[cfg(feature = "simd")]
+                    &lanes,
+                    child_l_aabb,
+                    mask,
+                );
+                if left != 0 {
+                    stack.push((*child_l_index, left));
+                }
+            }
+        }
+    }
+}
+
+/// `RopeNode::entry` of a leaf.
+pub const NO_CHILD: u32 = u32::MAX;
+
+/// One node of a `RopeBvh`.
+#[derive(Debug, Clone, Copy)]
+pub struct RopeNode {
+    /// Bounds of the node's subtree.
+    pub aabb: AABB,
+    /// First child (next in depth-first order), NO_CHILD for leaves.
+    pub entry: u32,
+    /// Next node to visit once this subtree is done or missed.
+    pub exit: u32,
+    /// Only meaningful for leaves.
+    pub shape_index: u32,
+}
+
+/// The BVH flattened in depth-first order with skip pointers ("ropes").
+///
+/// Traversal is a single loop with no stack: on a hit go to `entry`, on a
+/// miss or after a leaf go to `exit`. Nodes keep the crate's AABBs and its
+/// left-first order, so the candidates match `BVH::traverse` exactly.
+pub struct RopeBvh {
+    nodes: Vec<RopeNode>,
+}
+
+impl RopeBvh {
+    /// Flattens a built BVH; the BVH itself is not kept.
+    pub fn from_bvh(bvh: &BVH) -> Self {
+        let mut nodes = Vec::with_capacity(bvh.nodes.len());
+        match bvh.nodes.first() {
+            None => {}
+            Some(BVHNode::Leaf { shape_index, .. }) => {
+                // A single shape: the root has no stored AABB, so always visit it
+                let everything = AABB::with_bounds(
+                    Point3::new(f32::MIN, f32::MIN, f32::MIN),
+                    Point3::new(f32::MAX, f32::MAX, f32::MAX),
+                );
+                nodes.push(RopeNode { aabb: everything, entry: NO_CHILD, exit: 1, shape_index: *shape_index as u32 });
+            }
+            Some(BVHNode::Node { child_l_index, child_l_aabb, child_r_index, child_r_aabb, .. }) => {
+                // As in the crate, the root itself is never tested
+                Self::flatten(&bvh.nodes, *child_l_index, *child_l_aabb, &mut nodes);
+                Self::flatten(&bvh.nodes, *child_r_index, *child_r_aabb, &mut nodes);
+            }
+        }
+        RopeBvh { nodes }
+    }
+
+    fn flatten(bvh_nodes: &[BVHNode], index: usize, aabb: AABB, out: &mut Vec<RopeNode>) {
+        let slot = out.len();
+        out.push(RopeNode { aabb, entry: NO_CHILD, exit: 0, shape_index: 0 });
+        match &bvh_nodes[index] {
+            BVHNode::Leaf { shape_index, .. } => out[slot].shape_index = *shape_index as u32,
+            BVHNode::Node { child_l_index, child_l_aabb, child_r_index, child_r_aabb, .. } => {
+                out[slot].entry = (slot + 1) as u32;
+                Self::flatten(bvh_nodes, *child_l_index, *child_l_aabb, out);
+                Self::flatten(bvh_nodes, *child_r_index, *child_r_aabb, out);
+            }
+        }
+        out[slot].exit = out.len() as u32;
+    }
+
+    /// The flattened nodes, e.g. for uploading to the GPU.
+    pub fn nodes(&self) -> &[RopeNode] {
+        &self.nodes
+    }
+
+    /// Calls `visit` with the index of every shape whose leaf the ray reaches,
+    /// in the order `BVH::traverse` returns them, without allocating. Stops
+    /// as soon as `visit` breaks, e.g. once a shadow ray finds a blocker.
+    pub fn visit(&self, ray: &TracerRay, visit: impl FnMut(usize) -> ControlFlow<()>) -> ControlFlow<()> {
+        self.visit_leaves(ray, 1, visit)
+    }
+
+    /// Like `visit`, but a subtree of at most `max_leaf_size` shapes is
+    /// treated as one leaf: once the ray reaches its root every shape in it is
+    /// visited, in the same order, without testing the boxes inside. Fewer
+    /// box tests for more shape tests; a subtree of n shapes takes the 2n - 1
+    /// nodes after its root, so its leaves are found without a stack.
+    pub fn visit_leaves(
+        &self,
+        ray: &TracerRay,
+        max_leaf_size: usize,
+        mut visit: impl FnMut(usize) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let leaf_nodes = 2 * max_leaf_size.max(1) - 1;
+        let mut index = 0;
+        while index < self.nodes.len() {
+            let node = &self.nodes[index];
+            profile::count(Counter::NodeVisits, 1);
+            if !ray.intersects_aabb(&node.aabb) {
+                index = node.exit as usize;
+            } else if node.entry == NO_CHILD {
+                visit(node.shape_index as usize)?;
+                index = node.exit as usize;
+            } else if node.exit as usize - index <= leaf_nodes {
+                for inner in &self.nodes[index + 1..node.exit as usize] {
+                    if inner.entry == NO_CHILD {
+                        visit(inner.shape_index as usize)?;
+                    }
+                }
+                index = node.exit as usize;
+            } else {
+                index = node.entry as usize;
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Same contract as `BVH::traverse`: every shape whose leaf the ray reaches.
+    pub fn traverse<'a, T: Bounded>(&self, ray: &TracerRay, shapes: &'a [T]) -> Vec<&'a T> {
+        let mut hit_shapes = Vec::new();
+        let _ = self.visit(ray, |shape| {
+            hit_shapes.push(&shapes[shape]);
+            ControlFlow::Continue(())
+        });
+        hit_shapes
+    }
+
+    /// Like `traverse`, but returns shape indices for callers that need to edit.
+    pub fn traverse_indices(&self, ray: &TracerRay) -> Vec<usize> {
+        let mut hit_shapes = Vec::new();
+        let _ = self.visit(ray, |shape| {
+            hit_shapes.push(shape);
+            ControlFlow::Continue(())
+        });
+        hit_shapes
+    }
+}
+
+/// Pending nodes `visit_bvh` keeps on the stack. Each level of the tree
+/// leaves at most one right child waiting, so this covers trees 64 levels
+/// deep; deeper subtrees are walked by recursion instead.
+const STACK_DEPTH: usize = 64;
+
+/// `RopeBvh::visit` over the crate's own node array, with a fixed stack in
+/// place of the `Vec` that `BVH::traverse` fills.
+pub fn visit_bvh(bvh: &BVH, ray: &TracerRay, mut visit: impl FnMut(usize) -> ControlFlow<()>) -> ControlFlow<()> {
+    if bvh.nodes.is_empty() {
+        return ControlFlow::Continue(());
+    }
+    // As in the crate, the root itself is never tested
+    visit_subtree(&bvh.nodes, 0, ray, &mut visit)
+}
+
+fn visit_subtree(
+    nodes: &[BVHNode],
+    root: usize,
+    ray: &TracerRay,
+    visit: &mut impl FnMut(usize) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    let mut stack = [0usize; STACK_DEPTH];
+    stack[0] = root;
+    let mut len = 1;
+    while len > 0 {
+        len -= 1;
+        profile::count(Counter::NodeVisits, 1);
+        match &nodes[stack[len]] {
+            BVHNode::Leaf { shape_index, .. } => visit(*shape_index)?,
+            BVHNode::Node { child_l_index, child_l_aabb, child_r_index, child_r_aabb, .. } => {
+                let left = ray.intersects_aabb(child_l_aabb);
+                let right = ray.intersects_aabb(child_r_aabb);
+                if len + 2 > STACK_DEPTH {
+                    // Out of room: finish this node's children here, left first
+                    if left {
+                        visit_subtree(nodes, *child_l_index, ray, visit)?;
+                    }
+                    if right {
+                        visit_subtree(nodes, *child_r_index, ray, visit)?;
+                    }
+                    continue;
+                }
+                // Right is pushed first so the left subtree is visited first
+                if right {
+                    stack[len] = *child_r_index;
+                    len += 1;
+                }
+                if left {
+                    stack[len] = *child_l_index;
+                    len += 1;
+                }
+            }
+        }
+    }
+    ControlFlow::Continue(())
+}
+
+/// Below this many shapes a node's two subtrees are built one after the other.
+const PARALLEL_BUILD_MIN: usize = 4096;
+/// SAH buckets per split, as in the crate.
+pub const SAH_BUCKETS: usize = 6;
+/// Most buckets `build_bvh_with` takes.
+pub const MAX_SAH_BUCKETS: usize = 32;
+/// The crate's `EPSILON`: centers closer than this along every axis are split in half.
+const SPLIT_EPSILON: f32 = 0.00001;
+
+/// Bounds as plain arrays for the build; `EMPTY` is inside out, like `AABB::empty`.
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+impl Bounds {
+    const EMPTY: Bounds = Bounds { min: [f32::INFINITY; 3], max: [f32::NEG_INFINITY; 3] };
+
+    fn join(self, other: Bounds) -> Bounds {
+        Bounds {
+            min: std::array::from_fn(|axis| self.min[axis].min(other.min[axis])),
+            max: std::array::from_fn(|axis| self.max[axis].max(other.max[axis])),
+        }
+    }
+
+    fn grow(self, point: [f32; 3]) -> Bounds {
+        self.join(Bounds { min: point, max: point })
+    }
+
+    fn size(&self) -> [f32; 3] {
+        std::array::from_fn(|axis| self.max[axis] - self.min[axis])
+    }
+
+    fn surface_area(&self) -> f32 {
+        let [x, y, z] = self.size();
+        2.0 * (x * y + x * z + y * z)
+    }
+
+    // Ties go to the later axis, as in the crate
+    fn largest_axis(&self) -> usize {
+        let [x, y, z] = self.size();
+        if x > y && x > z {
+            0
+        } else if y > z {
+            1
+        } else {
+            2
+        }
+    }
+
+    fn to_aabb(self) -> AABB {
+        let [min, max] = [self.min, self.max].map(|[x, y, z]| Point3::new(x, y, z));
+        AABB::with_bounds(min, max)
+    }
+}
+
+/// A shape's bounds and their center, read once before the build.
+#[derive(Debug, Clone, Copy)]
+struct BuildShape {
+    bounds: Bounds,
+    center: [f32; 3],
+}
+
+/// Builds the tree `BVH::build` builds, node for node and in the same order,
+/// with the two subtrees of every large node built in parallel on the current
+/// rayon pool. The crate builds on one thread, which stalls a 100k-block
+/// world for seconds. Each split is the crate's: binned SAH over six buckets
+/// along the axis the centers spread most, or halving the list when they all
+/// sit at one point; the arithmetic is the same too, so the bounds come out
+/// bit for bit. Every shape is told its leaf, as the crate does.
+pub fn build_bvh<T: BHShape + Sync>(shapes: &mut [T]) -> BVH {
+    build_bvh_with(shapes, SAH_BUCKETS)
+}
+
+/// `build_bvh` with `sah_buckets` SAH buckets per split instead of the
+/// crate's six, from 2 to `MAX_SAH_BUCKETS`. More buckets try more split
+/// planes, which costs build time and may or may not pay off in traversal.
+pub fn build_bvh_with<T: BHShape + Sync>(shapes: &mut [T], sah_buckets: usize) -> BVH {
+    let sah_buckets = sah_buckets.clamp(2, MAX_SAH_BUCKETS);
+    if shapes.is_empty() {
+        return BVH { nodes: Vec::new() };
+    }
+    let prepared: Vec<BuildShape> = shapes
+        .par_iter()
+        .map(|shape| {
+            let aabb = shape.aabb();
+            let bounds = Bounds { min: [aabb.min.x, aabb.min.y, aabb.min.z], max: [aabb.max.x, aabb.max.y, aabb.max.z] };
+            let size = bounds.size();
+            BuildShape { bounds, center: std::array::from_fn(|axis| bounds.min[axis] + size[axis] / 2.0) }
+        })
+        .collect();
+    // One leaf per shape, so exactly 2n - 1 nodes
+    let placeholder = BVHNode::Leaf { parent_index: 0, depth: 0, shape_index: 0 };
+    let mut nodes = vec![placeholder; 2 * shapes.len() - 1];
+    let indices: Vec<usize> = (0..shapes.len()).collect();
+    build_subtree(&prepared, &indices, sah_buckets, &mut nodes, 0, 0, 0);
+    for (node_index, node) in nodes.iter().enumerate() {
+        if let BVHNode::Leaf { shape_index, .. } = node {
+            shapes[*shape_index].set_bh_node_index(node_index);
+        }
+    }
+    BVH { nodes }
+}
+
+/// Writes the subtree over `indices` to `out`, whose first node is `base` in
+/// the whole tree. A subtree of n shapes takes 2n - 1 nodes, so once a node is
+/// split both children know their place and can be built at the same time.
+fn build_subtree(
+    shapes: &[BuildShape],
+    indices: &[usize],
+    sah_buckets: usize,
+    out: &mut [BVHNode],
+    base: usize,
+    parent_index: usize,
+    depth: u32,
+) {
+    if let [shape_index] = indices {
+        out[0] = BVHNode::Leaf { parent_index, depth, shape_index: *shape_index };
+        return;
+    }
+    let (bounds, centers) = indices.iter().fold((Bounds::EMPTY, Bounds::EMPTY), |(bounds, centers), &i| {
+        (bounds.join(shapes[i].bounds), centers.grow(shapes[i].center))
+    });
+    let axis = centers.largest_axis();
+    let axis_size = centers.max[axis] - centers.min[axis];
+
+    let joint = |indices: &[usize]| indices.iter().fold(Bounds::EMPTY, |bounds, &i| bounds.join(shapes[i].bounds));
+    let (left, left_bounds, right, right_bounds) = if axis_size < SPLIT_EPSILON {
+        let (left, right) = indices.split_at(indices.len() / 2);
+        (left.to_vec(), joint(left), right.to_vec(), joint(right))
+    } else {
+        let mut buckets = [(0usize, Bounds::EMPTY); MAX_SAH_BUCKETS];
+        let mut assigned: [Vec<usize>; MAX_SAH_BUCKETS] = Default::default();
+        let (buckets, assigned) = (&mut buckets[..sah_buckets], &mut assigned[..sah_buckets]);
+        for &i in indices {
+            let relative = (shapes[i].center[axis] - centers.min[axis]) / axis_size;
+            let bucket = (relative * (sah_buckets as f32 - 0.01)) as usize;
+            buckets[bucket] = (buckets[bucket].0 + 1, buckets[bucket].1.join(shapes[i].bounds));
+            assigned[bucket].push(i);
+        }
+        let side = |buckets: &[(usize, Bounds)]| {
+            buckets.iter().fold((0, Bounds::EMPTY), |(count, bounds), (n, b)| (count + n, bounds.join(*b)))
+        };
+        let (mut best, mut best_cost) = (0, f32::INFINITY);
+        let (mut left_bounds, mut right_bounds) = (Bounds::EMPTY, Bounds::EMPTY);
+        for split in 0..sah_buckets - 1 {
+            let ((left_count, left), (right_count, right)) = (side(&buckets[..=split]), side(&buckets[split + 1..]));
+            let cost = (left_count as f32 * left.surface_area() + right_count as f32 * right.surface_area())
+                / bounds.surface_area();
+            if cost < best_cost {
+                (best, best_cost, left_bounds, right_bounds) = (split, cost, left, right);
+            }
+        }
+        (assigned[..=best].concat(), left_bounds, assigned[best + 1..].concat(), right_bounds)
+    };
+
+    let (node, children) = out.split_first_mut().expect("a split node has room for itself");
+    let (left_out, right_out) = children.split_at_mut(2 * left.len() - 1);
+    let (left_index, right_index) = (base + 1, base + 2 * left.len());
+    if indices.len() >= PARALLEL_BUILD_MIN {
+        rayon::join(
+            || build_subtree(shapes, &left, sah_buckets, left_out, left_index, base, depth + 1),
+            || build_subtree(shapes, &right, sah_buckets, right_out, right_index, base, depth + 1),
+        );
+    } else {
+        build_subtree(shapes, &left, sah_buckets, left_out, left_index, base, depth + 1);
+        build_subtree(shapes, &right, sah_buckets, right_out, right_index, base, depth + 1);
+    }
+    *node = BVHNode::Node {
+        parent_index,
+        depth,
+        child_l_index: left_index,
+        child_l_aabb: left_bounds.to_aabb(),
+        child_r_index: right_index,
+        child_r_aabb: right_bounds.to_aabb(),
+    };
+}
+
+/// Shape of a built tree, for comparing build options.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TreeStats {
+    /// Leaves, counting a subtree of at most `max_leaf_size` shapes as one.
+    pub leaves: usize,
+    /// Deepest leaf, the root being at depth 0.
+    pub max_depth: u32,
+    /// Depth of the average leaf.
+    pub mean_depth: f64,
+    /// Shapes in the average leaf.
+    pub mean_leaf_size: f64,
+}
+
+/// Depth and leaf occupancy of `bvh` as `RopeBvh::visit_leaves` walks it
+/// with `max_leaf_size`.
+pub fn tree_stats(bvh: &BVH, max_leaf_size: usize) -> TreeStats {
+    if bvh.nodes.is_empty() {
+        return TreeStats::default();
+    }
+    // Per leaf its depth; a subtree of n shapes takes 2n - 1 nodes
+    let mut depths = Vec::new();
+    let mut pending = vec![(0usize, bvh.nodes.len(), 0u32)];
+    while let Some((index, span, depth)) = pending.pop() {
+        match &bvh.nodes[index] {
+            BVHNode::Node { child_l_index, child_r_index, .. } if span.div_ceil(2) > max_leaf_size.max(1) => {
+                let left_span = child_r_index - child_l_index;
+                pending.push((*child_l_index, left_span, depth + 1));
+                pending.push((*child_r_index, span - 1 - left_span, depth + 1));
+            }
+            _ => depths.push(depth),
+        }
+    }
+    let leaves = depths.len();
+    TreeStats {
+        leaves,
+        max_depth: depths.iter().copied().max().unwrap_or(0),
+        mean_depth: depths.iter().map(|&depth| depth as f64).sum::<f64>() / leaves as f64,
+        mean_leaf_size: bvh.nodes.len().div_ceil(2) as f64 / leaves as f64,
+    }
+}
@@ -0,0 +1,66 @@
+// antialias.rs
+// Edge detection for selective supersampling. In this scene aliasing lives on
+// cube silhouettes and creases, so only pixels whose depth or normal jumps
+// relative to a neighbour get extra samples.
+use raylib::prelude::*;
+
+// Relative depth jump that counts as a silhouette
+const DEPTH_THRESHOLD: f32 = 0.1;
+// Normals closer than this (cosine) are the same face orientation
+const NORMAL_THRESHOLD: f32 = 0.9;
+
+fn is_discontinuity(depth_a: f32, depth_b: f32, normal_a: &Vector3, normal_b: &Vector3) -> bool {
+    // Hit next to sky
+    if depth_a.is_finite() != depth_b.is_finite() {
+        return true;
+    }
+    if !depth_a.is_finite() {
+        return false;
+    }
+    (depth_a - depth_b).abs() > DEPTH_THRESHOLD * depth_a.min(depth_b)
+        || normal_a.dot(*normal_b) < NORMAL_THRESHOLD
+}
+
+/// Whether two hits, both with a finite depth, are close enough in depth and
+/// normal to be on one surface by the edge pass's thresholds.
+pub fn same_surface(depth_a: f32, depth_b: f32, normal_a: &Vector3, normal_b: &Vector3) -> bool {
+    depth_a.is_finite() && depth_b.is_finite() && !is_discontinuity(depth_a, depth_b, normal_a, normal_b)
+}
+
+/// Marks pixels on a depth or normal discontinuity, dilated by one pixel so the
+/// supersampling covers both sides of every edge.
+pub fn edge_mask(depth: &[f32], normals: &[Vector3], width: i32, height: i32) -> Vec<bool> {
+    let index = |x: i32, y: i32| (y * width + x) as usize;
+    let mut edges = vec![false; depth.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let i = index(x, y);
+            // Right and down neighbours; the pair is marked on both sides
+            for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                if nx >= width || ny >= height {
+                    continue;
+                }
+                let j = index(nx, ny);
+                if is_discontinuity(depth[i], depth[j], &normals[i], &normals[j]) {
+                    edges[i] = true;
+                    edges[j] = true;
+                }
+            }
+        }
+    }
+
+    let mut dilated = edges.clone();
+    for y in 0..height {
+        for x in 0..width {
+            if !edges[index(x, y)] {
+                continue;
+            }
+            for ny in (y - 1).max(0)..=(y + 1).min(height - 1) {
+                for nx in (x - 1).max(0)..=(x + 1).min(width - 1) {
+                    dilated[index(nx, ny)] = true;
+                }
+            }
+        }
+    }
+    dilated
+}
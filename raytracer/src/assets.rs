@@ -0,0 +1,107 @@
+// assets.rs
+// Where files named `assets/...` are read from. Scene files and the code name
+// textures by that path, and the file is looked up in a list of directories:
+// `--assets-dir`, `[assets] dir`, an `assets` folder next to the executable
+// and, in debug builds, the crate's own `assets`. The block textures are also
+// compiled into the binary, so a missing folder leaves the world textured; any
+// other missing texture becomes the placeholder checkerboard, and a missing
+// skybox the procedural sky, neither of which needs a file.
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::AssetsConfig;
+use crate::error::Error;
+
+// The block textures, by the path they are loaded under
+const EMBEDDED: &[(&str, &[u8])] = &[
+    ("assets/grass.png", include_bytes!("../assets/grass.png")),
+    ("assets/glass.png", include_bytes!("../assets/glass.png")),
+    ("assets/magma.png", include_bytes!("../assets/magma.png")),
+    ("assets/diamond_ore.png", include_bytes!("../assets/diamond_ore.png")),
+    ("assets/oak.png", include_bytes!("../assets/oak.png")),
+    ("assets/wood_planks.png", include_bytes!("../assets/wood_planks.png")),
+    ("assets/stone.png", include_bytes!("../assets/stone.png")),
+    ("assets/obsidian.png", include_bytes!("../assets/obsidian.png")),
+    ("assets/water.png", include_bytes!("../assets/water.png")),
+    ("assets/leaves.png", include_bytes!("../assets/leaves.png")),
+    ("assets/dirt.png", include_bytes!("../assets/dirt.png")),
+];
+
+/// The copy of `path` compiled into the binary, if it is one of the block
+/// textures.
+pub fn embedded(path: &str) -> Option<&'static [u8]> {
+    EMBEDDED.iter().find(|(name, _)| *name == path).map(|(_, bytes)| *bytes)
+}
+
+/// The directories `assets/...` paths are searched in, first match wins.
+#[derive(Debug, Clone)]
+pub struct Assets {
+    dirs: Vec<PathBuf>,
+}
+
+impl Assets {
+    /// `--assets-dir`, `[assets] dir`, `assets` next to the executable and, in
+    /// debug builds, the crate's `assets`; repeats are dropped.
+    pub fn new(config: &AssetsConfig) -> Self {
+        let mut dirs: Vec<PathBuf> = config.cli_dir.iter().cloned().collect();
+        dirs.push(config.dir.clone());
+        if let Some(exe_dir) = std::env::current_exe().ok().as_deref().and_then(Path::parent) {
+            dirs.push(exe_dir.join("assets"));
+        }
+        if cfg!(debug_assertions) {
+            dirs.push(Path::new(env!("CARGO_MANIFEST_DIR")).join("assets"));
+        }
+        let mut unique: Vec<PathBuf> = Vec::new();
+        for dir in dirs {
+            if !unique.contains(&dir) {
+                unique.push(dir);
+            }
+        }
+        Assets { dirs: unique }
+    }
+
+    /// The directories, in search order.
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Files `path` may be read from, in order. Paths that do not start with
+    /// `assets/` are read as they are.
+    pub fn candidates(&self, path: &str) -> Vec<PathBuf> {
+        match path.strip_prefix("assets/") {
+            Some(rest) => self.dirs.iter().map(|dir| dir.join(rest)).collect(),
+            None => vec![PathBuf::from(path)],
+        }
+    }
+
+    /// The first candidate of `path` that exists, or every place searched.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, Error> {
+        let candidates = self.candidates(path);
+        match candidates.iter().find(|file| file.is_file()) {
+            Some(file) => Ok(file.clone()),
+            None => Err(Error::AssetNotFound { path: path.to_string(), searched: candidates }),
+        }
+    }
+
+    /// The bytes of `path` and the file they came from, falling back to the
+    /// embedded copy when no file exists.
+    pub fn read(&self, path: &str) -> Result<(Cow<'static, [u8]>, PathBuf), Error> {
+        match self.resolve(path) {
+            Ok(file) => {
+                let bytes = fs::read(&file).map_err(Error::io(&file))?;
+                Ok((Cow::Owned(bytes), file))
+            }
+            Err(error) => match embedded(path) {
+                Some(bytes) => Ok((Cow::Borrowed(bytes), PathBuf::from(path))),
+                None => Err(error),
+            },
+        }
+    }
+}
+
+impl Default for Assets {
+    fn default() -> Self {
+        Self::new(&AssetsConfig::default())
+    }
+}
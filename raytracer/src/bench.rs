@@ -0,0 +1,1187 @@
+// bench.rs
+// Headless benchmarks. `--bench-scene <tier>` renders every fixed camera of a
+// benchmark scene several times and prints per-stage timings; `--benchmark N`
+// renders N frames of the demo scene along a fixed orbit and reports frame time
+// statistics and rays traced, optionally as JSON to compare against later;
+// with `--profile` it adds the ray counters and stage times per frame, and
+// `--bvh-report` compares SAH bucket counts and leaf sizes on the benchmark
+// scenes by tree shape and by the work of a closest-hit ray per pixel.
+// Both measure exactly the same work on every run. `--bench-scene` also times
+// the sun's shadows traced per pixel against the shadow map, and the BVH
+// traversal that fills a candidate `Vec` per ray against the one that visits
+// candidates in place, cube tests that work out the inverse direction each
+// time against ones that take it from the ray, whole cubes tested against
+// their compact bounds, emissive shadows traced at every hit against the
+// per-face cache, and shadow rays that test every candidate against ones
+// that stop at the first blocker, and primary ray
+// generation with and without the cached camera-space directions, and each
+// `Schedule` of the primary pass over the threads. It renders every view with
+// reflections and refractions traced for every pixel and for one in four, and
+// reports how far apart the frames are, and again with emissive culling off
+// and at a strict threshold, counting shadow rays, and counts what the
+// per-thread scratch buffers allocate over two passes, and renders every view
+// walking the BVH with a stack and along its ropes. Last, it renders every view with
+// primary ray packets and one ray at a time and counts the pixels that differ,
+// which must be none, with or without the `simd` feature. It also builds the BVH with the `bvh` crate on one thread
+// and with the parallel build, and checks that the trees and the first view's
+// pixels come out the same; the medium and large tiers hold about 10k and
+// 100k cubes.
+// `--benchmark` ends by building the terrain chunks around its camera in
+// place, against the streamer's worst splice on the main thread.
+use std::f32::consts::PI;
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bvh::aabb::AABB;
+use bvh::bvh::{BVHNode, BVH};
+use log::{info, warn};
+use raylib::prelude::*;
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use serde::{Deserialize, Serialize};
+
+use crate::assets::Assets;
+use crate::accel::{build_bvh, tree_stats, RopeBvh, TracerRay};
+use crate::camera::{Camera, RayGenCache};
+use crate::chunks::{ChunkPos, ChunkStreamer, StreamedChunk, CHUNK_SIZE};
+use crate::cube::{compare_hits, compare_slabs, Cube, CubeBounds, SlabHit};
+use crate::config::{AssetsConfig, Config};
+use crate::light::Light;
+use crate::light_cache::CacheStats;
+use crate::profile::{self, Profile, Totals};
+use crate::ray_intersect::{Intersect, RayIntersect};
+use crate::scene::Scene;
+use crate::scratch;
+use crate::settings::{RenderSettings, Schedule, TransparencyMode};
+use crate::shadow_map::update_sun_shadow;
+use crate::textures::{SkyboxTextures, TextureManager, BLOCK_TEXTURES};
+use crate::tonemap::tonemap_buffer;
+use crate::worldgen::{benchmark_scene, demo_scene, generate_chunk, BenchTier, BlockPalette, TerrainSettings, WorldGenSettings};
+use crate::render::{render, render_scaled, sun_light, trace_any, trace_transmittance, RenderStats};
+
+const WIDTH: i32 = 640;
+const HEIGHT: i32 = 480;
+// Untimed frame per camera so caches and the thread pool are warm
+const WARMUP_FRAMES: usize = 1;
+const FRAMES_PER_VIEW: usize = 5;
+// Emissive culling threshold `CullCheck` renders with: a hit has at most
+// five nearby emissive blocks, so culling them all moves a channel by well
+// under one 8-bit step
+const CULL_EPSILON: f32 = 1.0 / (255.0 * 16.0);
+
+/// Mean, median and 95th percentile (nearest rank) of `samples`, in ms.
+fn summarize(samples: &mut [f64]) -> (f64, f64, f64) {
+    if samples.is_empty() {
+        return (0.0, 0.0, 0.0);
+    }
+    samples.sort_by(f64::total_cmp);
+    let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+    let median = samples[samples.len() / 2];
+    let p95 = samples[((samples.len() as f64 * 0.95).ceil() as usize).clamp(1, samples.len()) - 1];
+    (mean, median, p95)
+}
+
+/// The block textures and skybox the benchmarks render with, from `assets`;
+/// placeholders and the procedural sky for whatever is missing.
+pub fn load_bench_textures(assets: &AssetsConfig) -> TextureManager {
+    let mut texture_manager = TextureManager::with_assets(Assets::new(assets)).with_max_size(assets.max_texture_size);
+    for path in BLOCK_TEXTURES {
+        texture_manager.load_cpu_texture_or_placeholder(path);
+    }
+    if let Err(error) = texture_manager.load_cpu_skybox(SkyboxTextures::default_faces()) {
+        warn!("Using the procedural sky: {}", error.report());
+    }
+    texture_manager
+}
+
+/// The fixed midday sun above a benchmark scene, scaled with its size.
+pub fn bench_light(scene: &Scene) -> Light {
+    let extent = scene.objects.iter().map(|cube| cube.max_bounds.length()).fold(0.0, f32::max);
+    Light::new(Vector3::new(extent * 0.3, extent * 0.6 + 10.0, extent * 0.15), Vector3::new(1.0, 0.95, 0.8), 1.0)
+}
+
+/// `--bench-scene`: generates the tier's scene, renders it from fixed camera angles and prints the timings.
+pub fn run_bench_scene(tier: BenchTier, settings: &RenderSettings, pool: &ThreadPool, assets: &AssetsConfig) {
+    let texture_manager = load_bench_textures(assets);
+
+    let build_start = Instant::now();
+    let mut scene = pool.install(|| benchmark_scene(tier));
+    pool.install(|| scene.set_sah_buckets(settings.bvh_sah_buckets));
+    let build_ms = build_start.elapsed().as_secs_f64() * 1000.0;
+
+    let light = bench_light(&scene);
+    // The sun never moves here, so the map, if enabled, is traced once
+    pool.install(|| update_sun_shadow(&mut scene, &light, settings));
+
+    println!(
+        "bench-scene {}: {} cubes ({} bytes each, materials shared, {} traversed), {} emissive, {}x{}, {} views x {} frames, {} threads",
+        tier.name(),
+        scene.objects.len(),
+        std::mem::size_of::<Cube>(),
+        std::mem::size_of::<CubeBounds>(),
+        scene.emissive_indices.len(),
+        WIDTH,
+        HEIGHT,
+        scene.bookmarks.len(),
+        FRAMES_PER_VIEW,
+        settings.threads
+    );
+
+    let mut depth_buffer = vec![f32::INFINITY; (WIDTH * HEIGHT) as usize];
+    let mut sample_counts = vec![1; (WIDTH * HEIGHT) as usize];
+    let mut stats = RenderStats::default();
+    let (mut primary, mut edges, mut tonemap, mut frame) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for bookmark in &scene.bookmarks {
+        let camera = Camera::new(bookmark.eye, bookmark.center, Vector3::new(0.0, 1.0, 0.0));
+        let mut view_frames = Vec::new();
+        let mut view_rays = 0;
+        for i in 0..WARMUP_FRAMES + FRAMES_PER_VIEW {
+            let frame_start = Instant::now();
+            let hdr = pool.install(|| {
+                render(WIDTH, HEIGHT, &scene, &camera, &light, &texture_manager, settings, &mut depth_buffer, &mut sample_counts, &mut stats)
+            });
+            let tonemap_start = Instant::now();
+            let pixels = pool.install(|| tonemap_buffer(&hdr, settings, 1.0));
+            std::hint::black_box(pixels);
+            if i < WARMUP_FRAMES {
+                continue;
+            }
+            let frame_ms = frame_start.elapsed().as_secs_f64() * 1000.0;
+            primary.push(stats.primary_time.as_secs_f64() * 1000.0);
+            edges.push(stats.edge_time.as_secs_f64() * 1000.0);
+            tonemap.push(tonemap_start.elapsed().as_secs_f64() * 1000.0);
+            frame.push(frame_ms);
+            view_frames.push(frame_ms);
+            view_rays = stats.rays_traced;
+        }
+        let (mean, median, p95) = summarize(&mut view_frames);
+        println!(
+            "  view {:<10} mean {:8.2}ms  median {:8.2}ms  p95 {:8.2}ms  {:>10} rays",
+            bookmark.name, mean, median, p95, view_rays
+        );
+    }
+
+    println!("{:<10} {:>10} {:>10} {:>10}", "stage", "mean ms", "median ms", "p95 ms");
+    println!("{:<10} {:>10.2} {:>10.2} {:>10.2}", "build", build_ms, build_ms, build_ms);
+    for (name, samples) in [("primary", &mut primary), ("edges", &mut edges), ("tonemap", &mut tonemap), ("frame", &mut frame)] {
+        let (mean, median, p95) = summarize(samples);
+        println!("{:<10} {:>10.2} {:>10.2} {:>10.2}", name, mean, median, p95);
+    }
+
+    // Sun shadows both ways over the same views; rays first, so the map is
+    // traced from scratch and its cost measured
+    let mut shadow_settings = settings.clone();
+    let mut means = [0.0; 2];
+    let mut map_ms = 0.0;
+    for (mean, sun_shadow_map) in means.iter_mut().zip([false, true]) {
+        shadow_settings.sun_shadow_map = sun_shadow_map;
+        let map_start = Instant::now();
+        pool.install(|| update_sun_shadow(&mut scene, &light, &shadow_settings));
+        map_ms = map_start.elapsed().as_secs_f64() * 1000.0;
+        let mut frames = view_frame_times(&scene, &light, &texture_manager, &shadow_settings, pool);
+        *mean = summarize(&mut frames).0;
+    }
+    let [rays_ms, shadow_map_ms] = means;
+    println!(
+        "sun shadows: rays {:.2}ms ({:.1} FPS), {}x{} map {:.2}ms ({:.1} FPS), {:.2}ms to trace the map",
+        rays_ms,
+        1000.0 / rays_ms.max(1e-6),
+        shadow_settings.shadow_map_resolution,
+        shadow_settings.shadow_map_resolution,
+        shadow_map_ms,
+        1000.0 / shadow_map_ms.max(1e-6),
+        map_ms
+    );
+
+    // Each way of splitting the primary pass between the threads
+    let mut schedule_settings = settings.clone();
+    let policies = Schedule::ALL.map(|schedule| {
+        schedule_settings.schedule = schedule;
+        let mut frames = view_frame_times(&scene, &light, &texture_manager, &schedule_settings, pool);
+        let (mean, _, p95) = summarize(&mut frames);
+        format!("{} {:.2}ms (p95 {:.2}ms)", schedule.name().to_lowercase(), mean, p95)
+    });
+    println!("scheduling ({} threads): {}", pool.current_num_threads(), policies.join(", "));
+
+    // Emissive shadows traced at every hit, then once per block face
+    let mut cache_settings = settings.clone();
+    let mut means = [0.0; 2];
+    for (mean, emissive_shadow_cache) in means.iter_mut().zip([false, true]) {
+        cache_settings.emissive_shadow_cache = emissive_shadow_cache;
+        let mut frames = view_frame_times(&scene, &light, &texture_manager, &cache_settings, pool);
+        *mean = summarize(&mut frames).0;
+    }
+    let [traced_ms, cached_ms] = means;
+    let cache = emissive_cache_stats(&scene, &light, &texture_manager, &cache_settings, pool);
+    println!(
+        "emissive shadows ({} lights): traced {:.2}ms, cached {:.2}ms ({:.2}x), {:.1}% of {} lookups hit",
+        scene.emissive_lights.len(),
+        traced_ms,
+        cached_ms,
+        traced_ms / cached_ms.max(1e-6),
+        cache.hit_percent(),
+        cache.lookups
+    );
+
+    let (traversal, shadows) = TraversalTimes::measure(&scene, &light);
+    println!(
+        "traversal: {} rays, Vec per ray {:.2}ms ({} allocations, {:.2} per ray), in place {:.2}ms (none), {:.2}x",
+        traversal.rays,
+        traversal.collected_ms,
+        traversal.allocations,
+        traversal.allocations as f64 / traversal.rays.max(1) as f64,
+        traversal.visited_ms,
+        traversal.collected_ms / traversal.visited_ms.max(1e-6)
+    );
+    println!(
+        "cube tests: inverse direction per test {:.2}ms, once per ray {:.2}ms ({:.2}x)",
+        traversal.rederived_ms,
+        traversal.visited_ms,
+        traversal.rederived_ms / traversal.visited_ms.max(1e-6)
+    );
+    println!(
+        "cube data: whole cubes tested {:.2}ms, bounds only {:.2}ms ({:.2}x)",
+        traversal.visited_ms,
+        traversal.bounds_ms,
+        traversal.visited_ms / traversal.bounds_ms.max(1e-6)
+    );
+    println!(
+        "shadow rays: {} to the sun, every candidate tested {:.2}ms, first blocker {:.2}ms ({:.2}x), through glass {:.2}ms",
+        shadows.rays,
+        shadows.exhaustive_ms,
+        shadows.any_hit_ms,
+        shadows.exhaustive_ms / shadows.any_hit_ms.max(1e-6),
+        shadows.transmittance_ms
+    );
+
+    if let Some(bookmark) = scene.bookmarks.first() {
+        let camera = Camera::new(bookmark.eye, bookmark.center, Vector3::new(0.0, 1.0, 0.0));
+        let (direct_ms, cache_ms, cached_ms) = ray_generation_times(&camera, 1920, 1080);
+        println!(
+            "ray generation 1920x1080: per pixel {:.2}ms, cached {:.2}ms ({:.2}x), {:.2}ms to fill the cache",
+            direct_ms,
+            cached_ms,
+            direct_ms / cached_ms.max(1e-6),
+            cache_ms
+        );
+    }
+
+    let build = BuildCheck::run(&mut scene, &light, &texture_manager, settings, pool);
+    println!(
+        "bvh build ({} cubes): crate {:.2}ms, parallel {:.2}ms ({:.2}x), trees {}, {} of {} pixels differ",
+        scene.objects.len(),
+        build.crate_ms,
+        build.parallel_ms,
+        build.crate_ms / build.parallel_ms.max(1e-6),
+        if build.same_tree { "identical" } else { "differ" },
+        build.differing,
+        build.pixels
+    );
+
+    let half_res = HalfResCheck::run(&scene, &light, &texture_manager, settings, pool);
+    println!(
+        "half-res secondary: full {:.2}ms, half {:.2}ms ({:.2}x), {:.1}% of pixels traced their own, mean difference {:.4}, max {:.3}",
+        half_res.full_ms,
+        half_res.half_ms,
+        half_res.full_ms / half_res.half_ms.max(1e-6),
+        100.0 * half_res.fallbacks as f64 / half_res.pixels.max(1) as f64,
+        half_res.mean_difference,
+        half_res.max_difference
+    );
+
+    let cull = CullCheck::run(&scene, &light, &texture_manager, settings, pool);
+    println!(
+        "emissive culling (epsilon {:.5}): {} shadow rays, {} without culling ({:.1}% fewer), max difference {:.5} ({})",
+        CULL_EPSILON,
+        cull.culled_shadow_rays,
+        cull.all_shadow_rays,
+        100.0 * (1.0 - cull.culled_shadow_rays as f64 / cull.all_shadow_rays.max(1) as f64),
+        cull.max_difference,
+        if cull.max_difference < 1.0 / 255.0 { "under 1/255" } else { "1/255 or more" }
+    );
+
+    let [first, second] = scratch_growths(&scene, &light, &texture_manager, settings, pool);
+    println!("scratch buffers (weighted-blended glass): {} allocations on the first pass over the views, {} on the second", first, second);
+
+    let ropes = RopeCheck::run(&scene, &light, &texture_manager, settings, pool);
+    println!(
+        "bvh traversal: stack {:.2}ms, ropes {:.2}ms ({:.2}x), {} of {} pixels differ",
+        ropes.stack_ms,
+        ropes.rope_ms,
+        ropes.stack_ms / ropes.rope_ms.max(1e-6),
+        ropes.differing,
+        ropes.pixels
+    );
+
+    let packets = PacketCheck::run(&scene, &light, &texture_manager, settings, pool);
+    println!(
+        "primary packets ({}): {:.2}ms, one ray at a time {:.2}ms ({:.2}x), {} of {} pixels differ",
+        if cfg!(feature = "simd") { "simd" } else { "scalar lanes" },
+        packets.packet_ms,
+        packets.single_ms,
+        packets.single_ms / packets.packet_ms.max(1e-6),
+        packets.differing,
+        packets.pixels
+    );
+}
+
+/// The crate's one-thread BVH build against `build_bvh` over the same cubes,
+/// and the first view traced through each tree.
+struct BuildCheck {
+    crate_ms: f64,
+    parallel_ms: f64,
+    same_tree: bool,
+    differing: usize,
+    pixels: usize,
+}
+
+impl BuildCheck {
+    fn run(scene: &mut Scene, light: &Light, texture_manager: &TextureManager, settings: &RenderSettings, pool: &ThreadPool) -> Self {
+        let mut objects = scene.objects.clone();
+        let crate_start = Instant::now();
+        let crate_bvh = BVH::build(&mut objects);
+        let crate_ms = crate_start.elapsed().as_secs_f64() * 1000.0;
+        let parallel_start = Instant::now();
+        let parallel_bvh = pool.install(|| build_bvh(&mut objects));
+        let parallel_ms = parallel_start.elapsed().as_secs_f64() * 1000.0;
+        let same_tree = same_nodes(&crate_bvh.nodes, &parallel_bvh.nodes);
+
+        let mut check = BuildCheck { crate_ms, parallel_ms, same_tree, differing: 0, pixels: 0 };
+        let Some(bookmark) = scene.bookmarks.first() else {
+            return check;
+        };
+        let camera = Camera::new(bookmark.eye, bookmark.center, Vector3::new(0.0, 1.0, 0.0));
+        let mut depth_buffer = vec![f32::INFINITY; (WIDTH * HEIGHT) as usize];
+        let mut sample_counts = vec![1; (WIDTH * HEIGHT) as usize];
+        let mut stats = RenderStats::default();
+        let frames = [crate_bvh, parallel_bvh].map(|bvh| {
+            // Both trees index the same cubes, so they can stand in for each other
+            scene.ropes = RopeBvh::from_bvh(&bvh);
+            scene.bvh = bvh;
+            pool.install(|| {
+                render(WIDTH, HEIGHT, scene, &camera, light, texture_manager, settings, &mut depth_buffer, &mut sample_counts, &mut stats)
+            })
+        });
+        let [old, new] = frames;
+        check.differing = old
+            .iter()
+            .zip(&new)
+            .filter(|(a, b)| [a.x, a.y, a.z].map(f32::to_bits) != [b.x, b.y, b.z].map(f32::to_bits))
+            .count();
+        check.pixels = old.len();
+        check
+    }
+}
+
+/// True if both node arrays hold the same nodes, bounds bit for bit.
+fn same_nodes(a: &[BVHNode], b: &[BVHNode]) -> bool {
+    let bits = |aabb: &AABB| [aabb.min.x, aabb.min.y, aabb.min.z, aabb.max.x, aabb.max.y, aabb.max.z].map(f32::to_bits);
+    a.len() == b.len()
+        && a.iter().zip(b).all(|pair| match pair {
+            (
+                BVHNode::Leaf { parent_index, depth, shape_index },
+                BVHNode::Leaf { parent_index: other_parent, depth: other_depth, shape_index: other_shape },
+            ) => (parent_index, depth, shape_index) == (other_parent, other_depth, other_shape),
+            (
+                BVHNode::Node { parent_index, depth, child_l_index, child_l_aabb, child_r_index, child_r_aabb },
+                BVHNode::Node {
+                    parent_index: other_parent,
+                    depth: other_depth,
+                    child_l_index: other_l_index,
+                    child_l_aabb: other_l_aabb,
+                    child_r_index: other_r_index,
+                    child_r_aabb: other_r_aabb,
+                },
+            ) => {
+                (parent_index, depth, child_l_index, child_r_index) == (other_parent, other_depth, other_l_index, other_r_index)
+                    && bits(child_l_aabb) == bits(other_l_aabb)
+                    && bits(child_r_aabb) == bits(other_r_aabb)
+            }
+            _ => false,
+        })
+}
+
+/// Primary pass of every view with secondary rays at full and at half
+/// resolution, and how far apart the frames are.
+struct HalfResCheck {
+    full_ms: f64,
+    half_ms: f64,
+    fallbacks: usize,
+    // Per color channel, in HDR radiance
+    mean_difference: f64,
+    max_difference: f32,
+    pixels: usize,
+}
+
+impl HalfResCheck {
+    fn run(scene: &Scene, light: &Light, texture_manager: &TextureManager, settings: &RenderSettings, pool: &ThreadPool) -> Self {
+        let mut depth_buffer = vec![f32::INFINITY; (WIDTH * HEIGHT) as usize];
+        let mut sample_counts = vec![1; (WIDTH * HEIGHT) as usize];
+        let mut stats = RenderStats::default();
+        let mut check = HalfResCheck { full_ms: 0.0, half_ms: 0.0, fallbacks: 0, mean_difference: 0.0, max_difference: 0.0, pixels: 0 };
+        let mut difference_sum = 0.0;
+        for bookmark in &scene.bookmarks {
+            let camera = Camera::new(bookmark.eye, bookmark.center, Vector3::new(0.0, 1.0, 0.0));
+            let frames = [false, true].map(|half_res_secondary| {
+                let settings = RenderSettings { half_res_secondary, ..settings.clone() };
+                let hdr = pool.install(|| {
+                    render(WIDTH, HEIGHT, scene, &camera, light, texture_manager, &settings, &mut depth_buffer, &mut sample_counts, &mut stats)
+                });
+                (hdr, stats.primary_time.as_secs_f64() * 1000.0, stats.secondary_fallbacks)
+            });
+            let [(full, full_ms, _), (half, half_ms, fallbacks)] = frames;
+            check.full_ms += full_ms;
+            check.half_ms += half_ms;
+            check.fallbacks += fallbacks;
+            for (a, b) in full.iter().zip(&half) {
+                for difference in [(a.x - b.x).abs(), (a.y - b.y).abs(), (a.z - b.z).abs()] {
+                    difference_sum += difference as f64;
+                    check.max_difference = check.max_difference.max(difference);
+                }
+            }
+            check.pixels += full.len();
+        }
+        check.mean_difference = difference_sum / (3 * check.pixels.max(1)) as f64;
+        check
+    }
+}
+
+/// Every view with emissive culling off and at `CULL_EPSILON`: the shadow
+/// rays each traced and how far apart the frames are.
+struct CullCheck {
+    all_shadow_rays: u64,
+    culled_shadow_rays: u64,
+    // Per color channel, in HDR radiance
+    max_difference: f32,
+}
+
+impl CullCheck {
+    fn run(scene: &Scene, light: &Light, texture_manager: &TextureManager, settings: &RenderSettings, pool: &ThreadPool) -> Self {
+        let mut depth_buffer = vec![f32::INFINITY; (WIDTH * HEIGHT) as usize];
+        let mut sample_counts = vec![1; (WIDTH * HEIGHT) as usize];
+        let mut stats = RenderStats::default();
+        let mut check = CullCheck { all_shadow_rays: 0, culled_shadow_rays: 0, max_difference: 0.0 };
+        for bookmark in &scene.bookmarks {
+            let camera = Camera::new(bookmark.eye, bookmark.center, Vector3::new(0.0, 1.0, 0.0));
+            let frames = [0.0, CULL_EPSILON].map(|emissive_cull_epsilon| {
+                let settings = RenderSettings { emissive_cull_epsilon, profile: true, ..settings.clone() };
+                let hdr = pool.install(|| {
+                    render(WIDTH, HEIGHT, scene, &camera, light, texture_manager, &settings, &mut depth_buffer, &mut sample_counts, &mut stats)
+                });
+                (hdr, stats.profile.map_or(0, |profile| profile.shadow_rays))
+            });
+            let [(all, all_shadow_rays), (culled, culled_shadow_rays)] = frames;
+            check.all_shadow_rays += all_shadow_rays;
+            check.culled_shadow_rays += culled_shadow_rays;
+            for (a, b) in all.iter().zip(&culled) {
+                for difference in [(a.x - b.x).abs(), (a.y - b.y).abs(), (a.z - b.z).abs()] {
+                    check.max_difference = check.max_difference.max(difference);
+                }
+            }
+        }
+        check
+    }
+}
+
+/// Times the per-thread scratch buffers grew over each of two renders of every
+/// view with weighted-blended transparency, which gathers each camera ray's
+/// glass hits into one. The first render grows them to fit; the second
+/// should allocate nothing.
+fn scratch_growths(scene: &Scene, light: &Light, texture_manager: &TextureManager, settings: &RenderSettings, pool: &ThreadPool) -> [u64; 2] {
+    let settings = RenderSettings { transparency: TransparencyMode::WeightedBlended, ..settings.clone() };
+    let mut depth_buffer = vec![f32::INFINITY; (WIDTH * HEIGHT) as usize];
+    let mut sample_counts = vec![1; (WIDTH * HEIGHT) as usize];
+    let mut stats = RenderStats::default();
+    [(); 2].map(|_| {
+        let before = scratch::growths();
+        for bookmark in &scene.bookmarks {
+            let camera = Camera::new(bookmark.eye, bookmark.center, Vector3::new(0.0, 1.0, 0.0));
+            pool.install(|| {
+                render(WIDTH, HEIGHT, scene, &camera, light, texture_manager, &settings, &mut depth_buffer, &mut sample_counts, &mut stats)
+            });
+        }
+        scratch::growths() - before
+    })
+}
+
+/// Every view rendered walking the BVH with a stack and along its ropes, and
+/// how far apart the frames are. Whole frames, since shadow, reflection and
+/// refraction rays walk the tree too.
+struct RopeCheck {
+    stack_ms: f64,
+    rope_ms: f64,
+    differing: usize,
+    pixels: usize,
+}
+
+impl RopeCheck {
+    fn run(scene: &Scene, light: &Light, texture_manager: &TextureManager, settings: &RenderSettings, pool: &ThreadPool) -> Self {
+        let mut depth_buffer = vec![f32::INFINITY; (WIDTH * HEIGHT) as usize];
+        let mut sample_counts = vec![1; (WIDTH * HEIGHT) as usize];
+        let mut stats = RenderStats::default();
+        let mut check = RopeCheck { stack_ms: 0.0, rope_ms: 0.0, differing: 0, pixels: 0 };
+        for bookmark in &scene.bookmarks {
+            let camera = Camera::new(bookmark.eye, bookmark.center, Vector3::new(0.0, 1.0, 0.0));
+            let frames = [false, true].map(|stackless_traversal| {
+                let settings = RenderSettings { stackless_traversal, ..settings.clone() };
+                let start = Instant::now();
+                let hdr = pool.install(|| {
+                    render(WIDTH, HEIGHT, scene, &camera, light, texture_manager, &settings, &mut depth_buffer, &mut sample_counts, &mut stats)
+                });
+                (hdr, start.elapsed().as_secs_f64() * 1000.0)
+            });
+            let [(stack, stack_ms), (ropes, rope_ms)] = frames;
+            check.stack_ms += stack_ms;
+            check.rope_ms += rope_ms;
+            check.differing += stack
+                .iter()
+                .zip(&ropes)
+                .filter(|(a, b)| [a.x, a.y, a.z].map(f32::to_bits) != [b.x, b.y, b.z].map(f32::to_bits))
+                .count();
+            check.pixels += stack.len();
+        }
+        check
+    }
+}
+
+/// Primary pass of every view with ray packets and without, and how far apart
+/// the frames are.
+struct PacketCheck {
+    packet_ms: f64,
+    single_ms: f64,
+    differing: usize,
+    pixels: usize,
+}
+
+impl PacketCheck {
+    fn run(scene: &Scene, light: &Light, texture_manager: &TextureManager, settings: &RenderSettings, pool: &ThreadPool) -> Self {
+        let mut depth_buffer = vec![f32::INFINITY; (WIDTH * HEIGHT) as usize];
+        let mut sample_counts = vec![1; (WIDTH * HEIGHT) as usize];
+        let mut stats = RenderStats::default();
+        let mut check = PacketCheck { packet_ms: 0.0, single_ms: 0.0, differing: 0, pixels: 0 };
+        for bookmark in &scene.bookmarks {
+            let camera = Camera::new(bookmark.eye, bookmark.center, Vector3::new(0.0, 1.0, 0.0));
+            let frames = [false, true].map(|packet_traversal| {
+                let settings = RenderSettings { packet_traversal, ..settings.clone() };
+                let hdr = pool.install(|| {
+                    render(WIDTH, HEIGHT, scene, &camera, light, texture_manager, &settings, &mut depth_buffer, &mut sample_counts, &mut stats)
+                });
+                (hdr, stats.primary_time.as_secs_f64() * 1000.0)
+            });
+            let [(single, single_ms), (packet, packet_ms)] = frames;
+            check.single_ms += single_ms;
+            check.packet_ms += packet_ms;
+            // Bit for bit: a packet must find exactly what the single ray does
+            check.differing += single
+                .iter()
+                .zip(&packet)
+                .filter(|(a, b)| [a.x, a.y, a.z].map(f32::to_bits) != [b.x, b.y, b.z].map(f32::to_bits))
+                .count();
+            check.pixels += single.len();
+        }
+        check
+    }
+}
+
+/// SAH buckets and leaf size `run_bvh_report` builds and walks each scene
+/// with. The first is the `bvh` crate's tree and the renderer's default.
+const BVH_OPTIONS: [(u32, u32); 8] = [(6, 1), (12, 1), (16, 1), (32, 1), (6, 2), (6, 4), (16, 2), (16, 4)];
+
+/// `--bvh-report`: the BVH of every benchmark scene built with each of
+/// `BVH_OPTIONS`, with its depth and leaf occupancy, and the nodes visited,
+/// cubes tested and time spent by a closest-hit query per pixel of every view
+/// along the rope traversal, with the counters on. What the defaults of
+/// `bvh_sah_buckets` and `bvh_leaf_size` are chosen from.
+pub fn run_bvh_report(settings: &RenderSettings, pool: &ThreadPool) {
+    for tier in [BenchTier::Small, BenchTier::Medium, BenchTier::Large, BenchTier::Mirrors] {
+        let mut scene = pool.install(|| benchmark_scene(tier));
+        let rays: Vec<TracerRay> = scene
+            .bookmarks
+            .iter()
+            .flat_map(|bookmark| {
+                let camera = Camera::new(bookmark.eye, bookmark.center, Vector3::new(0.0, 1.0, 0.0));
+                (0..WIDTH * HEIGHT).map(move |i| {
+                    let (x, y) = ((i % WIDTH) as f32 + 0.5, (i / WIDTH) as f32 + 0.5);
+                    TracerRay::new(bookmark.eye, camera.ray_direction(x, y, WIDTH, HEIGHT))
+                })
+            })
+            .collect();
+        println!(
+            "bvh report {}: {} cubes, {} views, {} rays at {}x{}, {} threads",
+            tier.name(),
+            scene.objects.len(),
+            scene.bookmarks.len(),
+            rays.len(),
+            WIDTH,
+            HEIGHT,
+            settings.threads
+        );
+        println!(
+            "  {:>7} {:>4} {:>9} {:>9} {:>10} {:>7} {:>10} {:>9} {:>9} {:>9}",
+            "buckets", "leaf", "build ms", "max depth", "mean depth", "leaves", "cubes/leaf", "nodes/ray", "cubes/ray", "trace ms"
+        );
+        let mut fastest = (f64::INFINITY, BVH_OPTIONS[0]);
+        for (bvh_sah_buckets, bvh_leaf_size) in BVH_OPTIONS {
+            // Rebuilt only when the buckets change; the time is the last build's
+            pool.install(|| scene.set_sah_buckets(bvh_sah_buckets));
+            let tree = tree_stats(&scene.bvh, bvh_leaf_size as usize);
+            let query = RenderSettings { stackless_traversal: true, bvh_sah_buckets, bvh_leaf_size, ..settings.clone() };
+            let (nodes, cubes, trace_ms) = closest_hit_work(&scene, &rays, &query, pool);
+            let per_ray = |count: u64| count as f64 / rays.len().max(1) as f64;
+            println!(
+                "  {:>7} {:>4} {:>9.2} {:>9} {:>10.1} {:>7} {:>10.2} {:>9.1} {:>9.1} {:>9.2}",
+                bvh_sah_buckets,
+                bvh_leaf_size,
+                scene.bvh_build_time().as_secs_f64() * 1000.0,
+                tree.max_depth,
+                tree.mean_depth,
+                tree.leaves,
+                tree.mean_leaf_size,
+                per_ray(nodes),
+                per_ray(cubes),
+                trace_ms
+            );
+            if trace_ms < fastest.0 {
+                fastest = (trace_ms, (bvh_sah_buckets, bvh_leaf_size));
+            }
+        }
+        let (trace_ms, (buckets, leaf_size)) = fastest;
+        println!("  fastest: {} buckets, leaf size {} ({:.2}ms)", buckets, leaf_size, trace_ms);
+    }
+}
+
+/// BVH nodes visited, cubes tested and wall time in ms for the closest static
+/// hit of each of `rays`, traced in parallel on `pool`.
+fn closest_hit_work(scene: &Scene, rays: &[TracerRay], settings: &RenderSettings, pool: &ThreadPool) -> (u64, u64, f64) {
+    let was_enabled = profile::enabled();
+    profile::set_enabled(true);
+    let totals = Totals::default();
+    let start = Instant::now();
+    pool.install(|| {
+        rays.par_chunks(WIDTH as usize).for_each(|chunk| {
+            let before = profile::snapshot();
+            for ray in chunk {
+                std::hint::black_box(scene.intersect_nearest_static(ray, f32::INFINITY, settings));
+            }
+            totals.add_since(&before);
+        })
+    });
+    let trace_ms = start.elapsed().as_secs_f64() * 1000.0;
+    profile::set_enabled(was_enabled);
+    let counted = totals.profile();
+    (counted.node_visits, counted.cube_tests, trace_ms)
+}
+
+/// Time to compute every primary ray direction of a `width` x `height` frame
+/// from scratch, to fill a `RayGenCache`, and to rotate the cached ones, in ms.
+fn ray_generation_times(camera: &Camera, width: i32, height: i32) -> (f64, f64, f64) {
+    let direct_start = Instant::now();
+    for y in 0..height {
+        for x in 0..width {
+            std::hint::black_box(camera.ray_direction(x as f32, y as f32, width, height));
+        }
+    }
+    let direct_ms = direct_start.elapsed().as_secs_f64() * 1000.0;
+
+    let cache_start = Instant::now();
+    let cache = RayGenCache::new(camera, width, height);
+    let cache_ms = cache_start.elapsed().as_secs_f64() * 1000.0;
+
+    let cached_start = Instant::now();
+    for y in 0..height {
+        for x in 0..width {
+            std::hint::black_box(cache.direction(camera, x, y));
+        }
+    }
+    let cached_ms = cached_start.elapsed().as_secs_f64() * 1000.0;
+    (direct_ms, cache_ms, cached_ms)
+}
+
+/// Shadow rays from where each primary ray of `TraversalTimes` landed to the
+/// sun, on one thread: testing every candidate the way `cast_shadow` used to,
+/// then with `trace_any` and `trace_transmittance`, which stop early.
+struct ShadowRayTimes {
+    rays: usize,
+    exhaustive_ms: f64,
+    any_hit_ms: f64,
+    transmittance_ms: f64,
+}
+
+/// Closest-hit queries for one primary ray per pixel of every bookmark, on one
+/// thread: first gathering each ray's candidates into a fresh `Vec` as the
+/// traversal used to, then visiting them in place with `Scene::visit`, once
+/// with each cube test inverting the direction itself and once with the
+/// inverse the `TracerRay` carries. Last, the same with `Scene::visit_bounds`,
+/// which tests the compact bounds and reads a cube only for the hit it keeps.
+struct TraversalTimes {
+    rays: usize,
+    collected_ms: f64,
+    // In place, with each cube test dividing by the direction again
+    rederived_ms: f64,
+    visited_ms: f64,
+    bounds_ms: f64,
+    // Allocations and regrowths of the candidate `Vec`s
+    allocations: usize,
+}
+
+impl TraversalTimes {
+    fn measure(scene: &Scene, light: &Light) -> (Self, ShadowRayTimes) {
+        let settings = RenderSettings::default();
+        let rays: Vec<(Vector3, Vector3)> = scene
+            .bookmarks
+            .iter()
+            .flat_map(|bookmark| {
+                let camera = Camera::new(bookmark.eye, bookmark.center, Vector3::new(0.0, 1.0, 0.0));
+                (0..WIDTH * HEIGHT).map(move |i| {
+                    let (x, y) = ((i % WIDTH) as f32 + 0.5, (i / WIDTH) as f32 + 0.5);
+                    (bookmark.eye, camera.ray_direction(x, y, WIDTH, HEIGHT))
+                })
+            })
+            .collect();
+
+        let mut allocations = 0;
+        let collected_start = Instant::now();
+        for (origin, direction) in &rays {
+            let mut candidates = Vec::new();
+            let _ = scene.visit(&TracerRay::new(*origin, *direction), &settings, |cube| {
+                if candidates.len() == candidates.capacity() {
+                    allocations += 1;
+                }
+                candidates.push(cube);
+                ControlFlow::Continue(())
+            });
+            std::hint::black_box(nearest_distance(candidates, origin, direction));
+        }
+        let collected_ms = collected_start.elapsed().as_secs_f64() * 1000.0;
+
+        // The same in-place visit with the inverse direction worked out per cube
+        let rederived_start = Instant::now();
+        for (origin, direction) in &rays {
+            let mut nearest: Option<(&Cube, Intersect)> = None;
+            let _ = scene.visit(&TracerRay::new(*origin, *direction), &settings, |cube| {
+                let hit = cube.ray_intersect(origin, direction);
+                if hit.is_intersecting
+                    && nearest.as_ref().is_none_or(|(best, best_hit)| compare_hits((cube, &hit), (best, best_hit), direction).is_lt())
+                {
+                    nearest = Some((cube, hit));
+                }
+                ControlFlow::Continue(())
+            });
+            std::hint::black_box(nearest);
+        }
+        let rederived_ms = rederived_start.elapsed().as_secs_f64() * 1000.0;
+
+        let mut hit_points = Vec::new();
+        let visited_start = Instant::now();
+        for (origin, direction) in &rays {
+            let ray = TracerRay::new(*origin, *direction);
+            let mut nearest: Option<(&Cube, Intersect)> = None;
+            let _ = scene.visit(&ray, &settings, |cube| {
+                let hit = cube.intersect(&ray);
+                if hit.is_intersecting
+                    && nearest.as_ref().is_none_or(|(best, best_hit)| compare_hits((cube, &hit), (best, best_hit), direction).is_lt())
+                {
+                    nearest = Some((cube, hit));
+                }
+                ControlFlow::Continue(())
+            });
+            if let Some((_, hit)) = std::hint::black_box(nearest) {
+                hit_points.push(hit.point + hit.normal * 0.001);
+            }
+        }
+        let visited_ms = visited_start.elapsed().as_secs_f64() * 1000.0;
+
+        let bounds_start = Instant::now();
+        for (origin, direction) in &rays {
+            let ray = TracerRay::new(*origin, *direction);
+            let mut nearest: Option<(usize, CubeBounds, SlabHit)> = None;
+            let _ = scene.visit_bounds(&ray, &settings, |index, bounds| {
+                if let Some(hit) = bounds.slab(&ray) {
+                    if nearest.as_ref().is_none_or(|(_, best_bounds, best)| compare_slabs((bounds, &hit), (best_bounds, best), direction).is_lt()) {
+                        nearest = Some((index, *bounds, hit));
+                    }
+                }
+                ControlFlow::Continue(())
+            });
+            std::hint::black_box(nearest.map(|(index, _, slab)| scene.objects[index].hit(&ray, &slab)));
+        }
+        let bounds_ms = bounds_start.elapsed().as_secs_f64() * 1000.0;
+
+        let shadow_rays: Vec<(Vector3, Vector3, f32)> = hit_points
+            .into_iter()
+            .map(|origin| {
+                let to_light = light.position - origin;
+                (origin, to_light.normalized(), to_light.length())
+            })
+            .collect();
+        let exhaustive_start = Instant::now();
+        for (origin, direction, distance) in &shadow_rays {
+            let mut candidates = Vec::new();
+            let _ = scene.visit(&TracerRay::new(*origin, *direction), &settings, |cube| {
+                candidates.push(cube);
+                ControlFlow::Continue(())
+            });
+            std::hint::black_box(candidates.into_iter().any(|cube| {
+                let hit = cube.ray_intersect(origin, direction);
+                hit.is_intersecting && hit.distance < *distance
+            }));
+        }
+        let exhaustive_ms = exhaustive_start.elapsed().as_secs_f64() * 1000.0;
+        let any_hit_start = Instant::now();
+        for (origin, direction, distance) in &shadow_rays {
+            std::hint::black_box(trace_any(origin, direction, *distance, scene, &settings));
+        }
+        let any_hit_ms = any_hit_start.elapsed().as_secs_f64() * 1000.0;
+        let transmittance_start = Instant::now();
+        for (origin, direction, distance) in &shadow_rays {
+            std::hint::black_box(trace_transmittance(origin, direction, *distance, scene, &settings));
+        }
+        let transmittance_ms = transmittance_start.elapsed().as_secs_f64() * 1000.0;
+
+        (
+            TraversalTimes { rays: rays.len(), collected_ms, rederived_ms, visited_ms, bounds_ms, allocations },
+            ShadowRayTimes { rays: shadow_rays.len(), exhaustive_ms, any_hit_ms, transmittance_ms },
+        )
+    }
+}
+
+// Distance to the closest of `candidates`, as `measure` does it in place
+fn nearest_distance(candidates: Vec<&Cube>, origin: &Vector3, direction: &Vector3) -> Option<f32> {
+    candidates
+        .into_iter()
+        .map(|cube| (cube, cube.ray_intersect(origin, direction)))
+        .filter(|(_, hit)| hit.is_intersecting)
+        .min_by(|(a, a_hit), (b, b_hit)| compare_hits((a, a_hit), (b, b_hit), direction))
+        .map(|(_, hit)| hit.distance)
+}
+
+/// Emissive shadow cache lookups over one frame of every bookmark of `scene`.
+fn emissive_cache_stats(scene: &Scene, light: &Light, texture_manager: &TextureManager, settings: &RenderSettings, pool: &ThreadPool) -> CacheStats {
+    let mut depth_buffer = vec![f32::INFINITY; (WIDTH * HEIGHT) as usize];
+    let mut sample_counts = vec![1; (WIDTH * HEIGHT) as usize];
+    let mut stats = RenderStats::default();
+    let mut total = CacheStats::default();
+    for bookmark in &scene.bookmarks {
+        let camera = Camera::new(bookmark.eye, bookmark.center, Vector3::new(0.0, 1.0, 0.0));
+        pool.install(|| {
+            render(WIDTH, HEIGHT, scene, &camera, light, texture_manager, settings, &mut depth_buffer, &mut sample_counts, &mut stats)
+        });
+        let frame = stats.emissive_cache.unwrap_or_default();
+        total.hits += frame.hits;
+        total.lookups += frame.lookups;
+    }
+    total
+}
+
+/// Main-thread cost of the terrain around the `--benchmark` camera: building
+/// every chunk in place, the hitch streaming keeps off the main thread,
+/// against the worst splice of the streamer while the camera walks across
+/// the terrain.
+struct ChunkCheck {
+    chunks: usize,
+    inline_ms: f64,
+    worst_chunk_ms: f64,
+    worst_splice_ms: f32,
+}
+
+impl ChunkCheck {
+    fn run(scene: &mut Scene, eye: Vector3, terrain: &TerrainSettings) -> Self {
+        let palette = BlockPalette::new();
+        let center = ChunkPos::containing(&eye);
+        let mut check = ChunkCheck { chunks: 0, inline_ms: 0.0, worst_chunk_ms: 0.0, worst_splice_ms: 0.0 };
+        for dz in -terrain.view_distance..=terrain.view_distance {
+            for dx in -terrain.view_distance..=terrain.view_distance {
+                let pos = ChunkPos { x: center.x + dx, z: center.z + dz };
+                let start = Instant::now();
+                std::hint::black_box(StreamedChunk::build(pos, generate_chunk(pos, terrain, &palette)));
+                let ms = start.elapsed().as_secs_f64() * 1000.0;
+                check.chunks += 1;
+                check.inline_ms += ms;
+                check.worst_chunk_ms = check.worst_chunk_ms.max(ms);
+            }
+        }
+
+        let provider_terrain = terrain.clone();
+        let mut streamer = ChunkStreamer::new(
+            terrain.view_distance,
+            Arc::new(move |pos| generate_chunk(pos, &provider_terrain, &palette)),
+        );
+        // Load around the start, then step a chunk at a time four chunks over
+        for step in 0..=4 {
+            let eye = eye + Vector3::new((step * CHUNK_SIZE) as f32, 0.0, 0.0);
+            streamer.update(scene, &eye);
+            while streamer.pending_jobs() > 0 {
+                std::thread::sleep(Duration::from_millis(1));
+                streamer.update(scene, &eye);
+            }
+        }
+        check.worst_splice_ms = streamer.max_splice_ms;
+        scene.chunks.clear();
+        scene.touch();
+        check
+    }
+}
+
+/// Frame times of every bookmark of `scene`, warm-up frames left out.
+fn view_frame_times(scene: &Scene, light: &Light, texture_manager: &TextureManager, settings: &RenderSettings, pool: &ThreadPool) -> Vec<f64> {
+    let mut depth_buffer = vec![f32::INFINITY; (WIDTH * HEIGHT) as usize];
+    let mut sample_counts = vec![1; (WIDTH * HEIGHT) as usize];
+    let mut stats = RenderStats::default();
+    let mut frames = Vec::new();
+    for bookmark in &scene.bookmarks {
+        let camera = Camera::new(bookmark.eye, bookmark.center, Vector3::new(0.0, 1.0, 0.0));
+        for i in 0..WARMUP_FRAMES + FRAMES_PER_VIEW {
+            let frame_start = Instant::now();
+            let hdr = pool.install(|| {
+                render(WIDTH, HEIGHT, scene, &camera, light, texture_manager, settings, &mut depth_buffer, &mut sample_counts, &mut stats)
+            });
+            std::hint::black_box(pool.install(|| tonemap_buffer(&hdr, settings, 1.0)));
+            if i >= WARMUP_FRAMES {
+                frames.push(frame_start.elapsed().as_secs_f64() * 1000.0);
+            }
+        }
+    }
+    frames
+}
+
+// Sun of `--benchmark`, from morning to late afternoon over the run
+const DAY_START: f32 = 0.3;
+const DAY_END: f32 = PI - 0.3;
+
+/// The machine a benchmark ran on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MachineInfo {
+    /// Operating system, as Rust names it.
+    pub os: String,
+    /// CPU architecture.
+    pub arch: String,
+    /// CPU model, where the OS reports it.
+    pub cpu: String,
+    /// Logical cores.
+    pub cores: usize,
+    /// Render threads used.
+    pub threads: usize,
+}
+
+impl MachineInfo {
+    fn current(threads: usize) -> Self {
+        MachineInfo {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            cpu: cpu_model().unwrap_or_else(|| "unknown".to_string()),
+            cores: num_cpus::get(),
+            threads,
+        }
+    }
+}
+
+fn cpu_model() -> Option<String> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo
+        .lines()
+        .find(|line| line.starts_with("model name"))
+        .and_then(|line| line.split(':').nth(1))
+        .map(|model| model.trim().to_string())
+}
+
+/// Results of `--benchmark`, also its JSON format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    /// Frames rendered.
+    pub frames: u32,
+    /// Frame width in pixels.
+    pub width: i32,
+    /// Frame height in pixels.
+    pub height: i32,
+    /// Mean frame time in ms.
+    pub mean_ms: f64,
+    /// Median frame time in ms.
+    pub median_ms: f64,
+    /// 95th percentile frame time in ms.
+    pub p95_ms: f64,
+    /// Standard deviation of the frame time in ms.
+    pub stddev_ms: f64,
+    /// Every ray traced over the run: camera, shadow, reflection and refraction.
+    pub rays_traced: u64,
+    /// Ray counters and stage times summed over the run, when profiling.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<Profile>,
+    /// Where it ran.
+    pub machine: MachineInfo,
+}
+
+impl BenchmarkReport {
+    /// Rays traced per second of frame time.
+    pub fn rays_per_second(&self) -> f64 {
+        let total_seconds = self.mean_ms * self.frames as f64 / 1000.0;
+        if total_seconds > 0.0 { self.rays_traced as f64 / total_seconds } else { 0.0 }
+    }
+
+    fn print(&self) {
+        println!(
+            "benchmark: {} frames at {}x{}, {} threads on {} ({} cores, {} {})",
+            self.frames, self.width, self.height, self.machine.threads, self.machine.cpu, self.machine.cores, self.machine.os, self.machine.arch
+        );
+        println!("{:<12} {:>12}", "frame time", "ms");
+        for (name, value) in [("mean", self.mean_ms), ("median", self.median_ms), ("p95", self.p95_ms), ("stddev", self.stddev_ms)] {
+            println!("{:<12} {:>12.2}", name, value);
+        }
+        println!("rays traced: {} ({:.2} Mrays/s)", self.rays_traced, self.rays_per_second() / 1e6);
+        if let Some(profile) = &self.profile {
+            self.print_profile(profile);
+        }
+    }
+
+    /// The profile per frame: counts, then stage times summed over the threads.
+    fn print_profile(&self, profile: &Profile) {
+        let frames = self.frames.max(1) as f64;
+        println!("{:<16} {:>14}", "per frame", "count");
+        let counts = [
+            ("primary rays", profile.primary_rays),
+            ("shadow rays", profile.shadow_rays),
+            ("secondary rays", profile.secondary_rays),
+            ("BVH nodes", profile.node_visits),
+            ("cube tests", profile.cube_tests),
+        ];
+        for (name, count) in counts {
+            println!("{:<16} {:>14.0}", name, count as f64 / frames);
+        }
+        println!("{:<16} {:>14}", "per frame", "thread ms");
+        let stages = [("ray gen", profile.ray_gen), ("trace", profile.trace), ("shade", profile.shade), ("post", profile.post)];
+        for (name, time) in stages {
+            println!("{:<16} {:>14.2}", name, time.as_secs_f64() * 1000.0 / frames);
+        }
+    }
+
+    /// Prints each statistic next to `baseline` and the change in percent.
+    /// Negative is faster for times, positive is more work for rays.
+    fn print_comparison(&self, baseline: &BenchmarkReport) {
+        if (baseline.width, baseline.height, baseline.frames) != (self.width, self.height, self.frames) {
+            warn!(
+                "The baseline rendered {} frames at {}x{}; the numbers are not directly comparable",
+                baseline.frames, baseline.width, baseline.height
+            );
+        }
+        if baseline.machine != self.machine {
+            warn!("The baseline ran on {} with {} threads", baseline.machine.cpu, baseline.machine.threads);
+        }
+        println!("{:<12} {:>12} {:>12} {:>9}", "vs baseline", "baseline", "now", "change");
+        let rows = [
+            ("mean ms", baseline.mean_ms, self.mean_ms),
+            ("median ms", baseline.median_ms, self.median_ms),
+            ("p95 ms", baseline.p95_ms, self.p95_ms),
+            ("stddev ms", baseline.stddev_ms, self.stddev_ms),
+            ("Mrays/s", baseline.rays_per_second() / 1e6, self.rays_per_second() / 1e6),
+        ];
+        for (name, before, after) in rows {
+            let change = if before != 0.0 { 100.0 * (after - before) / before } else { 0.0 };
+            println!("{:<12} {:>12.2} {:>12.2} {:>+8.1}%", name, before, after, change);
+        }
+        println!("{:<12} {:>12} {:>12}", "rays traced", baseline.rays_traced, self.rays_traced);
+    }
+}
+
+/// Where `--benchmark` writes its report and what it compares against.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BenchmarkFiles<'a> {
+    /// `--benchmark-json`: write the report here.
+    pub json: Option<&'a Path>,
+    /// `--compare`: a report written by an earlier run.
+    pub compare: Option<&'a Path>,
+}
+
+/// `--benchmark`: renders `frames` frames of the default demo scene along a
+/// fixed orbit at the configured window size and field of view, and prints
+/// frame time statistics, rays traced and the machine, and the profile per
+/// frame when `RenderSettings::profile` is on.
+pub fn run_benchmark(
+    frames: u32,
+    config: &Config,
+    settings: &RenderSettings,
+    pool: &ThreadPool,
+    files: BenchmarkFiles,
+) -> Result<BenchmarkReport, String> {
+    let (width, height) = (config.window.width, config.window.height);
+    // Read first, so a bad baseline fails before the run rather than after
+    let baseline: Option<BenchmarkReport> = files
+        .compare
+        .map(|path| {
+            let text = fs::read_to_string(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+            serde_json::from_str(&text).map_err(|error| format!("{}: {}", path.display(), error))
+        })
+        .transpose()?;
+
+    let texture_manager = load_bench_textures(&config.assets);
+    // Default world settings, so the terrain seed is the same on every run
+    let mut scene = pool.install(|| demo_scene(&WorldGenSettings::default()));
+    pool.install(|| scene.set_sah_buckets(settings.bvh_sah_buckets));
+    // The interactive startup view, circled once over the run
+    let mut camera = Camera::new(Vector3::new(0.0, 10.0, 13.0), Vector3::new(0.0, 2.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+    config.camera.apply(&mut camera);
+    let mut settings = settings.clone();
+
+    let mut depth_buffer = vec![f32::INFINITY; (width * height) as usize];
+    let mut sample_counts = vec![1; (width * height) as usize];
+    let mut stats = RenderStats::default();
+    let mut frame_ms = Vec::with_capacity(frames as usize);
+    let mut rays_traced = 0;
+    let mut profile = settings.profile.then(Profile::default);
+    for i in 0..WARMUP_FRAMES as u32 + frames {
+        let progress = i.saturating_sub(WARMUP_FRAMES as u32) as f32 / frames.max(1) as f32;
+        let time_of_day = DAY_START + (DAY_END - DAY_START) * progress;
+        settings.sun_elevation = time_of_day.sin();
+        let light = sun_light(time_of_day, 1.0);
+
+        let frame_start = Instant::now();
+        pool.install(|| update_sun_shadow(&mut scene, &light, &settings));
+        let hdr = pool.install(|| {
+            render_scaled(width, height, &scene, &camera, &light, &texture_manager, &settings, &mut depth_buffer, &mut sample_counts, &mut stats)
+        });
+        let post_start = Instant::now();
+        std::hint::black_box(pool.install(|| tonemap_buffer(&hdr, &settings, 1.0)));
+        let post = post_start.elapsed();
+        if i >= WARMUP_FRAMES as u32 {
+            frame_ms.push(frame_start.elapsed().as_secs_f64() * 1000.0);
+            rays_traced += stats.rays_traced;
+            if let (Some(total), Some(frame)) = (profile.as_mut(), stats.profile) {
+                *total += Profile { post, ..frame };
+            }
+            camera.orbit(2.0 * PI / frames as f32, 0.0);
+        }
+    }
+
+    let (mean_ms, median_ms, p95_ms) = summarize(&mut frame_ms);
+    let variance = frame_ms.iter().map(|ms| (ms - mean_ms).powi(2)).sum::<f64>() / frame_ms.len().max(1) as f64;
+    let report = BenchmarkReport {
+        frames,
+        width,
+        height,
+        mean_ms,
+        median_ms,
+        p95_ms,
+        stddev_ms: variance.sqrt(),
+        rays_traced,
+        profile,
+        machine: MachineInfo::current(settings.threads),
+    };
+    report.print();
+    if let Some(baseline) = &baseline {
+        report.print_comparison(baseline);
+    }
+    if let Some(terrain) = &WorldGenSettings::default().terrain {
+        let chunks = ChunkCheck::run(&mut scene, camera.eye, terrain);
+        println!(
+            "chunk streaming: {} chunks built in place {:.2}ms (worst chunk {:.2}ms), worst splice on the main thread {:.3}ms",
+            chunks.chunks, chunks.inline_ms, chunks.worst_chunk_ms, chunks.worst_splice_ms
+        );
+    }
+    if let Some(path) = files.json {
+        let json = serde_json::to_string_pretty(&report).expect("benchmark report always serializes");
+        fs::write(path, json).map_err(|error| format!("{}: {}", path.display(), error))?;
+        info!("Wrote {}", path.display());
+    }
+    Ok(report)
+}
@@ -0,0 +1,205 @@
+// src/bin/render.rs - Headless renderer: PNG frames, benchmarks and scene diffs
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use log::{info, warn};
+use raytracer::assets::Assets;
+use raytracer::bench::{run_bench_scene, run_benchmark, run_bvh_report, BenchmarkFiles};
+use raytracer::calibration::run_calibration;
+use raytracer::cli::RenderCli;
+use raytracer::config::Config;
+use raytracer::framebuffer::{Backpressure, ExportFormat, ExportQueue, Framebuffer};
+use raytracer::gbuffer::{save_view_pngs, GBuffer};
+use raytracer::post::{AuxBuffers, PostChain};
+use raytracer::presets::PresetLibrary;
+use raytracer::recording::Recording;
+use raytracer::render::{render_pool, render_scaled_into, sun_light, RenderStats};
+use raytracer::scene;
+use raytracer::scene_check::run_validation;
+use raytracer::scene_file::load_scene;
+use raytracer::screenshot::ShotMetadata;
+use raytracer::settings::RenderSettings;
+use raytracer::shadow_map::update_sun_shadow;
+use raytracer::textures::{SkyboxTextures, TextureManager, BLOCK_TEXTURES};
+use raytracer::weather::WeatherSystem;
+
+/// Threads encoding replay frames while the next ones are traced.
+const EXPORT_WORKERS: usize = 2;
+
+/// Resolved replay frames that can wait for a free thread; at 1080p each is
+/// about 8 MB.
+const EXPORT_QUEUE: usize = 4;
+
+/// `--diff a.ron b.ron`: prints what changed between two scene files and exits
+/// with 1 if anything did, 0 if not, 2 on a load error.
+fn run_diff(a: &Path, b: &Path) -> ! {
+    let load = |path: &Path| {
+        load_scene(path).unwrap_or_else(|error| {
+            eprintln!("error: {}", error.report());
+            std::process::exit(2);
+        })
+    };
+    let changes = scene::diff(&load(a), &load(b));
+    print!("{}", changes);
+    std::process::exit(if changes.is_empty() { 0 } else { 1 });
+}
+
+/// Writes `frame` to `path`: a PPM for a `.ppm` path, the linear pixels as
+/// an EXR for an `.exr` one, otherwise a PNG with `metadata` in its tEXt
+/// chunks.
+fn save_frame(frame: &Framebuffer, path: &Path, settings: &RenderSettings, metadata: &ShotMetadata) -> Result<(), String> {
+    let saved = match ExportFormat::for_path(path, Vec::new()) {
+        ExportFormat::Ppm => frame.save_ppm(path, settings),
+        ExportFormat::Png(_) => frame.save_png(path, settings, Some(metadata)),
+        ExportFormat::Exr => frame.save_exr(path),
+    };
+    saved.map_err(|error| error.report())
+}
+
+/// Traces one frame of the startup scene on the CPU and writes it as a PNG.
+/// With `--replay`, writes one numbered PNG per recorded frame instead. With
+/// `--gbuffer`, each frame's G-buffer views are written next to it.
+fn run(cli: &RenderCli, config: &Config, pool: &rayon::ThreadPool) -> Result<(), String> {
+    let options = &cli.options;
+    let recording = options.replay.as_deref().map(Recording::load).transpose()?;
+    let mut settings = config.render_settings();
+    let world_settings = options.world_settings();
+    let seed = world_settings.underground.as_ref().map(|underground| underground.seed);
+    let mut scene = options.startup_scene(&world_settings).map_err(|error| error.report())?;
+    let mut camera = options.startup_camera(&scene, &config.camera)?;
+
+    let mut texture_manager = TextureManager::with_assets(Assets::new(&config.assets)).with_max_size(config.assets.max_texture_size);
+    for path in BLOCK_TEXTURES {
+        texture_manager.load_cpu_texture_or_placeholder(path);
+    }
+    if let Err(error) = texture_manager.load_cpu_skybox(SkyboxTextures::default_faces()) {
+        warn!("Using the procedural sky: {}", error.report());
+    }
+    for path in scene.materials.iter().filter_map(|m| m.texture.as_deref()) {
+        texture_manager.load_cpu_texture_or_placeholder(path);
+    }
+    texture_manager.log_summary();
+
+    let (width, height) = (config.window.width, config.window.height);
+    let mut depth_buffer = vec![f32::INFINITY; (width * height) as usize];
+    let mut sample_counts = vec![1; (width * height) as usize];
+    let mut gbuffer = cli.gbuffer.then(|| GBuffer::new((width * height) as usize));
+    let mut stats = RenderStats::default();
+    let mut frame = Framebuffer::new(width, height);
+    let output = &cli.output;
+    let Some(recording) = recording else {
+        let light = sun_light(0.0, 1.0);
+        pool.install(|| scene.set_sah_buckets(settings.bvh_sah_buckets));
+        pool.install(|| update_sun_shadow(&mut scene, &light, &settings));
+        frame.overwrite(|pixels| {
+            pool.install(|| {
+                render_scaled_into(
+                    pixels, width, height, &scene, &camera, &light, &texture_manager, &settings, &mut depth_buffer, &mut sample_counts,
+                    gbuffer.as_mut(), None, &mut stats,
+                )
+            })
+        });
+        PostChain::from_settings(&settings).apply(&mut frame, &AuxBuffers::new(&depth_buffer, &sample_counts, gbuffer.as_ref()));
+        let metadata = ShotMetadata::capture(&camera, width, height, 0.0, seed, &settings);
+        save_frame(&frame, output, &settings, &metadata)?;
+        info!("Wrote {}", output.display());
+        if let Some(gbuffer) = &gbuffer {
+            let views = save_view_pngs(output, width, height, &depth_buffer, gbuffer).map_err(|error| error.report())?;
+            info!("Wrote {}", views.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", "));
+        }
+        return Ok(());
+    };
+
+    let stem = output.with_extension("");
+    let extension = output.extension().map_or("png".into(), |extension| extension.to_string_lossy());
+    let mut weather = WeatherSystem::new();
+    let mut time_of_day = 0.0;
+    // Tracing waits for the disk rather than piling up resolved frames
+    let exports = ExportQueue::new(EXPORT_WORKERS, EXPORT_QUEUE, Backpressure::Block);
+    for (index, state) in recording.frames.iter().enumerate() {
+        state.apply(&mut camera, &mut time_of_day, &mut weather, &mut settings, &mut scene);
+        settings.fog_density = weather.weather.fog_density();
+        settings.sun_elevation = time_of_day.sin();
+        let light = sun_light(time_of_day, weather.weather.sun_factor());
+        pool.install(|| scene.set_sah_buckets(settings.bvh_sah_buckets));
+        pool.install(|| update_sun_shadow(&mut scene, &light, &settings));
+        frame.overwrite(|pixels| {
+            pool.install(|| {
+                render_scaled_into(
+                    pixels, width, height, &scene, &camera, &light, &texture_manager, &settings, &mut depth_buffer, &mut sample_counts,
+                    gbuffer.as_mut(), None, &mut stats,
+                )
+            })
+        });
+        PostChain::from_settings(&settings).apply(&mut frame, &AuxBuffers::new(&depth_buffer, &sample_counts, gbuffer.as_ref()));
+        let frame_path = PathBuf::from(format!("{}_{:04}.{}", stem.display(), index, extension));
+        let metadata = ShotMetadata::capture(&camera, width, height, time_of_day, seed, &settings);
+        let format = ExportFormat::for_path(&frame_path, metadata.png_text());
+        exports.enqueue(frame.export_pixels(&format, &settings), width, height, frame_path.clone(), format);
+        if let Some(gbuffer) = &gbuffer {
+            save_view_pngs(&frame_path, width, height, &depth_buffer, gbuffer).map_err(|error| error.report())?;
+        }
+        while let Some(result) = exports.poll() {
+            result.map_err(|error| error.report())?;
+        }
+    }
+    for result in exports.finish() {
+        result.map_err(|error| error.report())?;
+    }
+    info!("Wrote {} frames to {}_NNNN.{}", recording.frames.len(), stem.display(), extension);
+    Ok(())
+}
+
+fn main() {
+    let cli = RenderCli::parse();
+    cli.options.init_logging();
+    if let Some([a, b]) = cli.diff.as_deref() {
+        run_diff(a, b);
+    }
+    if let Some(path) = &cli.export_presets {
+        match PresetLibrary::builtin().save(path) {
+            Ok(()) => info!("Wrote the built-in presets to {}", path.display()),
+            Err(error) => {
+                eprintln!("error: {}", error);
+                std::process::exit(2);
+            }
+        }
+        return;
+    }
+
+    let config = cli.options.load_config();
+    if cli.options.validate {
+        std::process::exit(run_validation(&cli.options, &config));
+    }
+    let settings = config.render_settings();
+    let pool = render_pool(settings.threads);
+    if let Some(tier) = cli.bench_scene {
+        run_bench_scene(tier, &settings, &pool, &config.assets);
+        return;
+    }
+    if cli.bvh_report {
+        run_bvh_report(&settings, &pool);
+        return;
+    }
+    if let Some(frames) = cli.benchmark {
+        let files = BenchmarkFiles { json: cli.benchmark_json.as_deref(), compare: cli.compare.as_deref() };
+        if let Err(error) = run_benchmark(frames, &config, &settings, &pool, files) {
+            eprintln!("error: {}", error);
+            std::process::exit(2);
+        }
+        return;
+    }
+    if cli.calibration {
+        match run_calibration(&cli.output, config.window.width, config.window.height, &settings) {
+            Ok(matched) => std::process::exit(if matched { 0 } else { 1 }),
+            Err(error) => {
+                eprintln!("error: {}", error);
+                std::process::exit(2);
+            }
+        }
+    }
+    if let Err(error) = run(&cli, &config, &pool) {
+        eprintln!("error: {}", error);
+        std::process::exit(2);
+    }
+}
@@ -0,0 +1,101 @@
+// bloom.rs
+// Glow around emissive blocks, added to the linear HDR frame before tone
+// mapping. The bright pass keys on radiance luminance, which only emissive
+// surfaces push past the threshold: a sunlit white texture stays at or below
+// 1.0, the same 255 it quantizes to as a torch, while the torch is well above.
+// It is the `PostPass` the chain runs when `RenderSettings::bloom` is on.
+use rayon::prelude::*;
+use raylib::prelude::*;
+
+use crate::framebuffer::Framebuffer;
+use crate::post::{AuxBuffers, PostPass};
+use crate::settings::RenderSettings;
+
+/// Rec. 709 luminance of a linear color.
+pub fn luminance(color: Vector3) -> f32 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+/// The part of `color` above `threshold`, keeping its hue; zero below it.
+fn bright_part(color: Vector3, threshold: f32) -> Vector3 {
+    let lum = luminance(color);
+    if !lum.is_finite() || lum <= threshold {
+        return Vector3::zero();
+    }
+    color * ((lum - threshold) / lum)
+}
+
+/// Normalized Gaussian weights for offsets -radius..=radius.
+fn gaussian_kernel(radius: u32) -> Vec<f32> {
+    let sigma = (radius as f32 * 0.5).max(0.5);
+    let weights: Vec<f32> = (-(radius as i32)..=radius as i32)
+        .map(|offset| (-(offset * offset) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f32 = weights.iter().sum();
+    weights.into_iter().map(|w| w / sum).collect()
+}
+
+/// Adds a blurred copy of everything brighter than `threshold` back onto
+/// the frame. A frame with no pixel over the threshold comes out unchanged.
+#[derive(Debug, Clone, Copy)]
+pub struct Bloom {
+    /// See `RenderSettings::bloom_threshold`.
+    pub threshold: f32,
+    /// See `RenderSettings::bloom_intensity`.
+    pub intensity: f32,
+    /// See `RenderSettings::bloom_radius`.
+    pub radius: u32,
+}
+
+impl From<&RenderSettings> for Bloom {
+    fn from(settings: &RenderSettings) -> Self {
+        Bloom { threshold: settings.bloom_threshold, intensity: settings.bloom_intensity, radius: settings.bloom_radius }
+    }
+}
+
+impl PostPass for Bloom {
+    fn name(&self) -> &'static str {
+        "bloom"
+    }
+
+    fn apply(&self, frame: &mut Framebuffer, _aux: &AuxBuffers) {
+        let (width, height) = (frame.width() as usize, frame.height() as usize);
+        apply_bloom(frame.pixels_mut(), width, height, self);
+    }
+}
+
+fn apply_bloom(hdr: &mut [Vector3], width: usize, height: usize, bloom: &Bloom) {
+    if bloom.intensity <= 0.0 || hdr.is_empty() {
+        return;
+    }
+    let bright: Vec<Vector3> = hdr.par_iter().map(|&c| bright_part(c, bloom.threshold)).collect();
+    if bright.iter().all(|c| *c == Vector3::zero()) {
+        return;
+    }
+
+    // Separable blur: rows into `horizontal`, then columns back onto the frame
+    let kernel = gaussian_kernel(bloom.radius);
+    let radius = bloom.radius as isize;
+    let mut horizontal = vec![Vector3::zero(); bright.len()];
+    horizontal
+        .par_chunks_mut(width)
+        .zip(bright.par_chunks(width))
+        .for_each(|(out, row)| {
+            for (x, pixel) in out.iter_mut().enumerate() {
+                for (k, weight) in kernel.iter().enumerate() {
+                    let sx = (x as isize + k as isize - radius).clamp(0, width as isize - 1) as usize;
+                    *pixel += row[sx] * *weight;
+                }
+            }
+        });
+    hdr.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let mut glow = Vector3::zero();
+            for (k, weight) in kernel.iter().enumerate() {
+                let sy = (y as isize + k as isize - radius).clamp(0, height as isize - 1) as usize;
+                glow += horizontal[sy * width + x] * *weight;
+            }
+            *pixel += glow * bloom.intensity;
+        }
+    });
+}
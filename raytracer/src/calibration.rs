@@ -0,0 +1,130 @@
+// calibration.rs
+// `--calibration`: a test pattern pushed through the same bloom and tone mapping
+// as a rendered frame, in place of the scene. Top band: a linear ramp from 0 to
+// 1. Middle band: gray patches at known 8-bit levels, read back and checked.
+// Bottom band: a one-pixel black and white checkerboard, which a display shows
+// at half its light output, next to a patch of linear 0.5 written as is and
+// one of 0.5 encoded with the sRGB curve. Whichever of the two the checkerboard
+// blends into from a distance is the encoding the display applies.
+use std::path::Path;
+
+use log::info;
+use raylib::prelude::*;
+
+use crate::framebuffer::Framebuffer;
+use crate::post::{AuxBuffers, PostChain};
+use crate::screenshot::save_png;
+use crate::settings::{OutputTransfer, RenderSettings, ToneMapping};
+
+/// 8-bit levels of the gray patches, darkest first.
+pub const GRAY_LEVELS: [u8; 9] = [0, 32, 64, 96, 128, 160, 192, 224, 255];
+
+// The smallest image with every patch and band at least two pixels across
+const MIN_WIDTH: i32 = 2 * GRAY_LEVELS.len() as i32;
+const MIN_HEIGHT: i32 = 6;
+
+/// Linear value of a gray patch. `vector3_to_color` truncates, so the value
+/// sits in the middle of its 8-bit step rather than on its lower edge, where
+/// float error could drop it a level.
+fn patch_value(level: u8) -> f32 {
+    (level as f32 + 0.5) / 255.0
+}
+
+/// Column range of gray patch `index` in an image `width` pixels wide.
+fn patch_columns(index: usize, width: i32) -> (i32, i32) {
+    let count = GRAY_LEVELS.len() as i32;
+    (index as i32 * width / count, (index as i32 + 1) * width / count)
+}
+
+/// The test pattern as a linear HDR frame, row by row like `render::render`.
+pub fn test_pattern(width: i32, height: i32) -> Result<Vec<Vector3>, String> {
+    if width < MIN_WIDTH || height < MIN_HEIGHT {
+        return Err(format!(
+            "the calibration pattern needs at least {}x{} pixels, got {}x{}",
+            MIN_WIDTH, MIN_HEIGHT, width, height
+        ));
+    }
+    let gray = |value: f32| Vector3::new(value, value, value);
+    let band = height / 3;
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let color = if y < band {
+                gray(x as f32 / (width - 1) as f32)
+            } else if y < 2 * band {
+                let index = (0..GRAY_LEVELS.len()).find(|&i| x < patch_columns(i, width).1).unwrap_or(0);
+                gray(patch_value(GRAY_LEVELS[index]))
+            } else if x < width / 2 {
+                gray(((x + y) % 2) as f32)
+            } else if x < width * 3 / 4 {
+                gray(0.5)
+            } else {
+                gray(OutputTransfer::Srgb.encode(0.5))
+            };
+            pixels.push(color);
+        }
+    }
+    Ok(pixels)
+}
+
+/// A gray patch as it came out of the output pipeline.
+#[derive(Debug, Clone, Copy)]
+pub struct PatchReading {
+    /// Linear value the patch was drawn with.
+    pub linear: f32,
+    /// The 8-bit level it should read as.
+    pub expected: u8,
+    /// What the center of the patch reads as, red channel.
+    pub measured: u8,
+}
+
+/// Reads the center of every gray patch of a tone-mapped pattern.
+pub fn read_patches(pixels: &[Color], width: i32, height: i32) -> Vec<PatchReading> {
+    let y = height / 3 + height / 6;
+    GRAY_LEVELS
+        .iter()
+        .enumerate()
+        .map(|(index, &level)| {
+            let (start, end) = patch_columns(index, width);
+            PatchReading { linear: patch_value(level), expected: level, measured: pixels[(y * width + (start + end) / 2) as usize].r }
+        })
+        .collect()
+}
+
+/// True when the output pipeline should leave the patches as they are: clamp
+/// tone mapping, exposure 1, raw output and no bloom on values up to 1.
+fn is_neutral(settings: &RenderSettings) -> bool {
+    settings.tone_mapping == ToneMapping::Clamp
+        && settings.exposure == 1.0
+        && settings.output_transfer == OutputTransfer::Raw
+        && (!settings.bloom || settings.bloom_intensity <= 0.0 || settings.bloom_threshold >= 1.0)
+}
+
+/// Writes the pattern to `output` and prints each patch's expected and
+/// measured level. Returns whether every patch matched; with tone mapping,
+/// exposure, bloom or an output transfer that change the patches the readings are only printed,
+/// and the result is true.
+pub fn run_calibration(output: &Path, width: i32, height: i32, settings: &RenderSettings) -> Result<bool, String> {
+    // The patches are read back one pixel each, so noise would only hide
+    // what the pipeline does to them
+    let settings = &RenderSettings { dither: false, ..settings.clone() };
+    let mut frame = Framebuffer::from_pixels(width, height, test_pattern(width, height)?);
+    PostChain::from_settings(settings).apply(&mut frame, &AuxBuffers::default());
+    let pixels = frame.resolve(settings, 1.0);
+    save_png(&output.to_string_lossy(), &pixels, width, height)?;
+    info!("Wrote {}", output.display());
+
+    let neutral = is_neutral(settings);
+    println!("{:<10} {:>9} {:>9}", "linear", "expected", "measured");
+    let mut matched = true;
+    for reading in read_patches(&pixels, width, height) {
+        let flag = if reading.measured == reading.expected { "" } else { "  off" };
+        println!("{:<10.4} {:>9} {:>9}{}", reading.linear, reading.expected, reading.measured, flag);
+        matched &= reading.measured == reading.expected;
+    }
+    if !neutral {
+        println!("tone mapping, exposure, bloom or an output transfer is on, so the patches are not expected to match");
+        return Ok(true);
+    }
+    Ok(matched)
+}
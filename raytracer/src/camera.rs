@@ -1,16 +1,57 @@
 // camera.rs
 use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
 
+/// Indica si `v` sirve como dirección: componentes finitas y longitud no nula.
+pub fn is_valid_direction(v: &Vector3) -> bool {
+    v.x.is_finite() && v.y.is_finite() && v.z.is_finite() && v.dot(*v) > 1e-12
+}
+
+/// Posición guardada de la cámara a la que se puede saltar.
+#[derive(Debug, Clone)]
+pub struct CameraBookmark {
+    /// Nombre que se muestra y que acepta `--camera`.
+    pub name: String,
+    /// Posición de la cámara.
+    pub eye: Vector3,
+    /// Punto al que mira.
+    pub center: Vector3,
+}
+
+/// Eje en el que el campo de visión es fijo. El otro crece o encoge con la
+/// proporción de la imagen, así que la escena no se deforma al cambiar de
+/// resolución.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FovAxis {
+    /// `fov` es el ángulo de arriba a abajo; una imagen más ancha ve más a los lados.
+    #[default]
+    Vertical,
+    /// `fov` es el ángulo de izquierda a derecha; una imagen más alta ve más arriba y abajo.
+    Horizontal,
+}
+
+/// Cámara orbital con proyección en perspectiva.
 pub struct Camera {
-    pub eye: Vector3,     // donde esta la camara en el mundo, ejemplo (7,100,10)
-    pub center: Vector3,  // que mira la camara (mario), ejemplo (7,100,5)
-    pub up: Vector3,      // donde esta arriba
+    /// Donde esta la camara en el mundo, ejemplo (7,100,10)
+    pub eye: Vector3,
+    /// Que mira la camara (mario), ejemplo (7,100,5)
+    pub center: Vector3,
+    /// Donde esta arriba.
+    pub up: Vector3,
+    /// Dirección de la vista, normalizada; la calcula `update_basis`.
     pub forward: Vector3,
+    /// Derecha de la vista, normalizada; la calcula `update_basis`.
     pub right: Vector3,
+    /// Campo de visión en radianes, a lo largo de `fov_axis`.
+    pub fov: f32,
+    /// Eje en el que se mide `fov`.
+    pub fov_axis: FovAxis,
     changed: bool,        // para optimizar actualizaciones
 }
 
 impl Camera {
+    /// Cámara en `eye` mirando a `center`, con 60° de campo vertical.
     pub fn new(eye: Vector3, center: Vector3, up: Vector3) -> Self {
         let mut camera = Camera {
             eye,
@@ -18,6 +59,8 @@ impl Camera {
             up,
             forward: Vector3::zero(),
             right: Vector3::zero(),
+            fov: std::f32::consts::PI / 3.0,
+            fov_axis: FovAxis::Vertical,
             changed: true,
         };
 
@@ -25,9 +68,18 @@ impl Camera {
         camera
     }
 
+    /// Recalcula `forward`, `right` y `up` después de mover `eye` o `center`.
     pub fn update_basis(&mut self) {
-        self.forward = (self.center - self.eye).normalized();
-        self.right = self.forward.cross(self.up).normalized();
+        // Si eye == center (p. ej. al hacer zoom hasta el centro) o forward queda
+        // paralelo a up, se conserva la base anterior en vez de producir NaN.
+        let forward = (self.center - self.eye).normalized();
+        if is_valid_direction(&forward) {
+            self.forward = forward;
+        }
+        let right = self.forward.cross(self.up).normalized();
+        if is_valid_direction(&right) {
+            self.right = right;
+        }
         self.up = self.right.cross(self.forward);
         self.changed = true;
     }
@@ -78,6 +130,10 @@ impl Camera {
 
     /// Transforma un vector del espacio de cámara al espacio mundial usando los vectores base
     pub fn basis_change(&self, p: &Vector3) -> Vector3 {
+        // Un vector nulo o con NaN no tiene dirección: se devuelve forward
+        if !is_valid_direction(p) {
+            return self.forward;
+        }
         // Conversión de espacio de cámara a espacio mundial
         // En espacio de cámara:
         // - X apunta a la derecha
@@ -89,4 +145,171 @@ impl Camera {
             p.x * self.right.z + p.y * self.up.z - p.z * self.forward.z,
         )
     }
-}
\ No newline at end of file
+
+    /// Mitad del ancho y del alto de la imagen sobre el plano a distancia 1 del
+    /// ojo. El eje de `fov_axis` mide `tan(fov / 2)` y el otro sigue la proporción.
+    pub fn screen_extent(&self, width: i32, height: i32) -> (f32, f32) {
+        let aspect_ratio = width as f32 / height as f32;
+        let half = (self.fov * 0.5).tan();
+        match self.fov_axis {
+            FovAxis::Vertical => (half * aspect_ratio, half),
+            FovAxis::Horizontal => (half, half / aspect_ratio),
+        }
+    }
+
+    /// Dirección del rayo primario por el píxel (x, y): la inversa de `project`.
+    pub fn ray_direction(&self, x: f32, y: f32, width: i32, height: i32) -> Vector3 {
+        let (extent_x, extent_y) = self.screen_extent(width, height);
+        let screen_x = ((2.0 * x) / width as f32 - 1.0) * extent_x;
+        let screen_y = (-(2.0 * y) / height as f32 + 1.0) * extent_y;
+        self.basis_change(&Vector3::new(screen_x, screen_y, -1.0).normalized())
+    }
+
+    /// Proyecta un punto del mundo a píxeles con la misma convención que los rayos
+    /// primarios de `render`. Devuelve (x, y, distancia al ojo) o None si está detrás.
+    pub fn project(&self, point: Vector3, width: i32, height: i32) -> Option<(f32, f32, f32)> {
+        let relative = point - self.eye;
+        let z = relative.dot(self.forward);
+        if z <= 0.01 {
+            return None;
+        }
+        let (extent_x, extent_y) = self.screen_extent(width, height);
+        let screen_x = relative.dot(self.right) / z / extent_x;
+        let screen_y = relative.dot(self.up) / z / extent_y;
+        Some((
+            (screen_x + 1.0) * 0.5 * width as f32,
+            (1.0 - screen_y) * 0.5 * height as f32,
+            relative.length(),
+        ))
+    }
+
+    /// Copia desplazada de la cámara, p. ej. para el temblor de una explosión.
+    pub fn offset(&self, by: Vector3) -> Camera {
+        let mut camera = Camera::new(self.eye + by, self.center + by, self.up);
+        camera.fov = self.fov;
+        camera.fov_axis = self.fov_axis;
+        camera
+    }
+
+    /// Salta a un marcador conservando el vector up original.
+    pub fn go_to(&mut self, bookmark: &CameraBookmark) {
+        self.eye = bookmark.eye;
+        self.center = bookmark.center;
+        self.up = Vector3::new(0.0, 1.0, 0.0);
+        self.update_basis();
+    }
+}
+
+/// Direcciones de los rayos primarios en espacio de cámara, normalizadas, para
+/// cada píxel de una imagen. Solo dependen de la resolución y del campo de
+/// visión, así que sirven mientras no cambien; en cada cuadro basta rotarlas
+/// con la base de la cámara. Dan exactamente las mismas direcciones que
+/// `Camera::ray_direction`.
+pub struct RayGenCache {
+    width: i32,
+    height: i32,
+    fov: f32,
+    fov_axis: FovAxis,
+    extent: (f32, f32),
+    directions: Vec<Vector3>,
+}
+
+impl RayGenCache {
+    /// Calcula las direcciones de los píxeles (x, y) enteros de una imagen de
+    /// `width` x `height` vista por `camera`.
+    pub fn new(camera: &Camera, width: i32, height: i32) -> Self {
+        let extent = camera.screen_extent(width, height);
+        let mut cache = RayGenCache {
+            width,
+            height,
+            fov: camera.fov,
+            fov_axis: camera.fov_axis,
+            extent,
+            directions: Vec::with_capacity((width.max(0) * height.max(0)) as usize),
+        };
+        for y in 0..height {
+            for x in 0..width {
+                let direction = cache.camera_space(x as f32, y as f32);
+                cache.directions.push(direction);
+            }
+        }
+        cache
+    }
+
+    /// Indica si las direcciones sirven para `camera` a esta resolución.
+    pub fn fits(&self, camera: &Camera, width: i32, height: i32) -> bool {
+        (self.width, self.height, self.fov, self.fov_axis) == (width, height, camera.fov, camera.fov_axis)
+    }
+
+    /// Dirección en el mundo del rayo por el píxel (x, y).
+    pub fn direction(&self, camera: &Camera, x: i32, y: i32) -> Vector3 {
+        camera.basis_change(&self.directions[(y * self.width + x) as usize])
+    }
+
+    /// Direcciones en espacio de cámara de `len` píxeles seguidos de la fila
+    /// `y`, desde la columna `x`.
+    pub fn camera_space_row(&self, x: i32, y: i32, len: i32) -> &[Vector3] {
+        let start = (y * self.width + x) as usize;
+        &self.directions[start..start + len as usize]
+    }
+
+    /// Dirección en el mundo del rayo por un punto dentro del píxel (x, y),
+    /// desplazado (ox, oy) de su esquina, p. ej. para el supermuestreo.
+    pub fn offset_direction(&self, camera: &Camera, x: i32, y: i32, ox: f32, oy: f32) -> Vector3 {
+        camera.basis_change(&self.camera_space(x as f32 + ox, y as f32 + oy))
+    }
+
+    // Lo mismo que `Camera::ray_direction` antes de cambiar de base
+    fn camera_space(&self, x: f32, y: f32) -> Vector3 {
+        let (extent_x, extent_y) = self.extent;
+        let screen_x = ((2.0 * x) / self.width as f32 - 1.0) * extent_x;
+        let screen_y = (-(2.0 * y) / self.height as f32 + 1.0) * extent_y;
+        Vector3::new(screen_x, screen_y, -1.0).normalized()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ángulos (horizontal, vertical) entre los rayos de bordes opuestos de la imagen
+    fn view_angles(camera: &Camera, width: i32, height: i32) -> (f32, f32) {
+        let (w, h) = (width as f32, height as f32);
+        let across = camera.ray_direction(0.0, h * 0.5, width, height).dot(camera.ray_direction(w, h * 0.5, width, height));
+        let down = camera.ray_direction(w * 0.5, 0.0, width, height).dot(camera.ray_direction(w * 0.5, h, width, height));
+        (across.clamp(-1.0, 1.0).acos(), down.clamp(-1.0, 1.0).acos())
+    }
+
+    fn expected_angle(fov: f32, aspect: f32) -> f32 {
+        2.0 * ((fov * 0.5).tan() * aspect).atan()
+    }
+
+    fn assert_close(actual: f32, expected: f32, what: &str) {
+        assert!((actual - expected).abs() < 1e-4, "{}: {} vs {} rad", what, actual, expected);
+    }
+
+    #[test]
+    fn vertical_fov_keeps_the_vertical_angle_across_4_3_and_16_9() {
+        let camera = Camera::new(Vector3::new(3.0, 2.0, 5.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+        let (across_4_3, down_4_3) = view_angles(&camera, 640, 480);
+        let (across_16_9, down_16_9) = view_angles(&camera, 1280, 720);
+        assert_close(down_4_3, camera.fov, "4:3 vertical");
+        assert_close(down_16_9, camera.fov, "16:9 vertical");
+        assert_close(across_4_3, expected_angle(camera.fov, 4.0 / 3.0), "4:3 horizontal");
+        assert_close(across_16_9, expected_angle(camera.fov, 16.0 / 9.0), "16:9 horizontal");
+        assert!(across_16_9 > across_4_3);
+    }
+
+    #[test]
+    fn horizontal_fov_keeps_the_horizontal_angle_across_4_3_and_16_9() {
+        let mut camera = Camera::new(Vector3::new(3.0, 2.0, 5.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+        camera.fov_axis = FovAxis::Horizontal;
+        let (across_4_3, down_4_3) = view_angles(&camera, 640, 480);
+        let (across_16_9, down_16_9) = view_angles(&camera, 1280, 720);
+        assert_close(across_4_3, camera.fov, "4:3 horizontal");
+        assert_close(across_16_9, camera.fov, "16:9 horizontal");
+        assert_close(down_4_3, expected_angle(camera.fov, 3.0 / 4.0), "4:3 vertical");
+        assert_close(down_16_9, expected_angle(camera.fov, 9.0 / 16.0), "16:9 vertical");
+        assert!(down_16_9 < down_4_3);
+    }
+}
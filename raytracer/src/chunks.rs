@@ -0,0 +1,217 @@
+// chunks.rs
+// Streaming de chunks de terreno alrededor de la cámara. Un hilo de fondo genera
+// los bloques de cada chunk y construye su BVH; el hilo principal solo tiene que
+// insertarlo en la escena (un push), así que cargar un chunk no provoca tirones.
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use bvh::aabb::{Bounded, AABB};
+use bvh::bvh::BVH;
+use log::trace;
+use nalgebra::Point3;
+use raylib::prelude::*;
+
+use crate::accel::{build_bvh, RopeBvh, TracerRay};
+use crate::cube::{compare_hits, Cube};
+use crate::ray_intersect::Intersect;
+use crate::scene::Scene;
+
+/// Lado de un chunk en bloques (x y z).
+pub const CHUNK_SIZE: i32 = 8;
+
+/// Posición de un chunk en la rejilla horizontal de chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkPos {
+    /// Columna en x.
+    pub x: i32,
+    /// Columna en z.
+    pub z: i32,
+}
+
+impl ChunkPos {
+    /// Chunk que contiene `point`.
+    pub fn containing(point: &Vector3) -> Self {
+        let size = CHUNK_SIZE as f32;
+        // Los bloques están centrados en enteros: el chunk 0 cubre [-0.5, size - 0.5)
+        ChunkPos {
+            x: ((point.x + 0.5) / size).floor() as i32,
+            z: ((point.z + 0.5) / size).floor() as i32,
+        }
+    }
+
+    /// Distancia de Chebyshev, en chunks.
+    pub fn distance(&self, other: &ChunkPos) -> i32 {
+        (self.x - other.x).abs().max((self.z - other.z).abs())
+    }
+}
+
+/// Origen de los bloques de un chunk: generación procedural o un mundo guardado.
+pub type ChunkProvider = Arc<dyn Fn(ChunkPos) -> Vec<Cube> + Send + Sync>;
+
+/// Un chunk listo para trazar, con su propio BVH.
+pub struct StreamedChunk {
+    /// Posición del chunk.
+    pub pos: ChunkPos,
+    /// Bloques del chunk.
+    pub objects: Vec<Cube>,
+    bounds: AABB,
+    ropes: RopeBvh,
+}
+
+impl StreamedChunk {
+    /// Chunk con los bloques `objects` y su BVH ya construido.
+    pub fn build(pos: ChunkPos, mut objects: Vec<Cube>) -> Self {
+        let (bounds, ropes) = if objects.is_empty() {
+            let empty = AABB::with_bounds(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, 0.0));
+            (empty, RopeBvh::from_bvh(&BVH { nodes: Vec::new() }))
+        } else {
+            let bounds = objects.iter().skip(1).fold(objects[0].aabb(), |b, c| b.join(&c.aabb()));
+            (bounds, RopeBvh::from_bvh(&build_bvh(&mut objects)))
+        };
+        trace!("Chunk {:?}: {} blocks", pos, objects.len());
+        StreamedChunk { pos, objects, bounds, ropes }
+    }
+
+    /// Impacto más cercano dentro del chunk, si lo hay.
+    pub fn intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Option<Intersect> {
+        if self.objects.is_empty() {
+            return None;
+        }
+        let ray = TracerRay::new(*ray_origin, *ray_direction);
+        if !ray.intersects_aabb(&self.bounds) {
+            return None;
+        }
+        let mut nearest: Option<(&Cube, Intersect)> = None;
+        let _ = self.ropes.visit(&ray, |index| {
+            let cube = &self.objects[index];
+            let hit = cube.intersect(&ray);
+            if hit.is_intersecting
+                && nearest.as_ref().is_none_or(|(best, best_hit)| compare_hits((cube, &hit), (best, best_hit), ray_direction).is_lt())
+            {
+                nearest = Some((cube, hit));
+            }
+            ControlFlow::Continue(())
+        });
+        nearest.map(|(_, hit)| hit)
+    }
+
+    /// Indica si algún bloque del chunk contiene `point`.
+    pub fn contains(&self, point: &Vector3) -> bool {
+        self.cube_containing(point).is_some()
+    }
+
+    /// Un bloque del chunk que contiene el punto, si hay alguno.
+    pub fn cube_containing(&self, point: &Vector3) -> Option<&Cube> {
+        let ray = TracerRay::new(*point, Vector3::new(0.0, -1.0, 0.0));
+        if self.objects.is_empty() || !ray.intersects_aabb(&self.bounds) {
+            return None;
+        }
+        let mut found = None;
+        let _ = self.ropes.visit(&ray, |index| {
+            let cube = &self.objects[index];
+            if cube.contains(point) {
+                found = Some(cube);
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        });
+        found
+    }
+}
+
+/// Mantiene cargados los chunks a `view_distance` de la cámara.
+pub struct ChunkStreamer {
+    view_distance: i32,
+    pending: HashSet<ChunkPos>,
+    requests: Sender<ChunkPos>,
+    results: Receiver<StreamedChunk>,
+    /// Tiempo en el hilo principal del último frame con cambios.
+    pub last_splice_ms: f32,
+    /// Máximo de `last_splice_ms` desde que empezó el programa.
+    pub max_splice_ms: f32,
+}
+
+impl ChunkStreamer {
+    /// Arranca el hilo de fondo que pide los bloques a `provider`.
+    pub fn new(view_distance: i32, provider: ChunkProvider) -> Self {
+        let (requests, worker_requests) = mpsc::channel::<ChunkPos>();
+        let (worker_results, results) = mpsc::channel();
+        thread::Builder::new()
+            .name("chunk-streamer".to_string())
+            .spawn(move || {
+                // Termina cuando se suelta el ChunkStreamer y se cierra el canal
+                for pos in worker_requests {
+                    let chunk = StreamedChunk::build(pos, provider(pos));
+                    if worker_results.send(chunk).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("Could not start the chunk streaming thread");
+
+        ChunkStreamer {
+            view_distance: view_distance.max(0),
+            pending: HashSet::new(),
+            requests,
+            results,
+            last_splice_ms: 0.0,
+            max_splice_ms: 0.0,
+        }
+    }
+
+    /// Chunks pedidos que el hilo de fondo aún no devolvió.
+    pub fn pending_jobs(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Inserta los chunks terminados, descarta los lejanos y pide los que faltan.
+    pub fn update(&mut self, scene: &mut Scene, camera_position: &Vector3) {
+        let start = Instant::now();
+        let center = ChunkPos::containing(camera_position);
+        // Un chunk de margen antes de descartar, para no recargar al ir y venir por un borde
+        let keep_distance = self.view_distance + 1;
+        let mut changed = false;
+
+        while let Ok(mut chunk) = self.results.try_recv() {
+            self.pending.remove(&chunk.pos);
+            if chunk.pos.distance(&center) <= keep_distance {
+                // Los materiales editados en caliente también valen para chunks nuevos
+                scene.adopt_materials(&mut chunk.objects);
+                scene.chunks.push(chunk);
+                changed = true;
+            }
+        }
+
+        let before = scene.chunks.len();
+        scene.chunks.retain(|chunk| chunk.pos.distance(&center) <= keep_distance);
+        changed |= scene.chunks.len() != before;
+
+        let loaded: HashSet<ChunkPos> = scene.chunks.iter().map(|chunk| chunk.pos).collect();
+        let mut missing: Vec<ChunkPos> = Vec::new();
+        for dz in -self.view_distance..=self.view_distance {
+            for dx in -self.view_distance..=self.view_distance {
+                let pos = ChunkPos { x: center.x + dx, z: center.z + dz };
+                if !loaded.contains(&pos) && !self.pending.contains(&pos) {
+                    missing.push(pos);
+                }
+            }
+        }
+        // Primero los más cercanos a la cámara
+        missing.sort_by_key(|pos| pos.distance(&center));
+        for pos in missing {
+            if self.requests.send(pos).is_ok() {
+                self.pending.insert(pos);
+            }
+        }
+
+        if changed {
+            scene.touch();
+            self.last_splice_ms = start.elapsed().as_secs_f32() * 1000.0;
+            self.max_splice_ms = self.max_splice_ms.max(self.last_splice_ms);
+        }
+    }
+}
@@ -0,0 +1,411 @@
+// cli.rs
+// Command-line options of the two binaries. An option that is not given keeps
+// the value from raytracer.toml, or the built-in default when the file does not
+// set it.
+use std::path::{Path, PathBuf};
+
+use clap::builder::RangedU64ValueParser;
+use clap::{ArgAction, Args, Parser};
+use log::{warn, LevelFilter};
+use raylib::prelude::*;
+
+use crate::camera::{Camera, FovAxis};
+use crate::clouds::CloudLayer;
+use crate::config::{CameraConfig, Config, CONFIG_FILE};
+use crate::error::Error;
+use crate::scene::Scene;
+use crate::scene_check::SceneReport;
+use crate::scene_file::{load_scene, SCENE_FILE};
+use crate::settings::OutputTransfer;
+use crate::worldgen::{demo_scene, BenchTier, WorldGenSettings};
+
+/// Options shared by the interactive window and the headless renderer.
+#[derive(Debug, Args)]
+pub struct SceneOptions {
+    /// Settings file; a commented default is written if it does not exist
+    #[arg(long, value_name = "FILE", default_value = CONFIG_FILE)]
+    pub config: PathBuf,
+
+    /// Window width in pixels (also the rendered image width)
+    #[arg(long, value_parser = RangedU64ValueParser::<i32>::new().range(1..=8192))]
+    pub width: Option<i32>,
+
+    /// Window height in pixels (also the rendered image height)
+    #[arg(long, value_parser = RangedU64ValueParser::<i32>::new().range(1..=8192))]
+    pub height: Option<i32>,
+
+    /// Field of view in degrees (10 to 150), along --fov-axis
+    #[arg(long, value_name = "DEGREES", value_parser = parse_fov)]
+    pub fov: Option<f32>,
+
+    /// Axis the field of view is measured on: vertical or horizontal
+    #[arg(long, value_name = "AXIS", value_parser = parse_fov_axis)]
+    pub fov_axis: Option<FovAxis>,
+
+    /// Scene file to load instead of scene.ron or the generated demo
+    #[arg(long, value_name = "FILE")]
+    pub scene: Option<PathBuf>,
+
+    /// Directory searched first for texture paths written as `assets/...`
+    #[arg(long, value_name = "DIR")]
+    pub assets_dir: Option<PathBuf>,
+
+    /// Rays per pixel on geometry edges; 1 turns edge anti-aliasing off
+    #[arg(long, value_parser = RangedU64ValueParser::<u32>::new().range(1..=64))]
+    pub samples: Option<u32>,
+
+    /// Reflection and refraction bounces per primary ray
+    #[arg(long, value_parser = RangedU64ValueParser::<u32>::new().range(0..=16))]
+    pub max_depth: Option<u32>,
+
+    /// Encoding of the window and the PNGs: raw, srgb, gamma22 or bt709
+    #[arg(long, value_name = "CURVE", value_parser = parse_output_transfer)]
+    pub output_transfer: Option<OutputTransfer>,
+
+    /// Fraction of the resolution the CPU traces at, upscaled for display (0.1 to 1)
+    #[arg(long, value_name = "SCALE", value_parser = parse_render_scale)]
+    pub render_scale: Option<f32>,
+
+    /// Seed for the generated caves and ore veins
+    #[arg(long)]
+    pub seed: Option<u32>,
+
+    /// Name of the camera bookmark to start at
+    #[arg(long, value_name = "BOOKMARK")]
+    pub camera: Option<String>,
+
+    /// Render worker threads; 1 traces serially (default: every core)
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..=1024))]
+    pub threads: Option<usize>,
+
+    /// Count rays and time each stage of the frame (quality.profile)
+    #[arg(long)]
+    pub profile: bool,
+
+    /// Play back a session recorded with F7 instead of live input
+    #[arg(long, value_name = "FILE", conflicts_with = "camera")]
+    pub replay: Option<PathBuf>,
+
+    /// Print the scene statistics and validation report and exit: 0 if clean, 1 if it has problems
+    #[arg(long)]
+    pub validate: bool,
+
+    /// More log output: -v adds per-frame stats and BVH builds, -vv everything (RUST_LOG overrides)
+    #[arg(short, long, action = ArgAction::Count)]
+    pub verbose: u8,
+}
+
+/// `raytracer`: the interactive window.
+#[derive(Debug, Parser)]
+#[command(name = "raytracer", version, about = "CPU ray tracer for a Minecraft-style block world")]
+pub struct Cli {
+    /// Scene, quality and startup options.
+    #[command(flatten)]
+    pub options: SceneOptions,
+
+    /// Do not write the per-frame performance log or its exit summary
+    #[arg(long)]
+    pub no_perf_log: bool,
+
+    /// Cap the frame rate, sleeping out the rest of each frame (default: window.max_fps, 0 uncapped)
+    #[arg(long, value_name = "N", value_parser = RangedU64ValueParser::<u32>::new().range(1..=1000))]
+    pub max_fps: Option<u32>,
+
+    /// Present frames in step with the display's refresh (default: window.vsync)
+    #[arg(long)]
+    pub vsync: bool,
+}
+
+/// `raytracer-render`: renders without opening a window.
+#[derive(Debug, Parser)]
+#[command(
+    name = "raytracer-render",
+    version,
+    about = "Render the block world to PNG, benchmark it or compare scene files, without opening a window"
+)]
+pub struct RenderCli {
+    /// Scene, quality and startup options.
+    #[command(flatten)]
+    pub options: SceneOptions,
+
+    /// PNG to write, PPM if it ends in .ppm or linear EXR if it ends in .exr; with --replay, frames are numbered after it (render_0000.png, ...)
+    #[arg(long, value_name = "FILE", default_value = "render.png")]
+    pub output: PathBuf,
+
+    /// Also write the depth, normal, albedo and object id of each frame as PNGs next to it (render.depth.png, render.normal.png, ...)
+    #[arg(long)]
+    pub gbuffer: bool,
+
+    /// Time fixed camera angles of a generated scene (small, medium, large, or mirrors for reflection chains) and exit
+    #[arg(
+        long,
+        value_name = "TIER",
+        value_parser = parse_bench_tier,
+        conflicts_with_all = ["scene", "camera", "seed", "replay"]
+    )]
+    pub bench_scene: Option<BenchTier>,
+
+    /// Build the BVH of every benchmark scene with several SAH bucket counts and leaf sizes, print tree depth, leaf occupancy and nodes visited per primary ray, and exit
+    #[arg(long, conflicts_with_all = ["scene", "camera", "seed", "replay", "bench_scene"])]
+    pub bvh_report: bool,
+
+    /// Print what changed between two scene files and exit (1 if they differ)
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["A", "B"],
+        conflicts_with_all = ["scene", "bench_scene", "replay"]
+    )]
+    pub diff: Option<Vec<PathBuf>>,
+
+    /// Render this many frames of the demo scene along a fixed orbit, print timing statistics and exit
+    #[arg(
+        long,
+        value_name = "FRAMES",
+        value_parser = RangedU64ValueParser::<u32>::new().range(1..=100_000),
+        conflicts_with_all = ["scene", "camera", "seed", "replay", "bench_scene", "diff"]
+    )]
+    pub benchmark: Option<u32>,
+
+    /// With --benchmark, also write the results as JSON
+    #[arg(long, value_name = "FILE", requires = "benchmark")]
+    pub benchmark_json: Option<PathBuf>,
+
+    /// With --benchmark, print the change against a JSON file from an earlier run
+    #[arg(long, value_name = "BASELINE", requires = "benchmark")]
+    pub compare: Option<PathBuf>,
+
+    /// Write a gray ramp, patch and gamma checkerboard test pattern instead of the scene, check the patches and exit (1 if one is off)
+    #[arg(long, conflicts_with_all = ["scene", "camera", "replay", "bench_scene", "diff", "benchmark"])]
+    pub calibration: bool,
+
+    /// Write the built-in material presets to FILE (e.g. materials.ron) as a starting point and exit
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["scene", "bench_scene", "diff", "benchmark", "calibration"])]
+    pub export_presets: Option<PathBuf>,
+}
+
+fn log_scene_summary(scene: &Scene, source: &str) {
+    SceneReport::new(scene).log(source);
+}
+
+fn parse_render_scale(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(scale) if (0.1..=1.0).contains(&scale) => Ok(scale),
+        _ => Err(format!("expected a number from 0.1 to 1, got '{}'", value)),
+    }
+}
+
+fn parse_fov(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(fov) if (10.0..=150.0).contains(&fov) => Ok(fov),
+        _ => Err(format!("expected degrees from 10 to 150, got '{}'", value)),
+    }
+}
+
+fn parse_fov_axis(value: &str) -> Result<FovAxis, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "vertical" => Ok(FovAxis::Vertical),
+        "horizontal" => Ok(FovAxis::Horizontal),
+        _ => Err(format!("expected vertical or horizontal, got '{}'", value)),
+    }
+}
+
+fn parse_output_transfer(value: &str) -> Result<OutputTransfer, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "raw" => Ok(OutputTransfer::Raw),
+        "srgb" => Ok(OutputTransfer::Srgb),
+        "gamma22" => Ok(OutputTransfer::Gamma22),
+        "bt709" => Ok(OutputTransfer::Bt709),
+        _ => Err(format!("expected raw, srgb, gamma22 or bt709, got '{}'", value)),
+    }
+}
+
+fn parse_bench_tier(value: &str) -> Result<BenchTier, String> {
+    BenchTier::parse(value).ok_or_else(|| format!("expected small, medium, large or mirrors, got '{}'", value))
+}
+
+impl SceneOptions {
+    /// Sends log records to stderr: info and up by default, more with `-v`.
+    /// `RUST_LOG`, when set, takes precedence.
+    pub fn init_logging(&self) {
+        let level = match self.verbose {
+            0 => LevelFilter::Info,
+            1 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        };
+        env_logger::Builder::new()
+            .filter_level(level)
+            .format_timestamp(None)
+            .parse_env("RUST_LOG")
+            .init();
+    }
+
+    /// Reads the config file and copies the options that were given over it.
+    pub fn load_config(&self) -> Config {
+        let mut config = Config::load_or_create(&self.config);
+        self.apply(&mut config);
+        config
+    }
+
+    /// Copies the options that were given over the values read from the config file.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(width) = self.width {
+            config.window.width = width;
+        }
+        if let Some(height) = self.height {
+            config.window.height = height;
+        }
+        if let Some(fov) = self.fov {
+            config.camera.fov = fov;
+        }
+        if let Some(axis) = self.fov_axis {
+            config.camera.fov_axis = axis;
+        }
+        if let Some(dir) = &self.assets_dir {
+            config.assets.cli_dir = Some(dir.clone());
+        }
+        if let Some(samples) = self.samples {
+            config.quality.samples = samples;
+        }
+        if let Some(max_depth) = self.max_depth {
+            config.quality.max_depth = max_depth;
+        }
+        if let Some(transfer) = self.output_transfer {
+            config.quality.output_transfer = transfer;
+        }
+        if let Some(scale) = self.render_scale {
+            config.quality.render_scale = scale;
+        }
+        if let Some(threads) = self.threads {
+            config.quality.threads = threads;
+        }
+        if self.profile {
+            config.quality.profile = true;
+        }
+    }
+
+    /// Demo world settings with `--seed` applied.
+    pub fn world_settings(&self) -> WorldGenSettings {
+        let mut settings = WorldGenSettings::default();
+        if let (Some(seed), Some(underground)) = (self.seed, settings.underground.as_mut()) {
+            underground.seed = seed;
+        }
+        settings
+    }
+
+    /// File the editor saves the scene to: `--scene`, or `scene.ron`.
+    pub fn scene_path(&self) -> PathBuf {
+        self.scene.clone().unwrap_or_else(|| PathBuf::from(SCENE_FILE))
+    }
+
+    /// `--scene` if given (an error if it does not load), else `scene.ron` if it
+    /// exists, else the generated demo.
+    pub fn startup_scene(&self, world_settings: &WorldGenSettings) -> Result<Scene, Error> {
+        if let Some(path) = &self.scene {
+            let mut scene = load_scene(path)?;
+            scene.clouds = world_settings.clouds.as_ref().map(CloudLayer::generate);
+            log_scene_summary(&scene, &path.display().to_string());
+            return Ok(scene);
+        }
+        // A saved scene replaces the generated demo; delete it to regenerate
+        if !Path::new(SCENE_FILE).exists() {
+            let scene = demo_scene(world_settings);
+            log_scene_summary(&scene, "the generated demo");
+            return Ok(scene);
+        }
+        match load_scene(SCENE_FILE) {
+            Ok(mut scene) => {
+                scene.clouds = world_settings.clouds.as_ref().map(CloudLayer::generate);
+                log_scene_summary(&scene, SCENE_FILE);
+                Ok(scene)
+            }
+            Err(error) => {
+                warn!("Using the demo scene instead: {}", error.report());
+                let scene = demo_scene(world_settings);
+                log_scene_summary(&scene, "the generated demo");
+                Ok(scene)
+            }
+        }
+    }
+
+    /// The default view, or the bookmark named by `--camera`, with the
+    /// configured field of view.
+    pub fn startup_camera(&self, scene: &Scene, view: &CameraConfig) -> Result<Camera, String> {
+        let mut camera = Camera::new(
+            Vector3::new(0.0, 10.0, 13.0),
+            Vector3::new(0.0, 2.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0)
+        );
+        view.apply(&mut camera);
+        if let Some(name) = &self.camera {
+            let bookmark = scene.bookmarks.iter().find(|b| b.name == *name).ok_or_else(|| {
+                let names: Vec<&str> = scene.bookmarks.iter().map(|b| b.name.as_str()).collect();
+                format!("no camera bookmark named '{}' (the scene has: {})", name, names.join(", "))
+            })?;
+            camera.go_to(bookmark);
+        }
+        Ok(camera)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::error::ErrorKind;
+
+    #[test]
+    fn headless_render_parses_its_options() {
+        let cli = RenderCli::try_parse_from([
+            "raytracer-render", "--width", "320", "--height", "240", "--samples", "4", "--max-depth", "2",
+            "--render-scale", "0.5", "--fov", "70", "--seed", "7", "--output", "frame.png",
+        ])
+        .expect("a valid headless invocation");
+        assert_eq!(cli.output, PathBuf::from("frame.png"));
+        assert_eq!(cli.options.seed, Some(7));
+
+        let mut config = Config::default();
+        cli.options.apply(&mut config);
+        assert_eq!((config.window.width, config.window.height), (320, 240));
+        assert_eq!((config.quality.samples, config.quality.max_depth), (4, 2));
+        assert_eq!(config.quality.render_scale, 0.5);
+        assert_eq!(config.camera.fov, 70.0);
+    }
+
+    #[test]
+    fn output_is_rejected_by_the_window() {
+        let error = Cli::try_parse_from(["raytracer", "--output", "frame.png"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnknownArgument);
+    }
+
+    #[test]
+    fn conflicting_options_are_rejected() {
+        let error = RenderCli::try_parse_from(["raytracer-render", "--bench-scene", "small", "--scene", "scene.ron"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ArgumentConflict);
+        let error = RenderCli::try_parse_from(["raytracer-render", "--benchmark-json", "run.json"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::MissingRequiredArgument);
+        let error = RenderCli::try_parse_from(["raytracer-render", "--width", "0"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn render_scale_must_be_from_a_tenth_to_one() {
+        assert_eq!(parse_render_scale("0.1"), Ok(0.1));
+        assert_eq!(parse_render_scale("1"), Ok(1.0));
+        for value in ["0.09", "1.01", "0", "-0.5", "half", ""] {
+            assert!(parse_render_scale(value).is_err(), "{:?} was accepted", value);
+        }
+        let error = RenderCli::try_parse_from(["raytracer-render", "--render-scale", "2"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn fov_must_be_from_10_to_150_degrees() {
+        assert_eq!(parse_fov("10"), Ok(10.0));
+        assert_eq!(parse_fov("150"), Ok(150.0));
+        for value in ["9.9", "150.5", "0", "-60", "wide"] {
+            assert!(parse_fov(value).is_err(), "{:?} was accepted", value);
+        }
+        let error = Cli::try_parse_from(["raytracer", "--fov", "180"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ValueValidation);
+    }
+}
@@ -0,0 +1,203 @@
+// clouds.rs
+// Capa de nubes planas al estilo Minecraft: una rejilla 2D de celdas ocupadas
+// según ruido, a altura fija. Se intersecta con un DDA sobre la rejilla, así que
+// no vive en el BVH y puede desplazarse cada frame sin reconstruirlo.
+use std::sync::Arc;
+
+use raylib::prelude::*;
+use crate::material::Material;
+use crate::noise::value_noise_2d;
+use crate::ray_intersect::Intersect;
+
+const NOISE_SCALE: f32 = 0.35;
+const MIN_DISTANCE: f32 = 0.001;
+
+/// Forma y movimiento de la capa de nubes.
+#[derive(Debug, Clone)]
+pub struct CloudSettings {
+    /// Altura de la cara inferior de las nubes.
+    pub altitude: f32,
+    /// Grosor de las nubes.
+    pub thickness: f32,
+    /// Fracción del cielo con nube: 0.0 = despejado, 1.0 = completamente cubierto.
+    pub coverage: f32,
+    /// Tamaño de cada bloque de nube en x/z.
+    pub cell_size: f32,
+    /// La rejilla es de cells x cells.
+    pub cells: i32,
+    /// Unidades por segundo en +x.
+    pub drift_speed: f32,
+}
+
+impl Default for CloudSettings {
+    fn default() -> Self {
+        CloudSettings {
+            altitude: 14.0,
+            thickness: 1.0,
+            coverage: 0.35,
+            cell_size: 3.0,
+            cells: 24,
+            drift_speed: 0.6,
+        }
+    }
+}
+
+/// Rejilla de bloques de nube que se repite en x/z y se desplaza con el tiempo.
+pub struct CloudLayer {
+    settings: CloudSettings,
+    occupancy: Vec<bool>,
+    offset_x: f32,
+    /// Material con el que se sombrean las nubes.
+    pub material: Arc<Material>,
+}
+
+impl CloudLayer {
+    /// Decide con ruido qué celdas de la rejilla tienen nube.
+    pub fn generate(settings: &CloudSettings) -> Self {
+        let cells = settings.cells.max(1);
+        let threshold = 1.0 - settings.coverage.clamp(0.0, 1.0);
+        let mut occupancy = Vec::with_capacity((cells * cells) as usize);
+        for z in 0..cells {
+            for x in 0..cells {
+                occupancy.push(value_noise_2d(x as f32 * NOISE_SCALE, z as f32 * NOISE_SCALE) > threshold);
+            }
+        }
+
+        let mut material = Material::black();
+        material.albedo = [0.5, 0.0];
+        material.specular = 1.0;
+
+        let mut layer = CloudLayer {
+            settings: CloudSettings { cells, ..settings.clone() },
+            occupancy,
+            offset_x: 0.0,
+            material: Arc::new(material),
+        };
+        layer.update(0.0, 1.0);
+        layer
+    }
+
+    /// Desplaza las nubes según el tiempo y las tiñe según la altura del sol
+    /// (`sun_height` en [-1, 1]: seno del ángulo del sol).
+    pub fn update(&mut self, elapsed_seconds: f32, sun_height: f32) {
+        self.offset_x = elapsed_seconds * self.settings.drift_speed;
+
+        let day = Vector3::new(1.0, 1.0, 1.0);
+        let sunset = Vector3::new(1.0, 0.62, 0.45);
+        let night = Vector3::new(0.3, 0.32, 0.45);
+        let tint = if sun_height >= 0.3 {
+            day
+        } else if sun_height >= 0.0 {
+            sunset.lerp(day, sun_height / 0.3)
+        } else if sun_height >= -0.2 {
+            sunset.lerp(night, -sun_height / 0.2)
+        } else {
+            night
+        };
+
+        // La emisión hace de luz ambiente para que la cara inferior no se vea negra
+        let material = Arc::make_mut(&mut self.material);
+        material.diffuse = tint * 0.5;
+        material.emission = tint * 0.55;
+    }
+
+    /// Cuánto se desplazaron las nubes en x desde el principio.
+    pub fn drift(&self) -> f32 {
+        self.offset_x
+    }
+
+    fn occupied(&self, ix: i32, iz: i32) -> bool {
+        let cells = self.settings.cells;
+        let x = ix.rem_euclid(cells);
+        let z = iz.rem_euclid(cells);
+        self.occupancy[(z * cells + x) as usize]
+    }
+
+    /// Impacto más cercano del rayo con la capa, si lo hay.
+    pub fn intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Option<Intersect> {
+        let half = self.settings.cells as f32 * self.settings.cell_size * 0.5;
+        let min = [-half, self.settings.altitude, -half];
+        let max = [half, self.settings.altitude + self.settings.thickness, half];
+        let o = [ray_origin.x, ray_origin.y, ray_origin.z];
+        let d = [ray_direction.x, ray_direction.y, ray_direction.z];
+
+        // Recorte contra la caja que contiene toda la capa
+        let mut t_enter = f32::NEG_INFINITY;
+        let mut t_exit = f32::INFINITY;
+        let mut enter_axis = 1;
+        for axis in 0..3 {
+            if d[axis].abs() < 1e-8 {
+                if o[axis] < min[axis] || o[axis] > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let inv = 1.0 / d[axis];
+            let mut t0 = (min[axis] - o[axis]) * inv;
+            let mut t1 = (max[axis] - o[axis]) * inv;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > t_enter {
+                t_enter = t0;
+                enter_axis = axis;
+            }
+            t_exit = t_exit.min(t1);
+        }
+        if t_enter > t_exit || t_exit < MIN_DISTANCE {
+            return None;
+        }
+
+        let mut t = t_enter.max(MIN_DISTANCE);
+        let mut normal = if t_enter >= MIN_DISTANCE {
+            let mut n = [0.0; 3];
+            n[enter_axis] = -d[enter_axis].signum();
+            Vector3::new(n[0], n[1], n[2])
+        } else {
+            // El rayo empieza dentro de la capa: la normal mira hacia el origen
+            Vector3::new(0.0, -ray_direction.y.signum(), 0.0)
+        };
+
+        // DDA sobre la rejilla, en el espacio desplazado de las nubes
+        let cell = self.settings.cell_size;
+        let px = ray_origin.x + ray_direction.x * t - self.offset_x;
+        let pz = ray_origin.z + ray_direction.z * t;
+        let mut ix = (px / cell).floor() as i32;
+        let mut iz = (pz / cell).floor() as i32;
+
+        let axis_step = |p: f32, i: i32, dir: f32| -> (i32, f32, f32) {
+            if dir > 0.0 {
+                (1, t + ((i + 1) as f32 * cell - p) / dir, cell / dir)
+            } else if dir < 0.0 {
+                (-1, t + (i as f32 * cell - p) / dir, -cell / dir)
+            } else {
+                (0, f32::INFINITY, f32::INFINITY)
+            }
+        };
+        let (step_x, mut t_max_x, t_delta_x) = axis_step(px, ix, ray_direction.x);
+        let (step_z, mut t_max_z, t_delta_z) = axis_step(pz, iz, ray_direction.z);
+
+        let max_steps = 2 * self.settings.cells + 4;
+        for _ in 0..max_steps {
+            if self.occupied(ix, iz) {
+                let point = *ray_origin + *ray_direction * t;
+                return Some(Intersect::new(Arc::clone(&self.material), t, normal, point, 0.0, 0.0));
+            }
+            if t_max_x < t_max_z {
+                t = t_max_x;
+                ix += step_x;
+                t_max_x += t_delta_x;
+                normal = Vector3::new(-step_x as f32, 0.0, 0.0);
+            } else {
+                t = t_max_z;
+                iz += step_z;
+                t_max_z += t_delta_z;
+                normal = Vector3::new(0.0, 0.0, -step_z as f32);
+            }
+            if t > t_exit {
+                return None;
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,615 @@
+// config.rs
+// Persistent defaults read from raytracer.toml. Built-in defaults fill whatever
+// the file leaves out, and command-line options override both (see cli.rs).
+// The loaded `Config` is what the rest of the app reads its window size,
+// field of view, controls, quality, asset directory, HUD and key bindings from.
+use std::f32::consts::PI;
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::camera::{Camera, FovAxis};
+use crate::hud::HudMode;
+use crate::input::KeyBindings;
+use crate::perf_log::PERFORMANCE_LOG;
+use crate::scene_watch::ReloadEdits;
+use crate::settings::{OutputTransfer, RenderSettings, Schedule, ToneMapping};
+
+/// Read from the working directory unless `--config` names another file.
+pub const CONFIG_FILE: &str = "raytracer.toml";
+
+/// Everything read from the config file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Window size.
+    pub window: WindowConfig,
+    /// Field of view.
+    pub camera: CameraConfig,
+    /// Camera and simulation speeds.
+    pub controls: ControlsConfig,
+    /// Renderer quality.
+    pub quality: QualityConfig,
+    /// Where textures are read from.
+    pub assets: AssetsConfig,
+    /// Text overlay.
+    pub hud: HudConfig,
+    /// Per-frame timing log of the window.
+    pub performance_log: PerfLogConfig,
+    /// Reloading the scene file when it changes on disk.
+    pub hot_reload: HotReloadConfig,
+    /// The in-window settings panel.
+    pub settings_panel: SettingsPanelConfig,
+    /// Pausing the renderer.
+    pub pause: PauseConfig,
+    /// The session dump written on exit.
+    pub session: SessionConfig,
+    /// Key and mouse bindings of the window.
+    pub keys: KeyBindings,
+}
+
+/// Window size in pixels, also the size of `raytracer-render` images.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    /// Width in pixels.
+    pub width: i32,
+    /// Height in pixels.
+    pub height: i32,
+    /// Frame rate cap of the interactive window; 0 leaves it uncapped.
+    pub max_fps: u32,
+    /// Waits for the display's vertical blank before presenting a frame.
+    pub vsync: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig { width: 640, height: 480, max_fps: 0, vsync: false }
+    }
+}
+
+/// Field of view of the camera.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraConfig {
+    /// Field of view in degrees, along `fov_axis`.
+    #[serde(serialize_with = "short_f32")]
+    pub fov: f32,
+    /// Which axis keeps `fov` when the window proportions change.
+    pub fov_axis: FovAxis,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        CameraConfig { fov: 60.0, fov_axis: FovAxis::Vertical }
+    }
+}
+
+impl CameraConfig {
+    /// Gives `camera` the configured field of view.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.fov = self.fov.to_radians();
+        camera.fov_axis = self.fov_axis;
+    }
+}
+
+/// Per-frame speeds of the camera and the day/night cycle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlsConfig {
+    /// Radians per frame while an orbit key is held.
+    #[serde(serialize_with = "short_f32")]
+    pub rotation_speed: f32,
+    /// Blocks per frame while a zoom key is held.
+    #[serde(serialize_with = "short_f32")]
+    pub zoom_speed: f32,
+    /// Blocks per frame while raising or lowering the camera.
+    #[serde(serialize_with = "short_f32")]
+    pub vertical_speed: f32,
+    /// Radians of the day/night cycle per frame.
+    #[serde(serialize_with = "short_f32")]
+    pub day_night_speed: f32,
+    /// Start with the camera slowly orbiting the scene.
+    pub auto_rotate: bool,
+    /// While a camera key is held, draw a flat rasterized preview instead of
+    /// tracing; see `preview`.
+    pub raster_preview: bool,
+}
+
+impl Default for ControlsConfig {
+    fn default() -> Self {
+        ControlsConfig {
+            rotation_speed: PI / 100.0,
+            zoom_speed: 0.15,
+            vertical_speed: 0.15,
+            day_night_speed: 0.01,
+            auto_rotate: true,
+            raster_preview: false,
+        }
+    }
+}
+
+/// The `RenderSettings` worth keeping between runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QualityConfig {
+    /// Edge anti-aliasing rays per pixel; 1 turns the edge pass off.
+    pub samples: u32,
+    /// Reflection and refraction bounces.
+    pub max_depth: u32,
+    /// See `RenderSettings::half_res_secondary`.
+    pub half_res_secondary: bool,
+    /// See `RenderSettings::bounce_budget`.
+    pub bounce_budget: u32,
+    /// Fraction of the resolution the CPU traces at.
+    #[serde(serialize_with = "short_f32")]
+    pub render_scale: f32,
+    /// Render worker threads; 0 uses every core.
+    pub threads: usize,
+    /// See `RenderSettings::schedule`.
+    pub schedule: Schedule,
+    /// See `RenderSettings::shadow_blocker_samples`.
+    pub shadow_blocker_samples: u32,
+    /// See `RenderSettings::shadow_filter_samples`.
+    pub shadow_filter_samples: u32,
+    /// See `RenderSettings::sun_shadow_map`.
+    pub sun_shadow_map: bool,
+    /// See `RenderSettings::shadow_map_resolution`.
+    pub shadow_map_resolution: u32,
+    /// See `RenderSettings::packet_traversal`.
+    pub packet_traversal: bool,
+    /// See `RenderSettings::bvh_sah_buckets`.
+    pub bvh_sah_buckets: u32,
+    /// See `RenderSettings::bvh_leaf_size`.
+    pub bvh_leaf_size: u32,
+    /// See `RenderSettings::energy_conserving`.
+    pub energy_conserving: bool,
+    /// Tone mapping operator at startup.
+    pub tone_mapping: ToneMapping,
+    /// Linear multiplier applied before tone mapping.
+    #[serde(serialize_with = "short_f32")]
+    pub exposure: f32,
+    /// Radiance that Reinhard maps to white.
+    #[serde(serialize_with = "short_f32")]
+    pub white_point: f32,
+    /// See `RenderSettings::output_transfer`.
+    pub output_transfer: OutputTransfer,
+    /// See `RenderSettings::dither`.
+    pub dither: bool,
+    /// See `RenderSettings::texture_lod_distance`.
+    #[serde(serialize_with = "short_f32")]
+    pub texture_lod_distance: f32,
+    /// See `RenderSettings::emissive_soft_radius`.
+    #[serde(serialize_with = "short_f32")]
+    pub emissive_soft_radius: f32,
+    /// See `RenderSettings::emissive_cull_epsilon`.
+    #[serde(serialize_with = "short_f32")]
+    pub emissive_cull_epsilon: f32,
+    /// See `RenderSettings::bloom`.
+    pub bloom: bool,
+    /// See `RenderSettings::bloom_threshold`.
+    #[serde(serialize_with = "short_f32")]
+    pub bloom_threshold: f32,
+    /// See `RenderSettings::bloom_intensity`.
+    #[serde(serialize_with = "short_f32")]
+    pub bloom_intensity: f32,
+    /// See `RenderSettings::bloom_radius`.
+    pub bloom_radius: u32,
+    /// See `RenderSettings::profile`.
+    pub profile: bool,
+    /// See `RenderSettings::emissive_shadow_cache`.
+    pub emissive_shadow_cache: bool,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        // 0 rather than this machine's core count, so the file moves between machines
+        let mut quality = QualityConfig::from(&RenderSettings::default());
+        quality.threads = 0;
+        quality
+    }
+}
+
+impl From<&RenderSettings> for QualityConfig {
+    /// The persistent part of `settings`, as `[quality]` would need to hold it
+    /// to reproduce them.
+    fn from(settings: &RenderSettings) -> Self {
+        QualityConfig {
+            samples: settings.edge_samples,
+            max_depth: settings.max_depth,
+            half_res_secondary: settings.half_res_secondary,
+            bounce_budget: settings.bounce_budget,
+            render_scale: settings.render_scale,
+            threads: settings.threads,
+            schedule: settings.schedule,
+            shadow_blocker_samples: settings.shadow_blocker_samples,
+            shadow_filter_samples: settings.shadow_filter_samples,
+            sun_shadow_map: settings.sun_shadow_map,
+            shadow_map_resolution: settings.shadow_map_resolution,
+            packet_traversal: settings.packet_traversal,
+            bvh_sah_buckets: settings.bvh_sah_buckets,
+            bvh_leaf_size: settings.bvh_leaf_size,
+            energy_conserving: settings.energy_conserving,
+            tone_mapping: settings.tone_mapping,
+            exposure: settings.exposure,
+            white_point: settings.white_point,
+            output_transfer: settings.output_transfer,
+            dither: settings.dither,
+            texture_lod_distance: settings.texture_lod_distance,
+            emissive_soft_radius: settings.emissive_soft_radius,
+            emissive_cull_epsilon: settings.emissive_cull_epsilon,
+            bloom: settings.bloom,
+            bloom_threshold: settings.bloom_threshold,
+            bloom_intensity: settings.bloom_intensity,
+            bloom_radius: settings.bloom_radius,
+            profile: settings.profile,
+            emissive_shadow_cache: settings.emissive_shadow_cache,
+        }
+    }
+}
+
+/// Asset locations.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssetsConfig {
+    /// Directory that texture paths written as `assets/...` are read from.
+    pub dir: PathBuf,
+    /// Longest side, in texels, of the CPU copy of a texture; larger ones are
+    /// downscaled on load.
+    pub max_texture_size: u32,
+    /// `--assets-dir`, searched before `dir`; never written to the file.
+    #[serde(skip)]
+    pub cli_dir: Option<PathBuf>,
+}
+
+impl Default for AssetsConfig {
+    fn default() -> Self {
+        AssetsConfig { dir: PathBuf::from("assets"), max_texture_size: 1024, cli_dir: None }
+    }
+}
+
+/// The per-frame CSV timing log of the interactive window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PerfLogConfig {
+    /// Write the log at all; `--no-perf-log` turns it off for one run.
+    pub enabled: bool,
+    /// The CSV file, overwritten on every run.
+    pub path: PathBuf,
+}
+
+impl Default for PerfLogConfig {
+    fn default() -> Self {
+        PerfLogConfig { enabled: true, path: PathBuf::from(PERFORMANCE_LOG) }
+    }
+}
+
+/// Reloading the scene file while the window runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotReloadConfig {
+    /// Watch the scene file and reload it when it changes.
+    pub enabled: bool,
+    /// What happens to unsaved edits when the file changes under them.
+    pub on_edits: ReloadEdits,
+}
+
+impl Default for HotReloadConfig {
+    fn default() -> Self {
+        HotReloadConfig { enabled: true, on_edits: ReloadEdits::Ask }
+    }
+}
+
+/// The in-window settings panel.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SettingsPanelConfig {
+    /// Write the settings back to [quality] on exit; the panel can change it.
+    pub save_on_exit: bool,
+}
+
+/// Pausing the renderer, by key or when the window loses focus.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PauseConfig {
+    /// Pause while the window is not focused.
+    pub when_unfocused: bool,
+    /// Loop iterations per second while paused.
+    pub idle_fps: u32,
+    /// Advance the day/night cycle by the time spent paused on resume.
+    pub clock_runs: bool,
+    /// Present the last frame again instead of tracing it anew while nothing
+    /// it shows has changed.
+    pub when_unchanged: bool,
+}
+
+impl Default for PauseConfig {
+    fn default() -> Self {
+        PauseConfig { when_unfocused: true, idle_fps: 10, clock_runs: false, when_unchanged: true }
+    }
+}
+
+/// Picking up where the last run of the window left off.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// Reopen the scene and restore the camera from `last_session.json`.
+    pub restore: bool,
+}
+
+/// What the text overlay shows.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HudConfig {
+    /// The view the window starts in; F1 cycles through them.
+    pub mode: HudMode,
+    /// Include the key hint lines in the full view.
+    pub hints: bool,
+    /// Draw the HUD into screenshots too.
+    pub in_screenshots: bool,
+}
+
+impl Default for HudConfig {
+    fn default() -> Self {
+        HudConfig { mode: HudMode::Full, hints: true, in_screenshots: false }
+    }
+}
+
+// f32 written through its shortest decimal form, so 0.15 is not saved as
+// 0.15000000596046448
+fn short_f32<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(value.to_string().parse().unwrap_or(*value as f64))
+}
+
+// Comments written above each section and key of a new config file
+const SECTION_COMMENTS: &[(&str, &str)] = &[
+    ("window", "Window size in pixels (also the raytracer-render image size)"),
+    ("camera", "Field of view; --fov and --fov-axis override these"),
+    ("controls", "Camera and simulation speeds, per frame"),
+    ("quality", "Renderer quality; --samples, --max-depth, --render-scale and --threads override these"),
+    ("assets", "Where textures are read from"),
+    ("hud", "Text overlay"),
+    ("performance_log", "Per-frame CSV timings of the window, with a summary appended on exit"),
+    ("settings_panel", "The in-window settings panel (F10 by default)"),
+    ("pause", "Pausing the renderer (P by default); a paused window keeps showing the last frame"),
+    ("session", "The exit summary and last_session.json, written on close and on a crash"),
+    ("hot_reload", "Reloading the scene file when it changes on disk; the camera stays where it is"),
+    ("keys", "Key bindings: A-Z, 0-9, F1-F12, SPACE, ENTER, TAB, arrows (LEFT, RIGHT, UP, DOWN),\n# COMMA, PERIOD, MINUS, EQUAL, SLASH, LEFT_BRACKET, RIGHT_BRACKET, ..., or MOUSE_LEFT,\n# MOUSE_RIGHT, MOUSE_MIDDLE. Each one can be bound once; 1-9 are kept for the render layers"),
+];
+
+const KEY_COMMENTS: &[(&str, &str, &str)] = &[
+    ("window", "max_fps", "Frame rate cap of the window (1-1000); the loop sleeps out the rest of each frame.\n# 0 renders frames as fast as it can"),
+    ("window", "vsync", "Present frames in step with the display's refresh; works with or without max_fps"),
+    ("camera", "fov", "Degrees, from 10 to 150"),
+    ("camera", "fov_axis", "\"vertical\" keeps the height's view and widens with the window, \"horizontal\" keeps the width's"),
+    ("controls", "rotation_speed", "Radians while an orbit key is held"),
+    ("controls", "zoom_speed", "Blocks while a zoom key is held"),
+    ("controls", "vertical_speed", "Blocks while raising or lowering the camera"),
+    ("controls", "day_night_speed", "Radians of the day/night cycle"),
+    ("controls", "auto_rotate", "Start with the camera orbiting the scene"),
+    ("controls", "raster_preview", "While a camera key is held, show a flat rasterized preview instead of tracing each frame;\n# the traced image comes back as soon as the keys are let go"),
+    ("quality", "samples", "Rays per pixel on geometry edges (1-64); 1 turns edge anti-aliasing off"),
+    ("quality", "max_depth", "Reflection and refraction bounces per primary ray (0-16)"),
+    ("quality", "half_res_secondary", "Trace reflections and refractions for one pixel in four and share them with the neighbours\n# on the same surface; direct light stays at full resolution"),
+    ("quality", "bounce_budget", "Reflection and refraction rays a camera ray may spawn in all; past it they return the sky.\n# Caps the cost of glass over water at a high max_depth. 0 leaves it unlimited"),
+    ("quality", "render_scale", "Fraction of the resolution the CPU traces at (0.1-1)"),
+    ("quality", "threads", "Render worker threads; 0 uses every core"),
+    ("quality", "schedule", "How the frame is split between the threads: \"bands\" of rows, \"grouped_bands\" (fewer, larger\n# jobs) or \"center_out\" tiles, the middle of the frame first; --bench-scene times each"),
+    ("quality", "sun_shadow_map", "Sun shadows from a depth map, traced again when the sun or the blocks move, instead of\n# shadow rays; hard-edged, and faster once the map exists"),
+    ("quality", "shadow_map_resolution", "Texels along each side of the sun's shadow map (64-8192)"),
+    ("quality", "bvh_sah_buckets", "Split planes the BVH build tries per node (2-32); 6 builds what the bvh crate builds.\n# raytracer-render --bvh-report compares them on the benchmark scenes"),
+    ("quality", "bvh_leaf_size", "Cubes tested one by one once the BVH reaches a subtree this small, skipping its inner\n# boxes (1-16); 1 tests every box"),
+    ("quality", "tone_mapping", "\"clamp\" or \"reinhard\""),
+    ("quality", "output_transfer", "Encoding of the 8-bit window and PNGs: \"raw\" (values as they are), \"srgb\", \"gamma22\"\n# or \"bt709\" for video pipelines"),
+    ("quality", "dither", "Add a fixed pattern of sub-step noise before the 8-bit rounding, so the sky gradient does not\n# band"),
+    ("quality", "texture_lod_distance", "Blocks farther than this take their texture's average over a 4x4 grid instead of single\n# texels, which is cheaper and shimmers less; 0 turns it off"),
+    ("quality", "emissive_soft_radius", "Soft minimum distance of block light, in block sizes"),
+    ("quality", "emissive_cull_epsilon", "Skip the light and shadow ray of a torch or magma block that could add less than this to a\n# color channel (1/255 is one 8-bit step at exposure 1); 0 lights with every nearby block"),
+    ("quality", "emissive_shadow_cache", "Trace torch and magma shadows once per block face each frame and share them across the\n# face; faster with many torches, but shadows no longer vary within a face"),
+    ("quality", "bloom", "Glow around emissive blocks"),
+    ("quality", "bloom_threshold", "HDR luminance a pixel must exceed to glow; sunlit diffuse surfaces stay below 1.0"),
+    ("quality", "bloom_intensity", "How much of the glow is added back"),
+    ("quality", "bloom_radius", "Glow blur radius in pixels (0-64)"),
+    ("quality", "profile", "Count rays, BVH node visits and cube tests and time each stage of the frame, for the graphs\n# view of the HUD, the performance log and --benchmark"),
+    ("assets", "dir", "Texture paths written as assets/... are read from here, after --assets-dir and before\n# an assets folder next to the executable"),
+    ("assets", "max_texture_size", "Longest side of a texture as the tracer samples it (16-16384); larger images are\n# box-filtered down on load, keeping their aspect ratio"),
+    ("hud", "mode", "View at startup: \"off\", \"minimal\", \"full\" or \"graphs\"; F1 cycles them"),
+    ("hud", "hints", "Include the key hint lines in the full view"),
+    ("hud", "in_screenshots", "Draw the HUD into screenshots too; off, they show only the rendered frame"),
+    ("performance_log", "enabled", "Write the log; --no-perf-log turns it off for one run"),
+    ("performance_log", "path", "CSV file, overwritten on every run"),
+    ("settings_panel", "save_on_exit", "Write the render settings back to [quality] when the window closes"),
+    ("pause", "when_unfocused", "Pause while the window is not focused"),
+    ("pause", "idle_fps", "Loop iterations per second while paused (1-60)"),
+    ("pause", "clock_runs", "On resume, move the day/night cycle on by the time spent paused"),
+    ("pause", "when_unchanged", "Show the last frame again, without tracing, while the camera, lights, time, settings and scene are unchanged"),
+    ("session", "restore", "Reopen the last --scene and restore the camera and time of day; --scene and --camera override it"),
+    ("hot_reload", "enabled", "Watch the scene file (--scene, or scene.ron) while the window runs"),
+    ("hot_reload", "on_edits", "With unsaved edits: \"ask\" in the HUD, \"discard\" them or \"keep\" them and skip the reload"),
+];
+
+impl Config {
+    /// Reads `path`, writing a commented default file first if there is none.
+    /// A file that does not parse or holds invalid values is reported and the
+    /// built-in defaults are used instead; unknown keys are reported and skipped.
+    pub fn load_or_create(path: &Path) -> Config {
+        if !path.exists() {
+            let config = Config::default();
+            match std::fs::write(path, config.to_commented_toml()) {
+                Ok(()) => info!("Wrote default settings to {}", path.display()),
+                Err(error) => warn!("Could not write {}: {}", path.display(), error),
+            }
+            return config;
+        }
+        match Config::load(path) {
+            Ok(config) => config,
+            Err(error) => {
+                warn!("{}, using the default settings", error);
+                Config::default()
+            }
+        }
+    }
+
+    /// Reads and validates `path`, printing a warning for each unknown key.
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        let config: Config = toml::from_str(&text)
+            .map_err(|e| format!("Could not parse {}: {}", path.display(), e.to_string().trim_end()))?;
+        config.validate().map_err(|e| format!("{}: {}", path.display(), e))?;
+        for (line, key) in unknown_keys(&text) {
+            warn!("{}:{}: unknown setting '{}' ignored", path.display(), line, key);
+        }
+        Ok(config)
+    }
+
+    /// Same limits as the command-line options.
+    pub fn validate(&self) -> Result<(), String> {
+        let check = |ok: bool, message: &str| if ok { Ok(()) } else { Err(message.to_string()) };
+        check((1..=8192).contains(&self.window.width), "window.width must be from 1 to 8192")?;
+        check((1..=8192).contains(&self.window.height), "window.height must be from 1 to 8192")?;
+        check(self.window.max_fps <= 1000, "window.max_fps must be from 0 to 1000")?;
+        check((10.0..=150.0).contains(&self.camera.fov), "camera.fov must be from 10 to 150 degrees")?;
+        check((1..=64).contains(&self.quality.samples), "quality.samples must be from 1 to 64")?;
+        check(self.quality.max_depth <= 16, "quality.max_depth must be from 0 to 16")?;
+        check((0.1..=1.0).contains(&self.quality.render_scale), "quality.render_scale must be from 0.1 to 1")?;
+        check(self.quality.threads <= 1024, "quality.threads must be from 0 to 1024")?;
+        check((64..=8192).contains(&self.quality.shadow_map_resolution), "quality.shadow_map_resolution must be from 64 to 8192")?;
+        check((2..=32).contains(&self.quality.bvh_sah_buckets), "quality.bvh_sah_buckets must be from 2 to 32")?;
+        check((1..=16).contains(&self.quality.bvh_leaf_size), "quality.bvh_leaf_size must be from 1 to 16")?;
+        check(self.quality.exposure > 0.0, "quality.exposure must be positive")?;
+        check(self.quality.white_point >= 0.5, "quality.white_point must be at least 0.5")?;
+        check(self.quality.texture_lod_distance >= 0.0, "quality.texture_lod_distance must not be negative")?;
+        check(self.quality.emissive_cull_epsilon >= 0.0, "quality.emissive_cull_epsilon must not be negative")?;
+        check(self.quality.bloom_threshold >= 0.0, "quality.bloom_threshold must not be negative")?;
+        check(self.quality.bloom_intensity >= 0.0, "quality.bloom_intensity must not be negative")?;
+        check(self.quality.bloom_radius <= 64, "quality.bloom_radius must be from 0 to 64")?;
+        check((1..=60).contains(&self.pause.idle_fps), "pause.idle_fps must be from 1 to 60")?;
+        check((16..=16384).contains(&self.assets.max_texture_size), "assets.max_texture_size must be from 16 to 16384")?;
+        self.keys.validate()?;
+        Ok(())
+    }
+
+    /// Renderer settings with the configured quality on top of the defaults.
+    pub fn render_settings(&self) -> RenderSettings {
+        let quality = &self.quality;
+        let defaults = RenderSettings::default();
+        RenderSettings {
+            edge_samples: quality.samples,
+            max_depth: quality.max_depth,
+            half_res_secondary: quality.half_res_secondary,
+            bounce_budget: quality.bounce_budget,
+            render_scale: quality.render_scale,
+            threads: if quality.threads == 0 { defaults.threads } else { quality.threads },
+            schedule: quality.schedule,
+            shadow_blocker_samples: quality.shadow_blocker_samples,
+            shadow_filter_samples: quality.shadow_filter_samples,
+            sun_shadow_map: quality.sun_shadow_map,
+            shadow_map_resolution: quality.shadow_map_resolution,
+            packet_traversal: quality.packet_traversal,
+            bvh_sah_buckets: quality.bvh_sah_buckets,
+            bvh_leaf_size: quality.bvh_leaf_size,
+            energy_conserving: quality.energy_conserving,
+            tone_mapping: quality.tone_mapping,
+            exposure: quality.exposure,
+            white_point: quality.white_point,
+            output_transfer: quality.output_transfer,
+            dither: quality.dither,
+            texture_lod_distance: quality.texture_lod_distance,
+            emissive_soft_radius: quality.emissive_soft_radius,
+            emissive_cull_epsilon: quality.emissive_cull_epsilon,
+            bloom: quality.bloom,
+            bloom_threshold: quality.bloom_threshold,
+            bloom_intensity: quality.bloom_intensity,
+            bloom_radius: quality.bloom_radius,
+            profile: quality.profile,
+            emissive_shadow_cache: quality.emissive_shadow_cache,
+            ..defaults
+        }
+    }
+
+    /// Writes `quality` over the [quality] section of `path`, creating the
+    /// file if needed. The rest of the file, comments included, stays as it is.
+    pub fn save_quality(path: &Path, quality: &QualityConfig) -> Result<(), String> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(format!("Could not read {}: {}", path.display(), error)),
+        };
+        let mut document: toml_edit::DocumentMut = text
+            .parse()
+            .map_err(|e: toml_edit::TomlError| format!("Could not parse {}: {}", path.display(), e.to_string().trim_end()))?;
+        let values: toml_edit::DocumentMut = toml::to_string(quality)
+            .expect("QualityConfig always serializes")
+            .parse()
+            .expect("serialized settings parse");
+        if !document.contains_table("quality") {
+            document.insert("quality", toml_edit::table());
+        }
+        let table = document["quality"].as_table_mut().expect("[quality] is a table");
+        for (key, item) in values.iter() {
+            table.insert(key, item.clone());
+        }
+        std::fs::write(path, document.to_string()).map_err(|e| format!("Could not write {}: {}", path.display(), e))
+    }
+
+    fn to_commented_toml(&self) -> String {
+        let text = toml::to_string_pretty(self).expect("Config always serializes");
+        let mut document: toml_edit::DocumentMut = text.parse().expect("serialized config parses");
+        for &(section, comment) in SECTION_COMMENTS {
+            if let Some(table) = document.get_mut(section).and_then(|item| item.as_table_mut()) {
+                table.decor_mut().set_prefix(format!("\n# {}\n", comment));
+            }
+        }
+        for &(section, key, comment) in KEY_COMMENTS {
+            if let Some(table) = document.get_mut(section).and_then(|item| item.as_table_mut()) {
+                if let Some(mut key) = table.key_mut(key) {
+                    key.leaf_decor_mut().set_prefix(format!("# {}\n", comment));
+                }
+            }
+        }
+        format!(
+            "# Settings for the raytracer. Delete this file to get the defaults back;\n\
+             # command-line options override anything set here.\n{}",
+            document
+        )
+    }
+}
+
+/// Keys in `text` that `Config` does not have, as (line, dotted path).
+fn unknown_keys(text: &str) -> Vec<(usize, String)> {
+    let Ok(document) = toml_edit::ImDocument::parse(text) else {
+        return Vec::new();
+    };
+    let known = toml::Table::try_from(Config::default()).expect("Config always serializes");
+    let mut unknown = Vec::new();
+    collect_unknown(text, document.as_table(), &known, "", &mut unknown);
+    unknown
+}
+
+fn collect_unknown(
+    text: &str,
+    table: &dyn toml_edit::TableLike,
+    known: &toml::Table,
+    prefix: &str,
+    unknown: &mut Vec<(usize, String)>,
+) {
+    for (name, item) in table.iter() {
+        let path = format!("{}{}", prefix, name);
+        match known.get(name) {
+            None => {
+                let line = table
+                    .get_key_value(name)
+                    .and_then(|(key, _)| key.span())
+                    .map_or(0, |span| text[..span.start].matches('\n').count() + 1);
+                unknown.push((line, path));
+            }
+            Some(toml::Value::Table(inner)) => {
+                if let Some(item) = item.as_table_like() {
+                    collect_unknown(text, item, inner, &format!("{}.", path), unknown);
+                }
+            }
+            Some(_) => {}
+        }
+    }
+}
@@ -1,51 +1,272 @@
 // src/cube.rs
 use raylib::prelude::Vector3;
+use crate::accel::TracerRay;
 use crate::ray_intersect::{Intersect, RayIntersect};
 use crate::material::Material;
+use std::cmp::Ordering;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::Arc;
 use bvh::aabb::{AABB, Bounded};
 use bvh::bounding_hierarchy::BHShape;
-use nalgebra::{Point3, Vector3 as NVector3};
+use nalgebra::Point3;
 
+/// Bloque alineado con los ejes; la pieza básica de la escena.
 #[derive(Clone)]
 pub struct Cube {
+    /// Esquina mínima.
     pub min_bounds: Vector3,
+    /// Esquina máxima.
     pub max_bounds: Vector3,
-    pub material: Material,
+    /// Material de todas sus caras, compartido con los demás cubos que lo usan.
+    pub material: Arc<Material>,
+    /// Grupo con nombre ("trees", "house"...) para ocultarlo en bloque.
+    pub group: Option<Arc<str>>,
+    /// Capa de render (0..LAYER_COUNT), se muestra u oculta con las teclas 1-9.
+    pub layer: u8,
     node_index: usize,
+    id: u64,
 }
 
+// Siguiente identificador libre; el 0 no se reparte
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
 impl Cube {
-    /// Crea un nuevo cubo a partir de un centro y un tamaño.
-    pub fn new(center: Vector3, size: f32, material: Material) -> Self {
-        let half_size = Vector3::new(size / 2.0, size / 2.0, size / 2.0);
+    /// Crea un nuevo cubo a partir de un centro y un tamaño. Acepta un
+    /// `Material` suelto o un `Arc` para compartirlo; `Scene::new` junta luego
+    /// los materiales iguales en uno solo.
+    pub fn new(center: Vector3, size: f32, material: impl Into<Arc<Material>>) -> Self {
+        Self::with_extent(center, Vector3::new(size, size, size), material)
+    }
+
+    /// Caja de lados `extent` (ancho, alto, fondo) centrada en `center`.
+    pub fn with_extent(center: Vector3, extent: Vector3, material: impl Into<Arc<Material>>) -> Self {
+        let half_size = extent * 0.5;
         Self {
             min_bounds: center - half_size,
             max_bounds: center + half_size,
-            material,
+            material: material.into(),
+            group: None,
+            layer: 0,
             node_index: 0, // Default value
+            id: NEXT_ID.fetch_add(1, AtomicOrdering::Relaxed),
         }
     }
 
-    /// Calcula las coordenadas UV para texturizar, basándose en el punto de intersección y la normal de la cara.
-    fn get_uv(&self, point: &Vector3, normal: &Vector3) -> (f32, f32) {
+    /// Identificador estable: no cambia al moverlo, al reconstruir el BVH ni
+    /// cuando otros cubos entran o salen de la escena. Las copias lo conservan,
+    /// así que un cubo quitado y devuelto por deshacer sigue siendo el mismo.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// El mismo cubo dentro de `group`.
+    pub fn in_group(mut self, group: Option<Arc<str>>) -> Self {
+        self.group = group;
+        self
+    }
+
+    /// El mismo cubo en la capa `layer`.
+    pub fn on_layer(mut self, layer: u8) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    /// Indica si el punto está dentro del cubo (incluyendo el borde).
+    pub fn contains(&self, point: &Vector3) -> bool {
+        point.x >= self.min_bounds.x && point.x <= self.max_bounds.x &&
+        point.y >= self.min_bounds.y && point.y <= self.max_bounds.y &&
+        point.z >= self.min_bounds.z && point.z <= self.max_bounds.z
+    }
+
+    /// Celda entera de la rejilla si es un bloque de 1x1x1 alineado a enteros.
+    pub fn grid_cell(&self) -> Option<(i32, i32, i32)> {
+        let size = self.max_bounds - self.min_bounds;
+        let center = (self.min_bounds + self.max_bounds) * 0.5;
+        let aligned = [center.x, center.y, center.z].iter().all(|c| (c - c.round()).abs() < 1e-4);
+        let unit = [size.x, size.y, size.z].iter().all(|s| (s - 1.0).abs() < 1e-4);
+        if aligned && unit {
+            Some((center.x.round() as i32, center.y.round() as i32, center.z.round() as i32))
+        } else {
+            None
+        }
+    }
+
+    /// Calcula las coordenadas UV para texturizar, basándose en el punto de
+    /// intersección y la normal de la cara, y la proporción (ancho / alto) de la cara.
+    fn get_uv(&self, point: &Vector3, normal: &Vector3) -> (f32, f32, f32) {
         let size = self.max_bounds - self.min_bounds;
         let u: f32;
         let v: f32;
+        let aspect: f32;
 
         if normal.x.abs() > 0.5 { // Caras laterales (normal en X)
             u = (point.z - self.min_bounds.z) / size.z;
             v = (point.y - self.min_bounds.y) / size.y;
+            aspect = size.z / size.y;
         } else if normal.y.abs() > 0.5 { // Caras superior/inferior (normal en Y)
             u = (point.x - self.min_bounds.x) / size.x;
             v = (point.z - self.min_bounds.z) / size.z;
+            aspect = size.x / size.z;
         } else { // Caras frontal/trasera (normal en Z)
             u = (point.x - self.min_bounds.x) / size.x;
             v = (point.y - self.min_bounds.y) / size.y;
+            aspect = size.x / size.y;
         }
-        (u, v)
+        (u, v, aspect)
+    }
+
+    /// Límites del cubo, la parte que se prueba en el recorrido.
+    pub fn bounds(&self) -> CubeBounds {
+        CubeBounds { min: self.min_bounds, max: self.max_bounds }
+    }
+
+    /// Implementa el test de intersección rayo-cubo usando el método "Slab", con la
+    /// inversa de la dirección que el rayo ya trae calculada; ver `CubeBounds::slab`.
+    pub fn intersect(&self, ray: &TracerRay) -> Intersect {
+        self.bounds().slab(ray).map_or_else(Intersect::empty, |slab| self.hit(ray, &slab))
+    }
+
+    /// El impacto completo (punto, UV, material) de un `slab` que el rayo ya
+    /// encontró contra este cubo. Solo hace falta para el impacto aceptado.
+    pub fn hit(&self, ray: &TracerRay, slab: &SlabHit) -> Intersect {
+        let normal = slab.normal();
+
+        // El punto se ajusta al plano de la cara para que el redondeo no lo deje
+        // dentro o fuera del cubo.
+        let mut point = ray.origin + ray.dir * slab.distance;
+        let axis = slab.axis as usize;
+        let face = if slab.sign < 0.0 { component(&self.min_bounds, axis) } else { component(&self.max_bounds, axis) };
+        set_component(&mut point, axis, face);
+
+        let (u, v, face_aspect) = self.get_uv(&point, &normal);
+
+        Intersect {
+            face_aspect,
+            ..Intersect::new(
+                Arc::clone(&self.material),
+                slab.distance,
+                normal,
+                point,
+                u,
+                v,
+            )
+        }
+    }
+}
+
+/// Límites de un cubo sin nada más: lo único que lee el recorrido del BVH al
+/// probar candidatos. `Scene` guarda una copia por cubo, en el mismo orden que
+/// `objects`, para que esa prueba no arrastre el material ni el grupo a la caché.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CubeBounds {
+    /// Esquina mínima.
+    pub min: Vector3,
+    /// Esquina máxima.
+    pub max: Vector3,
+}
+
+/// Dónde corta un rayo un cubo, sin el resto del impacto: la distancia y la
+/// cara. `Cube::hit` lo completa.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlabHit {
+    /// Distancia desde el origen del rayo.
+    pub distance: f32,
+    /// Eje de la cara (0 = x, 1 = y, 2 = z).
+    pub axis: u8,
+    /// Lado de la cara en ese eje: -1 la del mínimo, 1 la del máximo.
+    pub sign: f32,
+}
+
+impl SlabHit {
+    /// Normal de la cara, la misma que lleva el `Intersect`.
+    pub fn normal(&self) -> Vector3 {
+        let mut normal = Vector3::zero();
+        set_component(&mut normal, self.axis as usize, self.sign);
+        normal
     }
 }
 
+impl CubeBounds {
+    /// Test "Slab" del rayo contra la caja.
+    ///
+    /// La normal sale del slab que fijó la distancia, no de comparar el punto con
+    /// los bordes: así siempre es exactamente una cara, también cerca de una
+    /// arista. Si dos slabs empatan (rayo justo a la arista) gana el eje donde el
+    /// rayo avanza más de frente, y ante un empate total el primero (x, y, z).
+    pub fn slab(&self, ray: &TracerRay) -> Option<SlabHit> {
+        let mut t_near = f32::NEG_INFINITY;
+        let mut t_far = f32::INFINITY;
+        let mut near_axis = 0;
+        let mut far_axis = 0;
+
+        for axis in 0..3 {
+            let origin = component(&ray.origin, axis);
+            let direction = component(&ray.dir, axis);
+            let inv_dir = component(&ray.inv_dir, axis);
+            let mut t0 = (component(&self.min, axis) - origin) * inv_dir;
+            let mut t1 = (component(&self.max, axis) - origin) * inv_dir;
+            if t0 > t1 { std::mem::swap(&mut t0, &mut t1); }
+
+            // Un rayo paralelo justo sobre el plano da NaN: las comparaciones
+            // fallan y ese eje no restringe nada.
+            let dominant = |other: usize| direction.abs() > component(&ray.dir, other).abs();
+            if t0 > t_near || (t0 == t_near && dominant(near_axis)) {
+                t_near = t0;
+                near_axis = axis;
+            }
+            if t1 < t_far || (t1 == t_far && dominant(far_axis)) {
+                t_far = t1;
+                far_axis = axis;
+            }
+            if t_near > t_far {
+                return None;
+            }
+        }
+
+        // Si t_near es negativo, el rayo empieza dentro del cubo y sale por t_far.
+        let (distance, axis, entering) = if t_near > 0.001 { (t_near, near_axis, true) } else { (t_far, far_axis, false) };
+
+        // Si la distancia es demasiado pequeña, negativa o infinita, no hay intersección visible.
+        if !(0.001..f32::INFINITY).contains(&distance) {
+            return None;
+        }
+
+        // Al entrar la normal se opone al rayo; al salir apunta con él (hacia fuera).
+        let direction = component(&ray.dir, axis);
+        let sign = if (direction > 0.0) == entering { -1.0 } else { 1.0 };
+        Some(SlabHit { distance, axis: axis as u8, sign })
+    }
+}
+
+/// Orden de dos impactos del mismo rayo, cada uno con su cubo: primero el más
+/// cercano. A la misma distancia (caras coplanarias, aristas compartidas en el
+/// suelo) va primero la cara que más de frente mira al rayo y, si también
+/// empatan, el cubo de esquina mínima menor. Así el ganador no depende del
+/// orden en que el BVH entrega los cubos y no cambia de un frame a otro.
+pub fn compare_hits(a: (&Cube, &Intersect), b: (&Cube, &Intersect), ray_direction: &Vector3) -> Ordering {
+    let key = |(cube, hit): (&Cube, &Intersect)| {
+        [hit.distance, hit.normal.dot(*ray_direction), cube.min_bounds.x, cube.min_bounds.y, cube.min_bounds.z]
+    };
+    compare_keys(key(a), key(b))
+}
+
+/// `compare_hits` sobre los datos del recorrido, antes de completar ningún
+/// impacto; da exactamente el mismo orden.
+pub fn compare_slabs(a: (&CubeBounds, &SlabHit), b: (&CubeBounds, &SlabHit), ray_direction: &Vector3) -> Ordering {
+    let key = |(bounds, hit): (&CubeBounds, &SlabHit)| {
+        [hit.distance, hit.normal().dot(*ray_direction), bounds.min.x, bounds.min.y, bounds.min.z]
+    };
+    compare_keys(key(a), key(b))
+}
+
+fn compare_keys(a: [f32; 5], b: [f32; 5]) -> Ordering {
+    a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| x.total_cmp(y))
+        .find(|order| order.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
 impl Bounded for Cube {
     fn aabb(&self) -> AABB {
         let min = Point3::new(self.min_bounds.x, self.min_bounds.y, self.min_bounds.z);
@@ -64,71 +285,99 @@ impl BHShape for Cube {
     }
 }
 
-impl RayIntersect for Cube {
-    /// Implementa el test de intersección rayo-cubo usando el método "Slab".
-    fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect {
-        let inv_dir = Vector3::new(1.0 / ray_direction.x, 1.0 / ray_direction.y, 1.0 / ray_direction.z);
-
-        let mut tmin = (self.min_bounds.x - ray_origin.x) * inv_dir.x;
-        let mut tmax = (self.max_bounds.x - ray_origin.x) * inv_dir.x;
-
-        if tmin > tmax { std::mem::swap(&mut tmin, &mut tmax); }
-
-        let mut tymin = (self.min_bounds.y - ray_origin.y) * inv_dir.y;
-        let mut tymax = (self.max_bounds.y - ray_origin.y) * inv_dir.y;
-
-        if tymin > tymax { std::mem::swap(&mut tymin, &mut tymax); }
-
-        if (tmin > tymax) || (tymin > tmax) {
-            return Intersect::empty();
-        }
+/// Componente `axis` (0 = x, 1 = y, 2 = z) de un vector.
+fn component(v: &Vector3, axis: usize) -> f32 {
+    match axis {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
 
-        if tymin > tmin { tmin = tymin; }
-        if tymax < tmax { tmax = tymax; }
+fn set_component(v: &mut Vector3, axis: usize, value: f32) {
+    match axis {
+        0 => v.x = value,
+        1 => v.y = value,
+        _ => v.z = value,
+    }
+}
 
-        let mut tzmin = (self.min_bounds.z - ray_origin.z) * inv_dir.z;
-        let mut tzmax = (self.max_bounds.z - ray_origin.z) * inv_dir.z;
+impl RayIntersect for Cube {
+    /// Ver `Cube::intersect`; crea el rayo para una sola prueba.
+    fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect {
+        self.intersect(&TracerRay::new(*ray_origin, *ray_direction))
+    }
+}
 
-        if tzmin > tzmax { std::mem::swap(&mut tzmin, &mut tzmax); }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::hash3;
 
-        if (tmin > tzmax) || (tzmin > tmax) {
-            return Intersect::empty();
-        }
+    // Cubo de lado 2 centrado en el origen: caras en ±1
+    fn unit_cube() -> Cube {
+        Cube::new(Vector3::zero(), 2.0, Material::black())
+    }
 
-        if tzmin > tmin { tmin = tzmin; }
-        if tzmax < tmax { tmax = tzmax; }
+    fn hit(cube: &Cube, origin: Vector3, direction: Vector3) -> Intersect {
+        let hit = cube.intersect(&TracerRay::new(origin, direction));
+        assert!(hit.is_intersecting, "{:?} {:?} missed", origin, direction);
+        hit
+    }
 
-        // Si tmin es negativo, el rayo empieza dentro del cubo, usamos tmax.
-        let distance = if tmin > 0.001 { tmin } else { tmax };
+    #[test]
+    fn ray_exactly_at_an_edge_takes_the_face_it_meets_most_head_on() {
+        let cube = unit_cube();
+        // Direcciones sin normalizar para que los dos slabs empaten exactamente en t = 2
+        let x_first = hit(&cube, Vector3::new(-5.0, 0.0, -3.0), Vector3::new(2.0, 0.0, 1.0));
+        assert_eq!(x_first.normal, Vector3::new(-1.0, 0.0, 0.0));
+        assert_eq!(x_first.point, Vector3::new(-1.0, 0.0, -1.0));
+        let z_first = hit(&cube, Vector3::new(-3.0, 0.0, -5.0), Vector3::new(1.0, 0.0, 2.0));
+        assert_eq!(z_first.normal, Vector3::new(0.0, 0.0, -1.0));
+        // Empate total en una esquina: gana el primer eje
+        let corner = hit(&cube, Vector3::new(-3.0, -3.0, -3.0), Vector3::new(1.0, 1.0, 1.0));
+        assert_eq!(corner.normal, Vector3::new(-1.0, 0.0, 0.0));
+    }
 
-        // Si la distancia es demasiado pequeña o negativa, no hay intersección visible.
-        if distance < 0.001 {
-            return Intersect::empty();
+    #[test]
+    fn rays_near_an_edge_get_a_single_face_normal() {
+        let cube = unit_cube();
+        let faces = [-1.0, 1.0].map(|s| [Vector3::new(s, 0.0, 0.0), Vector3::new(0.0, s, 0.0), Vector3::new(0.0, 0.0, s)]).concat();
+        for i in 0..20000 {
+            let random = |axis: i32| hash3(i, axis, 0, 7) * 2.0 - 1.0;
+            // Un punto de la arista x = -1, z = -1 movido una fracción minúscula
+            let jitter = Vector3::new(random(0), 0.0, random(1)) * 1e-5;
+            let target = Vector3::new(-1.0, random(2), -1.0) + jitter;
+            let origin = Vector3::new(-3.0 + random(3), random(4) * 3.0, -3.0 + random(5));
+            let direction = (target - origin).normalized();
+            let result = cube.intersect(&TracerRay::new(origin, direction));
+            if !result.is_intersecting {
+                continue;
+            }
+            assert!(faces.contains(&result.normal), "normal {:?} is not a face", result.normal);
+            assert!(result.normal.dot(direction) < 0.0, "normal {:?} faces away from the ray", result.normal);
+            // El punto queda sobre el plano de la cara elegida
+            assert_eq!(result.point.dot(result.normal), 1.0);
+            let outside = [result.point.x, result.point.y, result.point.z].iter().fold(0.0f32, |m, c| m.max(c.abs() - 1.0));
+            assert!(outside < 1e-5, "point {:?} off the cube", result.point);
         }
+    }
 
-        let point = *ray_origin + *ray_direction * distance;
-        
-        // Se determina la normal de la cara intersectada comparando la posición del punto
-        // con los límites del cubo.
-        let epsilon = 1e-4;
-        let mut normal = Vector3::zero();
+    #[test]
+    fn equal_distance_hits_are_ordered_by_facing_then_min_corner() {
+        let direction = Vector3::new(0.6, -0.8, 0.0);
+        let (a, b) = (unit_cube(), Cube::new(Vector3::new(2.0, 0.0, 0.0), 2.0, Material::black()));
+        let at = |normal: Vector3| Intersect::new(Material::black(), 3.0, normal, Vector3::zero(), 0.0, 0.0);
+        let (top, side) = (at(Vector3::new(0.0, 1.0, 0.0)), at(Vector3::new(-1.0, 0.0, 0.0)));
 
-        if (point.x - self.min_bounds.x).abs() < epsilon { normal.x = -1.0; }
-        else if (point.x - self.max_bounds.x).abs() < epsilon { normal.x = 1.0; }
-        else if (point.y - self.min_bounds.y).abs() < epsilon { normal.y = -1.0; }
-        else if (point.y - self.max_bounds.y).abs() < epsilon { normal.y = 1.0; }
-        else if (point.z - self.min_bounds.z).abs() < epsilon { normal.z = -1.0; }
-        else if (point.z - self.max_bounds.z).abs() < epsilon { normal.z = 1.0; }
-
-        let (u, v) = self.get_uv(&point, &normal);
-
-        Intersect::new(
-            self.material.clone(),
-            distance,
-            normal,
-            point,
-            u,
-            v,
-        )
-    }
-}
\ No newline at end of file
+        // La cara de arriba mira más de frente (-0.8 frente a -0.6)
+        assert_eq!(compare_hits((&b, &top), (&a, &side), &direction), Ordering::Less);
+        assert_eq!(compare_hits((&a, &side), (&b, &top), &direction), Ordering::Greater);
+        // Misma distancia y cara: gana la esquina mínima menor, en cualquier orden
+        assert_eq!(compare_hits((&a, &top), (&b, &top), &direction), Ordering::Less);
+        assert_eq!(compare_hits((&b, &top), (&a, &top), &direction), Ordering::Greater);
+        // La distancia manda sobre todo lo demás
+        let nearer = Intersect { distance: 2.5, ..at(Vector3::new(-1.0, 0.0, 0.0)) };
+        assert_eq!(compare_hits((&b, &nearer), (&a, &top), &direction), Ordering::Less);
+    }
+}
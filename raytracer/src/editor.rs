@@ -0,0 +1,416 @@
+// editor.rs
+// In-window material editor: pick a named material from the scene library and
+// tune it live; every cube sharing the name follows. While it is open the mouse
+// places cubes with the brush material (left) and removes them (right).
+// The middle button grabs a cube for the transform gizmo: the arrow keys move
+// it along X and Z and Page Up/Down along Y, or resize it with Shift held.
+// F5 writes the scene file. Z undoes the last edit, open or not. The keys are
+// the defaults of the [keys] section of raytracer.toml.
+use std::path::Path;
+
+use raylib::prelude::*;
+
+use crate::camera::Camera;
+use crate::cube::Cube;
+use crate::input::{Action, KeyBindings};
+use crate::material::vector3_to_color;
+use crate::scene::Scene;
+use crate::scene_file::save_scene;
+use crate::settings::RenderSettings;
+
+// Units per second while an adjust key is held
+const ADJUST_RATE: f32 = 0.5;
+// Oldest edits are dropped past this many
+const HISTORY_LIMIT: usize = 100;
+// Units a gizmo key press moves the grabbed block, or resizes it by
+const GIZMO_STEP: f32 = 0.25;
+// Smallest side the gizmo shrinks a block to
+const MIN_SIDE: f32 = 0.25;
+
+/// One change to the scene. An undo step can hold several: an explosion's
+/// removal is a single `Removed`, and blocks falling after an edit are
+/// attached to that edit's step as `Moved` (from, to) centers.
+pub enum Edit {
+    /// A block placed with a left click.
+    Placed(Cube),
+    /// Blocks removed together.
+    Removed(Vec<Cube>),
+    /// Blocks moved, as (from, to) centers.
+    Moved(Vec<(Vector3, Vector3)>),
+    /// A block moved or resized with the gizmo: its id and the (min, max)
+    /// bounds before and after.
+    Transformed(u64, (Vector3, Vector3), (Vector3, Vector3)),
+}
+
+/// Undo steps, oldest first, capped at `HISTORY_LIMIT`.
+pub struct EditHistory {
+    steps: Vec<Vec<Edit>>,
+    // Steps ever pushed, kept through undo and `clear`
+    made: usize,
+}
+
+impl EditHistory {
+    /// An empty history.
+    pub fn new() -> Self {
+        EditHistory { steps: Vec::new(), made: 0 }
+    }
+
+    /// Starts a new undo step with `edit`.
+    pub fn push(&mut self, edit: Edit) {
+        if self.steps.len() == HISTORY_LIMIT {
+            self.steps.remove(0);
+        }
+        self.steps.push(vec![edit]);
+        self.made += 1;
+    }
+
+    /// Adds a consequence of the latest step (e.g. blocks falling after a removal)
+    /// so both undo together.
+    pub fn attach(&mut self, edit: Edit) {
+        match self.steps.last_mut() {
+            Some(step) => step.push(edit),
+            None => self.push(edit),
+        }
+    }
+
+    /// Number of undo steps.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// True when there is nothing to undo.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Edits made since the history was created, undone and cleared ones
+    /// included.
+    pub fn made(&self) -> usize {
+        self.made
+    }
+
+    /// Drops every undo step, e.g. when the scene they refer to is replaced.
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+
+    /// Reverts the latest step. Returns false when there is nothing to undo.
+    pub fn undo(&mut self, scene: &mut Scene) -> bool {
+        let Some(step) = self.steps.pop() else {
+            return false;
+        };
+        for edit in step.into_iter().rev() {
+            match edit {
+                Edit::Placed(cube) => {
+                    // Already gone if something else removed it since
+                    if let Some(index) = scene.find_cube(&cube) {
+                        scene.remove_cube(index);
+                    }
+                }
+                Edit::Removed(cubes) => scene.add_cubes(cubes),
+                Edit::Moved(moves) => {
+                    let back: Vec<(usize, Vector3)> = moves
+                        .iter()
+                        .rev()
+                        .filter_map(|(from, to)| scene.cube_at(to).map(|index| (index, *from - *to)))
+                        .collect();
+                    scene.move_cubes(&back);
+                }
+                Edit::Transformed(id, (min, max), _) => {
+                    if let Some(index) = scene.index_of(id) {
+                        scene.set_bounds(index, min, max);
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The E panel: edits library materials and places or removes blocks with the mouse.
+pub struct MaterialEditor {
+    /// True while the panel is shown.
+    pub open: bool,
+    /// Highlighted row of the material library.
+    pub selected: usize,
+    /// Material placed by a left click, index into the scene library.
+    pub brush: usize,
+    /// Undo steps for edits, explosions and falling blocks.
+    pub history: EditHistory,
+    /// Id of the block held by the transform gizmo. An id rather than an
+    /// index, which shifts whenever a block is added or removed.
+    pub grabbed: Option<u64>,
+    status: Option<String>,
+}
+
+/// Which end of the pair the user just moved; the other one gives way
+/// when the sum has to stay at or below 1.
+#[derive(Clone, Copy)]
+enum Adjusted {
+    Reflectivity,
+    Transparency,
+}
+
+fn conserve(reflectivity: &mut f32, transparency: &mut f32, adjusted: Adjusted) {
+    if *reflectivity + *transparency <= 1.0 {
+        return;
+    }
+    match adjusted {
+        Adjusted::Reflectivity => *transparency = 1.0 - *reflectivity,
+        Adjusted::Transparency => *reflectivity = 1.0 - *transparency,
+    }
+}
+
+impl MaterialEditor {
+    /// A closed editor with an empty history.
+    pub fn new() -> Self {
+        MaterialEditor { open: false, selected: 0, brush: 0, history: EditHistory::new(), grabbed: None, status: None }
+    }
+
+    /// True while the gizmo holds a block that is still in the scene. The
+    /// orbit keys move the block then, not the camera.
+    pub fn gizmo_active(&self, scene: &Scene) -> bool {
+        self.open && self.grabbed.is_some_and(|id| scene.index_of(id).is_some())
+    }
+
+    /// Handles this frame's keys and clicks. Toggling the panel and undo work
+    /// even while it is closed.
+    pub fn handle_input(
+        &mut self,
+        window: &RaylibHandle,
+        keys: &KeyBindings,
+        scene: &mut Scene,
+        settings: &mut RenderSettings,
+        camera: &Camera,
+        scene_path: &Path,
+    ) {
+        if keys.pressed(window, Action::MaterialEditor) {
+            self.open = !self.open;
+        }
+        if keys.pressed(window, Action::Undo) && !self.history.undo(scene) {
+            self.status = Some("Nothing to undo".to_string());
+        }
+        if !self.open || scene.materials.is_empty() {
+            return;
+        }
+        let count = scene.materials.len();
+        self.selected = self.selected.min(count - 1);
+        self.brush = self.brush.min(count - 1);
+
+        if keys.pressed(window, Action::NextMaterial) {
+            self.selected = (self.selected + 1) % count;
+        }
+        if keys.pressed(window, Action::PreviousMaterial) {
+            self.selected = (self.selected + count - 1) % count;
+        }
+        if keys.pressed(window, Action::NextBrush) {
+            // Wraps past the end of the library
+            self.brush = (self.brush + 1) % count;
+        }
+        if keys.pressed(window, Action::EnergyConserving) {
+            settings.energy_conserving = !settings.energy_conserving;
+        }
+
+        let step = ADJUST_RATE * window.get_frame_time();
+        let axis = |up: Action, down: Action| -> f32 {
+            let mut delta = 0.0;
+            if keys.down(window, up) { delta += step; }
+            if keys.down(window, down) { delta -= step; }
+            delta
+        };
+        let reflectivity_delta = axis(Action::ReflectivityUp, Action::ReflectivityDown);
+        let transparency_delta = axis(Action::TransparencyUp, Action::TransparencyDown);
+
+        if reflectivity_delta != 0.0 || transparency_delta != 0.0 {
+            let conserving = settings.energy_conserving;
+            scene.update_material(self.selected, |material| {
+                material.reflectivity = (material.reflectivity + reflectivity_delta).clamp(0.0, 1.0);
+                material.transparency = (material.transparency + transparency_delta).clamp(0.0, 1.0);
+                if conserving {
+                    let adjusted = if reflectivity_delta != 0.0 { Adjusted::Reflectivity } else { Adjusted::Transparency };
+                    conserve(&mut material.reflectivity, &mut material.transparency, adjusted);
+                }
+            });
+        }
+
+        if keys.pressed(window, Action::GrabBlock) {
+            let mouse = window.get_mouse_position();
+            let direction = camera.ray_direction(mouse.x, mouse.y, window.get_screen_width(), window.get_screen_height());
+            let clicked = scene.pick(&camera.eye, &direction).map(|(index, _)| scene.objects[index].id());
+            // Clicking the held block again, or the sky, lets go
+            self.grabbed = if clicked == self.grabbed { None } else { clicked };
+        }
+        self.transform_grabbed(window, keys, scene);
+
+        let left = keys.pressed(window, Action::PlaceBlock);
+        let right = keys.pressed(window, Action::RemoveBlock);
+        if left || right {
+            let mouse = window.get_mouse_position();
+            let direction = camera.ray_direction(mouse.x, mouse.y, window.get_screen_width(), window.get_screen_height());
+            if let Some((index, hit)) = scene.pick(&camera.eye, &direction) {
+                if right {
+                    let cube = scene.remove_cube(index);
+                    self.history.push(Edit::Removed(vec![cube]));
+                } else {
+                    // The new cube goes in the grid cell in front of the clicked face
+                    let cell = hit.point + hit.normal * 0.5;
+                    let center = Vector3::new(cell.x.round(), cell.y.round(), cell.z.round());
+                    if !scene.is_solid_at(&center) {
+                        let cube = Cube::new(center, 1.0, scene.materials[self.brush].clone());
+                        self.history.push(Edit::Placed(cube.clone()));
+                        scene.add_cube(cube);
+                    }
+                }
+            }
+        }
+
+        if keys.pressed(window, Action::SaveScene) {
+            self.status = Some(match save_scene(scene, scene_path) {
+                Ok(()) => format!("Saved {}", scene_path.display()),
+                Err(error) => format!("Save failed: {}", error),
+            });
+        }
+    }
+
+    /// Moves or resizes the grabbed block by one step per key press, along the
+    /// world axes. Resizing keeps the center in place.
+    fn transform_grabbed(&mut self, window: &RaylibHandle, keys: &KeyBindings, scene: &mut Scene) {
+        let Some(index) = self.grabbed.and_then(|id| scene.index_of(id)) else {
+            return;
+        };
+        let mut axis = Vector3::zero();
+        if keys.pressed(window, Action::OrbitRight) { axis.x += 1.0; }
+        if keys.pressed(window, Action::OrbitLeft) { axis.x -= 1.0; }
+        if keys.pressed(window, Action::RaiseBlock) { axis.y += 1.0; }
+        if keys.pressed(window, Action::LowerBlock) { axis.y -= 1.0; }
+        if keys.pressed(window, Action::OrbitDown) { axis.z += 1.0; }
+        if keys.pressed(window, Action::OrbitUp) { axis.z -= 1.0; }
+        if axis == Vector3::zero() {
+            return;
+        }
+
+        let cube = &scene.objects[index];
+        let (min, max) = (cube.min_bounds, cube.max_bounds);
+        let (new_min, new_max) = if keys.down(window, Action::ScaleBlock) {
+            let center = (min + max) * 0.5;
+            let side = |side: f32, step: f32| (side + step * GIZMO_STEP).max(MIN_SIDE);
+            let half = Vector3::new(
+                side(max.x - min.x, axis.x),
+                side(max.y - min.y, axis.y),
+                side(max.z - min.z, axis.z),
+            ) * 0.5;
+            (center - half, center + half)
+        } else {
+            (min + axis * GIZMO_STEP, max + axis * GIZMO_STEP)
+        };
+        if (new_min, new_max) != (min, max) {
+            self.history.push(Edit::Transformed(cube.id(), (min, max), (new_min, new_max)));
+            scene.set_bounds(index, new_min, new_max);
+        }
+    }
+
+    /// Draws the panel at (x, y) when open.
+    pub fn draw(&self, d: &mut impl RaylibDraw, keys: &KeyBindings, scene: &Scene, settings: &RenderSettings, x: i32, y: i32) {
+        if !self.open {
+            return;
+        }
+        d.draw_rectangle(x, y, 300, 150, Color::new(0, 0, 0, 170));
+        let Some(material) = scene.materials.get(self.selected) else {
+            d.draw_text("Material editor: no named materials", x + 8, y + 8, 16, Color::WHITE);
+            return;
+        };
+
+        let name = material.name.as_deref().unwrap_or("?");
+        let swatch = vector3_to_color(material.diffuse);
+        d.draw_rectangle(x + 8, y + 8, 16, 16, swatch);
+        d.draw_text(
+            &format!("{} ({}/{})", name, self.selected + 1, scene.materials.len()),
+            x + 32, y + 8, 16, Color::WHITE,
+        );
+        d.draw_text(&format!("Reflectivity: {:.2}  ({}/{})", material.reflectivity, keys.reflectivity_up, keys.reflectivity_down), x + 8, y + 32, 16, Color::LIGHTGRAY);
+        d.draw_text(&format!("Transparency: {:.2}  ({}/{})", material.transparency, keys.transparency_up, keys.transparency_down), x + 8, y + 50, 16, Color::LIGHTGRAY);
+        d.draw_text(
+            &format!(
+                "Energy conserving: {}  ({})",
+                if settings.energy_conserving { "on" } else { "off" },
+                keys.energy_conserving
+            ),
+            x + 8, y + 68, 16, Color::LIGHTGRAY,
+        );
+        if let Some(brush) = scene.materials.get(self.brush) {
+            d.draw_rectangle(x + 8, y + 88, 14, 14, vector3_to_color(brush.diffuse));
+            d.draw_rectangle_lines(x + 8, y + 88, 14, 14, Color::WHITE);
+            d.draw_text(
+                &format!(
+                    "Brush: {}  ({}, {} place, {} remove)",
+                    brush.name.as_deref().unwrap_or("?"),
+                    keys.next_brush,
+                    keys.place_block,
+                    keys.remove_block
+                ),
+                x + 28, y + 88, 14, Color::LIGHTGRAY,
+            );
+        }
+        let gizmo = match self.grabbed.and_then(|id| scene.index_of(id)) {
+            Some(index) => {
+                let cube = &scene.objects[index];
+                let center = (cube.min_bounds + cube.max_bounds) * 0.5;
+                let size = cube.max_bounds - cube.min_bounds;
+                format!(
+                    "Grabbed: {:.2}x{:.2}x{:.2} at ({:.2}, {:.2}, {:.2})",
+                    size.x, size.y, size.z, center.x, center.y, center.z
+                )
+            }
+            None => format!("Grab: {} (arrows, {} {} move, {} resizes)", keys.grab_block, keys.raise_block, keys.lower_block, keys.scale_block),
+        };
+        d.draw_text(&gizmo, x + 8, y + 108, 14, Color::LIGHTGRAY);
+        let footer = self.status.clone().unwrap_or_else(|| {
+            format!(
+                "{} {}: Select | {}: Undo | {}: Save | {}: Close",
+                keys.previous_material, keys.next_material, keys.undo, keys.save_scene, keys.material_editor
+            )
+        });
+        d.draw_text(&footer, x + 8, y + 128, 14, Color::GRAY);
+    }
+
+    /// Outlines the grabbed block's edges over the frame.
+    pub fn draw_gizmo(&self, d: &mut impl RaylibDraw, scene: &Scene, camera: &Camera, width: i32, height: i32) {
+        let Some(index) = self.grabbed.filter(|_| self.open).and_then(|id| scene.index_of(id)) else {
+            return;
+        };
+        let cube = &scene.objects[index];
+        let (min, max) = (cube.min_bounds, cube.max_bounds);
+        // Corner i takes max on the axes whose bit is set: x = 1, y = 2, z = 4
+        let corner = |i: usize| {
+            Vector3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        };
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                // Each edge once, from the corner without the bit
+                if i & bit != 0 {
+                    continue;
+                }
+                let ends = camera.project(corner(i), width, height).zip(camera.project(corner(i | bit), width, height));
+                if let Some(((ax, ay, _), (bx, by, _))) = ends {
+                    d.draw_line(ax as i32, ay as i32, bx as i32, by as i32, Color::YELLOW);
+                }
+            }
+        }
+    }
+}
+
+impl Default for MaterialEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,120 @@
+// error.rs
+// Errors of the loading paths: asset files, images, scenes and GPU uploads.
+// Each variant keeps the file or texture it is about, so the binaries can
+// report what went wrong without a backtrace.
+use std::error::Error as _;
+use std::io;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+/// Something the program needs at startup could not be read, decoded or uploaded.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// A file could not be read, created or written.
+    #[error("could not access {}", path.display())]
+    Io {
+        /// The file.
+        path: PathBuf,
+        /// Why the operating system refused.
+        #[source]
+        source: io::Error,
+    },
+    /// An asset is in none of the asset directories and has no embedded copy.
+    #[error("could not find {path} (searched {})", list_paths(searched))]
+    AssetNotFound {
+        /// The path it was asked for by, e.g. `assets/grass.png`.
+        path: String,
+        /// Every file that was tried, in order.
+        searched: Vec<PathBuf>,
+    },
+    /// An image was read but raylib could not decode it.
+    #[error("could not decode the image {}", path.display())]
+    ImageDecode {
+        /// The image file.
+        path: PathBuf,
+    },
+    /// A scene file is not valid RON or refers to something it does not define.
+    #[error("could not parse the scene {}: {message}", path.display())]
+    SceneParse {
+        /// The scene file.
+        path: PathBuf,
+        /// What the parser or the scene builder complained about.
+        message: String,
+    },
+    /// A rendered frame could not be encoded or written as an image file.
+    #[error("could not write the image {}", path.display())]
+    ImageEncode {
+        /// The image file.
+        path: PathBuf,
+        /// What the encoder reported; creating the file counts too.
+        #[source]
+        source: png::EncodingError,
+    },
+    /// A rendered frame could not be encoded or written as an OpenEXR file.
+    #[error("could not write the image {}", path.display())]
+    ExrEncode {
+        /// The image file.
+        path: PathBuf,
+        /// What the encoder reported; creating the file counts too.
+        #[source]
+        source: image::ImageError,
+    },
+    /// A frame waiting in a full `ExportQueue` was dropped to make room.
+    #[error("dropped {} from the full export queue", path.display())]
+    ExportDropped {
+        /// The file it was going to be written to.
+        path: PathBuf,
+    },
+    /// raylib could not turn an image into a GPU texture.
+    #[error("could not upload the texture {name} to the GPU")]
+    GpuUpload {
+        /// Path of the texture, or what it is for.
+        name: String,
+    },
+}
+
+fn list_paths(paths: &[PathBuf]) -> String {
+    paths.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl Error {
+    /// For `map_err`: wraps an I/O error on `path`.
+    pub fn io(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Error {
+        let path = path.into();
+        move |source| Error::Io { path, source }
+    }
+
+    /// True for a texture that is missing or unreadable, which the caller can
+    /// replace with a placeholder and carry on.
+    pub fn is_missing_asset(&self) -> bool {
+        matches!(self, Error::Io { .. } | Error::AssetNotFound { .. } | Error::ImageDecode { .. })
+    }
+
+    /// The message, each underlying cause on its own line and a hint on what
+    /// to check, for printing to the user.
+    pub fn report(&self) -> String {
+        let mut report = self.to_string();
+        let mut cause = self.source();
+        while let Some(error) = cause {
+            report.push_str(&format!("\n  caused by: {}", error));
+            cause = error.source();
+        }
+        let hint = match self {
+            Error::Io { path, .. } if path.extension().is_some_and(|ext| ext == "png") => {
+                Some("textures are read from --assets-dir ([assets] dir in raytracer.toml)")
+            }
+            Error::Io { .. } => None,
+            Error::AssetNotFound { .. } => Some("point --assets-dir ([assets] dir in raytracer.toml) at the folder holding the textures"),
+            Error::ImageDecode { .. } => Some("the file is not an image raylib can read; re-export it as PNG"),
+            Error::ImageEncode { .. } | Error::ExrEncode { .. } => Some("check that the directory is writable and the disk is not full"),
+            Error::ExportDropped { .. } => Some("the disk could not keep up; export fewer frames or let the queue block"),
+            Error::SceneParse { .. } => Some("fix the file, or delete scene.ron to start from the generated demo"),
+            Error::GpuUpload { .. } => Some("the GPU may be out of memory or the texture too large"),
+        };
+        if let Some(hint) = hint {
+            report.push_str(&format!("\n  hint: {}", hint));
+        }
+        report
+    }
+}
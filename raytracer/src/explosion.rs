@@ -0,0 +1,198 @@
+// explosion.rs
+// TNT: al encenderla arde una mecha y después quita los bloques en un radio.
+// Cada explosión deja un destello de luz breve y hace temblar la cámara. Todo lo
+// que explota en un mismo frame se quita de una vez (un solo rebuild del BVH) y
+// se guarda en el historial como una única acción que se deshace entera.
+use std::collections::HashSet;
+
+use raylib::prelude::*;
+
+use crate::cube::Cube;
+use crate::editor::{Edit, EditHistory};
+use crate::light::Light;
+use crate::noise::hash3;
+use crate::scene::Scene;
+
+/// Nombre del material que explota.
+pub const TNT_MATERIAL: &str = "tnt";
+
+// Semilla del borde irregular del cráter
+const CRATER_SEED: u32 = 4242;
+// Mecha de una TNT alcanzada por otra explosión: arden en cadena, no a la vez
+const CHAIN_FUSE_SECONDS: f32 = 0.25;
+
+/// Tamaño y tiempos de las explosiones.
+#[derive(Debug, Clone)]
+pub struct ExplosionSettings {
+    /// Radio del cráter, en bloques.
+    pub radius: f32,
+    /// Fracción del radio en la que el borde se vuelve irregular.
+    pub falloff: f32,
+    /// Segundos entre encender la TNT y la explosión.
+    pub fuse_seconds: f32,
+    /// Duración del destello de luz.
+    pub flash_seconds: f32,
+    /// Intensidad inicial del destello.
+    pub flash_intensity: f32,
+    /// Duración del temblor de la cámara.
+    pub shake_seconds: f32,
+    /// Desplazamiento máximo de la cámara, en bloques.
+    pub shake_strength: f32,
+}
+
+impl Default for ExplosionSettings {
+    fn default() -> Self {
+        ExplosionSettings {
+            radius: 3.0,
+            falloff: 0.35,
+            fuse_seconds: 1.5,
+            flash_seconds: 0.4,
+            flash_intensity: 4.0,
+            shake_seconds: 0.6,
+            shake_strength: 0.25,
+        }
+    }
+}
+
+struct Fuse {
+    center: Vector3,
+    remaining: f32,
+}
+
+struct Flash {
+    position: Vector3,
+    remaining: f32,
+}
+
+/// Mechas encendidas, destellos y temblor en curso.
+pub struct Explosions {
+    /// Parámetros de las explosiones.
+    pub settings: ExplosionSettings,
+    fuses: Vec<Fuse>,
+    flashes: Vec<Flash>,
+    shake_remaining: f32,
+    elapsed: f32,
+}
+
+fn is_tnt(cube: &Cube) -> bool {
+    cube.material.name.as_deref() == Some(TNT_MATERIAL)
+}
+
+fn center_of(cube: &Cube) -> Vector3 {
+    (cube.min_bounds + cube.max_bounds) * 0.5
+}
+
+impl Explosions {
+    /// Sin ninguna mecha encendida.
+    pub fn new(settings: ExplosionSettings) -> Self {
+        Explosions { settings, fuses: Vec::new(), flashes: Vec::new(), shake_remaining: 0.0, elapsed: 0.0 }
+    }
+
+    /// Mechas que todavía arden.
+    pub fn lit_fuses(&self) -> usize {
+        self.fuses.len()
+    }
+
+    /// Enciende la mecha si `cube` es TNT y no estaba ya encendida.
+    pub fn ignite(&mut self, cube: &Cube) -> bool {
+        if !is_tnt(cube) {
+            return false;
+        }
+        self.light_fuse(center_of(cube), self.settings.fuse_seconds)
+    }
+
+    fn light_fuse(&mut self, center: Vector3, seconds: f32) -> bool {
+        if self.fuses.iter().any(|fuse| fuse.center == center) {
+            return false;
+        }
+        self.fuses.push(Fuse { center, remaining: seconds });
+        true
+    }
+
+    /// Avanza mechas, destellos y temblor; hace explotar las mechas consumidas.
+    /// Los destellos activos quedan en `scene.transient_lights`.
+    pub fn update(&mut self, dt: f32, scene: &mut Scene, history: &mut EditHistory) {
+        self.elapsed += dt;
+        self.shake_remaining = (self.shake_remaining - dt).max(0.0);
+        for flash in &mut self.flashes {
+            flash.remaining -= dt;
+        }
+        self.flashes.retain(|flash| flash.remaining > 0.0);
+        for fuse in &mut self.fuses {
+            fuse.remaining -= dt;
+        }
+
+        let (due, waiting): (Vec<Fuse>, Vec<Fuse>) = self.fuses.drain(..).partition(|fuse| fuse.remaining <= 0.0);
+        self.fuses = waiting;
+
+        let mut doomed: HashSet<usize> = HashSet::new();
+        for fuse in due {
+            // Si la TNT ya no está (se quitó o se deshizo) la mecha se apaga sin más
+            let Some(charge) = scene.objects.iter().position(|c| is_tnt(c) && center_of(c) == fuse.center) else {
+                continue;
+            };
+            if !doomed.insert(charge) {
+                continue; // ya la quitó otra explosión de este frame
+            }
+            self.blast(fuse.center, scene, &mut doomed);
+        }
+
+        if !doomed.is_empty() {
+            let mut indices: Vec<usize> = doomed.into_iter().collect();
+            indices.sort_unstable();
+            let removed = scene.remove_cubes(&indices);
+            history.push(Edit::Removed(removed));
+        }
+
+        scene.transient_lights = self
+            .flashes
+            .iter()
+            .map(|flash| {
+                let fade = flash.remaining / self.settings.flash_seconds;
+                Light::new(flash.position, Vector3::new(1.0, 0.7, 0.3), self.settings.flash_intensity * fade)
+                    .with_radius(0.5)
+            })
+            .collect();
+    }
+
+    /// Marca los bloques del cráter en `doomed`. Las TNT alcanzadas se encienden
+    /// con una mecha corta en vez de desaparecer.
+    fn blast(&mut self, center: Vector3, scene: &Scene, doomed: &mut HashSet<usize>) {
+        let radius = self.settings.radius.max(0.0);
+        let falloff = self.settings.falloff.clamp(0.0, 1.0);
+        let mut chained = Vec::new();
+        for (index, cube) in scene.objects.iter().enumerate() {
+            let block = center_of(cube);
+            let distance = (block - center).length();
+            if distance > radius {
+                continue;
+            }
+            // Esfera con el borde mordido: cada celda tiene su propio umbral
+            let jitter = hash3(block.x.round() as i32, block.y.round() as i32, block.z.round() as i32, CRATER_SEED);
+            if distance > radius * (1.0 - falloff * jitter) {
+                continue;
+            }
+            if is_tnt(cube) && block != center {
+                chained.push(block);
+            } else {
+                doomed.insert(index);
+            }
+        }
+        for block in chained {
+            self.light_fuse(block, CHAIN_FUSE_SECONDS);
+        }
+
+        self.flashes.push(Flash { position: center, remaining: self.settings.flash_seconds });
+        self.shake_remaining = self.settings.shake_seconds;
+    }
+
+    /// Desplazamiento de la cámara por el temblor; cero cuando no hay explosiones.
+    pub fn shake_offset(&self) -> Vector3 {
+        if self.shake_remaining <= 0.0 || self.settings.shake_seconds <= 0.0 {
+            return Vector3::zero();
+        }
+        let amplitude = self.settings.shake_strength * self.shake_remaining / self.settings.shake_seconds;
+        let t = self.elapsed;
+        Vector3::new((t * 61.0).sin(), (t * 47.0).sin(), (t * 53.0).cos()) * amplitude
+    }
+}
@@ -0,0 +1,119 @@
+// falling.rs
+// Bloques que caen (arena, grava): a paso fijo, cada bloque con `falls` y aire
+// debajo baja una celda por tick hasta apoyarse. Solo se miran las celdas que
+// tocó alguna edición (y las de encima), así que sin cambios no hace nada.
+use std::collections::{HashMap, HashSet};
+
+use raylib::prelude::*;
+
+use crate::editor::{Edit, EditHistory};
+use crate::scene::Scene;
+
+/// Segundos por tick de simulación.
+pub const TICK_SECONDS: f32 = 0.1;
+// Ticks máximos por frame, para no encadenar muchos tras un frame lento
+const MAX_TICKS_PER_FRAME: u32 = 4;
+
+type Cell = (i32, i32, i32);
+
+/// Simulación de los bloques que caen.
+pub struct FallingBlocks {
+    dirty: HashSet<Cell>,
+    accumulator: f32,
+}
+
+impl FallingBlocks {
+    /// Sin celdas pendientes.
+    pub fn new() -> Self {
+        FallingBlocks { dirty: HashSet::new(), accumulator: 0.0 }
+    }
+
+    /// Dormida cuando no hay ninguna celda pendiente de revisar.
+    pub fn is_idle(&self) -> bool {
+        self.dirty.is_empty()
+    }
+
+    /// Celdas tocadas por ediciones desde el último frame.
+    pub fn mark_edited(&mut self, cells: &[Cell]) {
+        // Cada celda editada puede caer ella misma o dejar sin apoyo a la de encima
+        for &(x, y, z) in cells {
+            self.dirty.insert((x, y, z));
+            self.dirty.insert((x, y + 1, z));
+        }
+    }
+
+    /// Avanza los ticks que tocan en `dt`; cada bloque que cae queda en `history` para poder deshacerlo.
+    pub fn update(&mut self, dt: f32, scene: &mut Scene, history: &mut EditHistory) {
+        if self.dirty.is_empty() {
+            self.accumulator = 0.0;
+            return;
+        }
+
+        self.accumulator += dt;
+        let mut ticks = 0;
+        while self.accumulator >= TICK_SECONDS && ticks < MAX_TICKS_PER_FRAME && !self.dirty.is_empty() {
+            self.accumulator -= TICK_SECONDS;
+            ticks += 1;
+            self.tick(scene, history);
+        }
+        self.accumulator = self.accumulator.min(TICK_SECONDS);
+    }
+
+    fn tick(&mut self, scene: &mut Scene, history: &mut EditHistory) {
+        let cells: HashMap<Cell, usize> = scene
+            .objects
+            .iter()
+            .enumerate()
+            .filter_map(|(index, cube)| cube.grid_cell().map(|cell| (cell, index)))
+            .collect();
+        // Nada cae por debajo del bloque más bajo de la escena
+        let Some(floor) = cells.keys().map(|(_, y, _)| *y).min() else {
+            self.dirty.clear();
+            return;
+        };
+
+        // De abajo arriba, para que una columna entera baje en el mismo tick
+        let mut pending: Vec<Cell> = self.dirty.drain().collect();
+        pending.sort_by_key(|&(x, y, z)| (y, x, z));
+
+        let mut vacated: HashSet<Cell> = HashSet::new();
+        let mut filled: HashSet<Cell> = HashSet::new();
+        let mut moves: Vec<(usize, Vector3)> = Vec::new();
+        let mut record: Vec<(Vector3, Vector3)> = Vec::new();
+        for (x, y, z) in pending {
+            let Some(&index) = cells.get(&(x, y, z)) else {
+                continue;
+            };
+            if !scene.objects[index].material.falls || y <= floor {
+                continue;
+            }
+            let below = (x, y - 1, z);
+            let center = Vector3::new(x as f32, y as f32, z as f32);
+            let below_center = Vector3::new(x as f32, (y - 1) as f32, z as f32);
+            let supported = filled.contains(&below)
+                || (!vacated.contains(&below) && (cells.contains_key(&below) || scene.is_solid_at(&below_center)));
+            if supported {
+                continue;
+            }
+
+            moves.push((index, Vector3::new(0.0, -1.0, 0.0)));
+            record.push((center, below_center));
+            vacated.insert((x, y, z));
+            filled.insert(below);
+            // Sigue cayendo el próximo tick, y lo que tenía encima también
+            self.dirty.insert(below);
+            self.dirty.insert((x, y + 1, z));
+        }
+
+        if !moves.is_empty() {
+            scene.move_cubes(&moves);
+            history.attach(Edit::Moved(record));
+        }
+    }
+}
+
+impl Default for FallingBlocks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,66 @@
+// frame_hook.rs
+// Closures that run once per frame before the frame is rendered, so a program
+// embedding the renderer can animate cubes, move the camera or swap materials
+// from code. The window's own auto-rotation and day/night cycle are hooks too
+// (`auto_rotate` and `day_night`); state the loop also reads or sets, like
+// whether the camera orbits, is shared through a `Cell`.
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::camera::Camera;
+use crate::scene::Scene;
+
+/// A per-frame hook: the scene, the camera and the seconds since the loop
+/// started, not counting pauses. A hook that edits `scene.objects` directly
+/// calls `Scene::mark_geometry_changed` so the BVH is rebuilt before the frame;
+/// one that changes what the frame shows some other way by hand calls
+/// `Scene::touch`, or the window may keep presenting the last frame.
+pub type FrameHook = Box<dyn FnMut(&mut Scene, &mut Camera, f32)>;
+
+/// The hooks of a render loop, run in the order they were added.
+#[derive(Default)]
+pub struct FrameHooks {
+    hooks: Vec<FrameHook>,
+}
+
+impl FrameHooks {
+    /// No hooks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `hook` after the ones already added.
+    pub fn push(&mut self, hook: impl FnMut(&mut Scene, &mut Camera, f32) + 'static) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Runs every hook, then rebuilds the BVH if one of them marked the
+    /// geometry as changed. Returns whether it was rebuilt.
+    pub fn run(&mut self, scene: &mut Scene, camera: &mut Camera, time: f32) -> bool {
+        for hook in &mut self.hooks {
+            hook(scene, camera, time);
+        }
+        scene.rebuild_if_changed()
+    }
+}
+
+/// Orbits the camera by `speed` radians a frame while `enabled` is set.
+pub fn auto_rotate(enabled: Rc<Cell<bool>>, speed: f32) -> impl FnMut(&mut Scene, &mut Camera, f32) {
+    move |_, camera, _| {
+        if enabled.get() {
+            camera.orbit(speed, 0.0);
+        }
+    }
+}
+
+/// Moves the sun angle in `time_of_day` on by `speed` radians a frame,
+/// wrapping to 0 after a full day.
+pub fn day_night(time_of_day: Rc<Cell<f32>>, speed: f32) -> impl FnMut(&mut Scene, &mut Camera, f32) {
+    move |_, _, _| {
+        let mut angle = time_of_day.get() + speed;
+        if angle > 2.0 * std::f32::consts::PI {
+            angle = 0.0;
+        }
+        time_of_day.set(angle);
+    }
+}
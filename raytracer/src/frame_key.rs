@@ -0,0 +1,53 @@
+// frame_key.rs
+// A hash of everything a traced frame depends on, so the window can tell that
+// the next frame would come out exactly like the one on screen and present
+// that one again instead of tracing it. The scene comes in through
+// `Scene::generation`, which every visible edit changes; the clouds, which
+// drift on their own, and the short-lived explosion lights are hashed as they
+// are. Rendering is deterministic and has no progressive accumulation, so an
+// unchanged key always means an identical image.
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use raylib::prelude::*;
+
+use crate::camera::Camera;
+use crate::light::Light;
+use crate::scene::Scene;
+use crate::settings::RenderSettings;
+
+/// Hash of the inputs of one frame; equal keys give the same pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameKey(u64);
+
+impl FrameKey {
+    /// The key of a frame of `scene` seen from `view` under `light`.
+    /// `display` is whatever else the shown image depends on outside the
+    /// renderer, e.g. the window size or an overlay.
+    pub fn capture(view: &Camera, light: &Light, settings: &RenderSettings, scene: &Scene, display: impl Hash) -> Self {
+        let mut hasher = DefaultHasher::new();
+        for v in [view.eye, view.center, view.up] {
+            hash_vector(&mut hasher, &v);
+        }
+        view.fov.to_bits().hash(&mut hasher);
+        (view.fov_axis as u8).hash(&mut hasher);
+        for light in std::iter::once(light).chain(&scene.transient_lights) {
+            hash_light(&mut hasher, light);
+        }
+        // Every field prints in the Debug text, floats and the sky included
+        format!("{:?}", settings).hash(&mut hasher);
+        scene.generation().hash(&mut hasher);
+        scene.clouds.as_ref().map(|clouds| clouds.drift().to_bits()).hash(&mut hasher);
+        display.hash(&mut hasher);
+        FrameKey(hasher.finish())
+    }
+}
+
+fn hash_vector(hasher: &mut DefaultHasher, v: &Vector3) {
+    [v.x, v.y, v.z].map(f32::to_bits).hash(hasher);
+}
+
+fn hash_light(hasher: &mut DefaultHasher, light: &Light) {
+    hash_vector(hasher, &light.position);
+    hash_vector(hasher, &light.color);
+    [light.intensity, light.radius, light.soft_radius].map(f32::to_bits).hash(hasher);
+}
@@ -1,44 +1,751 @@
+// framebuffer.rs
+// The HDR frame in linear radiance that `render` traces and the window loop
+// shows. Each pixel keeps the mean of the samples it received and how many
+// there were, so samples from several passes can be added up (progressive
+// accumulation) without a separate sum or a divide at the end. A whole traced
+// frame comes in through `overwrite`, as one sample per pixel. Bloom and the
+// exposure bracket work on it, and `resolve_into` turns it into screen colors
+// with tone mapping; that is also how it is saved as PNG or, with nothing but
+// std, as PPM. EXR keeps the linear radiance itself, unclamped, for grading
+// or compositing elsewhere. What changes is added to a dirty rectangle, and
+// the window uploads only that part to its texture through `take_dirty`, so a
+// long frame can be seen arriving tile by tile. `Comparison` splits the screen in two to
+// compare the frame with a pinned snapshot or with itself before
+// post-processing. The `ExportQueue` writes already resolved copies in the
+// background, so neither screenshots nor a long sequence stall the loop while
+// they are encoded.
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+use rayon::prelude::*;
 use raylib::prelude::*;
-pub struct Framebuffer {
+
+use crate::error::Error;
+use crate::screenshot::{write_png, ShotMetadata};
+use crate::settings::RenderSettings;
+use crate::material::vector3_to_color;
+use crate::tonemap::{tonemap, tonemap_dithered, tonemap_dithered_into, tonemap_into};
+
+/// A rectangle of pixels: top-left corner and size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    /// Left column.
+    pub x: i32,
+    /// Top row.
+    pub y: i32,
+    /// Width in pixels.
     pub width: i32,
+    /// Height in pixels.
     pub height: i32,
-    pub color_buffer: Image,
-    background_color: Color,
-    current_color: Color,
 }
+
+impl Rect {
+    /// The smallest rectangle that contains both.
+    pub fn union(self, other: Rect) -> Rect {
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Rect { x, y, width: right - x, height: bottom - y }
+    }
+
+    /// The part inside a `width` x `height` image, or None if no pixel is
+    /// left.
+    pub fn clip(self, width: i32, height: i32) -> Option<Rect> {
+        let (x, y) = (self.x.max(0), self.y.max(0));
+        let right = (self.x + self.width).min(width);
+        let bottom = (self.y + self.height).min(height);
+        (right > x && bottom > y).then(|| Rect { x, y, width: right - x, height: bottom - y })
+    }
+}
+
+/// An HDR image with the sample count of each pixel.
+pub struct Framebuffer {
+    width: i32,
+    height: i32,
+    // Mean of each pixel's samples, row by row
+    pixels: Vec<Vector3>,
+    // Samples averaged into each pixel; 0 where there are none yet
+    sample_counts: Vec<u32>,
+    // What changed since the last `take_dirty`
+    dirty: Option<Rect>,
+}
+
 impl Framebuffer {
+    /// A black `width` x `height` image with no samples, dirty all over.
     pub fn new(width: i32, height: i32) -> Self {
-        let background_color = Color::BLACK; // Un color por defecto
-        let color_buffer = Image::gen_image_color(width, height, background_color);
-        Framebuffer {
-            width,
-            height,
-            color_buffer,
-            background_color,
-            current_color: Color::WHITE,
+        let len = (width.max(0) * height.max(0)) as usize;
+        let dirty = Rect { x: 0, y: 0, width, height }.clip(width, height);
+        Framebuffer { width, height, pixels: vec![Vector3::zero(); len], sample_counts: vec![0; len], dirty }
+    }
+
+    /// An image of already traced `pixels`, one sample per pixel; `pixels`
+    /// is row by row and holds `width * height` colors.
+    pub fn from_pixels(width: i32, height: i32, pixels: Vec<Vector3>) -> Self {
+        assert_eq!(pixels.len(), (width * height) as usize, "the pixels do not fill the frame");
+        let sample_counts = vec![1; pixels.len()];
+        let dirty = Rect { x: 0, y: 0, width, height }.clip(width, height);
+        Framebuffer { width, height, pixels, sample_counts, dirty }
+    }
+
+    /// Width in pixels.
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// Height in pixels.
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Changes the size. At a new size the image turns black with no samples,
+    /// since the old ones no longer fall on the same pixels; at the same size
+    /// nothing changes.
+    pub fn resize(&mut self, width: i32, height: i32) {
+        if (width, height) != (self.width, self.height) {
+            *self = Framebuffer::new(width, height);
         }
     }
+
+    /// Back to black with no samples, e.g. when the camera moves and what was
+    /// accumulated no longer holds.
     pub fn clear(&mut self) {
-        self.color_buffer.clear_background(self.background_color);
+        self.pixels.fill(Vector3::zero());
+        self.sample_counts.fill(0);
+        self.mark_all_dirty();
+    }
+
+    /// Adds `rect`, clipped to the image, to what has to be uploaded again.
+    pub fn mark_dirty(&mut self, rect: Rect) {
+        if let Some(rect) = rect.clip(self.width, self.height) {
+            self.dirty = Some(self.dirty.map_or(rect, |dirty| dirty.union(rect)));
+        }
+    }
+
+    /// What changed since the last call, leaving it clean; None if nothing did.
+    pub fn take_dirty(&mut self) -> Option<Rect> {
+        self.dirty.take()
+    }
+
+    fn mark_all_dirty(&mut self) {
+        self.mark_dirty(Rect { x: 0, y: 0, width: self.width, height: self.height });
+    }
+
+    /// Adds a sample to pixel (`x`, `y`), which becomes the mean of all the
+    /// samples it received; outside the image it does nothing.
+    pub fn add_sample(&mut self, x: i32, y: i32, color: Vector3) {
+        let Some(index) = self.index(x, y) else {
+            return;
+        };
+        let count = self.sample_counts[index] + 1;
+        let pixel = &mut self.pixels[index];
+        *pixel += (color - *pixel) / count as f32;
+        self.sample_counts[index] = count;
+        self.mark_dirty(Rect { x, y, width: 1, height: 1 });
+    }
+
+    /// Samples averaged into pixel (`x`, `y`), or 0 outside the image.
+    pub fn sample_count(&self, x: i32, y: i32) -> u32 {
+        self.index(x, y).map_or(0, |index| self.sample_counts[index])
+    }
+
+    /// Replaces the image with a whole traced frame: `trace` gets the pixels
+    /// and must write all of them, and each one counts as one sample.
+    pub fn overwrite(&mut self, trace: impl FnOnce(&mut [Vector3])) {
+        trace(&mut self.pixels);
+        self.sample_counts.fill(1);
+        self.mark_all_dirty();
     }
-    pub fn set_pixel(&mut self, x: i32, y: i32) {
-        if x >= 0 && x < self.width && y >= 0 && y < self.height {
-            self.color_buffer.draw_pixel(x, y, self.current_color);
+
+    /// Puts the colors of a freshly traced tile into `rect`, which must lie
+    /// inside the image; `colors` is row by row, one sample per pixel.
+    pub fn write_rect(&mut self, rect: Rect, colors: &[Vector3]) {
+        assert_eq!(rect.clip(self.width, self.height), Some(rect), "the rectangle is not inside the frame");
+        assert_eq!(colors.len(), (rect.width * rect.height) as usize, "the colors do not fill the rectangle");
+        for (row, source) in colors.chunks(rect.width as usize).enumerate() {
+            let start = ((rect.y + row as i32) * self.width + rect.x) as usize;
+            self.pixels[start..start + source.len()].copy_from_slice(source);
+            self.sample_counts[start..start + source.len()].fill(1);
         }
+        self.mark_dirty(rect);
     }
-    
-    pub fn get_pixel_color(&mut self, x: i32, y: i32) -> Option<Color> {
-        if x >= 0 && x < self.width && y >= 0 && y < self.height {
-            Some(self.color_buffer.get_color(x, y))
+
+    /// The mean of each pixel, row by row.
+    pub fn pixels(&self) -> &[Vector3] {
+        &self.pixels
+    }
+
+    /// The pixels, to touch up in place, e.g. for bloom; the sample counts do
+    /// not change and the whole image becomes dirty.
+    pub fn pixels_mut(&mut self) -> &mut [Vector3] {
+        self.mark_all_dirty();
+        &mut self.pixels
+    }
+
+    /// Turns the image into screen colors in `colors`, as long as the image,
+    /// with the tone mapping of `settings` and its exposure multiplied by
+    /// `exposure_scale`, dithered if `settings.dither`.
+    pub fn resolve_into(&self, colors: &mut [Color], settings: &RenderSettings, exposure_scale: f32) {
+        if settings.dither {
+            tonemap_dithered_into(&self.pixels, colors, self.width.max(1) as usize, settings, exposure_scale);
         } else {
-            None
+            tonemap_into(&self.pixels, colors, settings, exposure_scale);
         }
     }
-    pub fn set_background_color(&mut self, color: Color) {
-        self.background_color = color;
+
+    /// `resolve_into` for the `rect` part only: the pixels of `colors`, a
+    /// whole frame, inside `rect` get exactly what a full resolve would give
+    /// them, and the rest are left as they are.
+    pub fn resolve_rect_into(&self, rect: Rect, colors: &mut [Color], settings: &RenderSettings, exposure_scale: f32) {
+        let Some(rect) = rect.clip(self.width, self.height) else { return };
+        let width = self.width as usize;
+        let (left, right) = (rect.x as usize, (rect.x + rect.width) as usize);
+        colors.par_chunks_mut(width).zip(self.pixels.par_chunks(width)).enumerate().skip(rect.y as usize).take(rect.height as usize).for_each(
+            |(y, (out, row))| {
+                for x in left..right {
+                    out[x] = if settings.dither {
+                        tonemap_dithered(row[x], x as u32, y as u32, settings, exposure_scale)
+                    } else {
+                        vector3_to_color(tonemap(row[x], settings, exposure_scale))
+                    };
+                }
+            },
+        );
+    }
+
+    /// `resolve_into` into a new `Vec`.
+    pub fn resolve(&self, settings: &RenderSettings, exposure_scale: f32) -> Vec<Color> {
+        let mut colors = vec![Color::BLACK; self.pixels.len()];
+        self.resolve_into(&mut colors, settings, exposure_scale);
+        colors
+    }
+
+    /// Saves the image, resolved with `settings`, as an 8-bit PNG with
+    /// `metadata`, if any, in tEXt chunks; see `ShotMetadata::png_text`.
+    pub fn save_png(&self, path: &Path, settings: &RenderSettings, metadata: Option<&ShotMetadata>) -> Result<(), Error> {
+        let text = metadata.map(ShotMetadata::png_text).unwrap_or_default();
+        write_png(path, &self.resolve(settings, 1.0), self.width, self.height, &text)
+    }
+
+    /// Saves the image, resolved with `settings`, as an 8-bit binary PPM (P6),
+    /// with no alpha channel or metadata.
+    pub fn save_ppm(&self, path: &Path, settings: &RenderSettings) -> Result<(), Error> {
+        write_ppm(path, &self.resolve(settings, 1.0), self.width, self.height)
     }
-    pub fn set_current_color(&mut self, color: Color) {
-        self.current_color = color;
+
+    /// Saves the linear pixels as they are, before tone mapping and exposure,
+    /// as a 32-bit float OpenEXR file.
+    pub fn save_exr(&self, path: &Path) -> Result<(), Error> {
+        write_exr(path, &self.pixels, self.width, self.height)
+    }
+
+    /// What the `ExportQueue` needs to write the frame as `format`: the
+    /// colors resolved with `settings`, or the linear pixels for EXR.
+    pub fn export_pixels(&self, format: &ExportFormat, settings: &RenderSettings) -> ExportPixels {
+        match format {
+            ExportFormat::Exr => ExportPixels::Linear(self.pixels.clone()),
+            ExportFormat::Png(_) | ExportFormat::Ppm => ExportPixels::Colors(self.resolve(settings, 1.0)),
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        (x >= 0 && x < self.width && y >= 0 && y < self.height).then(|| (y * self.width + x) as usize)
+    }
+}
+
+/// What the left side of a `Comparison` shows, already in screen colors.
+pub enum Reference {
+    /// A frame pinned when the comparison started; it stays the same when
+    /// the view or the settings change.
+    Snapshot(Vec<Color>),
+    /// The current frame before the post-processing passes, resolved again
+    /// for every traced frame.
+    Unprocessed(Vec<Color>),
+}
+
+/// Split-screen A/B comparison: the reference left of a vertical divider and
+/// the current frame right of it.
+pub struct Comparison {
+    /// The left side.
+    pub reference: Reference,
+    /// Where the divider falls, as a fraction of the width from 0 to 1.
+    pub divider: f32,
+}
+
+impl Comparison {
+    /// Pins `frame`, resolved with `settings`, as the reference, with the
+    /// divider in the middle.
+    pub fn snapshot(frame: &Framebuffer, settings: &RenderSettings) -> Self {
+        Comparison { reference: Reference::Snapshot(frame.resolve(settings, 1.0)), divider: 0.5 }
+    }
+
+    /// Compares with the frame before post-processing; the reference comes
+    /// in through `capture_unprocessed`.
+    pub fn unprocessed() -> Self {
+        Comparison { reference: Reference::Unprocessed(Vec::new()), divider: 0.5 }
+    }
+
+    /// With `Reference::Unprocessed`, keeps `frame` resolved with `settings`;
+    /// called before the post-processing passes. With a snapshot it does
+    /// nothing.
+    pub fn capture_unprocessed(&mut self, frame: &Framebuffer, settings: &RenderSettings) {
+        if let Reference::Unprocessed(colors) = &mut self.reference {
+            colors.resize(frame.pixels.len(), Color::BLACK);
+            frame.resolve_into(colors, settings, 1.0);
+        }
+    }
+
+    /// Name of the left side, for the screen.
+    pub fn label(&self) -> &'static str {
+        match self.reference {
+            Reference::Snapshot(_) => "snapshot",
+            Reference::Unprocessed(_) => "no post",
+        }
+    }
+
+    /// Column of the divider in an image `width` pixels wide.
+    pub fn divider_column(&self, width: i32) -> i32 {
+        (self.divider.clamp(0.0, 1.0) * width as f32).round() as i32
+    }
+
+    /// Copies the reference into `colors`, an image `width` pixels wide, left
+    /// of the divider. If the reference has another size, e.g. because the
+    /// window changed, it leaves `colors` as it is.
+    pub fn composite(&self, colors: &mut [Color], width: i32) {
+        let (Reference::Snapshot(reference) | Reference::Unprocessed(reference)) = &self.reference;
+        if reference.len() != colors.len() || width <= 0 {
+            return;
+        }
+        let split = self.divider_column(width) as usize;
+        for (row, source) in colors.chunks_mut(width as usize).zip(reference.chunks(width as usize)) {
+            row[..split].copy_from_slice(&source[..split]);
+        }
+    }
+}
+
+/// Writes `width` x `height` colors, row by row from the top, as an 8-bit
+/// binary PPM (P6).
+pub fn write_ppm(path: &Path, colors: &[Color], width: i32, height: i32) -> Result<(), Error> {
+    let mut bytes = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    bytes.extend(colors.iter().flat_map(|c| [c.r, c.g, c.b]));
+    fs::write(path, bytes).map_err(Error::io(path))
+}
+
+/// Writes `width` x `height` linear pixels, row by row from the top, as a
+/// 32-bit float RGB OpenEXR file, with no tone mapping or clamping.
+pub fn write_exr(path: &Path, pixels: &[Vector3], width: i32, height: i32) -> Result<(), Error> {
+    assert_eq!(pixels.len(), (width * height) as usize, "the pixels do not fill the image");
+    let channels: Vec<f32> = pixels.iter().flat_map(|p| [p.x, p.y, p.z]).collect();
+    let image = image::Rgb32FImage::from_raw(width as u32, height as u32, channels).expect("three channels per pixel fill the image");
+    image.save_with_format(path, image::ImageFormat::OpenExr).map_err(|source| Error::ExrEncode { path: path.to_path_buf(), source })
+}
+
+/// How the `ExportQueue` writes a file.
+#[derive(Debug, Clone)]
+pub enum ExportFormat {
+    /// 8-bit PNG with each `(key, text)` in a tEXt chunk.
+    Png(Vec<(&'static str, String)>),
+    /// 8-bit binary PPM, with no metadata.
+    Ppm,
+    /// 32-bit float OpenEXR of the linear HDR pixels, with no metadata.
+    Exr,
+}
+
+impl ExportFormat {
+    /// PPM for a `.ppm` path, EXR for an `.exr` one; otherwise PNG with
+    /// `text` in its tEXt chunks.
+    pub fn for_path(path: &Path, text: Vec<(&'static str, String)>) -> Self {
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+        if extension.eq_ignore_ascii_case("ppm") {
+            ExportFormat::Ppm
+        } else if extension.eq_ignore_ascii_case("exr") {
+            ExportFormat::Exr
+        } else {
+            ExportFormat::Png(text)
+        }
+    }
+}
+
+/// The pixels of a frame queued for export, as its format takes them.
+#[derive(Debug, Clone)]
+pub enum ExportPixels {
+    /// Resolved screen colors, for PNG and PPM.
+    Colors(Vec<Color>),
+    /// Linear HDR radiance, for EXR.
+    Linear(Vec<Vector3>),
+}
+
+impl ExportPixels {
+    fn len(&self) -> usize {
+        match self {
+            ExportPixels::Colors(colors) => colors.len(),
+            ExportPixels::Linear(pixels) => pixels.len(),
+        }
+    }
+
+    fn suits(&self, format: &ExportFormat) -> bool {
+        matches!(
+            (self, format),
+            (ExportPixels::Colors(_), ExportFormat::Png(_) | ExportFormat::Ppm) | (ExportPixels::Linear(_), ExportFormat::Exr)
+        )
+    }
+}
+
+/// What `ExportQueue::enqueue` does when the queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Waits for a thread to take a frame; none is lost.
+    Block,
+    /// Drops the oldest frame still waiting, which comes out of `poll` as
+    /// `Error::ExportDropped`; the loop never waits.
+    DropOldest,
+}
+
+// A frame and where it goes
+struct ExportJob {
+    pixels: ExportPixels,
+    width: i32,
+    height: i32,
+    path: PathBuf,
+    format: ExportFormat,
+}
+
+// What `ExportQueue` and its threads share
+#[derive(Default)]
+struct QueueState {
+    waiting: VecDeque<ExportJob>,
+    // In the hands of a thread
+    writing: usize,
+    // No more come in; the threads finish what is left and exit
+    closed: bool,
+}
+
+/// A queue of image files that worker threads encode and write in the
+/// background. At most `capacity` frames wait at once, so a long sequence
+/// does not fill memory; `Backpressure` decides what happens when no more
+/// fit. Results come out of `poll`, and `finish` (or dropping the queue)
+/// first writes everything pending.
+pub struct ExportQueue {
+    state: Arc<(Mutex<QueueState>, Condvar)>,
+    workers: Vec<JoinHandle<()>>,
+    results: Receiver<Result<PathBuf, Error>>,
+    // For dropped frames, which never reach a thread
+    sender: Sender<Result<PathBuf, Error>>,
+    capacity: usize,
+    policy: Backpressure,
+}
+
+impl ExportQueue {
+    /// A queue with `workers` threads and room for `capacity` waiting frames.
+    pub fn new(workers: usize, capacity: usize, policy: Backpressure) -> Self {
+        let state = Arc::new((Mutex::new(QueueState::default()), Condvar::new()));
+        let (sender, results) = channel();
+        let workers = (0..workers.max(1))
+            .map(|i| {
+                let state = Arc::clone(&state);
+                let sender = sender.clone();
+                thread::Builder::new()
+                    .name(format!("export-{}", i))
+                    .spawn(move || export_worker(&state, &sender))
+                    .expect("Could not start an export thread")
+            })
+            .collect();
+        ExportQueue { state, workers, results, sender, capacity: capacity.max(1), policy }
+    }
+
+    /// Queues `pixels`, `width` x `height` row by row, to be written to
+    /// `path` as `format`; see `Framebuffer::export_pixels`. With the queue
+    /// full it waits or drops the oldest, depending on the policy.
+    pub fn enqueue(&self, pixels: ExportPixels, width: i32, height: i32, path: PathBuf, format: ExportFormat) {
+        assert_eq!(pixels.len(), (width * height) as usize, "the pixels do not fill the image");
+        assert!(pixels.suits(&format), "EXR takes linear pixels, PNG and PPM resolved colors");
+        let (lock, changed) = &*self.state;
+        let mut state = lock.lock().expect("export queue lock");
+        while state.waiting.len() >= self.capacity {
+            match self.policy {
+                Backpressure::Block => state = changed.wait(state).expect("export queue lock"),
+                Backpressure::DropOldest => {
+                    if let Some(dropped) = state.waiting.pop_front() {
+                        let _ = self.sender.send(Err(Error::ExportDropped { path: dropped.path }));
+                    }
+                }
+            }
+        }
+        state.waiting.push_back(ExportJob { pixels, width, height, path, format });
+        changed.notify_all();
+    }
+
+    /// Frames waiting or being written.
+    pub fn pending(&self) -> usize {
+        let state = self.state.0.lock().expect("export queue lock");
+        state.waiting.len() + state.writing
+    }
+
+    /// A file finished since the last call: its path, or why it failed.
+    pub fn poll(&self) -> Option<Result<PathBuf, Error>> {
+        self.results.try_recv().ok()
+    }
+
+    /// Writes everything pending, stops the threads and returns the results
+    /// `poll` has not handed out yet.
+    pub fn finish(mut self) -> Vec<Result<PathBuf, Error>> {
+        self.shut_down();
+        self.results.try_iter().collect()
+    }
+
+    fn shut_down(&mut self) {
+        let (lock, changed) = &*self.state;
+        lock.lock().expect("export queue lock").closed = true;
+        changed.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for ExportQueue {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+// Takes frames until the queue is closed and empty
+fn export_worker(state: &(Mutex<QueueState>, Condvar), results: &Sender<Result<PathBuf, Error>>) {
+    let (lock, changed) = state;
+    loop {
+        let job = {
+            let mut state = lock.lock().expect("export queue lock");
+            loop {
+                if let Some(job) = state.waiting.pop_front() {
+                    state.writing += 1;
+                    break job;
+                }
+                if state.closed {
+                    return;
+                }
+                state = changed.wait(state).expect("export queue lock");
+            }
+        };
+        // There is room for another
+        changed.notify_all();
+        let written = match (&job.format, &job.pixels) {
+            (ExportFormat::Png(text), ExportPixels::Colors(colors)) => write_png(&job.path, colors, job.width, job.height, text),
+            (ExportFormat::Ppm, ExportPixels::Colors(colors)) => write_ppm(&job.path, colors, job.width, job.height),
+            (ExportFormat::Exr, ExportPixels::Linear(pixels)) => write_exr(&job.path, pixels, job.width, job.height),
+            _ => unreachable!("enqueue checks the pixels suit the format"),
+        };
+        lock.lock().expect("export queue lock").writing -= 1;
+        let _ = results.send(written.map(|()| job.path));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::hash3;
+
+    fn random_color(i: i32, seed: u32) -> Vector3 {
+        Vector3::new(hash3(i, 0, 0, seed), hash3(i, 1, 0, seed), hash3(i, 2, 0, seed)) * 4.0
+    }
+
+    #[test]
+    fn samples_average_to_their_mean() {
+        let mut frame = Framebuffer::new(4, 3);
+        let samples: Vec<Vector3> = (0..1000).map(|i| random_color(i, 1)).collect();
+        for sample in &samples {
+            frame.add_sample(2, 1, *sample);
+        }
+        let mean = samples.iter().fold(Vector3::zero(), |sum, sample| sum + *sample) / samples.len() as f32;
+        let pixel = frame.pixels()[(frame.width() + 2) as usize];
+        assert!((pixel - mean).length() < 1e-4, "running mean {:?} vs mean {:?}", pixel, mean);
+        assert_eq!(frame.sample_count(2, 1), 1000);
+        // The other pixels and the outside are untouched
+        assert_eq!(frame.sample_count(1, 1), 0);
+        assert_eq!(frame.pixels()[0], Vector3::zero());
+        frame.add_sample(-1, 7, Vector3::one());
+        assert_eq!(frame.sample_count(-1, 7), 0);
+    }
+
+    #[test]
+    fn resize_clears_the_sample_counts() {
+        let mut frame = Framebuffer::new(4, 3);
+        frame.overwrite(|pixels| pixels.fill(Vector3::one()));
+        frame.add_sample(0, 0, Vector3::zero());
+        assert_eq!(frame.sample_count(0, 0), 2);
+
+        // The same size keeps everything
+        frame.resize(4, 3);
+        assert_eq!(frame.sample_count(0, 0), 2);
+
+        frame.resize(5, 2);
+        assert_eq!((frame.width(), frame.height()), (5, 2));
+        assert_eq!(frame.pixels().len(), 10);
+        assert!((0..2).all(|y| (0..5).all(|x| frame.sample_count(x, y) == 0)));
+        assert!(frame.pixels().iter().all(|pixel| *pixel == Vector3::zero()));
+        assert_eq!(frame.take_dirty(), Some(Rect { x: 0, y: 0, width: 5, height: 2 }));
+    }
+
+    #[test]
+    fn resolve_into_matches_resolve() {
+        let (width, height) = (13, 7);
+        let frame = Framebuffer::from_pixels(width, height, (0..width * height).map(|i| random_color(i, 2)).collect());
+        for dither in [false, true] {
+            let settings = RenderSettings { dither, ..RenderSettings::default() };
+            for exposure_scale in [0.5, 1.0, 2.0] {
+                let mut colors = vec![Color::MAGENTA; (width * height) as usize];
+                frame.resolve_into(&mut colors, &settings, exposure_scale);
+                assert!(colors == frame.resolve(&settings, exposure_scale), "dither {} exposure {}", dither, exposure_scale);
+            }
+        }
+    }
+
+    #[test]
+    fn take_dirty_returns_the_union_of_the_marked_rects_once() {
+        let mut frame = Framebuffer::new(32, 24);
+        assert_eq!(frame.take_dirty(), Some(Rect { x: 0, y: 0, width: 32, height: 24 }));
+        assert_eq!(frame.take_dirty(), None);
+
+        frame.mark_dirty(Rect { x: 2, y: 3, width: 4, height: 5 });
+        frame.write_rect(Rect { x: 20, y: 1, width: 2, height: 2 }, &[Vector3::one(); 4]);
+        frame.mark_dirty(Rect { x: 10, y: 15, width: 1, height: 1 });
+        assert_eq!(frame.take_dirty(), Some(Rect { x: 2, y: 1, width: 20, height: 15 }));
+        assert_eq!(frame.take_dirty(), None);
+
+        // Clipped to the frame, and nothing at all when it lies outside
+        frame.mark_dirty(Rect { x: -5, y: 20, width: 10, height: 10 });
+        assert_eq!(frame.take_dirty(), Some(Rect { x: 0, y: 20, width: 5, height: 4 }));
+        frame.mark_dirty(Rect { x: 40, y: 0, width: 3, height: 3 });
+        assert_eq!(frame.take_dirty(), None);
+    }
+
+    #[test]
+    fn resolve_rect_matches_the_same_part_of_a_full_resolve() {
+        let (width, height) = (23, 11);
+        let frame = Framebuffer::from_pixels(width, height, (0..width * height).map(|i| random_color(i, 4)).collect());
+        let rects = [
+            Rect { x: 0, y: 0, width, height },
+            Rect { x: 5, y: 2, width: 7, height: 4 },
+            Rect { x: 18, y: 9, width: 10, height: 10 },
+            Rect { x: 0, y: 10, width: 1, height: 1 },
+        ];
+        for dither in [false, true] {
+            let settings = RenderSettings { dither, ..RenderSettings::default() };
+            let full = frame.resolve(&settings, 1.5);
+            for rect in rects {
+                let mut colors = vec![Color::MAGENTA; (width * height) as usize];
+                frame.resolve_rect_into(rect, &mut colors, &settings, 1.5);
+                let inside = rect.clip(width, height).unwrap();
+                for (i, color) in colors.iter().enumerate() {
+                    let (x, y) = (i as i32 % width, i as i32 / width);
+                    let covered = (inside.x..inside.x + inside.width).contains(&x) && (inside.y..inside.y + inside.height).contains(&y);
+                    let expected = if covered { full[i] } else { Color::MAGENTA };
+                    assert!(*color == expected, "dither {} rect {:?} pixel ({}, {})", dither, rect, x, y);
+                }
+            }
+        }
+    }
+
+    /// The longest run of equal red values along a row of `colors`, leaving
+    /// out black and white, where a ramp clamps and runs are expected.
+    fn longest_run(colors: &[Color], width: i32) -> usize {
+        colors
+            .chunks(width as usize)
+            .flat_map(|row| row.chunk_by(|a, b| a.r == b.r))
+            .filter(|run| run[0].r != 0 && run[0].r != 255)
+            .map(|run| run.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn dithered_gradient_has_no_long_bands() {
+        // Each 8-bit step of the ramp is 8192 / 255 = 32 columns wide. Where it
+        // is exactly on a step no offset below one step changes the result, so
+        // some run is left there; what dithering removes is the full band.
+        let (width, height) = (8192, 16);
+        let ramp = (0..width * height).map(|i| Vector3::one() * ((i % width) as f32 / (width - 1) as f32)).collect();
+        let frame = Framebuffer::from_pixels(width, height, ramp);
+
+        let banded = frame.resolve(&RenderSettings { dither: false, ..RenderSettings::default() }, 1.0);
+        let dithered = frame.resolve(&RenderSettings { dither: true, ..RenderSettings::default() }, 1.0);
+        assert!(longest_run(&banded, width) >= 32, "longest undithered run {}", longest_run(&banded, width));
+        assert!(longest_run(&dithered, width) <= 20, "longest dithered run {}", longest_run(&dithered, width));
+        // Still the same ramp on average: every column's mean is within half a step
+        for x in 0..width {
+            let mean = (0..height).map(|y| dithered[(y * width + x) as usize].r as f32).sum::<f32>() / height as f32;
+            let expected = x as f32 / (width - 1) as f32 * 255.0;
+            assert!((mean - expected).abs() <= 0.5, "column {}: mean {} vs {}", x, mean, expected);
+        }
+    }
+
+    #[test]
+    fn png_round_trip_matches_resolve() {
+        let (width, height) = (17, 9);
+        let frame = Framebuffer::from_pixels(width, height, (0..width * height).map(|i| random_color(i, 3)).collect());
+        let settings = RenderSettings { dither: true, ..RenderSettings::default() };
+        let camera = crate::camera::Camera::new(Vector3::new(1.0, 2.0, 3.0), Vector3::zero(), Vector3::up());
+        let metadata = ShotMetadata::capture(&camera, width, height, 0.5, Some(7), &settings);
+        let path = std::env::temp_dir().join(format!("raytracer-framebuffer-{}.png", std::process::id()));
+        frame.save_png(&path, &settings, Some(&metadata)).unwrap();
+
+        let decoder = png::Decoder::new(std::io::BufReader::new(fs::File::open(&path).unwrap()));
+        let mut reader = decoder.read_info().unwrap();
+        let mut bytes = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut bytes).unwrap();
+        let text: Vec<(String, String)> =
+            reader.info().uncompressed_latin1_text.iter().map(|chunk| (chunk.keyword.clone(), chunk.text.clone())).collect();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!((info.width, info.height), (width as u32, height as u32));
+        assert_eq!((info.color_type, info.bit_depth), (png::ColorType::Rgba, png::BitDepth::Eight));
+        let decoded: Vec<Color> = bytes[..info.buffer_size()].chunks(4).map(|c| Color::new(c[0], c[1], c[2], c[3])).collect();
+        assert!(decoded == frame.resolve(&settings, 1.0), "the decoded pixels differ from resolve");
+        let expected: Vec<(String, String)> = metadata.png_text().into_iter().map(|(key, value)| (key.to_string(), value)).collect();
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn exr_round_trip_keeps_the_linear_pixels() {
+        let (width, height) = (13, 7);
+        // Well past 1.0, where an 8-bit format would clip
+        let frame = Framebuffer::from_pixels(width, height, (0..width * height).map(|i| random_color(i, 5) * 40.0).collect());
+        let path = std::env::temp_dir().join(format!("raytracer-framebuffer-{}.exr", std::process::id()));
+        frame.save_exr(&path).unwrap();
+
+        let decoded = image::open(&path).unwrap().into_rgb32f();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(decoded.dimensions(), (width as u32, height as u32));
+        let pixels: Vec<Vector3> = decoded.pixels().map(|p| Vector3::new(p[0], p[1], p[2])).collect();
+        assert!(pixels == frame.pixels(), "the decoded pixels differ from the frame");
+    }
+
+    #[test]
+    fn export_queue_writes_exr_next_to_png() {
+        let (width, height) = (5, 4);
+        let frame = Framebuffer::from_pixels(width, height, (0..width * height).map(|i| random_color(i, 9) * 3.0).collect());
+        let settings = RenderSettings::default();
+        let dir = std::env::temp_dir();
+        let paths = ["exr", "png"].map(|extension| dir.join(format!("raytracer-export-{}.{}", std::process::id(), extension)));
+        let queue = ExportQueue::new(1, 2, Backpressure::Block);
+        for path in &paths {
+            let format = ExportFormat::for_path(path, Vec::new());
+            queue.enqueue(frame.export_pixels(&format, &settings), width, height, path.clone(), format);
+        }
+        let results = queue.finish();
+
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(Result::is_ok), "{:?}", results);
+        let exr = image::open(&paths[0]).unwrap().into_rgb32f();
+        let png = image::open(&paths[1]).unwrap().into_rgba8();
+        for path in &paths {
+            fs::remove_file(path).unwrap();
+        }
+        assert_eq!(exr.get_pixel(4, 3).0, [frame.pixels()[19].x, frame.pixels()[19].y, frame.pixels()[19].z]);
+        let resolved = frame.resolve(&settings, 1.0)[19];
+        assert_eq!(png.get_pixel(4, 3).0, [resolved.r, resolved.g, resolved.b, resolved.a]);
     }
-    // La función `swap_buffers` se ha eliminado. La lógica de dibujado ahora está en el bucle principal de `main.rs` para permitir dibujar el texto de los FPS encima de la imagen renderizada.
 }
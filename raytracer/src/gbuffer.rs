@@ -0,0 +1,140 @@
+// gbuffer.rs
+// Surface data of each pixel's first hit, kept next to the color for whatever
+// needs more than a color: the debug views, post passes, and images written
+// next to a render. Depth is not stored here because the frame always has it;
+// the edge pass and the weather read it every frame. The normal, albedo and
+// object id cost a texture lookup and three writes per pixel, so `render_into`
+// fills them only when it is handed a `GBuffer`. The window keeps one while a
+// view that shows it is on, and `render --gbuffer` while it writes the images.
+// The GPU backend returns colors only and leaves the buffers as sky.
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use raylib::prelude::*;
+
+use crate::error::Error;
+use crate::heatmap::heat_color;
+use crate::material::vector3_to_color;
+use crate::screenshot::write_png;
+
+/// Normal, albedo and object id of the first hit of each pixel's camera ray,
+/// by rows.
+#[derive(Debug, Clone, Default)]
+pub struct GBuffer {
+    /// Face normal; zero for the sky.
+    pub normal: Vec<Vector3>,
+    /// Diffuse color times diffuse albedo, with the texture and biome tint;
+    /// zero for the sky.
+    pub albedo: Vec<Vector3>,
+    /// Id of the block hit, the same for every pixel on one block; 0 for the sky.
+    pub object_id: Vec<u32>,
+}
+
+impl GBuffer {
+    /// Buffers for `len` pixels, all sky.
+    pub fn new(len: usize) -> Self {
+        GBuffer { normal: vec![Vector3::zero(); len], albedo: vec![Vector3::zero(); len], object_id: vec![0; len] }
+    }
+
+    /// Pixels the buffers hold.
+    pub fn len(&self) -> usize {
+        self.object_id.len()
+    }
+
+    /// Whether the buffers hold no pixels.
+    pub fn is_empty(&self) -> bool {
+        self.object_id.is_empty()
+    }
+
+    /// Sets every pixel back to sky.
+    pub fn clear(&mut self) {
+        self.normal.fill(Vector3::zero());
+        self.albedo.fill(Vector3::zero());
+        self.object_id.fill(0);
+    }
+}
+
+/// What the window shows in place of the frame, for checking the G-buffer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum GBufferView {
+    /// The frame itself.
+    #[default]
+    Off,
+    /// Hit distance, blue near to red far; black for the sky.
+    Depth,
+    /// Normal, each axis from -1 to 1 mapped to a channel from 0 to 255.
+    Normal,
+    /// Albedo, unlit and without tone mapping.
+    Albedo,
+    /// A random color per object id.
+    ObjectId,
+}
+
+impl GBufferView {
+    /// The view after this one; after the last, the frame.
+    pub fn next(self) -> Self {
+        match self {
+            GBufferView::Off => GBufferView::Depth,
+            GBufferView::Depth => GBufferView::Normal,
+            GBufferView::Normal => GBufferView::Albedo,
+            GBufferView::Albedo => GBufferView::ObjectId,
+            GBufferView::ObjectId => GBufferView::Off,
+        }
+    }
+
+    /// Name for the HUD and file names.
+    pub fn name(self) -> &'static str {
+        match self {
+            GBufferView::Off => "off",
+            GBufferView::Depth => "depth",
+            GBufferView::Normal => "normal",
+            GBufferView::Albedo => "albedo",
+            GBufferView::ObjectId => "id",
+        }
+    }
+
+    /// Whether the view reads a `GBuffer`; depth comes with every frame.
+    pub fn needs_gbuffer(self) -> bool {
+        matches!(self, GBufferView::Normal | GBufferView::Albedo | GBufferView::ObjectId)
+    }
+}
+
+/// Draws `view` into `colors`, one per pixel. Depth is scaled to the farthest
+/// hit in the frame. Views that need a G-buffer leave `colors` alone without
+/// one, and so does `GBufferView::Off`.
+pub fn draw_view(view: GBufferView, depth: &[f32], gbuffer: Option<&GBuffer>, colors: &mut [Color]) {
+    match (view, gbuffer) {
+        (GBufferView::Depth, _) => {
+            let far = depth.par_iter().copied().filter(|d| d.is_finite()).reduce(|| 0.0, f32::max).max(1e-3);
+            colors.par_iter_mut().zip(depth).for_each(|(color, &d)| {
+                *color = if d.is_finite() { heat_color(d / far) } else { Color::BLACK };
+            });
+        }
+        (GBufferView::Normal, Some(gbuffer)) => colors.par_iter_mut().zip(&gbuffer.normal).for_each(|(color, &normal)| {
+            *color = if normal == Vector3::zero() { Color::BLACK } else { vector3_to_color(normal * 0.5 + Vector3::one() * 0.5) };
+        }),
+        (GBufferView::Albedo, Some(gbuffer)) => {
+            colors.par_iter_mut().zip(&gbuffer.albedo).for_each(|(color, &albedo)| *color = vector3_to_color(albedo));
+        }
+        (GBufferView::ObjectId, Some(gbuffer)) => colors.par_iter_mut().zip(&gbuffer.object_id).for_each(|(color, &id)| {
+            let [r, g, b, _] = id.wrapping_mul(0x9E37_79B1).to_le_bytes();
+            *color = if id == 0 { Color::BLACK } else { Color::new(r, g, b, 255) };
+        }),
+        _ => {}
+    }
+}
+
+/// Writes the depth, normal, albedo and object id views as PNGs named after
+/// `path`: `render.png` gives `render.depth.png`, `render.normal.png`,
+/// `render.albedo.png` and `render.id.png`. Returns the paths written.
+pub fn save_view_pngs(path: &Path, width: i32, height: i32, depth: &[f32], gbuffer: &GBuffer) -> Result<Vec<PathBuf>, Error> {
+    let mut colors = vec![Color::BLACK; (width * height) as usize];
+    let mut written = Vec::new();
+    for view in [GBufferView::Depth, GBufferView::Normal, GBufferView::Albedo, GBufferView::ObjectId] {
+        draw_view(view, depth, Some(gbuffer), &mut colors);
+        let view_path = path.with_extension(format!("{}.png", view.name()));
+        write_png(&view_path, &colors, width, height, &[])?;
+        written.push(view_path);
+    }
+    Ok(written)
+}
@@ -0,0 +1,420 @@
+// gpu.rs
+// Optional wgpu compute backend (feature "gpu"). The scene is uploaded once:
+// rope BVH nodes, cubes, deduplicated materials and every texture flattened
+// into one texel buffer. Each frame only the uniforms change; the shader
+// mirrors the CPU primary ray + direct sun lighting (diffuse, Phong specular,
+// hard shadow, emission, texture, skybox, fog) and the framebuffer and depth
+// buffer are read back.
+//
+// Not on the GPU yet: emissive block lights, soft shadows, biome tint,
+// clouds, streamed chunks, reflection and refraction.
+use std::borrow::Cow;
+use std::sync::mpsc;
+
+use bytemuck::{Pod, Zeroable};
+use raylib::prelude::*;
+use wgpu::util::DeviceExt;
+
+use crate::accel::NO_CHILD;
+use crate::camera::Camera;
+use crate::light::Light;
+use crate::material::Material;
+use crate::scene::Scene;
+use crate::settings::RenderSettings;
+use crate::textures::TextureManager;
+
+const WORKGROUP_SIZE: u32 = 8;
+const NO_TEXTURE: u32 = u32::MAX;
+// Distance the shader writes for a miss
+const GPU_FAR: f32 = 1e30;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct GpuUniforms {
+    eye: [f32; 3],
+    perspective_scale: f32,
+    forward: [f32; 3],
+    aspect_ratio: f32,
+    right: [f32; 3],
+    width: u32,
+    up: [f32; 3],
+    height: u32,
+    light_position: [f32; 3],
+    light_intensity: f32,
+    light_color: [f32; 3],
+    fog_density: f32,
+    fog_color: [f32; 3],
+    has_skybox: u32,
+    node_count: u32,
+    _pad: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct GpuNode {
+    min: [f32; 3],
+    entry: u32,
+    max: [f32; 3],
+    exit: u32,
+    shape: u32,
+    _pad: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct GpuCube {
+    min: [f32; 3],
+    material: u32,
+    max: [f32; 3],
+    _pad: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable, PartialEq)]
+struct GpuMaterial {
+    diffuse: [f32; 3],
+    albedo_diffuse: f32,
+    emission: [f32; 3],
+    albedo_specular: f32,
+    specular: f32,
+    texture: u32,
+    _pad: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct GpuTextureRef {
+    offset: u32,
+    width: u32,
+    height: u32,
+    _pad: u32,
+}
+
+fn to_array(v: Vector3) -> [f32; 3] {
+    [v.x, v.y, v.z]
+}
+
+fn pack_texel(c: &Vector3) -> u32 {
+    let channel = |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u32;
+    channel(c.x) | channel(c.y) << 8 | channel(c.z) << 16 | 0xff << 24
+}
+
+/// Every texture the scene references, flattened into one texel array.
+/// The six skybox faces, when present, always occupy slots 0..6 in the
+/// front, back, left, right, top, bottom order the shader expects.
+#[derive(Default)]
+struct TextureSet {
+    refs: Vec<GpuTextureRef>,
+    texels: Vec<u32>,
+    slots: Vec<String>,
+}
+
+impl TextureSet {
+    fn slot(&mut self, texture_manager: &TextureManager, path: &str) -> u32 {
+        if let Some(index) = self.slots.iter().position(|p| p == path) {
+            return index as u32;
+        }
+        let Some((width, height, pixels)) = texture_manager.texture_pixels(path) else {
+            return NO_TEXTURE;
+        };
+        self.refs.push(GpuTextureRef {
+            offset: self.texels.len() as u32,
+            width: width as u32,
+            height: height as u32,
+            _pad: 0,
+        });
+        self.texels.extend(pixels.iter().map(pack_texel));
+        self.slots.push(path.to_string());
+        (self.refs.len() - 1) as u32
+    }
+}
+
+fn storage_buffer<T: Pod>(device: &wgpu::Device, label: &str, data: &[T], zeroed: T) -> wgpu::Buffer {
+    // Bindings cannot be empty, so an empty list still uploads one element
+    let contents: Cow<[T]> = if data.is_empty() { Cow::Owned(vec![zeroed]) } else { Cow::Borrowed(data) };
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(label),
+        contents: bytemuck::cast_slice(&contents),
+        usage: wgpu::BufferUsages::STORAGE,
+    })
+}
+
+/// The compute pipeline with the scene already uploaded, and the buffers one
+/// frame of `width` x `height` pixels is traced into and read back from.
+pub struct GpuRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    uniforms: wgpu::Buffer,
+    color_output: wgpu::Buffer,
+    depth_output: wgpu::Buffer,
+    color_readback: wgpu::Buffer,
+    depth_readback: wgpu::Buffer,
+    node_count: u32,
+    has_skybox: bool,
+    width: i32,
+    height: i32,
+}
+
+impl GpuRenderer {
+    /// Opens a high-performance adapter and uploads `scene` and every texture
+    /// it uses for frames of `width` x `height`. Fails, with the reason, if
+    /// there is no adapter or the device cannot be created.
+    pub fn new(scene: &Scene, texture_manager: &TextureManager, width: i32, height: i32) -> Result<Self, String> {
+        pollster::block_on(Self::new_async(scene, texture_manager, width, height))
+    }
+
+    async fn new_async(scene: &Scene, texture_manager: &TextureManager, width: i32, height: i32) -> Result<Self, String> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                force_fallback_adapter: false,
+                compatible_surface: None,
+            })
+            .await
+            .ok_or("no compatible GPU adapter")?;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("raytracer"),
+                    required_features: wgpu::Features::empty(),
+                    required_limits: wgpu::Limits::default(),
+                    memory_hints: wgpu::MemoryHints::Performance,
+                },
+                None,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut textures = TextureSet::default();
+        let has_skybox = match texture_manager.skybox() {
+            Some(sky) => [&sky.front, &sky.back, &sky.left, &sky.right, &sky.top, &sky.bottom]
+                .iter()
+                .all(|face| textures.slot(texture_manager, face) != NO_TEXTURE),
+            None => false,
+        };
+        if !has_skybox {
+            // A partial skybox would shift the material slots; start over
+            textures = TextureSet::default();
+        }
+
+        let mut materials: Vec<GpuMaterial> = Vec::new();
+        let mut material_index = |material: &Material, textures: &mut TextureSet| -> u32 {
+            let texture = match &material.texture {
+                Some(path) => textures.slot(texture_manager, path),
+                None => NO_TEXTURE,
+            };
+            let gpu_material = GpuMaterial {
+                diffuse: to_array(material.diffuse),
+                albedo_diffuse: material.albedo[0],
+                emission: to_array(material.emission),
+                albedo_specular: material.albedo[1],
+                specular: material.specular,
+                texture,
+                _pad: [0; 2],
+            };
+            match materials.iter().position(|m| *m == gpu_material) {
+                Some(index) => index as u32,
+                None => {
+                    materials.push(gpu_material);
+                    (materials.len() - 1) as u32
+                }
+            }
+        };
+
+        let cubes: Vec<GpuCube> = scene
+            .objects
+            .iter()
+            .map(|cube| GpuCube {
+                min: to_array(cube.min_bounds),
+                material: material_index(&cube.material, &mut textures),
+                max: to_array(cube.max_bounds),
+                _pad: 0,
+            })
+            .collect();
+
+        let nodes: Vec<GpuNode> = scene
+            .ropes
+            .nodes()
+            .iter()
+            .map(|node| GpuNode {
+                min: [node.aabb.min.x, node.aabb.min.y, node.aabb.min.z],
+                entry: node.entry,
+                max: [node.aabb.max.x, node.aabb.max.y, node.aabb.max.z],
+                exit: node.exit,
+                shape: node.shape_index,
+                _pad: [0; 3],
+            })
+            .collect();
+        debug_assert!(nodes.iter().all(|n| n.entry == NO_CHILD || n.entry < nodes.len() as u32));
+
+        let pixel_count = (width * height) as u64;
+        let output_size = pixel_count * 4;
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("uniforms"),
+            size: std::mem::size_of::<GpuUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let output = |label: &str, usage: wgpu::BufferUsages| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: output_size,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        let storage_out = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC;
+        let readback = wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST;
+        let color_output = output("color output", storage_out);
+        let depth_output = output("depth output", storage_out);
+        let color_readback = output("color readback", readback);
+        let depth_readback = output("depth readback", readback);
+
+        let node_buffer = storage_buffer(&device, "rope nodes", &nodes, GpuNode::zeroed());
+        let cube_buffer = storage_buffer(&device, "cubes", &cubes, GpuCube::zeroed());
+        let material_buffer = storage_buffer(&device, "materials", &materials, GpuMaterial::zeroed());
+        let texture_buffer = storage_buffer(&device, "texture refs", &textures.refs, GpuTextureRef::zeroed());
+        let texel_buffer = storage_buffer(&device, "texels", &textures.texels, 0u32);
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("trace"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("gpu_trace.wgsl"))),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("trace"),
+            layout: None,
+            module: &module,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let buffers = [
+            &uniforms,
+            &node_buffer,
+            &cube_buffer,
+            &material_buffer,
+            &texture_buffer,
+            &texel_buffer,
+            &color_output,
+            &depth_output,
+        ];
+        let entries: Vec<wgpu::BindGroupEntry> = buffers
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("trace"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        });
+
+        Ok(GpuRenderer {
+            device,
+            queue,
+            pipeline,
+            bind_group,
+            uniforms,
+            color_output,
+            depth_output,
+            color_readback,
+            depth_readback,
+            node_count: nodes.len() as u32,
+            has_skybox,
+            width,
+            height,
+        })
+    }
+
+    /// Same outputs as the CPU `render`: one color per pixel and the primary
+    /// hit distance (INFINITY on a miss) in `depth_buffer`.
+    pub fn render(
+        &mut self,
+        camera: &Camera,
+        light: &Light,
+        settings: &RenderSettings,
+        depth_buffer: &mut [f32],
+    ) -> Result<Vec<Color>, String> {
+        // The shader scales x by aspect_ratio * perspective_scale, which is the
+        // horizontal extent whichever axis the field of view is fixed on
+        let (_, extent_y) = camera.screen_extent(self.width, self.height);
+        let uniforms = GpuUniforms {
+            eye: to_array(camera.eye),
+            perspective_scale: extent_y,
+            forward: to_array(camera.forward),
+            aspect_ratio: self.width as f32 / self.height as f32,
+            right: to_array(camera.right),
+            width: self.width as u32,
+            up: to_array(camera.up),
+            height: self.height as u32,
+            light_position: to_array(light.position),
+            light_intensity: light.intensity,
+            light_color: to_array(light.color),
+            fog_density: settings.fog_density,
+            fog_color: to_array(settings.fog_color),
+            has_skybox: self.has_skybox as u32,
+            node_count: self.node_count,
+            _pad: [0; 3],
+        };
+        self.queue.write_buffer(&self.uniforms, 0, bytemuck::bytes_of(&uniforms));
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("trace") });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("trace"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.dispatch_workgroups(
+                (self.width as u32).div_ceil(WORKGROUP_SIZE),
+                (self.height as u32).div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+        let size = self.color_output.size();
+        encoder.copy_buffer_to_buffer(&self.color_output, 0, &self.color_readback, 0, size);
+        encoder.copy_buffer_to_buffer(&self.depth_output, 0, &self.depth_readback, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let (sender, receiver) = mpsc::channel();
+        for buffer in [&self.color_readback, &self.depth_readback] {
+            let sender = sender.clone();
+            buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        }
+        self.device.poll(wgpu::Maintain::Wait);
+        for _ in 0..2 {
+            receiver
+                .recv()
+                .map_err(|e| e.to_string())?
+                .map_err(|e| e.to_string())?;
+        }
+
+        let pixels = {
+            let data = self.color_readback.slice(..).get_mapped_range();
+            bytemuck::cast_slice::<u8, u32>(&data)
+                .iter()
+                .map(|p| Color::new(*p as u8, (*p >> 8) as u8, (*p >> 16) as u8, 255))
+                .collect()
+        };
+        {
+            let data = self.depth_readback.slice(..).get_mapped_range();
+            let depths: &[f32] = bytemuck::cast_slice(&data);
+            for (out, depth) in depth_buffer.iter_mut().zip(depths) {
+                *out = if *depth >= GPU_FAR { f32::INFINITY } else { *depth };
+            }
+        }
+        self.color_readback.unmap();
+        self.depth_readback.unmap();
+        Ok(pixels)
+    }
+}
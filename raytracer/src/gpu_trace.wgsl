@@ -0,0 +1,302 @@
+// gpu_trace.wgsl
+// GPU port of the primary ray path in render.rs: nearest hit through the rope
+// BVH, then sun lighting (diffuse + Phong specular, hard shadow), emission,
+// textures, skybox and fog. Constants match their CPU counterparts.
+
+struct Uniforms {
+    eye: vec3<f32>,
+    perspective_scale: f32,
+    forward: vec3<f32>,
+    aspect_ratio: f32,
+    right: vec3<f32>,
+    width: u32,
+    up: vec3<f32>,
+    height: u32,
+    light_position: vec3<f32>,
+    light_intensity: f32,
+    light_color: vec3<f32>,
+    fog_density: f32,
+    fog_color: vec3<f32>,
+    has_skybox: u32,
+    node_count: u32,
+};
+
+struct Node {
+    min: vec3<f32>,
+    entry: u32,
+    max: vec3<f32>,
+    exit: u32,
+    shape: u32,
+};
+
+struct Cube {
+    min: vec3<f32>,
+    material: u32,
+    max: vec3<f32>,
+    _pad: u32,
+};
+
+struct Material {
+    diffuse: vec3<f32>,
+    albedo_diffuse: f32,
+    emission: vec3<f32>,
+    albedo_specular: f32,
+    specular: f32,
+    texture: u32,
+};
+
+struct TextureRef {
+    offset: u32,
+    width: u32,
+    height: u32,
+    _pad: u32,
+};
+
+@group(0) @binding(0) var<uniform> u: Uniforms;
+@group(0) @binding(1) var<storage, read> nodes: array<Node>;
+@group(0) @binding(2) var<storage, read> cubes: array<Cube>;
+@group(0) @binding(3) var<storage, read> materials: array<Material>;
+@group(0) @binding(4) var<storage, read> textures: array<TextureRef>;
+@group(0) @binding(5) var<storage, read> texels: array<u32>;
+@group(0) @binding(6) var<storage, read_write> color_out: array<u32>;
+@group(0) @binding(7) var<storage, read_write> depth_out: array<f32>;
+
+const NO_CHILD: u32 = 0xffffffffu;
+const NO_TEXTURE: u32 = 0xffffffffu;
+const MIN_DISTANCE: f32 = 0.001;
+const SHADOW_STRENGTH: f32 = 0.7;
+const FOG_SKY_DISTANCE: f32 = 60.0;
+const FAR: f32 = 1e30;
+
+struct Hit {
+    distance: f32,
+    cube: u32,
+};
+
+// Avoids 0 * inf = NaN in the slab test for axis-parallel rays
+fn safe_inverse(d: vec3<f32>) -> vec3<f32> {
+    let eps = vec3<f32>(1e-8);
+    let safe = select(d, select(-eps, eps, d >= vec3<f32>(0.0)), abs(d) < eps);
+    return 1.0 / safe;
+}
+
+// (t_near, t_far) of the ray against a box
+fn slab(origin: vec3<f32>, inv_dir: vec3<f32>, bmin: vec3<f32>, bmax: vec3<f32>) -> vec2<f32> {
+    let t0 = (bmin - origin) * inv_dir;
+    let t1 = (bmax - origin) * inv_dir;
+    let lo = min(t0, t1);
+    let hi = max(t0, t1);
+    return vec2<f32>(max(max(lo.x, lo.y), lo.z), min(min(hi.x, hi.y), hi.z));
+}
+
+// Same rule as Cube::ray_intersect: entry distance, or exit when starting inside
+fn cube_distance(origin: vec3<f32>, inv_dir: vec3<f32>, cube: Cube) -> f32 {
+    let t = slab(origin, inv_dir, cube.min, cube.max);
+    if t.x > t.y {
+        return FAR;
+    }
+    let distance = select(t.y, t.x, t.x > MIN_DISTANCE);
+    if distance < MIN_DISTANCE {
+        return FAR;
+    }
+    return distance;
+}
+
+fn trace_closest(origin: vec3<f32>, direction: vec3<f32>) -> Hit {
+    var hit = Hit(FAR, NO_CHILD);
+    let inv_dir = safe_inverse(direction);
+    var index = 0u;
+    while index < u.node_count {
+        let node = nodes[index];
+        let t = slab(origin, inv_dir, node.min, node.max);
+        if t.x > t.y || t.y < 0.0 || t.x > hit.distance {
+            index = node.exit;
+        } else if node.entry == NO_CHILD {
+            let distance = cube_distance(origin, inv_dir, cubes[node.shape]);
+            if distance < hit.distance {
+                hit = Hit(distance, node.shape);
+            }
+            index = node.exit;
+        } else {
+            index = node.entry;
+        }
+    }
+    return hit;
+}
+
+fn occluded(origin: vec3<f32>, direction: vec3<f32>, max_distance: f32) -> bool {
+    let inv_dir = safe_inverse(direction);
+    var index = 0u;
+    while index < u.node_count {
+        let node = nodes[index];
+        let t = slab(origin, inv_dir, node.min, node.max);
+        if t.x > t.y || t.y < 0.0 || t.x > max_distance {
+            index = node.exit;
+        } else if node.entry == NO_CHILD {
+            if cube_distance(origin, inv_dir, cubes[node.shape]) < max_distance {
+                return true;
+            }
+            index = node.exit;
+        } else {
+            index = node.entry;
+        }
+    }
+    return false;
+}
+
+fn cube_normal(cube: Cube, p: vec3<f32>) -> vec3<f32> {
+    let epsilon = 1e-4;
+    if abs(p.x - cube.min.x) < epsilon { return vec3<f32>(-1.0, 0.0, 0.0); }
+    if abs(p.x - cube.max.x) < epsilon { return vec3<f32>(1.0, 0.0, 0.0); }
+    if abs(p.y - cube.min.y) < epsilon { return vec3<f32>(0.0, -1.0, 0.0); }
+    if abs(p.y - cube.max.y) < epsilon { return vec3<f32>(0.0, 1.0, 0.0); }
+    if abs(p.z - cube.min.z) < epsilon { return vec3<f32>(0.0, 0.0, -1.0); }
+    if abs(p.z - cube.max.z) < epsilon { return vec3<f32>(0.0, 0.0, 1.0); }
+    return vec3<f32>(0.0);
+}
+
+fn cube_uv(cube: Cube, p: vec3<f32>, n: vec3<f32>) -> vec2<f32> {
+    let size = cube.max - cube.min;
+    let local = (p - cube.min) / size;
+    if abs(n.x) > 0.5 {
+        return local.zy;
+    } else if abs(n.y) > 0.5 {
+        return local.xz;
+    }
+    return local.xy;
+}
+
+fn texel(texture: TextureRef, x: u32, y: u32) -> vec3<f32> {
+    let tx = min(x, texture.width - 1u);
+    let ty = min(y, texture.height - 1u);
+    return unpack4x8unorm(texels[texture.offset + ty * texture.width + tx]).rgb;
+}
+
+fn sample_texture(index: u32, uv: vec2<f32>) -> vec3<f32> {
+    let texture = textures[index];
+    let x = u32(max(uv.x, 0.0) * f32(texture.width));
+    let y = u32(max(uv.y, 0.0) * f32(texture.height));
+    return texel(texture, x, y);
+}
+
+// Matches TextureManager::sample_skybox, including the procedural fallback.
+// Slots 0..6 are front, back, left, right, top, bottom.
+fn sample_sky(d: vec3<f32>) -> vec3<f32> {
+    if u.has_skybox == 0u {
+        let t = (normalize(d).y + 1.0) * 0.5;
+        let green = vec3<f32>(0.1, 0.6, 0.2);
+        let white = vec3<f32>(1.0);
+        let blue = vec3<f32>(0.3, 0.5, 1.0);
+        if t < 0.54 {
+            return mix(green, white, t / 0.55);
+        } else if t < 0.55 {
+            return white;
+        } else if t < 0.8 {
+            return mix(white, blue, (t - 0.55) / 0.25);
+        }
+        return blue;
+    }
+
+    let a = abs(d);
+    var uv: vec2<f32>;
+    var face: u32;
+    if a.x > a.y && a.x > a.z {
+        if d.x > 0.0 {
+            uv = vec2<f32>(-d.z, -d.y) / a.x;
+            face = 3u;
+        } else {
+            uv = vec2<f32>(d.z, -d.y) / a.x;
+            face = 2u;
+        }
+    } else if a.y > a.z {
+        if d.y > 0.0 {
+            uv = vec2<f32>(d.x, -d.z) / a.y;
+            face = 4u;
+        } else {
+            uv = vec2<f32>(d.x, d.z) / a.y;
+            face = 5u;
+        }
+    } else {
+        if d.z > 0.0 {
+            uv = vec2<f32>(d.x, -d.y) / a.z;
+            face = 0u;
+        } else {
+            uv = vec2<f32>(-d.x, -d.y) / a.z;
+            face = 1u;
+        }
+    }
+    uv = clamp(uv * 0.5 + 0.5, vec2<f32>(0.0), vec2<f32>(1.0));
+    let texture = textures[face];
+    let x = u32(uv.x * f32(texture.width - 1u));
+    let y = u32(uv.y * f32(texture.height - 1u));
+    return texel(texture, x, y);
+}
+
+fn apply_fog(color: vec3<f32>, distance: f32) -> vec3<f32> {
+    if u.fog_density <= 0.0 {
+        return color;
+    }
+    let amount = 1.0 - exp(-u.fog_density * min(distance, FOG_SKY_DISTANCE));
+    return mix(color, u.fog_color, amount);
+}
+
+fn shade(direction: vec3<f32>, hit: Hit) -> vec3<f32> {
+    let cube = cubes[hit.cube];
+    let material = materials[cube.material];
+    let point = u.eye + direction * hit.distance;
+    let normal = cube_normal(cube, point);
+
+    let light_direction = normalize(u.light_position - point);
+    let shadow_origin = point + normal * 0.001;
+    let light_distance = length(u.light_position - shadow_origin);
+    var shadow = 0.0;
+    if occluded(shadow_origin, light_direction, light_distance) {
+        shadow = SHADOW_STRENGTH;
+    }
+    let light_intensity = u.light_intensity * (1.0 - shadow);
+
+    let view_direction = normalize(u.eye - point);
+    let reflection_direction = normalize(reflect(-light_direction, normal));
+    let diffuse_intensity = max(dot(normal, light_direction), 0.0) * light_intensity;
+    let specular = u.light_color * pow(max(dot(view_direction, reflection_direction), 0.0), material.specular) * light_intensity;
+
+    var diffuse_color = material.diffuse;
+    if material.texture != NO_TEXTURE {
+        diffuse_color = sample_texture(material.texture, cube_uv(cube, point, normal));
+    }
+
+    return material.emission
+        + diffuse_color * diffuse_intensity * material.albedo_diffuse
+        + specular * material.albedo_specular;
+}
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= u.width || id.y >= u.height {
+        return;
+    }
+
+    let screen_x = (2.0 * f32(id.x) / f32(u.width) - 1.0) * u.aspect_ratio * u.perspective_scale;
+    let screen_y = (1.0 - 2.0 * f32(id.y) / f32(u.height)) * u.perspective_scale;
+    let camera_direction = normalize(vec3<f32>(screen_x, screen_y, -1.0));
+    let direction = camera_direction.x * u.right + camera_direction.y * u.up - camera_direction.z * u.forward;
+
+    let hit = trace_closest(u.eye, direction);
+    var color: vec3<f32>;
+    var distance: f32;
+    if hit.cube == NO_CHILD {
+        color = sample_sky(direction);
+        distance = FAR;
+    } else {
+        color = shade(direction, hit);
+        distance = hit.distance;
+    }
+    color = apply_fog(color, distance);
+
+    let index = id.y * u.width + id.x;
+    // vector3_to_color truncates, so floor rather than round when packing
+    let rgb = vec3<u32>(min(max(color, vec3<f32>(0.0)) * 255.0, vec3<f32>(255.0)));
+    color_out[index] = rgb.x | (rgb.y << 8u) | (rgb.z << 16u) | (255u << 24u);
+    // Misses store FAR; the host turns it into INFINITY like the CPU path
+    depth_out[index] = distance;
+}
@@ -0,0 +1,43 @@
+// heatmap.rs
+// False-color debug views composited over the tone-mapped frame. Values are
+// normalized to [0, 1] by the caller and mapped blue -> cyan -> green ->
+// yellow -> red, so any per-pixel cost can be shown on the same scale.
+use rayon::prelude::*;
+use raylib::prelude::*;
+
+/// How much of the heatmap shows over the frame underneath.
+const OVERLAY_OPACITY: f32 = 0.6;
+
+const STOPS: [(u8, u8, u8); 5] = [(0, 0, 255), (0, 255, 255), (0, 255, 0), (255, 255, 0), (255, 0, 0)];
+
+/// Heatmap color of `t` in [0, 1]: blue for 0, red for 1. Out-of-range and
+/// NaN values are clamped.
+pub fn heat_color(t: f32) -> Color {
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+    let scaled = t * (STOPS.len() - 1) as f32;
+    let index = (scaled as usize).min(STOPS.len() - 2);
+    let f = scaled - index as f32;
+    let (a, b) = (STOPS[index], STOPS[index + 1]);
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * f).round() as u8;
+    Color::new(lerp(a.0, b.0), lerp(a.1, b.1), lerp(a.2, b.2), 255)
+}
+
+/// Blends `heat` over every pixel at `OVERLAY_OPACITY`. `heat` holds one
+/// value in [0, 1] per pixel.
+pub fn overlay(pixels: &mut [Color], heat: impl IndexedParallelIterator<Item = f32>) {
+    pixels.par_iter_mut().zip(heat).for_each(|(pixel, t)| {
+        let tint = heat_color(t);
+        let mix = |base: u8, tint: u8| {
+            (base as f32 * (1.0 - OVERLAY_OPACITY) + tint as f32 * OVERLAY_OPACITY).round() as u8
+        };
+        *pixel = Color::new(mix(pixel.r, tint.r), mix(pixel.g, tint.g), mix(pixel.b, tint.b), 255);
+    });
+}
+
+/// Importance map of the edge anti-aliasing pass: blue where a pixel got one
+/// camera ray, red where it got `max_samples`. With edge anti-aliasing off
+/// every pixel has one ray and the map is flat blue.
+pub fn overlay_sample_counts(pixels: &mut [Color], sample_counts: &[u32], max_samples: u32) {
+    let range = max_samples.saturating_sub(1).max(1) as f32;
+    overlay(pixels, sample_counts.par_iter().map(|&count| count.saturating_sub(1) as f32 / range));
+}
@@ -0,0 +1,387 @@
+// hud.rs
+// The text overlay of the window. It has four views, cycled with F1: off,
+// minimal (frame rate and render time), full (everything the loop knows about
+// the frame, plus the key hints) and graphs (a sparkline of recent frame
+// times, and with `[quality] profile` on the ray counts and where the frame's
+// time went). Lines are stacked top-down and wrapped at their " | " separators
+// when they would run past the right edge, so a narrow window still shows all
+// of it. The HUD is drawn over the frame after it is uploaded, so screenshots
+// leave it out unless `[hud] in_screenshots` asks for it.
+use std::collections::VecDeque;
+
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::chunks::ChunkStreamer;
+use crate::config::HudConfig;
+use crate::input::{Action, KeyBindings};
+use crate::profile::Profile;
+use crate::render::RenderStats;
+use crate::scene::{Scene, LAYER_COUNT};
+use crate::settings::{RenderSettings, ToneMapping};
+
+/// Frames kept for the frame-time graph.
+pub const GRAPH_FRAMES: usize = 240;
+
+// Frame time of the graph's reference line: 30 FPS
+const REFERENCE_MS: f32 = 33.0;
+
+const MARGIN: i32 = 10;
+const GRAPH_HEIGHT: i32 = 80;
+const STAGE_BAR_HEIGHT: i32 = 10;
+
+/// What the HUD shows.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HudMode {
+    /// Nothing.
+    Off,
+    /// Frame rate and render time.
+    Minimal,
+    /// Everything, with the key hints.
+    #[default]
+    Full,
+    /// Frame rate, render time and the frame-time graph.
+    Graphs,
+}
+
+impl HudMode {
+    /// The view after this one.
+    pub fn next(self) -> Self {
+        match self {
+            HudMode::Off => HudMode::Minimal,
+            HudMode::Minimal => HudMode::Full,
+            HudMode::Full => HudMode::Graphs,
+            HudMode::Graphs => HudMode::Off,
+        }
+    }
+}
+
+/// What the window loop knows about the frame being drawn.
+pub struct HudFrame<'a> {
+    /// Frames per second, from the time of the whole frame.
+    pub fps: i32,
+    /// Milliseconds from the start of the last frame to the start of this
+    /// one, time slept under a cap and the wait for vsync included.
+    pub frame_ms: f32,
+    /// Milliseconds spent tracing.
+    pub render_ms: u128,
+    /// Name of the renderer that traced the frame.
+    pub backend: &'a str,
+    /// Pass statistics of the CPU renderer; None when the GPU traced the frame.
+    pub stats: Option<&'a RenderStats>,
+    /// Ray counts and stage times, post and upload included, while the CPU
+    /// renderer is profiling.
+    pub profile: Option<Profile>,
+    /// Time of each post-processing pass, in the order they ran; empty when
+    /// the frame was not traced.
+    pub post_passes: &'a [(&'static str, std::time::Duration)],
+    /// The settings the frame was traced with.
+    pub settings: &'a RenderSettings,
+    /// The scene.
+    pub scene: &'a Scene,
+    /// The terrain streamer, if the scene streams terrain.
+    pub chunks: Option<&'a ChunkStreamer>,
+    /// True while the sun is up.
+    pub daytime: bool,
+    /// Name of the current weather.
+    pub weather: &'a str,
+    /// Bytes of the loaded textures on the CPU and on the GPU.
+    pub texture_bytes: (usize, usize),
+    /// Recording, replay and overlay notices, shown in every view but off.
+    pub notices: Vec<(String, Color)>,
+}
+
+/// Frame times of the last `GRAPH_FRAMES` frames, oldest first.
+#[derive(Debug, Default)]
+pub struct FrameGraph {
+    times: VecDeque<f32>,
+}
+
+impl FrameGraph {
+    /// Adds a frame, dropping the oldest once full.
+    pub fn push(&mut self, ms: f32) {
+        if self.times.len() == GRAPH_FRAMES {
+            self.times.pop_front();
+        }
+        self.times.push_back(ms);
+    }
+
+    fn mean(&self) -> f32 {
+        self.times.iter().sum::<f32>() / self.times.len().max(1) as f32
+    }
+
+    fn max(&self) -> f32 {
+        self.times.iter().copied().fold(0.0, f32::max)
+    }
+}
+
+/// The overlay: its view, whether it shows key hints and the graph's history.
+pub struct Hud {
+    /// The current view.
+    pub mode: HudMode,
+    hints: bool,
+    graph: FrameGraph,
+}
+
+fn mib(bytes: usize) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value { "on" } else { "off" }
+}
+
+/// `text` split at its " | " separators into rows no wider than `max_width`.
+/// A single part wider than that gets a row of its own.
+fn wrap(rl: &RaylibHandle, text: &str, size: i32, max_width: i32) -> Vec<String> {
+    let mut rows: Vec<String> = Vec::new();
+    for part in text.split(" | ") {
+        match rows.last_mut() {
+            Some(row) if rl.measure_text(&format!("{} | {}", row, part), size) <= max_width => {
+                row.push_str(" | ");
+                row.push_str(part);
+            }
+            _ => rows.push(part.to_string()),
+        }
+    }
+    rows
+}
+
+/// Lines drawn one under the other from the top-left corner.
+struct Column<'d, 'h> {
+    d: &'d mut RaylibDrawHandle<'h>,
+    y: i32,
+    max_width: i32,
+}
+
+impl Column<'_, '_> {
+    fn line(&mut self, text: &str, size: i32, color: Color) {
+        for row in wrap(self.d, text, size, self.max_width) {
+            self.d.draw_text(&row, MARGIN, self.y, size, color);
+            self.y += size + 4;
+        }
+    }
+}
+
+impl Hud {
+    /// The view and hints of `[hud]`, with an empty graph.
+    pub fn new(config: &HudConfig) -> Self {
+        Hud { mode: config.mode, hints: config.hints, graph: FrameGraph::default() }
+    }
+
+    /// Switches to the next view on the HUD key.
+    pub fn handle_input(&mut self, window: &RaylibHandle, keys: &KeyBindings) {
+        if keys.pressed(window, Action::Hud) {
+            self.mode = self.mode.next();
+        }
+    }
+
+    /// Adds this frame's time to the graph; called in every view, so the
+    /// graph is full when it is opened.
+    pub fn record(&mut self, frame_ms: f32) {
+        self.graph.push(frame_ms);
+    }
+
+    /// Draws the current view for a window `width` pixels wide.
+    pub fn draw(&self, d: &mut RaylibDrawHandle, keys: &KeyBindings, frame: &HudFrame, width: i32) {
+        if self.mode == HudMode::Off {
+            return;
+        }
+        let max_width = (width - 2 * MARGIN).max(1);
+        let mut column = Column { d, y: MARGIN, max_width };
+        column.line(&format!("FPS: {} | Frame Time: {:.1}ms", frame.fps, frame.frame_ms), 20, Color::WHITE);
+        column.line(
+            &format!(
+                "Render Time: {}ms ({}, {} threads, {})",
+                frame.render_ms,
+                frame.backend,
+                frame.settings.threads,
+                frame.settings.schedule.name()
+            ),
+            20, Color::WHITE,
+        );
+        match self.mode {
+            HudMode::Full => self.draw_full(&mut column, keys, frame),
+            HudMode::Graphs => {
+                self.draw_graph(&mut column);
+                draw_profile(&mut column, frame);
+            }
+            HudMode::Off | HudMode::Minimal => {}
+        }
+        for (notice, color) in &frame.notices {
+            column.line(notice, 16, *color);
+        }
+    }
+
+    fn draw_full(&self, column: &mut Column, keys: &KeyBindings, frame: &HudFrame) {
+        let settings = frame.settings;
+        column.line(
+            &format!(
+                "Time: {} | Weather: {} | Objects: {}",
+                if frame.daytime { "Day" } else { "Night" }, frame.weather, frame.scene.objects.len()
+            ),
+            16, Color::LIGHTGRAY,
+        );
+        if let Some(streamer) = frame.chunks {
+            column.line(
+                &format!(
+                    "Chunks: {} loaded | {} pending | splice {:.2}ms (max {:.2}ms)",
+                    frame.scene.chunks.len(), streamer.pending_jobs(), streamer.last_splice_ms, streamer.max_splice_ms
+                ),
+                16, Color::LIGHTGRAY,
+            );
+        }
+        match frame.stats {
+            Some(stats) => column.line(
+                &format!(
+                    "Rays: {} camera ({:.0}% of uniform) | {} in total | {} edge px | {} out of bounces",
+                    stats.primary_rays,
+                    100.0 * stats.primary_rays as f64 / stats.uniform_rays.max(1) as f64,
+                    stats.rays_traced,
+                    stats.edge_pixels,
+                    stats.budgets_spent
+                ),
+                16, Color::LIGHTGRAY,
+            ),
+            None => column.line("Rays: one camera ray per pixel on the GPU", 16, Color::LIGHTGRAY),
+        }
+        if let Some(cache) = frame.stats.and_then(|stats| stats.emissive_cache) {
+            column.line(
+                &format!("Block light cache: {:.0}% of {} lookups hit", cache.hit_percent(), cache.lookups),
+                16, Color::LIGHTGRAY,
+            );
+        }
+        let (cpu, gpu) = frame.texture_bytes;
+        column.line(
+            &format!(
+                "Memory: textures {:.1} MiB CPU, {:.1} MiB GPU | BVH {} nodes, built in {:.1} ms | No accumulation, every frame is traced anew",
+                mib(cpu), mib(gpu), frame.scene.bvh.nodes.len(), frame.scene.bvh_build_time().as_secs_f64() * 1000.0
+            ),
+            16, Color::LIGHTGRAY,
+        );
+        let tone_mapping = match settings.tone_mapping {
+            ToneMapping::Clamp => "Clamp".to_string(),
+            ToneMapping::Reinhard => format!("Reinhard, white {:.1}", settings.white_point),
+        };
+        column.line(
+            &format!(
+                "Depth {} | Scale {:.1} | Edge AA {}x | Glass {} | Sun Shadows {} | Tone Map {} | Exposure {:.1} | Bloom {} | Output {}",
+                settings.max_depth, settings.render_scale, settings.edge_samples, settings.transparency.name(),
+                if frame.scene.sun_shadow.is_some() { "map" } else { "rays" }, tone_mapping, settings.exposure,
+                on_off(settings.bloom), settings.output_transfer.name()
+            ),
+            16, Color::LIGHTGRAY,
+        );
+        if !self.hints {
+            return;
+        }
+        column.line(
+            &format!(
+                "{}: Toggle Auto-Rotate | {}: Cycle Weather | {}: Next Bookmark | {}: Material Editor | {}: Ignite TNT | {}: Groups",
+                keys.auto_rotate, keys.weather, keys.next_bookmark, keys.material_editor, keys.ignite_tnt, keys.groups
+            ),
+            16, Color::LIGHTGRAY,
+        );
+        column.line(
+            &format!(
+                "{}/{}/{}/{}: Rotate | {}/{}: Up/Down | {}/{}: Zoom | {}: Glass | {}: Edge AA | {}/{}: White Point",
+                keys.orbit_left, keys.orbit_right, keys.orbit_up, keys.orbit_down,
+                keys.move_up, keys.move_down, keys.zoom_out, keys.zoom_in,
+                keys.transparency, keys.edge_samples, keys.white_point_down, keys.white_point_up
+            ),
+            16, Color::LIGHTGRAY,
+        );
+        column.line(
+            &format!(
+                "{}: Tone Map | {}: Screenshot | {}: Exposure Bracket | {}: Record | {}: Sample Map | {}: G-Buffer",
+                keys.tone_mapping, keys.screenshot, keys.exposure_bracket, keys.record, keys.sample_map, keys.gbuffer_view
+            ),
+            16, Color::LIGHTGRAY,
+        );
+        let layers: String = (0..LAYER_COUNT)
+            .map(|layer| if frame.scene.layer_visible(layer) { char::from(b'1' + layer) } else { '-' })
+            .collect();
+        column.line(
+            &format!(
+                "1-9: Layers {} | {}: Settings | {}: Pause | {}: HUD",
+                layers, keys.settings_panel, keys.pause, keys.hud
+            ),
+            16, Color::LIGHTGRAY,
+        );
+    }
+
+    fn draw_graph(&self, column: &mut Column) {
+        let graph = &self.graph;
+        let last = graph.times.back().copied().unwrap_or(0.0);
+        column.line(
+            &format!("Frame time: {:.1}ms | mean {:.1}ms | max {:.1}ms", last, graph.mean(), graph.max()),
+            16, Color::LIGHTGRAY,
+        );
+        let width = column.max_width.min(2 * GRAPH_FRAMES as i32);
+        let (x, y) = (MARGIN, column.y);
+        column.d.draw_rectangle(x, y, width, GRAPH_HEIGHT, Color::new(0, 0, 0, 160));
+        // Room above the reference line for spikes, growing with the worst one
+        let scale_ms = graph.max().max(REFERENCE_MS * 1.5);
+        let to_y = |ms: f32| y + GRAPH_HEIGHT - ((ms / scale_ms) * GRAPH_HEIGHT as f32).round() as i32;
+        let reference_y = to_y(REFERENCE_MS);
+        column.d.draw_line(x, reference_y, x + width, reference_y, Color::YELLOW);
+        column.d.draw_text(&format!("{} ms", REFERENCE_MS), x + 4, reference_y - 12, 10, Color::YELLOW);
+        // Newest frame at the right edge
+        let step = width as f32 / (GRAPH_FRAMES - 1) as f32;
+        let first = GRAPH_FRAMES - graph.times.len();
+        let points: Vec<(i32, i32)> = graph
+            .times
+            .iter()
+            .enumerate()
+            .map(|(i, &ms)| (x + ((first + i) as f32 * step).round() as i32, to_y(ms)))
+            .collect();
+        for pair in points.windows(2) {
+            let color = if pair[1].1 < reference_y { Color::RED } else { Color::GREEN };
+            column.d.draw_line(pair[0].0, pair[0].1, pair[1].0, pair[1].1, color);
+        }
+        column.y += GRAPH_HEIGHT + 4;
+    }
+}
+
+/// The profile counters, and a bar splitting the render threads' time
+/// between ray generation (blue), tracing (green) and shading (orange).
+fn draw_profile(column: &mut Column, frame: &HudFrame) {
+    let Some(profile) = &frame.profile else {
+        if frame.stats.is_some() {
+            column.line("Profile off: turn it on in the settings panel or with [quality] profile", 16, Color::GRAY);
+        }
+        return;
+    };
+    let ms = |time: std::time::Duration| time.as_secs_f64() * 1000.0;
+    column.line(
+        &format!(
+            "Rays: {} primary | {} shadow | {} reflected/refracted | {} BVH nodes | {} cube tests",
+            profile.primary_rays, profile.shadow_rays, profile.secondary_rays, profile.node_visits, profile.cube_tests
+        ),
+        16, Color::LIGHTGRAY,
+    );
+    column.line(
+        &format!(
+            "Thread time: ray gen {:.1}ms | trace {:.1}ms | shade {:.1}ms | post {:.1}ms | upload {:.1}ms",
+            ms(profile.ray_gen), ms(profile.trace), ms(profile.shade), ms(profile.post), ms(profile.upload)
+        ),
+        16, Color::LIGHTGRAY,
+    );
+    if !frame.post_passes.is_empty() {
+        let passes: Vec<String> = frame.post_passes.iter().map(|(name, time)| format!("{} {:.1}ms", name, ms(*time))).collect();
+        column.line(&format!("Post passes: {}", passes.join(" | ")), 16, Color::LIGHTGRAY);
+    }
+    let total = ms(profile.ray_gen) + ms(profile.trace) + ms(profile.shade);
+    if total <= 0.0 {
+        return;
+    }
+    let width = column.max_width.min(2 * GRAPH_FRAMES as i32);
+    let mut x = MARGIN;
+    for (time, color) in [(profile.ray_gen, Color::SKYBLUE), (profile.trace, Color::GREEN), (profile.shade, Color::ORANGE)] {
+        let segment = (ms(time) / total * width as f64).round() as i32;
+        column.d.draw_rectangle(x, column.y, segment, STAGE_BAR_HEIGHT, color);
+        x += segment;
+    }
+    column.y += STAGE_BAR_HEIGHT + 4;
+}
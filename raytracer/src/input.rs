@@ -0,0 +1,563 @@
+// input.rs
+// Actions of the interactive window and the keys or mouse buttons they are
+// bound to. The bindings are read from the [keys] section of raytracer.toml;
+// the defaults are the original hardcoded keys. The window asks whether an
+// action fired this frame, never for a raw key, so every binding can be moved.
+use std::fmt;
+
+use raylib::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A key or mouse button, written in the config by name ("SPACE", "F6", "A",
+/// "MOUSE_LEFT").
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+    /// A keyboard key.
+    Key(KeyboardKey),
+    /// A mouse button.
+    Mouse(MouseButton),
+}
+
+const KEY_NAMES: &[(&str, KeyboardKey)] = {
+    use KeyboardKey::*;
+    &[
+        ("A", KEY_A), ("B", KEY_B), ("C", KEY_C), ("D", KEY_D), ("E", KEY_E), ("F", KEY_F),
+        ("G", KEY_G), ("H", KEY_H), ("I", KEY_I), ("J", KEY_J), ("K", KEY_K), ("L", KEY_L),
+        ("M", KEY_M), ("N", KEY_N), ("O", KEY_O), ("P", KEY_P), ("Q", KEY_Q), ("R", KEY_R),
+        ("S", KEY_S), ("T", KEY_T), ("U", KEY_U), ("V", KEY_V), ("W", KEY_W), ("X", KEY_X),
+        ("Y", KEY_Y), ("Z", KEY_Z),
+        ("0", KEY_ZERO), ("1", KEY_ONE), ("2", KEY_TWO), ("3", KEY_THREE), ("4", KEY_FOUR),
+        ("5", KEY_FIVE), ("6", KEY_SIX), ("7", KEY_SEVEN), ("8", KEY_EIGHT), ("9", KEY_NINE),
+        ("F1", KEY_F1), ("F2", KEY_F2), ("F3", KEY_F3), ("F4", KEY_F4), ("F5", KEY_F5), ("F6", KEY_F6),
+        ("F7", KEY_F7), ("F8", KEY_F8), ("F9", KEY_F9), ("F10", KEY_F10), ("F11", KEY_F11), ("F12", KEY_F12),
+        ("SPACE", KEY_SPACE), ("ENTER", KEY_ENTER), ("TAB", KEY_TAB), ("BACKSPACE", KEY_BACKSPACE),
+        ("INSERT", KEY_INSERT), ("DELETE", KEY_DELETE), ("HOME", KEY_HOME), ("END", KEY_END),
+        ("PAGE_UP", KEY_PAGE_UP), ("PAGE_DOWN", KEY_PAGE_DOWN),
+        ("LEFT", KEY_LEFT), ("RIGHT", KEY_RIGHT), ("UP", KEY_UP), ("DOWN", KEY_DOWN),
+        ("MINUS", KEY_MINUS), ("EQUAL", KEY_EQUAL), ("COMMA", KEY_COMMA), ("PERIOD", KEY_PERIOD),
+        ("SLASH", KEY_SLASH), ("BACKSLASH", KEY_BACKSLASH), ("SEMICOLON", KEY_SEMICOLON),
+        ("APOSTROPHE", KEY_APOSTROPHE), ("GRAVE", KEY_GRAVE),
+        ("LEFT_SHIFT", KEY_LEFT_SHIFT), ("RIGHT_SHIFT", KEY_RIGHT_SHIFT),
+        ("LEFT_CONTROL", KEY_LEFT_CONTROL), ("RIGHT_CONTROL", KEY_RIGHT_CONTROL),
+        ("LEFT_BRACKET", KEY_LEFT_BRACKET), ("RIGHT_BRACKET", KEY_RIGHT_BRACKET),
+    ]
+};
+
+const MOUSE_NAMES: &[(&str, MouseButton)] = &[
+    ("MOUSE_LEFT", MouseButton::MOUSE_BUTTON_LEFT),
+    ("MOUSE_RIGHT", MouseButton::MOUSE_BUTTON_RIGHT),
+    ("MOUSE_MIDDLE", MouseButton::MOUSE_BUTTON_MIDDLE),
+];
+
+/// Keys 1-9, which always show or hide render layers 0-8.
+pub const LAYER_KEYS: [KeyboardKey; 9] = {
+    use KeyboardKey::*;
+    [KEY_ONE, KEY_TWO, KEY_THREE, KEY_FOUR, KEY_FIVE, KEY_SIX, KEY_SEVEN, KEY_EIGHT, KEY_NINE]
+};
+
+impl Binding {
+    /// Binding for a name from the config file, ignoring case.
+    pub fn parse(name: &str) -> Option<Binding> {
+        KEY_NAMES
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|&(_, key)| Binding::Key(key))
+            .or_else(|| {
+                MOUSE_NAMES
+                    .iter()
+                    .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                    .map(|&(_, button)| Binding::Mouse(button))
+            })
+    }
+
+    /// Name the binding is written as in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Binding::Key(key) => KEY_NAMES.iter().find(|(_, k)| *k == key).map_or("?", |(name, _)| name),
+            Binding::Mouse(button) => MOUSE_NAMES.iter().find(|(_, b)| *b == button).map_or("?", |(name, _)| name),
+        }
+    }
+
+    /// True on the frame the key or button goes down.
+    pub fn pressed(self, window: &RaylibHandle) -> bool {
+        match self {
+            Binding::Key(key) => window.is_key_pressed(key),
+            Binding::Mouse(button) => window.is_mouse_button_pressed(button),
+        }
+    }
+
+    /// True while the key or button is held.
+    pub fn down(self, window: &RaylibHandle) -> bool {
+        match self {
+            Binding::Key(key) => window.is_key_down(key),
+            Binding::Mouse(button) => window.is_mouse_button_down(button),
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Serialize for Binding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Binding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Binding::parse(&name).ok_or_else(|| serde::de::Error::custom(format!("unknown key name '{}'", name)))
+    }
+}
+
+/// Something the user can do from the window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Orbit the camera left (held).
+    OrbitLeft,
+    /// Orbit the camera right (held).
+    OrbitRight,
+    /// Orbit the camera up (held).
+    OrbitUp,
+    /// Orbit the camera down (held).
+    OrbitDown,
+    /// Move toward the orbit center (held).
+    ZoomIn,
+    /// Move away from the orbit center (held).
+    ZoomOut,
+    /// Raise the camera (held).
+    MoveUp,
+    /// Lower the camera (held).
+    MoveDown,
+    /// Toggle the automatic orbit.
+    ToggleAutoRotate,
+    /// Cycle clear, rain and snow.
+    CycleWeather,
+    /// Jump to the next camera bookmark.
+    NextBookmark,
+    /// Open the group menu or select its next row.
+    Groups,
+    /// Show or hide the selected group.
+    HideGroup,
+    /// Light the TNT under the mouse.
+    IgniteTnt,
+    /// Switch between recursive and blended glass.
+    Transparency,
+    /// Switch between clamp and Reinhard tone mapping.
+    ToneMapping,
+    /// Raise the Reinhard white point.
+    WhitePointUp,
+    /// Lower the Reinhard white point.
+    WhitePointDown,
+    /// Cycle the edge anti-aliasing sample count.
+    EdgeSamples,
+    /// Switch between the CPU and GPU renderers.
+    Backend,
+    /// Write -2, 0 and +2 EV PNGs of the current frame.
+    ExposureBracket,
+    /// Save the world to `world.mcw`.
+    SaveWorld,
+    /// Load `world.mcw`.
+    LoadWorld,
+    /// Start or stop recording the session.
+    Record,
+    /// Show the per-pixel sample count heatmap.
+    SampleMap,
+    /// Cycle the depth, normal, albedo and object id views.
+    GBufferView,
+    /// Show the tiles of a frame as they are traced.
+    StreamTiles,
+    /// Cycle the A/B comparison: a snapshot, the frame without post, off.
+    Compare,
+    /// Save the displayed frame and its settings.
+    Screenshot,
+    /// Reload the scene file, or accept a pending reload.
+    ReloadScene,
+    /// Skip a pending reload and keep unsaved edits.
+    KeepEdits,
+    /// Open or close the settings panel.
+    SettingsPanel,
+    /// Pause or resume rendering.
+    Pause,
+    /// Cycle the HUD: off, minimal, full, graphs.
+    Hud,
+    /// Open or close the material editor.
+    MaterialEditor,
+    /// Undo the last edit.
+    Undo,
+    /// Select the next library material in the editor.
+    NextMaterial,
+    /// Select the previous library material in the editor.
+    PreviousMaterial,
+    /// Make the next library material the brush.
+    NextBrush,
+    /// Toggle energy-conserving materials in the editor.
+    EnergyConserving,
+    /// Raise the selected material's reflectivity (held).
+    ReflectivityUp,
+    /// Lower the selected material's reflectivity (held).
+    ReflectivityDown,
+    /// Raise the selected material's transparency (held).
+    TransparencyUp,
+    /// Lower the selected material's transparency (held).
+    TransparencyDown,
+    /// Place a brush block in front of the clicked face.
+    PlaceBlock,
+    /// Remove the clicked block.
+    RemoveBlock,
+    /// Write the scene file.
+    SaveScene,
+    /// Grab the clicked block for the transform gizmo, or let it go.
+    GrabBlock,
+    /// Move the grabbed block up.
+    RaiseBlock,
+    /// Move the grabbed block down.
+    LowerBlock,
+    /// Resize the grabbed block instead of moving it (held).
+    ScaleBlock,
+}
+
+impl Action {
+    /// Every action, in the order of the [keys] section.
+    pub const ALL: [Action; 51] = [
+        Action::OrbitLeft, Action::OrbitRight, Action::OrbitUp, Action::OrbitDown,
+        Action::ZoomIn, Action::ZoomOut, Action::MoveUp, Action::MoveDown,
+        Action::ToggleAutoRotate, Action::CycleWeather, Action::NextBookmark, Action::Groups,
+        Action::HideGroup, Action::IgniteTnt, Action::Transparency, Action::ToneMapping,
+        Action::WhitePointUp, Action::WhitePointDown, Action::EdgeSamples, Action::Backend,
+        Action::ExposureBracket, Action::SaveWorld, Action::LoadWorld, Action::Record,
+        Action::SampleMap, Action::GBufferView, Action::StreamTiles,
+        Action::Compare, Action::Screenshot, Action::ReloadScene, Action::KeepEdits, Action::SettingsPanel,
+        Action::Pause, Action::Hud, Action::MaterialEditor, Action::Undo, Action::NextMaterial, Action::PreviousMaterial,
+        Action::NextBrush, Action::EnergyConserving, Action::ReflectivityUp, Action::ReflectivityDown,
+        Action::TransparencyUp, Action::TransparencyDown, Action::PlaceBlock, Action::RemoveBlock,
+        Action::SaveScene, Action::GrabBlock, Action::RaiseBlock, Action::LowerBlock, Action::ScaleBlock,
+    ];
+
+    /// Name of the action's key in the [keys] section.
+    pub fn config_name(self) -> &'static str {
+        match self {
+            Action::OrbitLeft => "orbit_left",
+            Action::OrbitRight => "orbit_right",
+            Action::OrbitUp => "orbit_up",
+            Action::OrbitDown => "orbit_down",
+            Action::ZoomIn => "zoom_in",
+            Action::ZoomOut => "zoom_out",
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::ToggleAutoRotate => "auto_rotate",
+            Action::CycleWeather => "weather",
+            Action::NextBookmark => "next_bookmark",
+            Action::Groups => "groups",
+            Action::HideGroup => "hide_group",
+            Action::IgniteTnt => "ignite_tnt",
+            Action::Transparency => "transparency",
+            Action::ToneMapping => "tone_mapping",
+            Action::WhitePointUp => "white_point_up",
+            Action::WhitePointDown => "white_point_down",
+            Action::EdgeSamples => "edge_samples",
+            Action::Backend => "backend",
+            Action::ExposureBracket => "exposure_bracket",
+            Action::SaveWorld => "save_world",
+            Action::LoadWorld => "load_world",
+            Action::Record => "record",
+            Action::SampleMap => "sample_map",
+            Action::GBufferView => "gbuffer_view",
+            Action::StreamTiles => "stream_tiles",
+            Action::Compare => "compare",
+            Action::Screenshot => "screenshot",
+            Action::ReloadScene => "reload_scene",
+            Action::KeepEdits => "keep_edits",
+            Action::SettingsPanel => "settings_panel",
+            Action::Pause => "pause",
+            Action::Hud => "hud",
+            Action::MaterialEditor => "material_editor",
+            Action::Undo => "undo",
+            Action::NextMaterial => "next_material",
+            Action::PreviousMaterial => "previous_material",
+            Action::NextBrush => "next_brush",
+            Action::EnergyConserving => "energy_conserving",
+            Action::ReflectivityUp => "reflectivity_up",
+            Action::ReflectivityDown => "reflectivity_down",
+            Action::TransparencyUp => "transparency_up",
+            Action::TransparencyDown => "transparency_down",
+            Action::PlaceBlock => "place_block",
+            Action::RemoveBlock => "remove_block",
+            Action::SaveScene => "save_scene",
+            Action::GrabBlock => "grab_block",
+            Action::RaiseBlock => "raise_block",
+            Action::LowerBlock => "lower_block",
+            Action::ScaleBlock => "scale_block",
+        }
+    }
+}
+
+/// The binding of every action; the [keys] section of raytracer.toml. The 1-9
+/// layer keys are fixed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    /// Orbit the camera left (held).
+    pub orbit_left: Binding,
+    /// Orbit the camera right (held).
+    pub orbit_right: Binding,
+    /// Orbit the camera up (held).
+    pub orbit_up: Binding,
+    /// Orbit the camera down (held).
+    pub orbit_down: Binding,
+    /// Move toward the orbit center (held).
+    pub zoom_in: Binding,
+    /// Move away from the orbit center (held).
+    pub zoom_out: Binding,
+    /// Raise the camera (held).
+    pub move_up: Binding,
+    /// Lower the camera (held).
+    pub move_down: Binding,
+    /// Toggle the automatic orbit.
+    pub auto_rotate: Binding,
+    /// Cycle clear, rain and snow.
+    pub weather: Binding,
+    /// Jump to the next camera bookmark.
+    pub next_bookmark: Binding,
+    /// Open the group menu or select its next row.
+    pub groups: Binding,
+    /// Show or hide the selected group.
+    pub hide_group: Binding,
+    /// Light the TNT under the mouse.
+    pub ignite_tnt: Binding,
+    /// Switch between recursive and blended glass.
+    pub transparency: Binding,
+    /// Switch between clamp and Reinhard tone mapping.
+    pub tone_mapping: Binding,
+    /// Raise the Reinhard white point.
+    pub white_point_up: Binding,
+    /// Lower the Reinhard white point.
+    pub white_point_down: Binding,
+    /// Cycle the edge anti-aliasing sample count.
+    pub edge_samples: Binding,
+    /// Switch between the CPU and GPU renderers.
+    pub backend: Binding,
+    /// Write -2, 0 and +2 EV PNGs of the current frame.
+    pub exposure_bracket: Binding,
+    /// Save the world to `world.mcw`.
+    pub save_world: Binding,
+    /// Load `world.mcw`.
+    pub load_world: Binding,
+    /// Start or stop recording the session.
+    pub record: Binding,
+    /// Show the per-pixel sample count heatmap over the frame.
+    pub sample_map: Binding,
+    /// Show the depth, normal, albedo and object id of the first hits in place
+    /// of the frame, one after the other.
+    pub gbuffer_view: Binding,
+    /// Draw each tile of a frame as soon as it is traced instead of waiting
+    /// for the whole frame.
+    pub stream_tiles: Binding,
+    /// Split the window between the frame and a snapshot pinned on the press,
+    /// then between the frame with and without post-processing, then back to
+    /// the frame alone. Dragging with the left mouse button moves the split.
+    pub compare: Binding,
+    /// Save the displayed frame and its settings to `screenshots/`.
+    pub screenshot: Binding,
+    /// Reload the scene file now, or accept a pending reload over unsaved edits.
+    pub reload_scene: Binding,
+    /// Keep unsaved edits and skip a pending reload.
+    pub keep_edits: Binding,
+    /// Open or close the settings panel.
+    pub settings_panel: Binding,
+    /// Stop rendering and keep showing the last frame, or resume.
+    pub pause: Binding,
+    /// Switch the HUD to its next view.
+    pub hud: Binding,
+    /// Open or close the material editor.
+    pub material_editor: Binding,
+    /// Undo the last edit, with the editor open or not.
+    pub undo: Binding,
+    /// Select the next library material in the editor.
+    pub next_material: Binding,
+    /// Select the previous library material in the editor.
+    pub previous_material: Binding,
+    /// Make the next library material the brush.
+    pub next_brush: Binding,
+    /// Toggle energy-conserving reflectivity and transparency in the editor.
+    pub energy_conserving: Binding,
+    /// Raise the selected material's reflectivity (held).
+    pub reflectivity_up: Binding,
+    /// Lower the selected material's reflectivity (held).
+    pub reflectivity_down: Binding,
+    /// Raise the selected material's transparency (held).
+    pub transparency_up: Binding,
+    /// Lower the selected material's transparency (held).
+    pub transparency_down: Binding,
+    /// Place a brush block, with the editor open.
+    pub place_block: Binding,
+    /// Remove the block under the mouse, with the editor open.
+    pub remove_block: Binding,
+    /// Write the scene file, with the editor open.
+    pub save_scene: Binding,
+    /// Grab the block under the mouse for the transform gizmo, with the editor
+    /// open; again, or on empty sky, lets it go.
+    pub grab_block: Binding,
+    /// Move the grabbed block up; the orbit keys move it along X and Z.
+    pub raise_block: Binding,
+    /// Move the grabbed block down.
+    pub lower_block: Binding,
+    /// Hold to resize the grabbed block along the axis instead of moving it.
+    pub scale_block: Binding,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use KeyboardKey::*;
+        let key = Binding::Key;
+        KeyBindings {
+            orbit_left: key(KEY_LEFT),
+            orbit_right: key(KEY_RIGHT),
+            orbit_up: key(KEY_UP),
+            orbit_down: key(KEY_DOWN),
+            zoom_in: key(KEY_D),
+            zoom_out: key(KEY_A),
+            move_up: key(KEY_W),
+            move_down: key(KEY_S),
+            auto_rotate: key(KEY_SPACE),
+            weather: key(KEY_R),
+            next_bookmark: key(KEY_B),
+            groups: key(KEY_V),
+            hide_group: key(KEY_H),
+            ignite_tnt: key(KEY_T),
+            transparency: key(KEY_O),
+            tone_mapping: key(KEY_M),
+            white_point_up: key(KEY_PERIOD),
+            white_point_down: key(KEY_COMMA),
+            edge_samples: key(KEY_X),
+            backend: key(KEY_G),
+            exposure_bracket: key(KEY_F3),
+            save_world: key(KEY_F6),
+            load_world: key(KEY_F9),
+            record: key(KEY_F7),
+            sample_map: key(KEY_F8),
+            gbuffer_view: key(KEY_L),
+            stream_tiles: key(KEY_N),
+            compare: key(KEY_Q),
+            screenshot: key(KEY_F2),
+            reload_scene: key(KEY_F4),
+            keep_edits: key(KEY_F11),
+            settings_panel: key(KEY_F10),
+            pause: key(KEY_P),
+            hud: key(KEY_F1),
+            material_editor: key(KEY_E),
+            undo: key(KEY_Z),
+            next_material: key(KEY_RIGHT_BRACKET),
+            previous_material: key(KEY_LEFT_BRACKET),
+            next_brush: key(KEY_TAB),
+            energy_conserving: key(KEY_C),
+            reflectivity_up: key(KEY_U),
+            reflectivity_down: key(KEY_J),
+            transparency_up: key(KEY_I),
+            transparency_down: key(KEY_K),
+            place_block: Binding::Mouse(MouseButton::MOUSE_BUTTON_LEFT),
+            remove_block: Binding::Mouse(MouseButton::MOUSE_BUTTON_RIGHT),
+            save_scene: key(KEY_F5),
+            grab_block: Binding::Mouse(MouseButton::MOUSE_BUTTON_MIDDLE),
+            raise_block: key(KEY_PAGE_UP),
+            lower_block: key(KEY_PAGE_DOWN),
+            scale_block: key(KEY_LEFT_SHIFT),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// What `action` is bound to.
+    pub fn binding(&self, action: Action) -> Binding {
+        match action {
+            Action::OrbitLeft => self.orbit_left,
+            Action::OrbitRight => self.orbit_right,
+            Action::OrbitUp => self.orbit_up,
+            Action::OrbitDown => self.orbit_down,
+            Action::ZoomIn => self.zoom_in,
+            Action::ZoomOut => self.zoom_out,
+            Action::MoveUp => self.move_up,
+            Action::MoveDown => self.move_down,
+            Action::ToggleAutoRotate => self.auto_rotate,
+            Action::CycleWeather => self.weather,
+            Action::NextBookmark => self.next_bookmark,
+            Action::Groups => self.groups,
+            Action::HideGroup => self.hide_group,
+            Action::IgniteTnt => self.ignite_tnt,
+            Action::Transparency => self.transparency,
+            Action::ToneMapping => self.tone_mapping,
+            Action::WhitePointUp => self.white_point_up,
+            Action::WhitePointDown => self.white_point_down,
+            Action::EdgeSamples => self.edge_samples,
+            Action::Backend => self.backend,
+            Action::ExposureBracket => self.exposure_bracket,
+            Action::SaveWorld => self.save_world,
+            Action::LoadWorld => self.load_world,
+            Action::Record => self.record,
+            Action::SampleMap => self.sample_map,
+            Action::GBufferView => self.gbuffer_view,
+            Action::StreamTiles => self.stream_tiles,
+            Action::Compare => self.compare,
+            Action::Screenshot => self.screenshot,
+            Action::ReloadScene => self.reload_scene,
+            Action::KeepEdits => self.keep_edits,
+            Action::SettingsPanel => self.settings_panel,
+            Action::Pause => self.pause,
+            Action::Hud => self.hud,
+            Action::MaterialEditor => self.material_editor,
+            Action::Undo => self.undo,
+            Action::NextMaterial => self.next_material,
+            Action::PreviousMaterial => self.previous_material,
+            Action::NextBrush => self.next_brush,
+            Action::EnergyConserving => self.energy_conserving,
+            Action::ReflectivityUp => self.reflectivity_up,
+            Action::ReflectivityDown => self.reflectivity_down,
+            Action::TransparencyUp => self.transparency_up,
+            Action::TransparencyDown => self.transparency_down,
+            Action::PlaceBlock => self.place_block,
+            Action::RemoveBlock => self.remove_block,
+            Action::SaveScene => self.save_scene,
+            Action::GrabBlock => self.grab_block,
+            Action::RaiseBlock => self.raise_block,
+            Action::LowerBlock => self.lower_block,
+            Action::ScaleBlock => self.scale_block,
+        }
+    }
+
+    /// True on the frame `action`'s key or button goes down.
+    pub fn pressed(&self, window: &RaylibHandle, action: Action) -> bool {
+        self.binding(action).pressed(window)
+    }
+
+    /// True while `action`'s key or button is held.
+    pub fn down(&self, window: &RaylibHandle, action: Action) -> bool {
+        self.binding(action).down(window)
+    }
+
+    /// Fails on the first key or button bound to two actions, or a binding on
+    /// one of the fixed 1-9 layer keys.
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, &action) in Action::ALL.iter().enumerate() {
+            let binding = self.binding(action);
+            if let Binding::Key(key) = binding {
+                if let Some(layer) = LAYER_KEYS.iter().position(|&k| k == key) {
+                    return Err(format!(
+                        "keys.{} is bound to {}, which toggles render layer {}",
+                        action.config_name(), binding, layer
+                    ));
+                }
+            }
+            if let Some(&other) = Action::ALL[i + 1..].iter().find(|&&other| self.binding(other) == binding) {
+                return Err(format!(
+                    "keys.{} and keys.{} are both bound to {}",
+                    action.config_name(), other.config_name(), binding
+                ));
+            }
+        }
+        Ok(())
+    }
+}
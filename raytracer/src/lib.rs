@@ -0,0 +1,127 @@
+//! CPU ray tracer for a Minecraft-style block world.
+//!
+//! The library holds everything except the window loop: the scene and its
+//! acceleration structures, world generation and simulation, the renderer and
+//! file formats. Two binaries sit on top of it: `raytracer` (`src/bin/interactive.rs`),
+//! the raylib window with input handling, and `raytracer-render`
+//! (`src/bin/render.rs`), which renders to PNG without a window.
+//!
+//! A frame is traced with [`render::render`] (or [`render::render_scaled`]) into
+//! linear HDR radiance and mapped for display with [`tonemap::tonemap_buffer`].
+//! A render loop keeps a [`framebuffer::Framebuffer`] instead, traces into it
+//! with the `_into` variants, so frames reuse it, runs the [`post::PostChain`]
+//! on it and resolves it to display colors.
+#![deny(missing_docs)]
+
+/// Asset directory search and the textures compiled into the binary.
+pub mod assets;
+/// BVH traversal helpers: ray packets and the stackless rope BVH.
+pub mod accel;
+/// Edge detection for the selective supersampling pass.
+pub mod antialias;
+/// `--bench-scene`: fixed-camera timings of a generated scene.
+pub mod bench;
+/// Glow around emissive blocks on the HDR frame.
+pub mod bloom;
+/// `--calibration`: a gamma and tone mapping test pattern.
+pub mod calibration;
+/// Orbit camera, ray generation and bookmarks.
+pub mod camera;
+/// Background streaming of terrain chunks around the camera.
+pub mod chunks;
+/// Command-line options of both binaries.
+pub mod cli;
+/// Flat Minecraft-style cloud layer, traced outside the BVH.
+pub mod clouds;
+/// Persistent defaults and key bindings from `raytracer.toml`.
+pub mod config;
+/// The axis-aligned block every scene is built from.
+pub mod cube;
+/// In-window material editor and block placement.
+pub mod editor;
+/// Errors of the loading paths.
+pub mod error;
+/// TNT fuses, craters, flashes and camera shake.
+pub mod explosion;
+/// Sand and gravel falling until they rest on something.
+pub mod falling;
+/// Closures run before every frame to animate the scene and camera.
+pub mod frame_hook;
+/// Hash of a frame's inputs, to present an unchanged frame again.
+pub mod frame_key;
+/// The HDR frame: per-pixel sample accumulation and the resolve to display colors.
+pub mod framebuffer;
+/// Normal, albedo and object id of each pixel's first hit, and their debug views.
+pub mod gbuffer;
+/// Optional wgpu compute backend.
+#[cfg(feature = "gpu")]
+pub mod gpu;
+/// False-color debug overlays.
+pub mod heatmap;
+/// The text overlay and its frame-time graph.
+pub mod hud;
+/// Actions of the window and the keys and mouse buttons bound to them.
+pub mod input;
+/// Point and spherical area lights.
+pub mod light;
+/// Per-frame cache of emissive block shadows.
+pub mod light_cache;
+/// Surface materials and their procedural tints.
+pub mod material;
+/// Deterministic value noise.
+pub mod noise;
+/// Ray hits and the trait shapes implement to report them.
+pub mod ray_intersect;
+/// Per-frame timing log of the interactive window.
+pub mod perf_log;
+/// Post-processing passes run on the HDR frame before it is resolved.
+pub mod post;
+/// Rasterized stand-in for the traced frame while the camera moves.
+pub mod preview;
+/// Ray counters and stage timers behind the `profile` setting.
+pub mod profile;
+/// Per-frame session recording and replay.
+pub mod recording;
+/// The CPU ray tracer, and closest-hit and any-hit ray queries against a scene.
+pub mod render;
+/// Sample patterns for soft shadows and supersampling.
+pub mod sampling;
+/// The scene: cubes, their BVH, dynamic layers and material library.
+pub mod scene;
+/// Per-thread reusable buffers for per-ray temporaries.
+pub mod scratch;
+/// Scene statistics and checks for duplicate, overlapping and stray cubes.
+pub mod scene_check;
+/// Named material presets that scene files import.
+pub mod presets;
+/// RON scene files.
+pub mod scene_file;
+/// Reloading the scene file when it changes on disk.
+pub mod scene_watch;
+/// PNG export of rendered frames.
+pub mod screenshot;
+/// Sun shadows from an orthographic depth map.
+pub mod shadow_map;
+/// Exit summary and the last session's camera and scene, also dumped on a panic.
+pub mod session;
+/// Render quality and look settings.
+pub mod settings;
+/// In-window panel for changing the render settings.
+pub mod settings_panel;
+/// Eight-lane SIMD loops of primary ray packets.
+#[cfg(feature = "simd")]
+pub mod simd;
+/// Reflection and refraction directions and Fresnel reflectance.
+pub mod snell;
+/// Block textures and the skybox, on the GPU and for CPU sampling.
+pub mod textures;
+/// HDR radiance to display colors.
+pub mod tonemap;
+/// Water flowing out of source blocks.
+pub mod water;
+/// Rain and snow particles and their effect on light and fog.
+pub mod weather;
+/// Compressed binary world saves.
+pub mod world;
+/// Demo and benchmark scene generation.
+pub mod worldgen;
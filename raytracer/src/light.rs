@@ -1,19 +1,53 @@
 // light.rs
 use raylib::prelude::*;
 
+/// Luz puntual o de área esférica.
 #[derive(Clone, Copy, Debug)]
 pub struct Light {
+    /// Posición en el mundo.
     pub position: Vector3,
+    /// Color lineal.
     pub color: Vector3,
+    /// Multiplica al color.
     pub intensity: f32,
+    /// Radio de la esfera que emite; 0.0 = luz puntual ideal (sombras duras).
+    pub radius: f32,
+    /// Distancia mínima suave de la caída; 0.0 = sin caída con la distancia (el sol).
+    pub soft_radius: f32,
 }
 
 impl Light {
+    /// Luz puntual sin caída con la distancia.
     pub fn new(position: Vector3, color: Vector3, intensity: f32) -> Self {
         Light {
             position,
             color,
             intensity,
+            radius: 0.0,
+            soft_radius: 0.0,
         }
     }
-}
\ No newline at end of file
+
+    /// Da tamaño a la luz para que proyecte sombras suaves.
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    /// Hace que la luz caiga con el cuadrado de la distancia, valiendo 1 a un
+    /// bloque. `soft_radius` es la distancia mínima suave: muy cerca la luz se
+    /// satura en `(1 + s²) / s²` en vez de dispararse.
+    pub fn with_falloff(mut self, soft_radius: f32) -> Self {
+        self.soft_radius = soft_radius;
+        self
+    }
+
+    /// Factor por el que se multiplica la intensidad a `distance` de la luz.
+    pub fn attenuation(&self, distance: f32) -> f32 {
+        if self.soft_radius <= 0.0 {
+            return 1.0;
+        }
+        let s2 = self.soft_radius * self.soft_radius;
+        (1.0 + s2) / (distance * distance + s2)
+    }
+}
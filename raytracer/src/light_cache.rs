@@ -0,0 +1,98 @@
+// light_cache.rs
+// Per-frame cache of emissive block shadows, for `RenderSettings::
+// emissive_shadow_cache`. Blocks and their lights hold still for a frame, so
+// the occlusion of one light as seen from one block face only needs tracing
+// once: every later primary hit on that face reuses it. The entries are spread
+// over mutex-guarded shards by key, so threads shading different faces rarely
+// wait on each other, and the value is worked out outside the lock.
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::sync::Mutex;
+
+const SHARDS: usize = 64;
+
+/// One light seen from one block face.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LightKey {
+    /// Grid cell of the block.
+    pub cell: [i32; 3],
+    /// Which of its six faces: axis * 2, plus 1 when it faces the negative side.
+    pub face: u8,
+    /// Index of the light in `Scene::emissive_lights`.
+    pub light: u32,
+}
+
+/// Lookups the cache answered and all lookups, since it was last cleared.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheStats {
+    /// Lookups that found their entry.
+    pub hits: u64,
+    /// Every lookup.
+    pub lookups: u64,
+}
+
+impl CacheStats {
+    /// Percentage of lookups that hit; 0 before the first.
+    pub fn hit_percent(&self) -> f64 {
+        100.0 * self.hits as f64 / self.lookups.max(1) as f64
+    }
+}
+
+#[derive(Default)]
+struct Shard {
+    entries: HashMap<LightKey, f32>,
+    stats: CacheStats,
+}
+
+/// Occlusion per `LightKey`, shared by the render threads.
+pub struct LightCache {
+    hasher: RandomState,
+    shards: [Mutex<Shard>; SHARDS],
+}
+
+impl Default for LightCache {
+    fn default() -> Self {
+        LightCache { hasher: RandomState::new(), shards: std::array::from_fn(|_| Mutex::default()) }
+    }
+}
+
+impl LightCache {
+    fn shard(&self, key: &LightKey) -> &Mutex<Shard> {
+        &self.shards[self.hasher.hash_one(key) as usize % SHARDS]
+    }
+
+    /// The occlusion stored for `key`, or `trace`'s, stored for next time.
+    /// Two threads missing the same key at once both trace it; `trace` must
+    /// give the same answer whoever calls it.
+    pub fn get_or_trace(&self, key: LightKey, trace: impl FnOnce() -> f32) -> f32 {
+        let shard = self.shard(&key);
+        {
+            let mut shard = shard.lock().expect("light cache lock");
+            shard.stats.lookups += 1;
+            if let Some(&occlusion) = shard.entries.get(&key) {
+                shard.stats.hits += 1;
+                return occlusion;
+            }
+        }
+        let occlusion = trace();
+        shard.lock().expect("light cache lock").entries.insert(key, occlusion);
+        occlusion
+    }
+
+    /// Drops every entry and resets the counts, for a new frame.
+    pub fn clear(&self) {
+        for shard in &self.shards {
+            let mut shard = shard.lock().expect("light cache lock");
+            shard.entries.clear();
+            shard.stats = CacheStats::default();
+        }
+    }
+
+    /// Counts over every shard since the last `clear`.
+    pub fn stats(&self) -> CacheStats {
+        self.shards.iter().fold(CacheStats::default(), |total, shard| {
+            let stats = shard.lock().expect("light cache lock").stats;
+            CacheStats { hits: total.hits + stats.hits, lookups: total.lookups + stats.lookups }
+        })
+    }
+}
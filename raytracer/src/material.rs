@@ -1,20 +1,118 @@
 // material.rs
 use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::noise::value_noise_2d;
 
-#[derive(Debug, Clone)]
+/// Fuente de un tinte que se multiplica al color difuso según la posición en el mundo.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TintSource {
+    /// Ruido sobre x/z del bloque que interpola entre los colores de `palette`.
+    Noise {
+        /// Colores entre los que se interpola.
+        palette: Arc<[Vector3]>,
+        /// Frecuencia del ruido, por bloque.
+        scale: f32,
+    },
+}
+
+impl TintSource {
+    /// Tinte en la posición (x, z) del mundo.
+    pub fn sample(&self, x: f32, z: f32) -> Vector3 {
+        match self {
+            TintSource::Noise { palette, scale } => {
+                if palette.is_empty() {
+                    return Vector3::one();
+                }
+                let t = value_noise_2d(x * scale, z * scale) * (palette.len() - 1) as f32;
+                let index = (t.floor() as usize).min(palette.len() - 1);
+                let next = (index + 1).min(palette.len() - 1);
+                let k = t - index as f32;
+                palette[index] * (1.0 - k) + palette[next] * k
+            }
+        }
+    }
+}
+
+/// Cómo cubre la textura una cara con otra proporción que ella, p. ej. una
+/// puerta de 16x32 en la cara de un bloque.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextureFit {
+    /// Se estira hasta cubrir la cara entera.
+    #[default]
+    Stretch,
+    /// Conserva su proporción, centrada en la cara; las franjas que deja
+    /// libres llevan el color difuso del material.
+    Preserve,
+}
+
+impl TextureFit {
+    /// Coordenadas en [0, 1] dentro de una textura de `width` x `height` para
+    /// el punto (u, v) de una cara `face_aspect` veces más ancha que alta, o
+    /// None si cae en una franja de `Preserve`. Una textura con la proporción
+    /// de la cara devuelve (u, v) tal cual.
+    pub fn map(self, u: f32, v: f32, face_aspect: f32, width: i32, height: i32) -> Option<(f32, f32)> {
+        if self == TextureFit::Stretch || width <= 0 || height <= 0 {
+            return Some((u, v));
+        }
+        // Ancho de la textura sobre la cara con su altura ajustada a la de la cara
+        let ratio = width as f32 / height as f32 / face_aspect;
+        let (u, v) = match ratio.partial_cmp(&1.0) {
+            Some(std::cmp::Ordering::Less) => ((u - 0.5) / ratio + 0.5, v),
+            Some(std::cmp::Ordering::Greater) => (u, (v - 0.5) * ratio + 0.5),
+            _ => (u, v),
+        };
+        ((0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v)).then_some((u, v))
+    }
+}
+
+/// Propiedades de superficie de un cubo.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Material {
-    pub diffuse: Vector3, // Color
-    pub albedo: [f32; 2], // que tan colorido es: [color del objeto, color que viene de la luz]
-    pub specular: f32, // brillo
-    pub reflectivity: f32, // reflectividad, 1.0 espejo, 0.0 no refleja nada
-    pub transparency: f32, // transparencia, 1.0 perfectamente transparente, 0.0 no transparente
-    pub refractive_index: f32, // indice de refraccion
+    /// Color difuso.
+    pub diffuse: Vector3,
+    /// Que tan colorido es: [color del objeto, color que viene de la luz].
+    pub albedo: [f32; 2],
+    /// Exponente especular (brillo).
+    pub specular: f32,
+    /// Reflectividad, 1.0 espejo, 0.0 no refleja nada.
+    pub reflectivity: f32,
+    /// Transparencia, 1.0 perfectamente transparente, 0.0 no transparente.
+    pub transparency: f32,
+    /// Indice de refraccion.
+    pub refractive_index: f32,
+    /// Rugosidad de la superficie (0 = pulida). Desenfoca por igual el reflejo
+    /// y lo que se ve a través, con la misma distribución GGX.
+    pub roughness: f32,
+    /// Rugosidad solo de lo que se ve a través (vidrio esmerilado), con el
+    /// reflejo tan nítido como diga `roughness`; 0 = la de `roughness`.
+    pub refraction_roughness: f32,
+    /// Número de Abbe de la dispersión: cuanto más bajo, más se separan los
+    /// colores al refractar (vidrio BK7 64, flint denso 30). 0 = sin
+    /// dispersión, el mismo `refractive_index` para todos.
+    pub abbe_number: f32,
+    /// Ruta de la textura difusa.
     pub texture: Option<String>, // path to texture
+    /// Ruta del mapa de normales.
     pub normal_map_id: Option<String>, // path to normal map
-    pub emission: Vector3, // Color y fuerza de la luz que emite el material
+    /// Color y fuerza de la luz que emite el material.
+    pub emission: Vector3,
+    /// Tinte por posición (bioma), None = sin tinte.
+    pub tint: Option<TintSource>,
+    /// Cae si queda aire debajo (arena, grava)
+    pub falls: bool,
+    /// Los texeles casi transparentes de la textura no se dibujan y el rayo
+    /// sigue de largo (hojas).
+    pub alpha_cutout: bool,
+    /// Cómo cubre la textura una cara de otra proporción.
+    pub texture_fit: TextureFit,
+    /// Nombre en la escena; los cubos con el mismo nombre comparten material.
+    pub name: Option<Arc<str>>,
 }
 
 impl Material {
+    /// Material sin nombre, sin tinte y que no cae.
     pub fn new(diffuse: Vector3, albedo: [f32; 2], specular: f32, reflectivity: f32, transparency: f32, refractive_index: f32, texture: Option<String>, normal_map_id: Option<String>, emission: Vector3) -> Self {
         Material {
             diffuse,
@@ -23,12 +121,27 @@ impl Material {
             reflectivity,
             transparency,
             refractive_index,
+            roughness: 0.0,
+            refraction_roughness: 0.0,
+            abbe_number: 0.0,
             texture,
             normal_map_id,
             emission,
+            tint: None,
+            falls: false,
+            alpha_cutout: false,
+            texture_fit: TextureFit::Stretch,
+            name: None,
         }
     }
     
+    /// El mismo material con nombre de biblioteca `name`.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Material negro que no refleja, no transmite ni emite; base para construir otros.
     pub fn black() -> Self {
         Material {
             diffuse: Vector3::zero(),
@@ -37,13 +150,22 @@ impl Material {
             reflectivity: 0.0,
             transparency: 0.0,
             refractive_index: 0.0,
+            roughness: 0.0,
+            refraction_roughness: 0.0,
+            abbe_number: 0.0,
             texture: None,
             normal_map_id: None,
             emission: Vector3::zero(),
+            tint: None,
+            falls: false,
+            alpha_cutout: false,
+            texture_fit: TextureFit::Stretch,
+            name: None,
         }
     }
 }
 
+/// Color de 8 bits para un color en [0, 1]; satura por encima de 1.
 pub fn vector3_to_color(v: Vector3) -> Color {
     Color::new(
         (v.x * 255.0).min(255.0) as u8,
@@ -53,6 +175,7 @@ pub fn vector3_to_color(v: Vector3) -> Color {
     )
 }
 
+/// Color en [0, 1] para un color de 8 bits.
 pub fn color_to_vector3(color: Color) -> Vector3 {
     Vector3::new(
         color.r as f32 / 255.0,
@@ -60,3 +183,47 @@ pub fn color_to_vector3(color: Color) -> Vector3 {
         color.b as f32 / 255.0,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Una puerta de 16x32 en la cara cuadrada de un bloque
+    const WIDTH: i32 = 16;
+    const HEIGHT: i32 = 32;
+
+    fn preserve(u: f32, v: f32) -> Option<(f32, f32)> {
+        TextureFit::Preserve.map(u, v, 1.0, WIDTH, HEIGHT)
+    }
+
+    #[test]
+    fn tall_texture_is_letterboxed_without_distortion() {
+        // Media cara de ancho: franjas libres de 0.25 a cada lado
+        for u in [0.0, 0.1, 0.24, 0.76, 0.9, 1.0] {
+            for v in [0.0, 0.5, 1.0] {
+                assert_eq!(preserve(u, v), None, "({}, {}) cae en una franja", u, v);
+            }
+        }
+        assert_eq!(preserve(0.5, 0.5), Some((0.5, 0.5)));
+        // Los bordes de las franjas son los de la textura, a toda su altura
+        assert_eq!(preserve(0.25, 0.0), Some((0.0, 0.0)));
+        assert_eq!(preserve(0.75, 1.0), Some((1.0, 1.0)));
+
+        // Un paso por la cara recorre los mismos texeles a lo ancho que a lo alto
+        let step = 0.1;
+        let (u0, v0) = preserve(0.3, 0.2).unwrap();
+        let (u1, _) = preserve(0.3 + step, 0.2).unwrap();
+        let (_, v1) = preserve(0.3, 0.2 + step).unwrap();
+        let across = (u1 - u0) * WIDTH as f32;
+        let down = (v1 - v0) * HEIGHT as f32;
+        assert!((across - down).abs() < 1e-4, "{} texeles a lo ancho, {} a lo alto", across, down);
+    }
+
+    #[test]
+    fn square_textures_and_stretch_keep_the_face_coordinates() {
+        for (u, v) in [(0.0, 0.0), (0.3, 0.8), (1.0, 1.0)] {
+            assert_eq!(TextureFit::Preserve.map(u, v, 1.0, 16, 16), Some((u, v)));
+            assert_eq!(TextureFit::Stretch.map(u, v, 1.0, WIDTH, HEIGHT), Some((u, v)));
+        }
+    }
+}
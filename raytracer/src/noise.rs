@@ -0,0 +1,69 @@
+// noise.rs
+// Ruido de valor determinista: mismo input, mismo output, sin estado global.
+
+fn hash2(x: i32, z: i32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343) ^ (z as u32).wrapping_mul(0xd816_3841);
+    h = (h ^ (h >> 13)).wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 16;
+    h as f32 / u32::MAX as f32
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Ruido de valor 2D en [0, 1], interpolado suavemente entre celdas enteras.
+pub fn value_noise_2d(x: f32, z: f32) -> f32 {
+    let x0 = x.floor();
+    let z0 = z.floor();
+    let tx = smoothstep(x - x0);
+    let tz = smoothstep(z - z0);
+    let (ix, iz) = (x0 as i32, z0 as i32);
+
+    let a = hash2(ix, iz);
+    let b = hash2(ix + 1, iz);
+    let c = hash2(ix, iz + 1);
+    let d = hash2(ix + 1, iz + 1);
+
+    let top = a + (b - a) * tx;
+    let bottom = c + (d - c) * tx;
+    top + (bottom - top) * tz
+}
+
+/// Valor pseudoaleatorio en [0, 1] de una celda entera.
+pub fn hash3(x: i32, y: i32, z: i32, seed: u32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xcb1a_b31f)
+        ^ (z as u32).wrapping_mul(0xd816_3841)
+        ^ seed.wrapping_mul(0x27d4_eb2d);
+    h = (h ^ (h >> 13)).wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 16;
+    h as f32 / u32::MAX as f32
+}
+
+/// Ruido de valor 3D en [0, 1]; `seed` cambia el patrón completo.
+pub fn value_noise_3d(x: f32, y: f32, z: f32, seed: u32) -> f32 {
+    let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+    let (tx, ty, tz) = (smoothstep(x - x0), smoothstep(y - y0), smoothstep(z - z0));
+    let (ix, iy, iz) = (x0 as i32, y0 as i32, z0 as i32);
+
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let corner = |dx: i32, dy: i32, dz: i32| hash3(ix + dx, iy + dy, iz + dz, seed);
+
+    let bottom = lerp(
+        lerp(corner(0, 0, 0), corner(1, 0, 0), tx),
+        lerp(corner(0, 0, 1), corner(1, 0, 1), tx),
+        tz,
+    );
+    let top = lerp(
+        lerp(corner(0, 1, 0), corner(1, 1, 0), tx),
+        lerp(corner(0, 1, 1), corner(1, 1, 1), tx),
+        tz,
+    );
+    lerp(bottom, top, ty)
+}
+
+/// Ruido "ridged": vale 1 sobre la isosuperficie 0.5 del ruido y cae hacia los lados.
+pub fn ridged_noise_3d(x: f32, y: f32, z: f32, seed: u32) -> f32 {
+    1.0 - (value_noise_3d(x, y, z, seed) * 2.0 - 1.0).abs()
+}
@@ -0,0 +1,195 @@
+// perf_log.rs
+// Per-frame CSV timings of the interactive window, buffered, with a summary
+// appended on exit. A panic hook flushes whatever is still buffered, so a crash
+// keeps the frames leading up to it. The profile columns after Backend are
+// empty unless `[quality] profile` is on.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::error::Error;
+use crate::profile::Profile;
+
+/// Where the log goes unless `[performance_log]` in raytracer.toml says otherwise.
+pub const PERFORMANCE_LOG: &str = "performance_log.txt";
+
+const HEADER: &str = "Frame,FPS,RenderTimeMs,PrimaryMs,EdgeAaMs,PostMs,UploadMs,IdleMs,Backend,\
+    PrimaryRays,ShadowRays,SecondaryRays,NodeVisits,CubeTests,RayGenMs,TraceMs,ShadeMs";
+
+/// Timings of one frame, in milliseconds. Primary and edge cover the two
+/// passes of `render::render`, shading and shadow rays included; both are 0 on
+/// the GPU backend.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameTimings {
+    /// Frames per second, from the time of the whole frame.
+    pub fps: i32,
+    /// The whole trace, from the first ray to the finished HDR frame.
+    pub render_ms: f64,
+    /// The one-ray-per-pixel pass.
+    pub primary_ms: f64,
+    /// The edge anti-aliasing pass.
+    pub edge_ms: f64,
+    /// Bloom, tone mapping and overlays.
+    pub post_ms: f64,
+    /// Copying the frame into the window texture.
+    pub upload_ms: f64,
+    /// Sleeping under the frame rate cap; 0 when uncapped.
+    pub idle_ms: f64,
+    /// Ray counts and stage times of the CPU renderer, when profiling.
+    pub profile: Option<Profile>,
+}
+
+/// Statistics over every frame logged.
+#[derive(Debug, Clone, Copy)]
+pub struct PerfSummary {
+    /// Frames logged.
+    pub frames: usize,
+    /// Mean render time.
+    pub mean_ms: f64,
+    /// Median render time.
+    pub median_ms: f64,
+    /// 95th percentile render time.
+    pub p95_ms: f64,
+    /// Lowest frame rate.
+    pub min_fps: i32,
+    /// Highest frame rate.
+    pub max_fps: i32,
+}
+
+impl PerfSummary {
+    /// The summary as `#` comment lines, for the console and the end of the log.
+    pub fn lines(&self) -> Vec<String> {
+        vec![
+            format!("# frames: {}", self.frames),
+            format!(
+                "# render time: mean {:.2}ms, median {:.2}ms, p95 {:.2}ms",
+                self.mean_ms, self.median_ms, self.p95_ms
+            ),
+            format!("# fps: min {}, max {}", self.min_fps, self.max_fps),
+        ]
+    }
+}
+
+/// The open log, or nothing at all when it is disabled.
+pub struct PerfLog {
+    path: PathBuf,
+    writer: Option<Arc<Mutex<BufWriter<File>>>>,
+    render_ms: Vec<f64>,
+    fps_range: Option<(i32, i32)>,
+}
+
+impl PerfLog {
+    /// Creates `path`, writes the header and installs the panic hook that
+    /// flushes it.
+    pub fn create(path: &Path, render_threads: usize) -> Result<Self, Error> {
+        let file = File::create(path).map_err(Error::io(path))?;
+        let writer = Arc::new(Mutex::new(BufWriter::new(file)));
+        let mut log = PerfLog {
+            path: path.to_path_buf(),
+            writer: Some(Arc::clone(&writer)),
+            render_ms: Vec::new(),
+            fps_range: None,
+        };
+        log.note(&format!("render threads: {}", render_threads))?;
+        log.write_line(HEADER)?;
+
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            // try_lock: the panic may have happened mid-write with the lock held
+            if let Ok(mut writer) = writer.try_lock() {
+                let _ = writer.flush();
+            }
+            previous(info);
+        }));
+        Ok(log)
+    }
+
+    /// A log that writes and summarizes nothing, for `--no-perf-log`.
+    pub fn disabled() -> Self {
+        PerfLog { path: PathBuf::new(), writer: None, render_ms: Vec::new(), fps_range: None }
+    }
+
+    /// Appends the row of frame `frame`.
+    pub fn record(&mut self, frame: u64, backend: &str, timings: &FrameTimings) -> Result<(), Error> {
+        if self.writer.is_none() {
+            return Ok(());
+        }
+        self.render_ms.push(timings.render_ms);
+        self.fps_range = Some(match self.fps_range {
+            Some((min, max)) => (min.min(timings.fps), max.max(timings.fps)),
+            None => (timings.fps, timings.fps),
+        });
+        let profile = match &timings.profile {
+            Some(p) => format!(
+                "{},{},{},{},{},{:.2},{:.2},{:.2}",
+                p.primary_rays,
+                p.shadow_rays,
+                p.secondary_rays,
+                p.node_visits,
+                p.cube_tests,
+                p.ray_gen.as_secs_f64() * 1000.0,
+                p.trace.as_secs_f64() * 1000.0,
+                p.shade.as_secs_f64() * 1000.0
+            ),
+            None => ",,,,,,,".to_string(),
+        };
+        self.write_line(&format!(
+            "{},{},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{},{}",
+            frame,
+            timings.fps,
+            timings.render_ms,
+            timings.primary_ms,
+            timings.edge_ms,
+            timings.post_ms,
+            timings.upload_ms,
+            timings.idle_ms,
+            backend,
+            profile
+        ))
+    }
+
+    /// Appends `text` as a `#` comment line.
+    pub fn note(&mut self, text: &str) -> Result<(), Error> {
+        self.write_line(&format!("# {}", text))
+    }
+
+    /// Statistics over the frames recorded so far, None before the first one.
+    pub fn summary(&self) -> Option<PerfSummary> {
+        let (min_fps, max_fps) = self.fps_range?;
+        let mut sorted = self.render_ms.clone();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+        Some(PerfSummary {
+            frames: sorted.len(),
+            mean_ms: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            median_ms: percentile(0.5),
+            p95_ms: percentile(0.95),
+            min_fps,
+            max_fps,
+        })
+    }
+
+    /// Prints the summary, appends it to the log and flushes it.
+    pub fn finish(mut self) -> Result<(), Error> {
+        if let Some(summary) = self.summary() {
+            println!("Performance summary ({}):", self.path.display());
+            for line in summary.lines() {
+                println!("  {}", line.trim_start_matches("# "));
+                self.write_line(&line)?;
+            }
+        }
+        match &self.writer {
+            Some(writer) => writer.lock().expect("perf log lock").flush().map_err(Error::io(&self.path)),
+            None => Ok(()),
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), Error> {
+        match &self.writer {
+            Some(writer) => writeln!(writer.lock().expect("perf log lock"), "{}", line).map_err(Error::io(&self.path)),
+            None => Ok(()),
+        }
+    }
+}
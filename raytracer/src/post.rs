@@ -0,0 +1,87 @@
+// post.rs
+// Post-processing of the HDR frame, after it is traced and before it is
+// resolved to display colors. Every effect is a `PostPass`: linear radiance in,
+// linear radiance out, in place on the `Framebuffer` and parallel over it with
+// rayon. `PostChain::from_settings` lists the passes that are turned on, in
+// the one order they always run in, and `apply` runs them and times each, for
+// the profile. A new effect is a `PostPass` plus its toggle in
+// `RenderSettings`, pushed in `from_settings` at its place in the order.
+use std::time::{Duration, Instant};
+
+use raylib::prelude::*;
+
+use crate::bloom::Bloom;
+use crate::framebuffer::Framebuffer;
+use crate::gbuffer::GBuffer;
+use crate::settings::RenderSettings;
+
+/// What the tracer leaves next to the colors, for passes that need more than
+/// a pixel's color. Empty slices when the frame was not traced, e.g. the
+/// calibration pattern, and the G-buffer ones also when no `GBuffer` was
+/// filled.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AuxBuffers<'a> {
+    /// Distance to the first hit along each camera ray; infinite for the sky.
+    pub depth: &'a [f32],
+    /// Camera rays averaged into each pixel.
+    pub sample_counts: &'a [u32],
+    /// See `GBuffer::normal`.
+    pub normal: &'a [Vector3],
+    /// See `GBuffer::albedo`.
+    pub albedo: &'a [Vector3],
+    /// See `GBuffer::object_id`.
+    pub object_id: &'a [u32],
+}
+
+impl<'a> AuxBuffers<'a> {
+    /// The buffers of a traced frame, with `gbuffer` if one was filled.
+    pub fn new(depth: &'a [f32], sample_counts: &'a [u32], gbuffer: Option<&'a GBuffer>) -> Self {
+        let (normal, albedo, object_id) = gbuffer.map_or((&[][..], &[][..], &[][..]), |gbuffer| {
+            (&gbuffer.normal[..], &gbuffer.albedo[..], &gbuffer.object_id[..])
+        });
+        AuxBuffers { depth, sample_counts, normal, albedo, object_id }
+    }
+}
+
+/// One effect on the HDR frame.
+pub trait PostPass: Send + Sync {
+    /// Short name for the profile, e.g. "bloom".
+    fn name(&self) -> &'static str;
+
+    /// Applies the effect to `frame` in place, using rayon for the pixels.
+    fn apply(&self, frame: &mut Framebuffer, aux: &AuxBuffers);
+}
+
+/// The passes turned on in a `RenderSettings`, in order.
+#[derive(Default)]
+pub struct PostChain {
+    passes: Vec<Box<dyn PostPass>>,
+}
+
+impl PostChain {
+    /// The passes `settings` turns on, in the order they run.
+    pub fn from_settings(settings: &RenderSettings) -> Self {
+        let mut passes: Vec<Box<dyn PostPass>> = Vec::new();
+        if settings.bloom && settings.bloom_intensity > 0.0 {
+            passes.push(Box::new(Bloom::from(settings)));
+        }
+        PostChain { passes }
+    }
+
+    /// Names of the passes, in the order they run.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.passes.iter().map(|pass| pass.name())
+    }
+
+    /// Runs every pass on `frame`, in order, and returns the time each took.
+    pub fn apply(&self, frame: &mut Framebuffer, aux: &AuxBuffers) -> Vec<(&'static str, Duration)> {
+        self.passes
+            .iter()
+            .map(|pass| {
+                let start = Instant::now();
+                pass.apply(frame, aux);
+                (pass.name(), start.elapsed())
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,191 @@
+// presets.rs
+// Biblioteca de materiales con nombre que las escenas importan. La de serie son
+// los bloques del mundo generado (`BlockPalette`); un `materials.ron` añade
+// materiales o sustituye los de serie con el mismo nombre. Una escena puede
+// nombrar un preset directamente en sus cubos, o declararlo en `presets`
+// cambiando solo algunos campos, con el mismo nombre o con uno nuevo. Los
+// materiales completos de la escena (`materials`) mandan sobre todo lo anterior.
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::material::TextureFit;
+use crate::scene_file::{MaterialEntry, TintEntry};
+use crate::worldgen::BlockPalette;
+
+/// Nombre habitual del archivo de presets.
+pub const PRESETS_FILE: &str = "materials.ron";
+
+/// Contenido de un archivo de presets.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PresetLibrary {
+    /// Presets; cada nombre aparece una sola vez.
+    pub materials: Vec<MaterialEntry>,
+}
+
+impl PresetLibrary {
+    /// Los materiales de los bloques del mundo generado.
+    pub fn builtin() -> Self {
+        let palette = BlockPalette::new();
+        let materials = palette
+            .materials()
+            .into_iter()
+            .filter_map(|material| material.name.as_deref().map(|name| MaterialEntry::from_material(name, material)))
+            .collect();
+        PresetLibrary { materials }
+    }
+
+    /// La biblioteca de serie con los presets de `path` encima.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = fs::read_to_string(path).map_err(Error::io(path))?;
+        let file: PresetLibrary =
+            ron::from_str(&text).map_err(|e| Error::SceneParse { path: path.to_path_buf(), message: e.to_string() })?;
+        let mut library = Self::builtin();
+        for entry in file.materials {
+            library.insert(entry);
+        }
+        Ok(library)
+    }
+
+    /// Guarda la biblioteca como RON en `path`, p. ej. para partir de la de serie.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Añade `entry`, sustituyendo el preset que tenga su nombre.
+    pub fn insert(&mut self, entry: MaterialEntry) {
+        match self.materials.iter_mut().find(|m| m.name == entry.name) {
+            Some(existing) => *existing = entry,
+            None => self.materials.push(entry),
+        }
+    }
+
+    /// Preset llamado `name`.
+    pub fn get(&self, name: &str) -> Option<&MaterialEntry> {
+        self.materials.iter().find(|m| m.name == name)
+    }
+}
+
+/// Material de una escena hecho a partir de un preset: los campos que faltan
+/// vienen del preset. En `texture`, `normal_map` y `tint`, `Some(None)` quita
+/// lo que tuviera el preset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresetOverride {
+    /// Nombre por el que lo referencian los cubos.
+    pub name: String,
+    /// Preset del que parte; sin él, el que se llame como `name`.
+    #[serde(default)]
+    pub preset: Option<String>,
+    /// Color difuso, en [0, 1].
+    #[serde(default)]
+    pub diffuse: Option<[f32; 3]>,
+    /// Peso de la parte difusa y de la especular.
+    #[serde(default)]
+    pub albedo: Option<[f32; 2]>,
+    /// Exponente especular.
+    #[serde(default)]
+    pub specular: Option<f32>,
+    /// Fracción de luz reflejada.
+    #[serde(default)]
+    pub reflectivity: Option<f32>,
+    /// Fracción de luz que atraviesa la superficie.
+    #[serde(default)]
+    pub transparency: Option<f32>,
+    /// Índice de refracción.
+    #[serde(default)]
+    pub refractive_index: Option<f32>,
+    /// Rugosidad.
+    #[serde(default)]
+    pub roughness: Option<f32>,
+    /// Rugosidad de la refracción.
+    #[serde(default)]
+    pub refraction_roughness: Option<f32>,
+    /// Número de Abbe de la dispersión.
+    #[serde(default)]
+    pub abbe_number: Option<f32>,
+    /// Ruta de la textura difusa.
+    #[serde(default)]
+    pub texture: Option<Option<String>>,
+    /// Ruta del mapa de normales.
+    #[serde(default)]
+    pub normal_map: Option<Option<String>>,
+    /// Luz emitida.
+    #[serde(default)]
+    pub emission: Option<[f32; 3]>,
+    /// Tinte por bloque.
+    #[serde(default)]
+    pub tint: Option<Option<TintEntry>>,
+    /// Cae si no tiene nada debajo.
+    #[serde(default)]
+    pub falls: Option<bool>,
+    /// Recorta los texeles transparentes de la textura.
+    #[serde(default)]
+    pub alpha_cutout: Option<bool>,
+    /// Cómo cubre la textura una cara de otra proporción.
+    #[serde(default)]
+    pub texture_fit: Option<TextureFit>,
+}
+
+impl PresetOverride {
+    /// El preset con los campos de la escena encima; falla si no existe.
+    pub fn resolve(&self, library: &PresetLibrary) -> Result<MaterialEntry, String> {
+        let base = self.preset.as_deref().unwrap_or(&self.name);
+        let mut entry = library
+            .get(base)
+            .cloned()
+            .ok_or_else(|| format!("material '{}' uses unknown preset '{}'", self.name, base))?;
+        entry.name = self.name.clone();
+        if let Some(diffuse) = self.diffuse {
+            entry.diffuse = diffuse;
+        }
+        if let Some(albedo) = self.albedo {
+            entry.albedo = albedo;
+        }
+        if let Some(specular) = self.specular {
+            entry.specular = specular;
+        }
+        if let Some(reflectivity) = self.reflectivity {
+            entry.reflectivity = reflectivity;
+        }
+        if let Some(transparency) = self.transparency {
+            entry.transparency = transparency;
+        }
+        if let Some(refractive_index) = self.refractive_index {
+            entry.refractive_index = refractive_index;
+        }
+        if let Some(roughness) = self.roughness {
+            entry.roughness = roughness;
+        }
+        if let Some(refraction_roughness) = self.refraction_roughness {
+            entry.refraction_roughness = refraction_roughness;
+        }
+        if let Some(abbe_number) = self.abbe_number {
+            entry.abbe_number = abbe_number;
+        }
+        if let Some(texture) = &self.texture {
+            entry.texture = texture.clone();
+        }
+        if let Some(normal_map) = &self.normal_map {
+            entry.normal_map = normal_map.clone();
+        }
+        if let Some(emission) = self.emission {
+            entry.emission = emission;
+        }
+        if let Some(tint) = &self.tint {
+            entry.tint = tint.clone();
+        }
+        if let Some(falls) = self.falls {
+            entry.falls = falls;
+        }
+        if let Some(alpha_cutout) = self.alpha_cutout {
+            entry.alpha_cutout = alpha_cutout;
+        }
+        if let Some(texture_fit) = self.texture_fit {
+            entry.texture_fit = texture_fit;
+        }
+        Ok(entry)
+    }
+}
@@ -0,0 +1,82 @@
+// preview.rs
+// Rasterized stand-in for the traced frame while the camera is being moved.
+// Tracing a frame can take a good part of a second, which makes steering the
+// camera with the keys sluggish; while a camera key is held the window draws
+// the cubes with raylib's own rasterizer instead, flat colored and unlit, and
+// traces again once the keys are let go. The box list is built once per
+// `Scene::generation`, so edits and streamed chunks show up, and each box
+// takes its texture's average color (or the diffuse color) and its emission.
+// Nothing here is ray traced: no shadows, reflections, glass or sky, which
+// is why the window marks every preview frame as such.
+use raylib::prelude::*;
+
+use crate::camera::Camera as ViewCamera;
+use crate::cube::Cube;
+use crate::material::vector3_to_color;
+use crate::scene::Scene;
+use crate::textures::TextureManager;
+
+/// One cube as the preview draws it.
+struct PreviewBox {
+    center: Vector3,
+    size: Vector3,
+    // Lit part of the color, scaled by the light each frame
+    color: Vector3,
+    // Emission, drawn at full strength day or night
+    emission: Vector3,
+}
+
+/// The boxes of a scene ready to rasterize.
+#[derive(Default)]
+pub struct RasterPreview {
+    boxes: Vec<PreviewBox>,
+    // `Scene::generation` the boxes were built for
+    generation: Option<u64>,
+}
+
+impl RasterPreview {
+    /// Builds the boxes again if `scene` changed since the last call.
+    pub fn sync(&mut self, scene: &Scene, texture_manager: &TextureManager) {
+        if self.generation == Some(scene.generation()) {
+            return;
+        }
+        let chunks = scene.chunks.iter().flat_map(|chunk| &chunk.objects);
+        self.boxes = scene.objects.iter().chain(chunks).map(|cube| preview_box(cube, texture_manager)).collect();
+        self.generation = Some(scene.generation());
+    }
+
+    /// Clears to `sky` and draws every box in front of `view` with raylib's
+    /// camera matched to it. `light` scales the boxes' lit colors, e.g. lower
+    /// at night.
+    pub fn draw(&self, d: &mut RaylibDrawHandle, view: &ViewCamera, width: i32, height: i32, light: f32, sky: Color) {
+        d.clear_background(sky);
+        let (_, extent_y) = view.screen_extent(width, height);
+        let fovy = (2.0 * extent_y.atan()).to_degrees();
+        let camera = Camera3D::perspective(view.eye, view.center, view.up, fovy);
+        let mut d3 = d.begin_mode3D(camera);
+        for preview in &self.boxes {
+            // Boxes wholly behind the eye would only be clipped away
+            let reach = preview.size.length() * 0.5;
+            if (preview.center - view.eye).dot(view.forward) < -reach {
+                continue;
+            }
+            let color = vector3_to_color(preview.color * light + preview.emission);
+            d3.draw_cube_v(preview.center, preview.size, color);
+        }
+    }
+}
+
+fn preview_box(cube: &Cube, texture_manager: &TextureManager) -> PreviewBox {
+    let material = &cube.material;
+    let base = material
+        .texture
+        .as_deref()
+        .and_then(|texture| texture_manager.texture_stats(texture))
+        .map_or(material.diffuse, |stats| stats.average);
+    PreviewBox {
+        center: (cube.min_bounds + cube.max_bounds) * 0.5,
+        size: cube.max_bounds - cube.min_bounds,
+        color: base * material.albedo[0],
+        emission: material.emission,
+    }
+}
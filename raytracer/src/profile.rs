@@ -0,0 +1,174 @@
+// profile.rs
+// Ray counters and stage timers behind `RenderSettings::profile`. Each render
+// thread counts into thread-locals, and `render` adds what every parallel job
+// counted into shared atomics once the job is done, the way it counts rays
+// traced; the totals come out exact under rayon without the hot path ever
+// touching a shared cache line. Stage times are summed over the threads, so
+// with N threads busy they add up to about N times the wall time of the pass.
+// With the setting off, a count or a timer costs one relaxed load of a flag.
+use std::cell::Cell;
+use std::ops::AddAssign;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// What a counter counts.
+#[derive(Debug, Clone, Copy)]
+pub enum Counter {
+    /// Camera rays, edge samples included.
+    PrimaryRays,
+    /// Rays towards a light, hard and soft shadows alike.
+    ShadowRays,
+    /// Reflection and refraction rays.
+    SecondaryRays,
+    /// BVH nodes reached; a packet reaching a node counts it once.
+    NodeVisits,
+    /// Ray-cube intersection tests.
+    CubeTests,
+}
+
+/// A stage of the trace, timed on the thread that runs it.
+#[derive(Debug, Clone, Copy)]
+pub enum Stage {
+    /// Primary ray directions.
+    RayGen,
+    /// Finding what a camera ray hits first.
+    Trace,
+    /// Lighting the hit, shadow and secondary rays included.
+    Shade,
+}
+
+const COUNTERS: usize = 5;
+// Counters first, then the nanoseconds of each stage
+const SLOTS: usize = COUNTERS + 3;
+
+thread_local! {
+    static SLOTS_HERE: [Cell<u64>; SLOTS] = const { [const { Cell::new(0) }; SLOTS] };
+}
+
+/// Turns counting and timing on or off for every thread; `render` sets it from
+/// `RenderSettings::profile` as it starts.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether counts and timers are being kept.
+#[inline]
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn add_here(slot: usize, amount: u64) {
+    SLOTS_HERE.with(|slots| slots[slot].set(slots[slot].get() + amount));
+}
+
+/// Adds `amount` to `counter` on this thread.
+#[inline]
+pub fn count(counter: Counter, amount: u64) {
+    if enabled() {
+        add_here(counter as usize, amount);
+    }
+}
+
+/// Starts timing a stage; None while profiling is off.
+#[inline]
+pub fn start() -> Option<Instant> {
+    enabled().then(Instant::now)
+}
+
+/// Adds the time since `start` to `stage` on this thread.
+#[inline]
+pub fn stop(stage: Stage, start: Option<Instant>) {
+    if let Some(start) = start {
+        add_here(COUNTERS + stage as usize, start.elapsed().as_nanos() as u64);
+    }
+}
+
+/// What this thread has counted so far, to take the difference over a job.
+#[derive(Debug, Clone, Copy)]
+pub struct Snapshot([u64; SLOTS]);
+
+/// This thread's counts and times so far.
+pub fn snapshot() -> Snapshot {
+    Snapshot(SLOTS_HERE.with(|slots| std::array::from_fn(|slot| slots[slot].get())))
+}
+
+/// Counts and times added up over the parallel jobs of a render.
+#[derive(Debug, Default)]
+pub struct Totals([AtomicU64; SLOTS]);
+
+impl Totals {
+    /// Adds what this thread counted since `before`; called at the end of
+    /// each job, on the thread that ran it.
+    pub fn add_since(&self, before: &Snapshot) {
+        let now = snapshot();
+        for (total, (now, before)) in self.0.iter().zip(now.0.iter().zip(before.0)) {
+            if *now != before {
+                total.fetch_add(now - before, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// The totals so far, without post and upload, which `render` does not see.
+    pub fn profile(&self) -> Profile {
+        let slot = |slot: usize| self.0[slot].load(Ordering::Relaxed);
+        let stage = |stage: Stage| Duration::from_nanos(slot(COUNTERS + stage as usize));
+        Profile {
+            primary_rays: slot(Counter::PrimaryRays as usize),
+            shadow_rays: slot(Counter::ShadowRays as usize),
+            secondary_rays: slot(Counter::SecondaryRays as usize),
+            node_visits: slot(Counter::NodeVisits as usize),
+            cube_tests: slot(Counter::CubeTests as usize),
+            ray_gen: stage(Stage::RayGen),
+            trace: stage(Stage::Trace),
+            shade: stage(Stage::Shade),
+            post: Duration::ZERO,
+            upload: Duration::ZERO,
+        }
+    }
+}
+
+/// Where a frame's work went. Ray gen, trace and shade are summed over the
+/// render threads; post and upload run on the main thread and are filled in
+/// by whoever runs them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    /// Camera rays, edge samples included.
+    pub primary_rays: u64,
+    /// Shadow rays.
+    pub shadow_rays: u64,
+    /// Reflection and refraction rays.
+    pub secondary_rays: u64,
+    /// BVH nodes reached.
+    pub node_visits: u64,
+    /// Ray-cube intersection tests.
+    pub cube_tests: u64,
+    /// Generating primary ray directions.
+    pub ray_gen: Duration,
+    /// Finding the primary hits.
+    pub trace: Duration,
+    /// Shading them.
+    pub shade: Duration,
+    /// Bloom, tone mapping and overlays.
+    pub post: Duration,
+    /// Copying the frame into the window texture.
+    pub upload: Duration,
+}
+
+impl AddAssign for Profile {
+    fn add_assign(&mut self, other: Profile) {
+        self.primary_rays += other.primary_rays;
+        self.shadow_rays += other.shadow_rays;
+        self.secondary_rays += other.secondary_rays;
+        self.node_visits += other.node_visits;
+        self.cube_tests += other.cube_tests;
+        self.ray_gen += other.ray_gen;
+        self.trace += other.trace;
+        self.shade += other.shade;
+        self.post += other.post;
+        self.upload += other.upload;
+    }
+}
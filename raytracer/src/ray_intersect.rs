@@ -1,51 +1,62 @@
 // ray_intersect.rs
-use raylib::prelude::{Color, Vector3};
+use std::sync::{Arc, OnceLock};
+
+use raylib::prelude::Vector3;
 use crate::material::Material;
+
+// El material de todos los rayos que no chocan, creado una sola vez
+static NO_MATERIAL: OnceLock<Arc<Material>> = OnceLock::new();
+
+/// Resultado de lanzar un rayo contra un objeto.
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
 pub struct Intersect {
-    pub material: Material,
+    /// Material en el punto de impacto, compartido con el objeto.
+    pub material: Arc<Material>,
+    /// Distancia desde el origen del rayo.
     pub distance: f32,
+    /// Falso si el rayo no chocó; el resto de campos no vale nada.
     pub is_intersecting: bool,
+    /// Normal de la cara, hacia fuera.
     pub normal: Vector3,
+    /// Punto de impacto.
     pub point: Vector3,
+    /// Coordenada de textura horizontal en la cara, en [0, 1].
     pub u: f32,
+    /// Coordenada de textura vertical en la cara, en [0, 1].
     pub v: f32,
+    /// Ancho de la cara a lo largo de `u` entre su alto a lo largo de `v`.
+    pub face_aspect: f32,
 }
 impl Intersect {
-    pub fn new(material: Material, distance: f32, normal: Vector3, point: Vector3, u: f32, v: f32) -> Self {
+    /// Impacto a `distance` con estos datos.
+    pub fn new(material: impl Into<Arc<Material>>, distance: f32, normal: Vector3, point: Vector3, u: f32, v: f32) -> Self {
         Intersect {
-            material,
+            material: material.into(),
             distance,
             is_intersecting: true,
             normal,
             point,
             u,
             v,
+            face_aspect: 1.0,
         }
     }
+    /// Sin impacto.
     pub fn empty() -> Self {
         Intersect {
-            material: Material {
-                diffuse: Vector3::zero(),
-                albedo: [0.0, 0.0],
-                specular: 0.0,
-                reflectivity: 0.0,
-                transparency: 0.0,
-                refractive_index: 0.0,
-                texture: None,
-                normal_map_id: None,
-                emission: Vector3::zero(), // <-- CAMPO AÑADIDO
-            },
+            material: Arc::clone(NO_MATERIAL.get_or_init(|| Arc::new(Material::black()))),
             distance: 0.0,
             is_intersecting: false,
             normal: Vector3::zero(),
             point: Vector3::zero(),
             u: 0.0,
             v: 0.0,
+            face_aspect: 1.0,
         }
     }
 }
+/// Algo que se puede intersectar con un rayo.
 pub trait RayIntersect {
+    /// Impacto más cercano delante de `ray_origin` en `ray_direction` (normalizada).
     fn ray_intersect(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect;
 }
\ No newline at end of file
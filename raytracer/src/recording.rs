@@ -0,0 +1,191 @@
+// recording.rs
+// Session recording: the camera and the view toggles captured once per frame,
+// saved as RON and played back frame by frame. Playback advances one recorded
+// frame per rendered frame whatever the frame rate, so a replay shows exactly
+// the views that were recorded. Simulation input (TNT, edits) is not recorded.
+use std::fs;
+use std::path::Path;
+
+use log::{error, info};
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+use crate::scene::{Scene, LAYER_COUNT};
+use crate::settings::{RenderSettings, ToneMapping, TransparencyMode};
+use crate::weather::{Weather, WeatherSystem};
+
+/// Written when a recording stops.
+pub const RECORDING_FILE: &str = "session.ron";
+
+/// Everything that decides what one frame shows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrameState {
+    /// Camera position.
+    pub eye: [f32; 3],
+    /// Point the camera looks at.
+    pub center: [f32; 3],
+    /// Day/night cycle angle in radians.
+    pub time_of_day: f32,
+    /// Weather shown.
+    pub weather: Weather,
+    /// Glass mode.
+    pub transparency: TransparencyMode,
+    /// Tone mapping operator.
+    pub tone_mapping: ToneMapping,
+    /// Reinhard white point.
+    pub white_point: f32,
+    /// Edge anti-aliasing rays per pixel.
+    pub edge_samples: u32,
+    /// Bit n set when layer n is hidden.
+    pub hidden_layers: u16,
+}
+
+impl FrameState {
+    /// The state of the current frame.
+    pub fn capture(camera: &Camera, time_of_day: f32, weather: Weather, settings: &RenderSettings, scene: &Scene) -> Self {
+        let hidden_layers = (0..LAYER_COUNT)
+            .filter(|&layer| !scene.layer_visible(layer))
+            .fold(0u16, |mask, layer| mask | 1 << layer);
+        FrameState {
+            eye: [camera.eye.x, camera.eye.y, camera.eye.z],
+            center: [camera.center.x, camera.center.y, camera.center.z],
+            time_of_day,
+            weather,
+            transparency: settings.transparency,
+            tone_mapping: settings.tone_mapping,
+            white_point: settings.white_point,
+            edge_samples: settings.edge_samples,
+            hidden_layers,
+        }
+    }
+
+    /// Puts the recorded view back, overriding whatever live input did this frame.
+    pub fn apply(
+        &self,
+        camera: &mut Camera,
+        time_of_day: &mut f32,
+        weather: &mut WeatherSystem,
+        settings: &mut RenderSettings,
+        scene: &mut Scene,
+    ) {
+        camera.eye = Vector3::new(self.eye[0], self.eye[1], self.eye[2]);
+        camera.center = Vector3::new(self.center[0], self.center[1], self.center[2]);
+        camera.up = Vector3::new(0.0, 1.0, 0.0);
+        camera.update_basis();
+        *time_of_day = self.time_of_day;
+        weather.set(self.weather);
+        settings.transparency = self.transparency;
+        settings.tone_mapping = self.tone_mapping;
+        settings.white_point = self.white_point;
+        settings.edge_samples = self.edge_samples;
+        for layer in 0..LAYER_COUNT {
+            scene.set_layer_visible(layer, self.hidden_layers & (1 << layer) == 0);
+        }
+    }
+}
+
+/// A recorded session, one entry per frame.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Recording {
+    /// Frames in the order they were shown.
+    pub frames: Vec<FrameState>,
+}
+
+impl Recording {
+    /// Reads a recording written by `save`; an empty one is an error.
+    pub fn load(path: &Path) -> Result<Recording, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let recording: Recording = ron::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if recording.frames.is_empty() {
+            return Err(format!("{}: the recording has no frames", path.display()));
+        }
+        Ok(recording)
+    }
+
+    /// Writes the recording as RON.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// Toggled by a key in the main loop; saves to `RECORDING_FILE` when stopped.
+#[derive(Default)]
+pub struct SessionRecorder {
+    recording: Option<Recording>,
+}
+
+impl SessionRecorder {
+    /// True between the start and stop keys.
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Frames captured so far.
+    pub fn frames(&self) -> usize {
+        self.recording.as_ref().map_or(0, |r| r.frames.len())
+    }
+
+    /// Starts a new recording, or stops and saves the current one.
+    pub fn toggle(&mut self) {
+        match self.recording.take() {
+            None => {
+                self.recording = Some(Recording::default());
+                info!("Recording session");
+            }
+            Some(recording) => save_recording(&recording),
+        }
+    }
+
+    /// Appends the frame built by `state`; only called while recording.
+    pub fn capture(&mut self, state: impl FnOnce() -> FrameState) {
+        if let Some(recording) = self.recording.as_mut() {
+            recording.frames.push(state());
+        }
+    }
+
+    /// Saves a recording that was still running when the window closed.
+    pub fn finish(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            save_recording(&recording);
+        }
+    }
+}
+
+fn save_recording(recording: &Recording) {
+    match recording.save(Path::new(RECORDING_FILE)) {
+        Ok(()) => info!("Saved {} frames to {}", recording.frames.len(), RECORDING_FILE),
+        Err(error) => error!("Could not save {}: {}", RECORDING_FILE, error),
+    }
+}
+
+/// Steps through a loaded recording one frame per call.
+pub struct Replay {
+    recording: Recording,
+    next: usize,
+}
+
+impl Replay {
+    /// Starts at the first frame.
+    pub fn new(recording: Recording) -> Self {
+        Replay { recording, next: 0 }
+    }
+
+    /// Frames in the recording.
+    pub fn frame_count(&self) -> usize {
+        self.recording.frames.len()
+    }
+
+    /// Frames played so far.
+    pub fn position(&self) -> usize {
+        self.next
+    }
+
+    /// None once every frame was played.
+    pub fn next_frame(&mut self) -> Option<&FrameState> {
+        let frame = self.recording.frames.get(self.next)?;
+        self.next += 1;
+        Some(frame)
+    }
+}
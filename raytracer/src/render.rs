@@ -0,0 +1,2286 @@
+// render.rs
+// The CPU path tracer: primary rays (optionally in BVH packets), shading with
+// soft shadows, reflection and refraction, weighted-blended transparency, fog and
+// the edge supersampling pass. Produces linear HDR frames; see `tonemap`.
+use std::cell::Cell;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, Once};
+use std::time::{Duration, Instant};
+
+use log::warn;
+use raylib::prelude::*;
+use rayon::prelude::*;
+
+use crate::accel::{traverse_packet, TracerRay, PACKET_SIZE};
+use crate::antialias::{edge_mask, same_surface};
+use crate::camera::{is_valid_direction, Camera, RayGenCache};
+use crate::cube::{compare_hits, Cube, SlabHit};
+use crate::framebuffer::Rect;
+use crate::gbuffer::GBuffer;
+use crate::light::Light;
+use crate::light_cache::{CacheStats, LightCache, LightKey};
+use crate::material::Material;
+use crate::noise::hash3;
+use crate::profile::{self, Counter, Profile, Snapshot, Stage, Totals};
+use crate::ray_intersect::{Intersect, RayIntersect};
+use crate::sampling::{ggx_microfacet, lattice_point, subpixel_offset, tangent_basis, vogel_disk};
+use crate::scene::{Occlusion, Scene};
+use crate::scratch::ScratchPool;
+use crate::settings::{RenderSettings, Schedule, TransparencyMode};
+use crate::snell::{fresnel_dielectric, media, reflect, refract_rough, rgb_iors};
+use crate::textures::TextureManager;
+
+thread_local! {
+    // Rays traced on this thread so far; `render` adds up the difference over
+    // each parallel job, so the hot path never touches a shared counter
+    static RAYS_TRACED: Cell<u64> = const { Cell::new(0) };
+    // Bounce rays the current camera ray may still spawn; None once it has
+    // run out, which is counted in BUDGETS_SPENT. Unlimited outside `render`
+    static BOUNCES_LEFT: Cell<Option<u32>> = const { Cell::new(Some(u32::MAX)) };
+    static BUDGETS_SPENT: Cell<u64> = const { Cell::new(0) };
+    // The one color channel, 0 to 2 for red to blue, the current ray carries
+    // since a dispersive material split the camera ray; None for all three
+    static DISPERSION_CHANNEL: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+fn count_rays(count: u64) {
+    RAYS_TRACED.with(|rays| rays.set(rays.get() + count));
+}
+
+fn rays_traced_here() -> u64 {
+    RAYS_TRACED.with(Cell::get)
+}
+
+/// Gives the camera ray about to be traced on this thread a fresh
+/// `RenderSettings::bounce_budget`.
+fn start_camera_ray(settings: &RenderSettings) {
+    let budget = if settings.bounce_budget == 0 { u32::MAX } else { settings.bounce_budget };
+    BOUNCES_LEFT.with(|left| left.set(Some(budget)));
+}
+
+/// Index of refraction `material` bends the current ray by: its own, or that
+/// of the ray's color channel if a dispersive material split it.
+fn ray_refractive_index(material: &Material) -> f32 {
+    match DISPERSION_CHANNEL.with(Cell::get) {
+        Some(channel) if material.abbe_number > 0.0 => rgb_iors(material.refractive_index, material.abbe_number)[channel],
+        _ => material.refractive_index,
+    }
+}
+
+/// Takes a reflection or refraction ray from the current camera ray's budget;
+/// false once it is spent.
+fn take_bounce() -> bool {
+    BOUNCES_LEFT.with(|left| match left.get() {
+        Some(0) => {
+            left.set(None);
+            BUDGETS_SPENT.with(|spent| spent.set(spent.get() + 1));
+            false
+        }
+        Some(n) => {
+            left.set(Some(n - 1));
+            true
+        }
+        None => false,
+    })
+}
+
+fn budgets_spent_here() -> u64 {
+    BUDGETS_SPENT.with(Cell::get)
+}
+
+// Fraction of a light a fully shadowed point loses; also used by the shadow map
+pub(crate) const SHADOW_STRENGTH: f32 = 0.7;
+// Keeps the penumbra estimate sane when the blocker sits right on the light.
+const MAX_PENUMBRA: f32 = 1.0;
+// Light left after the transparent blocks a shadow ray has crossed, below
+// which the ray counts as blocked and stops looking
+const MIN_TRANSMITTANCE: f32 = 0.05;
+
+/// Distance to the nearest occluder between `origin` and `target`, if any,
+/// other than `emitter`.
+fn shadow_blocker_distance(
+    origin: &Vector3,
+    target: &Vector3,
+    emitter: Option<&Cube>,
+    scene: &Scene,
+    settings: &RenderSettings,
+) -> Option<f32> {
+    let to_target = *target - *origin;
+    let target_distance = to_target.length();
+    let ray = TracerRay::new(*origin, to_target / target_distance);
+    profile::count(Counter::ShadowRays, 1);
+    count_rays(1);
+    scene.intersect_nearest(&ray, target_distance, settings, |cube, _| !is_emitter(cube, emitter)).map(|hit| hit.distance)
+}
+
+/// Whether `cube` is the block `emitter` that gives off a light. An emissive
+/// block's light sits at its center, so every shadow ray towards it would
+/// end inside the block; the block itself never shadows its own light.
+fn is_emitter(cube: &Cube, emitter: Option<&Cube>) -> bool {
+    emitter.is_some_and(|emitter| std::ptr::eq(cube, emitter))
+}
+
+/// Unit direction and distance from a hit to a light, worked out once per
+/// light and shared by its shading and its shadow ray.
+#[derive(Clone, Copy)]
+struct ToLight {
+    direction: Vector3,
+    distance: f32,
+}
+
+impl ToLight {
+    /// None for a light right on the point, which lights it from no
+    /// direction; the caller skips it.
+    fn new(point: &Vector3, light: &Light) -> Option<Self> {
+        let offset = light.position - *point;
+        let distance = offset.length();
+        let direction = offset / distance;
+        (distance > 0.0 && is_valid_direction(&direction)).then_some(ToLight { direction, distance })
+    }
+}
+
+/// Shadow at `intersect` from `light`, which `to_light` points at, given off
+/// by the block `emitter` if it is an emissive block's. The hard shadow ray
+/// starts just off the surface and stops at the light's distance from the
+/// hit, a thousandth closer than from its origin at most.
+fn cast_shadow(
+    intersect: &Intersect,
+    light: &Light,
+    to_light: &ToLight,
+    emitter: Option<&Cube>,
+    scene: &Scene,
+    settings: &RenderSettings,
+) -> f32 {
+    if light.radius > 0.0 && settings.shadow_blocker_samples > 0 {
+        return cast_soft_shadow(intersect, light, emitter, scene, settings);
+    }
+
+    let shadow_ray_origin = intersect.point + intersect.normal * 0.001;
+    profile::count(Counter::ShadowRays, 1);
+    let transmittance = transmittance_past(&shadow_ray_origin, &to_light.direction, to_light.distance, emitter, scene, settings);
+    SHADOW_STRENGTH * (1.0 - transmittance)
+}
+
+/// Percentage-closer soft shadows for lights with a radius.
+///
+/// A blocker search over the light's disk estimates the average occluder
+/// distance; the penumbra width follows from similar triangles
+/// (`radius * (d_receiver - d_blocker) / d_blocker`, measured from the light)
+/// and the final occlusion is averaged over that width around the receiver.
+fn cast_soft_shadow(
+    intersect: &Intersect,
+    light: &Light,
+    emitter: Option<&Cube>,
+    scene: &Scene,
+    settings: &RenderSettings,
+) -> f32 {
+    let shadow_ray_origin = intersect.point + intersect.normal * 0.001;
+    let to_light = light.position - shadow_ray_origin;
+    let receiver_distance = to_light.length();
+    let (light_u, light_v) = tangent_basis(&(to_light / receiver_distance));
+
+    // 1. Blocker search
+    let blocker_samples = settings.shadow_blocker_samples;
+    let mut blocker_sum = 0.0;
+    let mut blocker_count = 0;
+    for i in 0..blocker_samples {
+        let (dx, dy) = vogel_disk(i, blocker_samples);
+        let target = light.position + light_u * (dx * light.radius) + light_v * (dy * light.radius);
+        if let Some(distance) = shadow_blocker_distance(&shadow_ray_origin, &target, emitter, scene, settings) {
+            blocker_sum += distance;
+            blocker_count += 1;
+        }
+    }
+
+    // No blockers: fully lit
+    if blocker_count == 0 {
+        return 0.0;
+    }
+
+    // 2. Penumbra estimate (depths measured from the light)
+    let blocker_depth = (receiver_distance - blocker_sum / blocker_count as f32).max(1e-3);
+    let penumbra = (light.radius * (receiver_distance - blocker_depth) / blocker_depth).min(MAX_PENUMBRA);
+
+    // 3. Filter the occlusion across the penumbra
+    let filter_samples = settings.shadow_filter_samples.max(1);
+    let (surface_u, surface_v) = tangent_basis(&intersect.normal);
+    let mut occluded = 0.0;
+    for i in 0..filter_samples {
+        let (dx, dy) = vogel_disk(i, filter_samples);
+        let offset = surface_u * (dx * penumbra * 0.5) + surface_v * (dy * penumbra * 0.5);
+        let sample_origin = shadow_ray_origin + offset;
+        let to_light = light.position - sample_origin;
+        let light_distance = to_light.length();
+        profile::count(Counter::ShadowRays, 1);
+        occluded += 1.0 - transmittance_past(&sample_origin, &(to_light / light_distance), light_distance, emitter, scene, settings);
+    }
+
+    SHADOW_STRENGTH * occluded / filter_samples as f32
+}
+
+const ORIGIN_BIAS: f32 = 1e-4;
+fn offset_origin(intersect: &Intersect, ray_direction: &Vector3) -> Vector3 {
+    let offset = intersect.normal * ORIGIN_BIAS;
+    if ray_direction.dot(intersect.normal) < 0.0 {
+        intersect.point - offset
+    } else {
+        intersect.point + offset
+    }
+}
+
+/// Alpha test of `Material::alpha_cutout` surfaces: a hit on a texel whose
+/// coverage is not above `threshold` does not count, and the ray goes on to
+/// whatever is behind the block. Only the face the ray enters is tested, so a
+/// cut-out block shows no inner faces through its holes.
+/// Without textures (`solid`) every block is hit whole.
+#[derive(Clone, Copy)]
+struct AlphaTest<'a> {
+    textures: Option<&'a TextureManager>,
+    threshold: f32,
+}
+
+impl<'a> AlphaTest<'a> {
+    /// Fixed threshold for one-ray-per-pixel and secondary rays.
+    fn new(textures: &'a TextureManager) -> Self {
+        AlphaTest { textures: Some(textures), threshold: 0.5 }
+    }
+
+    /// No alpha test: cut-out blocks stop the ray like any other.
+    fn solid() -> Self {
+        AlphaTest { textures: None, threshold: 0.5 }
+    }
+
+    /// Threshold for camera ray `sample` of `samples` in pixel (x, y). The
+    /// thresholds are stratified over [0, 1) and rotated by a per-pixel hash,
+    /// so a texel with coverage `a` is hit by about `a * samples` of the rays
+    /// (alpha to coverage) without the same pattern repeating on every pixel.
+    /// Coverage 0 never passes and coverage 1 always does.
+    fn dithered(textures: &'a TextureManager, x: usize, y: usize, sample: u32, samples: u32) -> Self {
+        let rotation = hash3(x as i32, y as i32, 0, ALPHA_DITHER_SEED).min(0.999);
+        AlphaTest { textures: Some(textures), threshold: (sample as f32 + rotation) / samples as f32 }
+    }
+
+    fn passes(&self, intersect: &Intersect) -> bool {
+        match (&intersect.material.texture, self.textures) {
+            (Some(texture), Some(textures)) if intersect.material.alpha_cutout => {
+                // The bands around a letterboxed texture are solid
+                texture_uv(intersect, texture, textures).is_none_or(|(u, v)| textures.alpha_at(texture, u, v) > self.threshold)
+            }
+            _ => true,
+        }
+    }
+
+    /// `passes` for `slab` on `cube`, completing the hit only when there is a
+    /// cut-out texture to look up.
+    fn passes_slab(&self, cube: &Cube, ray: &TracerRay, slab: &SlabHit) -> bool {
+        let material = &cube.material;
+        if self.textures.is_none() || !material.alpha_cutout || material.texture.is_none() {
+            return true;
+        }
+        self.passes(&cube.hit(ray, slab))
+    }
+}
+
+/// Where `intersect` lands in `texture` once `Material::texture_fit` has fitted
+/// it to the face, or None on the bands a letterboxed texture leaves uncovered.
+fn texture_uv(intersect: &Intersect, texture: &str, texture_manager: &TextureManager) -> Option<(f32, f32)> {
+    let (width, height) = texture_manager.texture_pixels(texture).map_or((1, 1), |(w, h, _)| (w, h));
+    intersect.material.texture_fit.map(intersect.u, intersect.v, intersect.face_aspect, width, height)
+}
+
+const ALPHA_DITHER_SEED: u32 = 0x51ed_270b;
+
+/// Closest hit in the whole scene: BVH geometry plus the dynamic layers.
+fn closest_hit(
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    scene: &Scene,
+    settings: &RenderSettings,
+    alpha: AlphaTest,
+) -> Intersect {
+    let ray = TracerRay::new(*ray_origin, *ray_direction);
+    count_rays(1);
+    scene
+        .intersect_nearest(&ray, f32::INFINITY, settings, |cube, slab| alpha.passes_slab(cube, &ray, slab))
+        .unwrap_or_else(Intersect::empty)
+}
+
+/// Closest hit along a ray in the whole scene, BVH geometry and the dynamic
+/// layers (chunks, clouds), or None if it escapes. Blocks count whole, cut-out
+/// texels included. For tools that need ray queries (picking, measuring)
+/// without going through the renderer.
+pub fn trace_closest(
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    scene: &Scene,
+    settings: &RenderSettings,
+) -> Option<Intersect> {
+    let intersect = closest_hit(ray_origin, ray_direction, scene, settings, AlphaTest::solid());
+    intersect.is_intersecting.then_some(intersect)
+}
+
+/// Whether anything in the scene blocks the ray before `max_distance`. Stops
+/// at the first blocker found, so it is cheaper than `trace_closest` for
+/// shadow and visibility tests.
+pub fn trace_any(
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    max_distance: f32,
+    scene: &Scene,
+    settings: &RenderSettings,
+) -> bool {
+    let ray = TracerRay::new(*ray_origin, *ray_direction);
+    count_rays(1);
+    scene.intersect_any(&ray, max_distance, settings, |_| true).is_blocked()
+}
+
+/// Fraction of light that gets through along the ray up to `max_distance`:
+/// 1 if nothing is in the way, 0 once an opaque block is. Each transparent
+/// block crossed lets its `transparency` through. Like `trace_any`, it stops
+/// as soon as the answer is known: at the first opaque block, or once less
+/// than `MIN_TRANSMITTANCE` is left. Chunks and clouds block the ray whole.
+pub fn trace_transmittance(
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    max_distance: f32,
+    scene: &Scene,
+    settings: &RenderSettings,
+) -> f32 {
+    transmittance_past(ray_origin, ray_direction, max_distance, None, scene, settings)
+}
+
+/// `trace_transmittance` with the block `emitter` left out, for shadow rays
+/// towards the light it gives off.
+fn transmittance_past(
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    max_distance: f32,
+    emitter: Option<&Cube>,
+    scene: &Scene,
+    settings: &RenderSettings,
+) -> f32 {
+    let ray = TracerRay::new(*ray_origin, *ray_direction);
+    count_rays(1);
+    let mut transmittance = 1.0;
+    let occlusion = scene.intersect_any(&ray, max_distance, settings, |cube| {
+        if is_emitter(cube, emitter) {
+            return false;
+        }
+        transmittance *= cube.material.transparency;
+        transmittance < MIN_TRANSMITTANCE
+    });
+    match occlusion {
+        Occlusion::Blocked => 0.0,
+        Occlusion::Clear => transmittance,
+    }
+}
+
+/// What shading a ray takes besides the ray and its hit: the frame's scene,
+/// sun, textures and settings, and the scratch buffers of the thread tracing
+/// it, which the context holds until it is dropped. Each parallel job of
+/// `render` makes its own.
+pub struct ShadeContext<'a> {
+    /// The blocks and their lights.
+    pub scene: &'a Scene,
+    /// The sun.
+    pub light: &'a Light,
+    /// Block textures and the skybox.
+    pub texture_manager: &'a TextureManager,
+    /// How to shade.
+    pub settings: &'a RenderSettings,
+    scratch: ScratchPool,
+}
+
+impl<'a> ShadeContext<'a> {
+    /// A context for shading on this thread, with the thread's scratch pool.
+    pub fn new(scene: &'a Scene, light: &'a Light, texture_manager: &'a TextureManager, settings: &'a RenderSettings) -> Self {
+        ShadeContext { scene, light, texture_manager, settings, scratch: ScratchPool::take() }
+    }
+}
+
+impl Drop for ShadeContext<'_> {
+    fn drop(&mut self) {
+        std::mem::take(&mut self.scratch).give_back();
+    }
+}
+
+/// Radiance arriving along one ray; `depth` counts the bounces so far.
+pub fn cast_ray(ray_origin: &Vector3, ray_direction: &Vector3, depth: u32, context: &mut ShadeContext) -> Vector3 {
+    let (texture_manager, settings) = (context.texture_manager, context.settings);
+    if depth > settings.max_depth {
+        return texture_manager.sample_skybox(*ray_direction, settings);
+    }
+
+    let intersect = closest_hit(ray_origin, ray_direction, context.scene, settings, AlphaTest::new(texture_manager));
+    shade(ray_origin, ray_direction, &intersect, depth, context)
+}
+
+// Emissive blocks that light each hit, the nearest ones
+const NEAREST_EMISSIVE: usize = 5;
+
+/// Fills `nearest` with the `NEAREST_EMISSIVE` emissive lights closest to
+/// `point`, nearest first, as their squared distance and index in `lights`;
+/// of lights equally far, the first. A light right on the point is its own
+/// block and left out.
+fn nearest_emissive(lights: &[Light], point: &Vector3, nearest: &mut Vec<(f32, usize)>) {
+    nearest.extend(lights.iter().enumerate().filter_map(|(index, light)| {
+        let offset = light.position - *point;
+        let distance_sq = offset.dot(offset);
+        (0.01..f32::INFINITY).contains(&distance_sq).then_some((distance_sq, index))
+    }));
+    let by_distance = |a: &(f32, usize), b: &(f32, usize)| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1));
+    if nearest.len() > NEAREST_EMISSIVE {
+        nearest.select_nth_unstable_by(NEAREST_EMISSIVE - 1, by_distance);
+        nearest.truncate(NEAREST_EMISSIVE);
+    }
+    nearest.sort_unstable_by(by_distance);
+}
+
+// Emissive block shadows of the frame's primary hits, per block face; see
+// `RenderSettings::emissive_shadow_cache`
+static EMISSIVE_SHADOWS: LazyLock<LightCache> = LazyLock::new(LightCache::default);
+
+/// Shadow of emissive light `index` at a hit `depth` bounces deep. With
+/// `RenderSettings::emissive_shadow_cache` on, a primary hit takes it from the
+/// frame's cache, traced once per block face from the face's center, so every
+/// point of the face shares it and it does not matter which thread got there
+/// first. Hits seen in reflections trace their own.
+fn emissive_shadow(
+    intersect: &Intersect,
+    emissive: &Light,
+    to_light: &ToLight,
+    index: usize,
+    depth: u32,
+    scene: &Scene,
+    settings: &RenderSettings,
+) -> f32 {
+    let emitter = Some(&scene.objects[scene.emissive_indices[index]]);
+    if !settings.emissive_shadow_cache || depth > 0 {
+        return cast_shadow(intersect, emissive, to_light, emitter, scene, settings);
+    }
+    let normal = intersect.normal;
+    let components = [normal.x, normal.y, normal.z];
+    let axis = (0..3).max_by(|&a, &b| components[a].abs().total_cmp(&components[b].abs())).unwrap_or(1);
+    let sign = if components[axis] < 0.0 { -1.0 } else { 1.0 };
+    let mut face_normal = [0.0; 3];
+    face_normal[axis] = sign;
+    let face_normal = Vector3::new(face_normal[0], face_normal[1], face_normal[2]);
+    let cell = intersect.point - face_normal * 0.5;
+    let cell = [cell.x.round(), cell.y.round(), cell.z.round()];
+    let key = LightKey {
+        cell: cell.map(|c| c as i32),
+        face: axis as u8 * 2 + u8::from(sign < 0.0),
+        light: index as u32,
+    };
+    EMISSIVE_SHADOWS.get_or_trace(key, || {
+        let mut face = intersect.clone();
+        face.point = Vector3::new(cell[0], cell[1], cell[2]) + face_normal * 0.5;
+        face.normal = face_normal;
+        ToLight::new(&face.point, emissive).map_or(0.0, |to_light| cast_shadow(&face, emissive, &to_light, emitter, scene, settings))
+    })
+}
+
+/// Color of the light of an emissive block of `material`, whose emission gives
+/// `color`: tinted by the average color of the block's texture, so the light
+/// takes the color its faces show, at the same brightness.
+fn emissive_light_color(material: &Material, color: Vector3, texture_manager: &TextureManager) -> Vector3 {
+    let Some(stats) = material.texture.as_deref().and_then(|path| texture_manager.texture_stats(path)) else {
+        return color;
+    };
+    let tinted = color * stats.average;
+    let length = tinted.length();
+    if length > 0.0 {
+        tinted * (color.length() / length)
+    } else {
+        color
+    }
+}
+
+/// Diffuse color at a hit, before any light: the texel of the block's texture
+/// there, or the material's diffuse color, times the biome tint if it has one.
+fn surface_color(intersect: &Intersect, texture_manager: &TextureManager, settings: &RenderSettings) -> Vector3 {
+    // The CPU copy, so headless renders without GPU textures sample the same
+    // texels. Faces without a texture, and the bands a letterboxed one leaves
+    // uncovered, take the diffuse color
+    let texel = intersect.material.texture.as_deref().and_then(|texture_path| {
+        let (u, v) = texture_uv(intersect, texture_path, texture_manager)?;
+        if settings.texture_lod_distance > 0.0 && intersect.distance > settings.texture_lod_distance {
+            if let Some(stats) = texture_manager.texture_stats(texture_path) {
+                return Some(stats.mini_mip_at(u, v));
+            }
+        }
+        let (width, height) = texture_manager.texture_pixels(texture_path).map_or((1, 1), |(w, h, _)| (w, h));
+        let tx = (u * width as f32) as u32; let ty = (v * height as f32) as u32;
+        Some(texture_manager.get_pixel_color(texture_path, tx, ty))
+    });
+    let diffuse_color = texel.unwrap_or(intersect.material.diffuse);
+    match &intersect.material.tint {
+        Some(tint) => {
+            // Sample at the block's cell so a whole face shares one tint
+            let cell = intersect.point - intersect.normal * 0.5;
+            diffuse_color * tint.sample(cell.x.round(), cell.z.round())
+        }
+        None => diffuse_color,
+    }
+}
+
+/// Upper bound on what `light` adds to any channel of `local_lighting` at a
+/// hit: its intensity after falloff, as if unshadowed and shining head on,
+/// times the brightest channel the surface reflects. `diffuse_peak` is the
+/// brightest channel of the hit's diffuse color, times its diffuse albedo.
+fn contribution_bound(light: &Light, to_light: &ToLight, diffuse_peak: f32, material: &Material) -> f32 {
+    let specular_peak = light.color.x.max(light.color.y).max(light.color.z) * material.albedo[1];
+    light.intensity * light.attenuation(to_light.distance) * (diffuse_peak + specular_peak)
+}
+
+/// Direct lighting at a hit `depth` bounces deep (diffuse + specular from the
+/// sun, transient lights and nearby emissive blocks), without emission or
+/// secondary rays. Allocates nothing once the thread's scratch buffers have
+/// grown: the nearest emissive blocks are chosen in one of them.
+fn local_lighting(ray_origin: &Vector3, intersect: &Intersect, depth: u32, context: &mut ShadeContext) -> Vector3 {
+    let ShadeContext { scene, light, texture_manager, settings, .. } = *context;
+    let mut total_diffuse_intensity = 0.0;
+    let mut total_specular = Vector3::zero();
+
+    // Constant over the lights, like the normal
+    let view_direction = (*ray_origin - intersect.point).normalized();
+    let normal = intersect.normal;
+
+    let diffuse_color = surface_color(intersect, texture_manager, settings);
+    // Brightest diffuse channel, for `contribution_bound`
+    let diffuse_peak = diffuse_color.x.max(diffuse_color.y).max(diffuse_color.z) * intersect.material.albedo[0];
+
+    let mut add_light = |current_light: &Light, to_light: &ToLight, shadow_intensity: f32| {
+        // Mirroring a unit vector about the unit normal keeps it unit
+        let reflection_direction = reflect(&-to_light.direction, &normal);
+        let light_intensity = current_light.intensity * current_light.attenuation(to_light.distance) * (1.0 - shadow_intensity);
+
+        total_diffuse_intensity += normal.dot(to_light.direction).max(0.0) * light_intensity;
+
+        let specular_intensity = view_direction.dot(reflection_direction).max(0.0).powf(intersect.material.specular) * light_intensity;
+        total_specular += current_light.color * specular_intensity;
+    };
+
+    // The sun's map, if built, replaces its shadow rays. A light right on
+    // the hit has no direction to light it from and is left out
+    if let Some(to_sun) = ToLight::new(&intersect.point, light) {
+        let sun_shadow = match &scene.sun_shadow {
+            Some(map) if map.casts_for(light) => map.shadow(intersect, light, scene),
+            _ => cast_shadow(intersect, light, &to_sun, None, scene, settings),
+        };
+        add_light(light, &to_sun, sun_shadow);
+    }
+    for transient in &scene.transient_lights {
+        let Some(to_light) = ToLight::new(&intersect.point, transient) else {
+            continue;
+        };
+        add_light(transient, &to_light, cast_shadow(intersect, transient, &to_light, None, scene, settings));
+    }
+    let mut nearest = context.scratch.take_candidates();
+    nearest_emissive(&scene.emissive_lights, &intersect.point, &mut nearest);
+    for &(_, index) in nearest.iter() {
+        let emissive = &scene.emissive_lights[index];
+        // The radius is half the block; the falloff scales with the whole block
+        let mut emissive = emissive.with_falloff(2.0 * emissive.radius * settings.emissive_soft_radius);
+        emissive.color = emissive_light_color(&scene.objects[scene.emissive_indices[index]].material, emissive.color, texture_manager);
+        let Some(to_light) = ToLight::new(&intersect.point, &emissive) else {
+            continue;
+        };
+        // Too faint to show even unshadowed: skip it and its shadow ray
+        if contribution_bound(&emissive, &to_light, diffuse_peak, &intersect.material) < settings.emissive_cull_epsilon {
+            continue;
+        }
+        add_light(&emissive, &to_light, emissive_shadow(intersect, &emissive, &to_light, index, depth, scene, settings));
+    }
+    context.scratch.give_back_candidates(nearest);
+
+    let diffuse = diffuse_color * total_diffuse_intensity;
+    diffuse * intersect.material.albedo[0] + total_specular * intersect.material.albedo[1]
+}
+
+// Microfacet samples per lobe where a camera ray hits a rough surface. Deeper
+// bounces take the mirror and straight-through directions, so a rough glass
+// pane behind another does not multiply the ray count again
+const ROUGH_SAMPLES: u32 = 4;
+
+// Mirror hits `shade` follows in a loop before the rest of the path recurses
+const MIRROR_CHAIN: usize = 16;
+
+/// Whether a hit at `depth` only reflects: an opaque surface whose secondary
+/// light all comes from the one mirror direction.
+fn reflects_only(intersect: &Intersect, depth: u32) -> bool {
+    let material = &intersect.material;
+    material.reflectivity > 0.0 && material.transparency <= 0.0 && (material.roughness <= 0.0 || depth > 0)
+}
+
+/// Shades a hit (or the sky if `intersect` missed), spawning secondary rays.
+///
+/// Hits that only reflect are followed in a loop rather than by recursion:
+/// each one keeps its own light and the weight its reflection carries, until
+/// the path reaches the sky, runs out of depth or bounce budget, or hits a
+/// surface that also refracts, which `shade_surface` shades with real
+/// recursion. The chain is then added up from the far end, in the order the
+/// recursion adds it, so a mirror chain comes out the same to the bit without
+/// a stack frame per bounce.
+fn shade(ray_origin: &Vector3, ray_direction: &Vector3, intersect: &Intersect, depth: u32, context: &mut ShadeContext) -> Vector3 {
+    let ShadeContext { scene, texture_manager, settings, .. } = *context;
+    let mut chain = [(Vector3::zero(), 0.0f32); MIRROR_CHAIN];
+    let mut links = 0;
+    let (mut origin, mut direction, mut intersect, mut depth) = (*ray_origin, *ray_direction, intersect, depth);
+    let mut next_hit;
+    let far = loop {
+        if !intersect.is_intersecting {
+            break texture_manager.sample_skybox(direction, settings);
+        }
+        if links == MIRROR_CHAIN || !reflects_only(intersect, depth) {
+            break shade_surface(&origin, &direction, intersect, depth, context);
+        }
+        let material = &intersect.material;
+        let local = local_lighting(&origin, intersect, depth, context);
+        chain[links] = (material.emission + local * local_weight(material, settings), material.reflectivity);
+        links += 1;
+
+        // `bounce` and `cast_ray`, unrolled
+        let reflected = reflect(&direction, &intersect.normal);
+        if !take_bounce() {
+            break texture_manager.sample_skybox(reflected, settings);
+        }
+        profile::count(Counter::SecondaryRays, 1);
+        origin = offset_origin(intersect, &reflected);
+        direction = reflected;
+        depth += 1;
+        if depth > settings.max_depth {
+            break texture_manager.sample_skybox(direction, settings);
+        }
+        next_hit = closest_hit(&origin, &direction, scene, settings, AlphaTest::new(texture_manager));
+        intersect = &next_hit;
+    };
+    chain[..links].iter().rev().fold(far, |color, &(own, weight)| own + color * weight)
+}
+
+/// Share of the direct lighting a surface of `material` keeps; see
+/// `RenderSettings::energy_conserving`.
+fn local_weight(material: &Material, settings: &RenderSettings) -> f32 {
+    if settings.energy_conserving {
+        (1.0 - material.reflectivity - material.transparency).max(0.0)
+    } else {
+        1.0
+    }
+}
+
+/// Shades a hit with every lobe its material has, recursing through `bounce`
+/// for each secondary ray.
+fn shade_surface(ray_origin: &Vector3, ray_direction: &Vector3, intersect: &Intersect, depth: u32, context: &mut ShadeContext) -> Vector3 {
+    if let Some(beyond) = continues_medium(intersect, ray_direction, context.scene) {
+        return cast_ray(&beyond, ray_direction, depth, context);
+    }
+    let emission = intersect.material.emission;
+    let local = local_lighting(ray_origin, intersect, depth, context);
+    let (reflection_color, refraction_color) = surface_lobes(ray_direction, intersect, depth, context);
+
+    let color = emission +
+                local * local_weight(&intersect.material, context.settings) +
+                reflection_color +
+                refraction_color;
+    color
+}
+
+/// The reflected and the refracted light of a hit, each averaged over its
+/// microfacet samples.
+fn surface_lobes(ray_direction: &Vector3, intersect: &Intersect, depth: u32, context: &mut ShadeContext) -> (Vector3, Vector3) {
+    let transparency = intersect.material.transparency;
+    let normal = intersect.normal;
+
+    // Microfacet samples both lobes take: the face normal on a polished
+    // surface, GGX samples of the roughness on a rough one. Frosted glass
+    // refracts through facets of its own roughness at the same sample points,
+    // so at equal roughness both lobes use the same facets
+    let roughness = intersect.material.roughness;
+    let refraction_roughness = match intersect.material.refraction_roughness {
+        frosted if frosted > 0.0 && transparency > 0.0 => frosted,
+        _ => roughness,
+    };
+    let rough = depth == 0 && (roughness > 0.0 || refraction_roughness > 0.0);
+    let samples = if rough { ROUGH_SAMPLES } else { 1 };
+
+    // Of the transparent share, the Fresnel term of each facet is reflected and
+    // the rest refracted, from whichever side the ray hits: glancing off water
+    // from above mostly reflects, and from below, past the critical angle, all
+    // of it does. Each lobe averages its samples, so roughness spreads the
+    // reflected and transmitted light without changing how much of each there is
+    let mut reflection_color = Vector3::zero();
+    let mut refraction_color = Vector3::zero();
+    let reflectivity = intersect.material.reflectivity;
+    let refractive_index = ray_refractive_index(&intersect.material);
+    // A camera ray refracted into a dispersive material goes on as one ray per
+    // channel; the split between the lobes stays that of the material's index
+    let dispersion = (depth == 0 && intersect.material.abbe_number > 0.0)
+        .then(|| rgb_iors(intersect.material.refractive_index, intersect.material.abbe_number));
+    for i in 0..samples {
+        let sample = lattice_point(i, ROUGH_SAMPLES);
+        let facet = &if rough && roughness > 0.0 { ggx_microfacet(&normal, roughness, sample.0, sample.1) } else { normal };
+        let mut refract_weight = 0.0;
+        let mut refract_direction = None;
+        let mut fresnel_weight = 0.0;
+        if transparency > 0.0 {
+            let (eta_i, eta_t) = media(ray_direction, &normal, refractive_index);
+            let refraction_roughness = if rough { refraction_roughness } else { 0.0 };
+            // A grazing hit can round to a near-zero or NaN direction; like total
+            // internal reflection, all of the light reflects then. The Fresnel
+            // term is that of the facet the ray refracted through
+            let refracted = refract_rough(ray_direction, &normal, eta_i, eta_t, refraction_roughness, sample)
+                .filter(|(direction, _)| is_valid_direction(direction));
+            let reflected = match refracted {
+                Some((_, refract_facet)) => fresnel_dielectric(ray_direction.dot(refract_facet), eta_i, eta_t),
+                None => 1.0,
+            };
+            refract_direction = refracted.map(|(direction, _)| direction);
+            fresnel_weight = transparency * reflected;
+            refract_weight = transparency - fresnel_weight;
+        }
+
+        let reflect_weight = reflectivity + fresnel_weight;
+        if reflect_weight > 0.0 {
+            let mut reflect_direction = reflect(ray_direction, facet);
+            // A facet tilted far enough reflects into the surface; mirror instead
+            if reflect_direction.dot(normal) * ray_direction.dot(normal) > 0.0 {
+                reflect_direction = reflect(ray_direction, &normal);
+            }
+            reflection_color += bounce(intersect, &reflect_direction, depth, context) * reflect_weight;
+        }
+        if let Some(refract_direction) = refract_direction.filter(|_| refract_weight > 0.0) {
+            let refracted = match dispersion {
+                Some(iors) => refract_channels(ray_direction, intersect, iors, refraction_roughness, sample, context),
+                None => bounce(intersect, &refract_direction, depth, context),
+            };
+            refraction_color += refracted * refract_weight;
+        }
+    }
+    reflection_color /= samples as f32;
+    refraction_color /= samples as f32;
+    (reflection_color, refraction_color)
+}
+
+/// What a camera ray refracted into a dispersive material brings: each channel
+/// refracts by its own index in `iors`, through the facet `sample` picks with
+/// `roughness`, and is traced on its own, so every dispersive surface further
+/// along bends it by that index too. A channel past its critical angle brings
+/// nothing.
+fn refract_channels(
+    ray_direction: &Vector3,
+    intersect: &Intersect,
+    iors: [f32; 3],
+    roughness: f32,
+    sample: (f32, f32),
+    context: &mut ShadeContext,
+) -> Vector3 {
+    let mut color = [0.0; 3];
+    for (channel, ior) in iors.into_iter().enumerate() {
+        let (eta_i, eta_t) = media(ray_direction, &intersect.normal, ior);
+        let Some((direction, _)) = refract_rough(ray_direction, &intersect.normal, eta_i, eta_t, roughness, sample)
+            .filter(|(direction, _)| is_valid_direction(direction))
+        else {
+            continue;
+        };
+        let previous = DISPERSION_CHANNEL.with(|split| split.replace(Some(channel)));
+        let traced = bounce(intersect, &direction, 0, context);
+        DISPERSION_CHANNEL.with(|split| split.set(previous));
+        color[channel] = [traced.x, traced.y, traced.z][channel];
+    }
+    Vector3::new(color[0], color[1], color[2])
+}
+
+/// Key of the material whose reflection and refraction rays a camera hit
+/// spawns, for `SecondaryLight::material`: 0 for the sky, for a hit that
+/// spawns none and for a face the ray goes straight on through.
+fn lobe_key(intersect: &Intersect, ray_direction: &Vector3, scene: &Scene) -> usize {
+    let material = &intersect.material;
+    if !intersect.is_intersecting
+        || (material.reflectivity <= 0.0 && material.transparency <= 0.0)
+        || continues_medium(intersect, ray_direction, scene).is_some()
+    {
+        return 0;
+    }
+    Arc::as_ptr(material) as usize
+}
+
+/// What `shade` gives a camera ray's hit without its reflection and refraction
+/// rays: emission and direct lighting, the sky, or whatever lies beyond a face
+/// the ray goes straight on through.
+fn shade_own(ray_origin: &Vector3, ray_direction: &Vector3, intersect: &Intersect, context: &mut ShadeContext) -> Vector3 {
+    if !intersect.is_intersecting {
+        return context.texture_manager.sample_skybox(*ray_direction, context.settings);
+    }
+    if let Some(beyond) = continues_medium(intersect, ray_direction, context.scene) {
+        return cast_ray(&beyond, ray_direction, 0, context);
+    }
+    let local = local_lighting(ray_origin, intersect, 0, context);
+    intersect.material.emission + local * local_weight(&intersect.material, context.settings)
+}
+
+/// What the reflection and refraction rays of a camera ray's hit bring, which
+/// `shade` adds to `shade_own`; only for hits with a `lobe_key`.
+fn shade_lobes(ray_direction: &Vector3, intersect: &Intersect, context: &mut ShadeContext) -> Vector3 {
+    if reflects_only(intersect, 0) {
+        let reflected = reflect(ray_direction, &intersect.normal);
+        return bounce(intersect, &reflected, 0, context) * intersect.material.reflectivity;
+    }
+    let (reflection_color, refraction_color) = surface_lobes(ray_direction, intersect, 0, context);
+    reflection_color + refraction_color
+}
+
+/// Radiance along a reflected or refracted ray leaving `intersect`, or the sky
+/// in that direction once the camera ray's bounce budget is spent.
+fn bounce(intersect: &Intersect, direction: &Vector3, depth: u32, context: &mut ShadeContext) -> Vector3 {
+    if !take_bounce() {
+        return context.texture_manager.sample_skybox(*direction, context.settings);
+    }
+    profile::count(Counter::SecondaryRays, 1);
+    let origin = offset_origin(intersect, direction);
+    cast_ray(&origin, direction, depth + 1, context)
+}
+
+/// Depth weight from McGuire and Bavoil's weighted-blended OIT: nearer surfaces
+/// dominate the average without the hits having to be sorted.
+fn oit_weight(distance: f32, alpha: f32) -> f32 {
+    let z = distance / 5.0;
+    alpha * (10.0 / (1e-5 + z * z + (distance / 200.0).powi(6))).clamp(1e-2, 3e3)
+}
+
+/// Primary ray for `TransparencyMode::WeightedBlended`. The opaque hit is shaded
+/// as usual; every transparent hit in front of it only gets direct lighting and
+/// is blended over it by coverage (`1 - transparency`) and depth weight.
+/// Returns the color and the opaque hit. `tracing` is the trace stage's timer,
+/// stopped once the hits are found.
+fn trace_weighted_blended(
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    alpha: AlphaTest,
+    tracing: Option<Instant>,
+    context: &mut ShadeContext,
+) -> (Vector3, Intersect) {
+    let mut transparent = context.scratch.take_intersects();
+    let traced = weighted_blended_into(&mut transparent, ray_origin, ray_direction, alpha, tracing, context);
+    context.scratch.give_back_intersects(transparent);
+    traced
+}
+
+// `trace_weighted_blended` with the transparent hits gathered into `transparent`
+fn weighted_blended_into(
+    transparent: &mut Vec<Intersect>,
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    alpha: AlphaTest,
+    tracing: Option<Instant>,
+    context: &mut ShadeContext,
+) -> (Vector3, Intersect) {
+    let ShadeContext { scene, settings, .. } = *context;
+    let ray = TracerRay::new(*ray_origin, *ray_direction);
+    let mut opaque: Option<(&Cube, Intersect)> = None;
+    count_rays(1);
+    let _ = scene.visit(&ray, settings, |object| {
+        let hit = object.intersect(&ray);
+        if !hit.is_intersecting || !alpha.passes(&hit) {
+            return ControlFlow::Continue(());
+        }
+        if hit.material.transparency > 0.0 {
+            transparent.push(hit);
+        } else if opaque.as_ref().is_none_or(|(cube, best)| compare_hits((object, &hit), (cube, best), ray_direction).is_lt()) {
+            opaque = Some((object, hit));
+        }
+        ControlFlow::Continue(())
+    });
+    let opaque = opaque.map_or_else(Intersect::empty, |(_, hit)| hit);
+    let opaque = scene.with_dynamic_hits(ray_origin, ray_direction, opaque);
+    profile::stop(Stage::Trace, tracing);
+    let shading = profile::start();
+    let opaque_distance = if opaque.is_intersecting { opaque.distance } else { f32::INFINITY };
+    let background = shade(ray_origin, ray_direction, &opaque, 0, context);
+
+    let mut accumulated = Vector3::zero();
+    let mut accumulated_weight = 0.0;
+    let mut revealage = 1.0;
+    for hit in transparent.iter().filter(|hit| hit.distance < opaque_distance) {
+        let alpha = (1.0 - hit.material.transparency).clamp(0.0, 1.0);
+        let color = hit.material.emission + local_lighting(ray_origin, hit, 0, context);
+        let weight = oit_weight(hit.distance, alpha);
+        accumulated += color * weight;
+        accumulated_weight += weight;
+        revealage *= 1.0 - alpha;
+    }
+    let color = if accumulated_weight > 0.0 {
+        let average = accumulated / accumulated_weight;
+        average * (1.0 - revealage) + background * revealage
+    } else {
+        background
+    };
+    profile::stop(Stage::Shade, shading);
+    (color, opaque)
+}
+
+/// Where the ray goes on from if `intersect` is a face between two transparent
+/// blocks of the same medium (one water block into the next): that crosses
+/// no real surface, so the ray goes straight on. The hit can be the exit face
+/// of the block the ray is in or the coplanar entry face of the next one,
+/// which `compare_hits` puts first, so both sides of the face are checked.
+fn continues_medium(intersect: &Intersect, ray_direction: &Vector3, scene: &Scene) -> Option<Vector3> {
+    let material = &intersect.material;
+    if material.transparency <= 0.0 {
+        return None;
+    }
+    let same_medium = |point: &Vector3| {
+        scene.cube_containing(point).is_some_and(|cube| {
+            cube.material.transparency > 0.0 && cube.material.refractive_index == material.refractive_index
+        })
+    };
+    let beyond = offset_origin(intersect, ray_direction);
+    let behind = intersect.point * 2.0 - beyond;
+    (same_medium(&behind) && same_medium(&beyond)).then_some(beyond)
+}
+
+/// What the camera's eye is in, which decides how primary rays start.
+#[derive(Clone, Copy)]
+pub enum EyeMedium<'a> {
+    /// Open air: primary rays are traced as usual.
+    Air,
+    /// An opaque block: the view is the inside of its faces.
+    Embedded(&'a Cube),
+    /// Glass or water: primary rays are traced out of it and dimmed by what
+    /// it absorbs on the way.
+    Submerged(&'a Cube),
+}
+
+/// The block around `eye`, if any, and whether light passes through it.
+pub fn eye_medium<'a>(scene: &'a Scene, eye: &Vector3) -> EyeMedium<'a> {
+    match scene.cube_containing(eye) {
+        None => EyeMedium::Air,
+        Some(cube) if cube.material.transparency > 0.0 => EyeMedium::Submerged(cube),
+        Some(cube) => EyeMedium::Embedded(cube),
+    }
+}
+
+// Absorption of a block with no transparency and a black diffuse color, per
+// block travelled
+const ABSORPTION_PER_BLOCK: f32 = 1.0;
+// Same-medium blocks a ray from a submerged eye is followed through
+const MAX_MEDIUM_BLOCKS: usize = 64;
+
+/// Beer-Lambert absorption over `distance` inside `medium`. Each channel is
+/// absorbed by what the material lets through neither as transparency nor as
+/// its diffuse color, so water fades to blue and clear glass barely at all.
+fn absorb(color: Vector3, distance: f32, medium: &Material) -> Vector3 {
+    let opacity = (1.0 - medium.transparency).clamp(0.0, 1.0) * ABSORPTION_PER_BLOCK * distance;
+    let diffuse = medium.diffuse;
+    Vector3::new(
+        color.x * (-opacity * (1.0 - diffuse.x.clamp(0.0, 1.0))).exp(),
+        color.y * (-opacity * (1.0 - diffuse.y.clamp(0.0, 1.0))).exp(),
+        color.z * (-opacity * (1.0 - diffuse.z.clamp(0.0, 1.0))).exp(),
+    )
+}
+
+/// First real surface of a ray that starts inside a transparent block, past
+/// the boundaries between blocks of the same medium, with its distance
+/// measured from `ray_origin`.
+fn leave_medium(
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    scene: &Scene,
+    settings: &RenderSettings,
+    alpha: AlphaTest,
+) -> Intersect {
+    let mut origin = *ray_origin;
+    for _ in 0..MAX_MEDIUM_BLOCKS {
+        let intersect = closest_hit(&origin, ray_direction, scene, settings, alpha);
+        if !intersect.is_intersecting {
+            return intersect;
+        }
+        match continues_medium(&intersect, ray_direction, scene) {
+            Some(beyond) => origin = beyond,
+            // From the eye itself, so the bias past each boundary is not lost
+            None => return Intersect { distance: (intersect.point - *ray_origin).length(), ..intersect },
+        }
+    }
+    closest_hit(&origin, ray_direction, scene, settings, alpha)
+}
+
+/// The face of `cube` a ray from inside it reaches, turned to face the ray.
+fn inner_face(cube: &Cube, ray_origin: &Vector3, ray_direction: &Vector3) -> Intersect {
+    let mut intersect = cube.ray_intersect(ray_origin, ray_direction);
+    intersect.normal = -intersect.normal;
+    intersect
+}
+
+// Distance used for fogging rays that escape to the sky
+const FOG_SKY_DISTANCE: f32 = 60.0;
+
+fn apply_fog(color: Vector3, distance: f32, settings: &RenderSettings) -> Vector3 {
+    if settings.fog_density <= 0.0 {
+        return color;
+    }
+    let amount = fog_amount(distance, settings);
+    color * (1.0 - amount) + settings.fog_color * amount
+}
+
+/// How much of the fog color replaces what lies `distance` away.
+fn fog_amount(distance: f32, settings: &RenderSettings) -> f32 {
+    if settings.fog_density <= 0.0 {
+        return 0.0;
+    }
+    1.0 - (-settings.fog_density * distance.min(FOG_SKY_DISTANCE)).exp()
+}
+
+static DEGENERATE_RAY_WARNING: Once = Once::new();
+
+/// Replaces a zero-length or NaN ray direction with `fallback`, warning once.
+fn sanitize_direction(direction: Vector3, fallback: Vector3) -> Vector3 {
+    if is_valid_direction(&direction) {
+        return direction;
+    }
+    DEGENERATE_RAY_WARNING.call_once(|| {
+        warn!("Degenerate ray direction {:?}, using camera forward instead", direction);
+    });
+    fallback
+}
+
+/// One primary ray through the full pipeline: the fogged color and the hit,
+/// the opaque one under `TransparencyMode::WeightedBlended`.
+fn trace_primary(
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    alpha: AlphaTest,
+    medium: EyeMedium,
+    context: &mut ShadeContext,
+) -> (Vector3, Intersect) {
+    let ShadeContext { scene, settings, .. } = *context;
+    start_camera_ray(settings);
+    profile::count(Counter::PrimaryRays, 1);
+    let tracing = profile::start();
+    let intersect = match medium {
+        EyeMedium::Air if settings.transparency == TransparencyMode::WeightedBlended => {
+            let (shaded, opaque) = trace_weighted_blended(ray_origin, ray_direction, alpha, tracing, context);
+            return (apply_fog(shaded, hit_distance(&opaque), settings), opaque);
+        }
+        EyeMedium::Air => closest_hit(ray_origin, ray_direction, scene, settings, alpha),
+        EyeMedium::Embedded(cube) => inner_face(cube, ray_origin, ray_direction),
+        EyeMedium::Submerged(_) => leave_medium(ray_origin, ray_direction, scene, settings, alpha),
+    };
+    profile::stop(Stage::Trace, tracing);
+
+    let shading = profile::start();
+    let hit_distance = hit_distance(&intersect);
+    let mut shaded = shade(ray_origin, ray_direction, &intersect, 0, context);
+    if let EyeMedium::Submerged(cube) = medium {
+        shaded = absorb(shaded, hit_distance, &cube.material);
+    }
+    let color = apply_fog(shaded, hit_distance, settings);
+    profile::stop(Stage::Shade, shading);
+    (color, intersect)
+}
+
+/// Distance to a camera ray's hit; infinite for the sky.
+fn hit_distance(intersect: &Intersect) -> f32 {
+    if intersect.is_intersecting { intersect.distance } else { f32::INFINITY }
+}
+
+/// Ray counts and pass times from the last `render`, to compare edge-only
+/// supersampling with supersampling every pixel at the same sample count.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderStats {
+    /// Pixels the edge pass supersampled.
+    pub edge_pixels: usize,
+    /// Camera rays traced in both passes.
+    pub primary_rays: u64,
+    /// What supersampling every pixel would have cost.
+    pub uniform_rays: u64,
+    /// Time spent in the one-ray-per-pixel pass.
+    pub primary_time: Duration,
+    /// Time spent supersampling edges.
+    pub edge_time: Duration,
+    /// Every ray traced: camera, shadow, reflection and refraction.
+    pub rays_traced: u64,
+    /// Camera rays that used up `RenderSettings::bounce_budget`.
+    pub budgets_spent: u64,
+    /// Ray counts and stage times while `RenderSettings::profile` is on.
+    pub profile: Option<Profile>,
+    /// Lookups of the emissive shadow cache, while it is on.
+    pub emissive_cache: Option<CacheStats>,
+    /// Pixels that traced their own reflection and refraction rays under
+    /// `RenderSettings::half_res_secondary`, for want of a neighbour on the
+    /// same surface to take them from.
+    pub secondary_fallbacks: usize,
+}
+
+// Camera-space primary ray directions of the last frame size rendered; kept
+// until the resolution or field of view changes
+static RAY_GEN_CACHE: Mutex<Option<Arc<RayGenCache>>> = Mutex::new(None);
+
+/// Side of the square tiles `Schedule::CenterOut` hands out, a whole number of packets.
+const TILE_SIZE: i32 = 4 * PACKET_SIZE as i32;
+
+/// Top-left corners of the `TILE_SIZE` tiles covering the frame, the ones
+/// whose centers are nearest the frame's center first.
+fn center_out_tiles(width: i32, height: i32) -> Vec<(i32, i32)> {
+    let mut tiles: Vec<(i32, i32)> =
+        (0..height).step_by(TILE_SIZE as usize).flat_map(|y| (0..width).step_by(TILE_SIZE as usize).map(move |x| (x, y))).collect();
+    // Twice the offset from the middle, to stay in integers
+    let offset = |(x, y): &(i32, i32)| {
+        let (dx, dy) = (2 * x + TILE_SIZE - width, 2 * y + TILE_SIZE - height);
+        dx as i64 * dx as i64 + dy as i64 * dy as i64
+    };
+    tiles.sort_by_key(offset);
+    tiles
+}
+
+/// What the current thread had counted when a parallel job started, to add
+/// what the job counted to the frame's totals once it is done.
+struct JobStart {
+    rays: u64,
+    budgets_spent: u64,
+    profile: Snapshot,
+}
+
+impl JobStart {
+    fn here() -> Self {
+        JobStart { rays: rays_traced_here(), budgets_spent: budgets_spent_here(), profile: profile::snapshot() }
+    }
+
+    fn finish(self, rays: &AtomicU64, budgets_spent: &AtomicU64, totals: &Totals) {
+        rays.fetch_add(rays_traced_here() - self.rays, Ordering::Relaxed);
+        budgets_spent.fetch_add(budgets_spent_here() - self.budgets_spent, Ordering::Relaxed);
+        totals.add_since(&self.profile);
+    }
+}
+
+/// The reflection and refraction light of a camera ray's hit under
+/// `RenderSettings::half_res_secondary`, kept apart from its color until the
+/// frame is traced.
+#[derive(Debug, Clone, Copy, Default)]
+struct SecondaryLight {
+    /// `lobe_key` of the hit: neighbours only share the light between pixels
+    /// of the same material. 0 where there is none to add.
+    material: usize,
+    /// The light, through the fog; None where it is left to the neighbours.
+    light: Option<Vector3>,
+}
+
+/// What a `GBuffer` keeps of a camera ray's hit besides its normal; left at
+/// zero when no G-buffer is being filled.
+#[derive(Debug, Clone, Copy, Default)]
+struct Surface {
+    albedo: Vector3,
+    object_id: u32,
+}
+
+/// Id of the block `intersect` hit: a hash of its cell, never 0, so every
+/// pixel on one block gets the same id and neighbouring blocks almost surely
+/// different ones. Blocks larger than a cell get one id per cell they span.
+fn object_id(intersect: &Intersect) -> u32 {
+    let cell = intersect.point - intersect.normal * 0.5;
+    let [x, y, z] = [cell.x, cell.y, cell.z].map(|c| c.round() as i32 as u32);
+    (x.wrapping_mul(0x9E37_79B1) ^ y.wrapping_mul(0x85EB_CA77) ^ z.wrapping_mul(0xC2B2_AE3D)).max(1)
+}
+
+/// One camera ray: fogged color, hit distance, normal, under
+/// `RenderSettings::half_res_secondary` the secondary light the color leaves
+/// out, and the rest of its G-buffer entry.
+type Traced = (Vector3, f32, Vector3, SecondaryLight, Surface);
+
+/// The secondary light at (x, y) from the pixels that traced it, at the corners
+/// of the 2x2 quads around it: averaged bilinearly over those on the same
+/// surface, which have its material and its depth and normal within the edge
+/// pass's thresholds. None if no such pixel is near.
+fn upsampled_secondary(
+    secondary: &[SecondaryLight],
+    depth: &[f32],
+    normals: &[Vector3],
+    width: i32,
+    height: i32,
+    x: i32,
+    y: i32,
+) -> Option<Vector3> {
+    let here = (y * width + x) as usize;
+    let (x0, y0) = (x & !1, y & !1);
+    let mut sum = Vector3::zero();
+    let mut total = 0.0;
+    for ay in [y0, y0 + 2] {
+        for ax in [x0, x0 + 2] {
+            let weight = (1.0 - (x - ax).abs() as f32 / 2.0) * (1.0 - (y - ay).abs() as f32 / 2.0);
+            if ax >= width || ay >= height || weight <= 0.0 {
+                continue;
+            }
+            let anchor = (ay * width + ax) as usize;
+            let Some(light) = secondary[anchor].light else {
+                continue;
+            };
+            if secondary[anchor].material == secondary[here].material
+                && same_surface(depth[here], depth[anchor], &normals[here], &normals[anchor])
+            {
+                sum += light * weight;
+                total += weight;
+            }
+        }
+    }
+    (total > 0.0).then(|| sum / total)
+}
+
+/// Buffers a thread reuses from one packet to the next.
+#[derive(Default)]
+struct PacketScratch {
+    directions: Vec<Vector3>,
+    candidates: Vec<(usize, u32)>,
+}
+
+/// What every job of the primary pass shares, whichever way the frame is split.
+struct PrimaryPass<'a> {
+    scene: &'a Scene,
+    camera: &'a Camera,
+    light: &'a Light,
+    texture_manager: &'a TextureManager,
+    settings: &'a RenderSettings,
+    ray_gen: &'a RayGenCache,
+    medium: EyeMedium<'a>,
+    packets: bool,
+    // Secondary light kept apart and traced for one pixel of each 2x2 quad
+    split: bool,
+    // Albedo and object id worked out for a G-buffer
+    gbuffer: bool,
+}
+
+impl PrimaryPass<'_> {
+    /// The G-buffer entry of a camera ray's hit, if one is being filled.
+    fn surface(&self, intersect: &Intersect) -> Surface {
+        if !self.gbuffer || !intersect.is_intersecting {
+            return Surface::default();
+        }
+        let albedo = surface_color(intersect, self.texture_manager, self.settings) * intersect.material.albedo[0];
+        Surface { albedo, object_id: object_id(intersect) }
+    }
+
+    /// A context to shade this pass's rays with on the current thread.
+    fn context(&self) -> ShadeContext<'_> {
+        ShadeContext::new(self.scene, self.light, self.texture_manager, self.settings)
+    }
+
+    /// Traces the `width` x `height` pixels from (`x0`, `y0`) in square
+    /// packets, handing each pixel's `Traced` to `put` with its coordinates in
+    /// the frame. `y0` must be a multiple of
+    /// `PACKET_SIZE` and `x0` too, so the packets are the same however the
+    /// frame is split.
+    #[allow(clippy::too_many_arguments)]
+    fn trace_tile(
+        &self,
+        x0: i32,
+        y0: i32,
+        width: i32,
+        height: i32,
+        scratch: &mut PacketScratch,
+        context: &mut ShadeContext,
+        mut put: impl FnMut(i32, i32, Traced),
+    ) {
+        let packet = PACKET_SIZE as i32;
+        for py in (y0..y0 + height).step_by(PACKET_SIZE) {
+            for px in (x0..x0 + width).step_by(PACKET_SIZE) {
+                let packet_width = packet.min(x0 + width - px);
+                let packet_height = packet.min(y0 + height - py);
+                self.trace_packet(px, py, packet_width, packet_height, scratch, context, &mut put);
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn trace_packet(
+        &self,
+        x0: i32,
+        y0: i32,
+        packet_width: i32,
+        packet_height: i32,
+        scratch: &mut PacketScratch,
+        context: &mut ShadeContext,
+        put: &mut impl FnMut(i32, i32, Traced),
+    ) {
+        let PrimaryPass { scene, camera, texture_manager, settings, .. } = *self;
+        let camera_eye = camera.eye;
+        let directions = &mut scratch.directions;
+        let generating = profile::start();
+        directions.clear();
+        #[cfg(not(feature = "simd"))]
+        for dy in 0..packet_height {
+            for dx in 0..packet_width {
+                directions.push(sanitize_direction(self.ray_gen.direction(camera, x0 + dx, y0 + dy), camera.forward));
+            }
+        }
+        #[cfg(feature = "simd")]
+        {
+            directions.resize((packet_height * packet_width) as usize, Vector3::zero());
+            for (dy, row) in directions.chunks_mut(packet_width as usize).enumerate() {
+                crate::simd::rotate_to_world(camera, self.ray_gen.camera_space_row(x0, y0 + dy as i32, packet_width), row);
+            }
+            for direction in directions.iter_mut() {
+                *direction = sanitize_direction(*direction, camera.forward);
+            }
+        }
+        profile::stop(Stage::RayGen, generating);
+
+        if self.packets {
+            let tracing = profile::start();
+            traverse_packet(&scene.bvh, &camera_eye, directions, &mut scratch.candidates);
+            profile::stop(Stage::Trace, tracing);
+            count_rays(directions.len() as u64);
+            profile::count(Counter::PrimaryRays, directions.len() as u64);
+        }
+
+        for (lane, direction) in directions.iter().enumerate() {
+            let (x, y) = (x0 + lane as i32 % packet_width, y0 + lane as i32 / packet_width);
+            let traced = if self.packets || self.split {
+                let tracing = profile::start();
+                let intersect = if self.packets {
+                    let bit = 1u32 << lane;
+                    let lane_candidates = scratch
+                        .candidates
+                        .iter()
+                        .filter(|(_, mask)| mask & bit != 0)
+                        .map(|(shape_index, _)| *shape_index);
+                    let ray = TracerRay::new(camera_eye, *direction);
+                    let alpha = AlphaTest::new(texture_manager);
+                    scene
+                        .intersect_nearest_among(&ray, f32::INFINITY, lane_candidates, |cube, slab| alpha.passes_slab(cube, &ray, slab))
+                        .unwrap_or_else(Intersect::empty)
+                } else {
+                    profile::count(Counter::PrimaryRays, 1);
+                    closest_hit(&camera_eye, direction, scene, settings, AlphaTest::new(texture_manager))
+                };
+                profile::stop(Stage::Trace, tracing);
+                let shading = profile::start();
+                let hit_distance = hit_distance(&intersect);
+                let surface = self.surface(&intersect);
+                start_camera_ray(settings);
+                let traced = if self.split {
+                    let material = lobe_key(&intersect, direction, scene);
+                    let own = shade_own(&camera_eye, direction, &intersect, context);
+                    // The top-left pixel of each 2x2 quad traces the secondary rays
+                    let light = (material != 0 && x % 2 == 0 && y % 2 == 0)
+                        .then(|| shade_lobes(direction, &intersect, context) * (1.0 - fog_amount(hit_distance, settings)));
+                    (apply_fog(own, hit_distance, settings), hit_distance, intersect.normal, SecondaryLight { material, light }, surface)
+                } else {
+                    let shaded = shade(&camera_eye, direction, &intersect, 0, context);
+                    (apply_fog(shaded, hit_distance, settings), hit_distance, intersect.normal, SecondaryLight::default(), surface)
+                };
+                profile::stop(Stage::Shade, shading);
+                traced
+            } else {
+                let (color, intersect) = trace_primary(&camera_eye, direction, AlphaTest::new(texture_manager), self.medium, context);
+                (color, hit_distance(&intersect), intersect.normal, SecondaryLight::default(), self.surface(&intersect))
+            };
+            put(x, y, traced);
+        }
+    }
+
+    /// The secondary light of pixel (x, y), traced again from its camera ray
+    /// for a pixel no neighbour could lend it to; zero if the ray now lands on
+    /// a hit without any.
+    fn trace_secondary(&self, x: i32, y: i32, context: &mut ShadeContext) -> Vector3 {
+        let PrimaryPass { scene, camera, texture_manager, settings, .. } = *self;
+        let direction = sanitize_direction(self.ray_gen.direction(camera, x, y), camera.forward);
+        let tracing = profile::start();
+        profile::count(Counter::PrimaryRays, 1);
+        let intersect = closest_hit(&camera.eye, &direction, scene, settings, AlphaTest::new(texture_manager));
+        profile::stop(Stage::Trace, tracing);
+        if lobe_key(&intersect, &direction, scene) == 0 {
+            return Vector3::zero();
+        }
+        let shading = profile::start();
+        start_camera_ray(settings);
+        let lobes = shade_lobes(&direction, &intersect, context) * (1.0 - fog_amount(intersect.distance, settings));
+        profile::stop(Stage::Shade, shading);
+        lobes
+    }
+}
+
+/// The cached primary ray directions for `camera` at `width` x `height`,
+/// computed again only if they no longer fit.
+fn ray_gen_cache(camera: &Camera, width: i32, height: i32) -> Arc<RayGenCache> {
+    let mut cached = RAY_GEN_CACHE.lock().expect("ray generation cache lock");
+    match cached.as_ref() {
+        Some(cache) if cache.fits(camera, width, height) => Arc::clone(cache),
+        _ => Arc::clone(cached.insert(Arc::new(RayGenCache::new(camera, width, height)))),
+    }
+}
+
+/// Called from the render threads with each block of the primary pass as soon
+/// as it is traced: where it lies in the frame and its colors, by rows. They
+/// lack the edge pass and, under `RenderSettings::half_res_secondary`, the
+/// secondary light most pixels borrow from their neighbours, which only the
+/// finished frame has.
+pub type TileSink<'a> = dyn Fn(Rect, &[Vector3]) + Sync + 'a;
+
+/// Traces a frame into linear HDR radiance; see `tonemap` for display.
+/// `sample_counts` receives the camera rays averaged into each pixel.
+/// Allocates the frame; a render loop reuses one through `render_into`, which
+/// can also fill a G-buffer.
+#[allow(clippy::too_many_arguments)]
+pub fn render(
+    width: i32,
+    height: i32,
+    scene: &Scene,
+    camera: &Camera,
+    light: &Light,
+    texture_manager: &TextureManager,
+    settings: &RenderSettings,
+    depth_buffer: &mut [f32],
+    sample_counts: &mut [u32],
+    stats: &mut RenderStats,
+) -> Vec<Vector3> {
+    let mut pixels = vec![Vector3::zero(); (width * height) as usize];
+    render_into(&mut pixels, width, height, scene, camera, light, texture_manager, settings, depth_buffer, sample_counts, None, None, stats);
+    pixels
+}
+
+/// `render` into `pixels`, which holds `width * height` colors and is
+/// overwritten entirely. With `gbuffer`, also fills it from the hit of each
+/// pixel's first camera ray, and with `on_tile`, hands it each block of the
+/// primary pass as it is done.
+#[allow(clippy::too_many_arguments)]
+pub fn render_into(
+    pixels: &mut [Vector3],
+    width: i32,
+    height: i32,
+    scene: &Scene,
+    camera: &Camera,
+    light: &Light,
+    texture_manager: &TextureManager,
+    settings: &RenderSettings,
+    depth_buffer: &mut [f32],
+    sample_counts: &mut [u32],
+    gbuffer: Option<&mut GBuffer>,
+    on_tile: Option<&TileSink>,
+    stats: &mut RenderStats,
+) {
+    let camera_eye = camera.eye;
+    let medium = eye_medium(scene, &camera_eye);
+
+    let ray_gen = ray_gen_cache(camera, width, height);
+
+    profile::set_enabled(settings.profile);
+    if settings.emissive_shadow_cache {
+        EMISSIVE_SHADOWS.clear();
+    }
+    let primary_start = Instant::now();
+    let rays = AtomicU64::new(0);
+    let budgets_spent = AtomicU64::new(0);
+    let totals = Totals::default();
+    let mut normals = vec![Vector3::zero(); (width * height) as usize];
+    let split = settings.half_res_secondary && settings.transparency == TransparencyMode::Recursive && matches!(medium, EyeMedium::Air);
+    let mut secondary = vec![SecondaryLight::default(); if split { (width * height) as usize } else { 0 }];
+    let mut surfaces = vec![Surface::default(); if gbuffer.is_some() { (width * height) as usize } else { 0 }];
+    let pass = PrimaryPass {
+        scene,
+        camera,
+        light,
+        texture_manager,
+        settings,
+        ray_gen: &ray_gen,
+        medium,
+        // Packets only know how to start in the open
+        packets: settings.packet_traversal
+            && settings.transparency == TransparencyMode::Recursive
+            && matches!(medium, EyeMedium::Air),
+        split,
+        gbuffer: gbuffer.is_some(),
+    };
+
+    match settings.schedule {
+        Schedule::Bands | Schedule::GroupedBands => {
+            // Each parallel job is a band of PACKET_SIZE rows, traced in square packets
+            let band_len = width as usize * PACKET_SIZE;
+            let bands = (height as usize).div_ceil(PACKET_SIZE);
+            let min_len = match settings.schedule {
+                Schedule::GroupedBands => bands / (rayon::current_num_threads() * 4),
+                _ => 1,
+            };
+            // Empty bands when there is no secondary light to keep apart
+            let secondary_bands: Vec<&mut [SecondaryLight]> = if split {
+                secondary.chunks_mut(band_len).collect()
+            } else {
+                (0..bands).map(|_| Default::default()).collect()
+            };
+            // And without a G-buffer to fill
+            let surface_bands: Vec<&mut [Surface]> = if pass.gbuffer {
+                surfaces.chunks_mut(band_len).collect()
+            } else {
+                (0..bands).map(|_| Default::default()).collect()
+            };
+            pixels
+                .par_chunks_mut(band_len)
+                .zip(depth_buffer.par_chunks_mut(band_len))
+                .zip(normals.par_chunks_mut(band_len))
+                .zip(secondary_bands)
+                .zip(surface_bands)
+                .enumerate()
+                .with_min_len(min_len.max(1))
+                .for_each(|(band, ((((rows, depth_rows), normal_rows), secondary_rows), surface_rows))| {
+                    let job = JobStart::here();
+                    let mut context = pass.context();
+                    let y0 = (band * PACKET_SIZE) as i32;
+                    let band_height = rows.len() as i32 / width;
+                    pass.trace_tile(0, y0, width, band_height, &mut PacketScratch::default(), &mut context, |x, y, (color, hit_distance, normal, lobes, surface)| {
+                        let index = ((y - y0) * width + x) as usize;
+                        rows[index] = color;
+                        depth_rows[index] = hit_distance;
+                        normal_rows[index] = normal;
+                        if let Some(slot) = secondary_rows.get_mut(index) {
+                            *slot = lobes;
+                        }
+                        if let Some(slot) = surface_rows.get_mut(index) {
+                            *slot = surface;
+                        }
+                    });
+                    if let Some(on_tile) = on_tile {
+                        on_tile(Rect { x: 0, y: y0, width, height: band_height }, rows);
+                    }
+                    job.finish(&rays, &budgets_spent, &totals);
+                });
+        }
+        Schedule::CenterOut => {
+            let tiles = center_out_tiles(width, height);
+            let next = AtomicUsize::new(0);
+            // Every thread of the pool takes tiles until none are left; the
+            // traced tiles are copied into the frame once all are done
+            let traced = rayon::broadcast(|_| {
+                let job = JobStart::here();
+                let mut scratch = PacketScratch::default();
+                let mut context = pass.context();
+                let mut done = Vec::new();
+                while let Some(&(x0, y0)) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let tile_width = TILE_SIZE.min(width - x0);
+                    let tile_height = TILE_SIZE.min(height - y0);
+                    let mut tile = vec![Traced::default(); (tile_width * tile_height) as usize];
+                    pass.trace_tile(x0, y0, tile_width, tile_height, &mut scratch, &mut context, |x, y, traced| {
+                        tile[((y - y0) * tile_width + x - x0) as usize] = traced;
+                    });
+                    if let Some(on_tile) = on_tile {
+                        let colors: Vec<Vector3> = tile.iter().map(|traced| traced.0).collect();
+                        on_tile(Rect { x: x0, y: y0, width: tile_width, height: tile_height }, &colors);
+                    }
+                    done.push((x0, y0, tile_width, tile));
+                }
+                job.finish(&rays, &budgets_spent, &totals);
+                done
+            });
+            for (x0, y0, tile_width, tile) in traced.into_iter().flatten() {
+                for (i, (color, hit_distance, normal, lobes, surface)) in tile.into_iter().enumerate() {
+                    let (dx, dy) = (i as i32 % tile_width, i as i32 / tile_width);
+                    let index = ((y0 + dy) * width + x0 + dx) as usize;
+                    pixels[index] = color;
+                    depth_buffer[index] = hit_distance;
+                    normals[index] = normal;
+                    if let Some(slot) = secondary.get_mut(index) {
+                        *slot = lobes;
+                    }
+                    if let Some(slot) = surfaces.get_mut(index) {
+                        *slot = surface;
+                    }
+                }
+            }
+        }
+    }
+    if let Some(gbuffer) = gbuffer {
+        gbuffer.normal.copy_from_slice(&normals);
+        gbuffer.albedo.par_iter_mut().zip(&mut gbuffer.object_id).zip(&surfaces).for_each(|((albedo, object_id), surface)| {
+            *albedo = surface.albedo;
+            *object_id = surface.object_id;
+        });
+    }
+
+    // Half-resolution secondary rays: every pixel that needs the light of its
+    // reflection and refraction rays and did not trace them takes it from its
+    // neighbours, and the frame gets it on top
+    let fallbacks = AtomicU64::new(0);
+    if split {
+        pixels.par_chunks_mut(width as usize).enumerate().for_each(|(y, row)| {
+            let job = JobStart::here();
+            let mut context = pass.context();
+            let y = y as i32;
+            let mut traced = 0;
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let x = x as i32;
+                let index = (y * width + x) as usize;
+                if secondary[index].material == 0 {
+                    continue;
+                }
+                *pixel += secondary[index].light.or_else(|| upsampled_secondary(&secondary, depth_buffer, &normals, width, height, x, y)).unwrap_or_else(|| {
+                    traced += 1;
+                    pass.trace_secondary(x, y, &mut context)
+                });
+            }
+            fallbacks.fetch_add(traced, Ordering::Relaxed);
+            job.finish(&rays, &budgets_spent, &totals);
+        });
+    }
+    let fallbacks = fallbacks.load(Ordering::Relaxed);
+
+    let pixel_count = (width * height) as u64;
+    let samples = settings.edge_samples.max(1);
+    *stats = RenderStats {
+        edge_pixels: 0,
+        primary_rays: pixel_count + fallbacks,
+        uniform_rays: pixel_count * samples as u64,
+        primary_time: primary_start.elapsed(),
+        edge_time: Duration::ZERO,
+        rays_traced: rays.load(Ordering::Relaxed),
+        budgets_spent: budgets_spent.load(Ordering::Relaxed),
+        profile: settings.profile.then(|| totals.profile()),
+        emissive_cache: settings.emissive_shadow_cache.then(|| EMISSIVE_SHADOWS.stats()),
+        secondary_fallbacks: fallbacks as usize,
+    };
+    sample_counts.fill(1);
+    if samples == 1 {
+        return;
+    }
+
+    // Second pass: only pixels on a silhouette or crease get `samples` rays.
+    // Holes in cut-out leaves are depth edges too, so canopy outlines land
+    // here and get a dithered alpha threshold per ray
+    let edge_start = Instant::now();
+    let edges = edge_mask(depth_buffer, &normals, width, height);
+    pixels
+        .par_chunks_mut(width as usize)
+        .zip(edges.par_chunks(width as usize))
+        .zip(sample_counts.par_chunks_mut(width as usize))
+        .enumerate()
+        .for_each(|(y, ((row, row_edges), row_counts))| {
+            let job = JobStart::here();
+            let mut context = ShadeContext::new(scene, light, texture_manager, settings);
+            for (x, pixel) in row.iter_mut().enumerate() {
+                if !row_edges[x] {
+                    continue;
+                }
+                row_counts[x] = samples;
+                let mut sum = Vector3::zero();
+                for i in 0..samples {
+                    let (ox, oy) = subpixel_offset(i, samples);
+                    let generating = profile::start();
+                    let direction = ray_gen.offset_direction(camera, x as i32, y as i32, ox, oy);
+                    let direction = sanitize_direction(direction, camera.forward);
+                    profile::stop(Stage::RayGen, generating);
+                    let alpha = AlphaTest::dithered(texture_manager, x, y, i, samples);
+                    sum += trace_primary(&camera_eye, &direction, alpha, medium, &mut context).0;
+                }
+                *pixel = sum / samples as f32;
+            }
+            job.finish(&rays, &budgets_spent, &totals);
+        });
+    let edge_pixels = edges.iter().filter(|&&edge| edge).count();
+    stats.edge_pixels = edge_pixels;
+    stats.primary_rays += edge_pixels as u64 * samples as u64;
+    stats.edge_time = edge_start.elapsed();
+    stats.rays_traced = rays.load(Ordering::Relaxed);
+    stats.budgets_spent = budgets_spent.load(Ordering::Relaxed);
+    stats.profile = settings.profile.then(|| totals.profile());
+    stats.emissive_cache = settings.emissive_shadow_cache.then(|| EMISSIVE_SHADOWS.stats());
+}
+
+/// Nearest-neighbour resize into `dst`, to show a frame traced below window resolution.
+fn upscale<T: Copy + Send + Sync>(src: &[T], src_width: i32, src_height: i32, dst: &mut [T], width: i32, height: i32) {
+    dst.par_iter_mut().enumerate().for_each(|(i, out)| {
+        let i = i as i32;
+        let x = (i % width) * src_width / width;
+        let y = (i / width) * src_height / height;
+        *out = src[(y * src_width + x) as usize];
+    });
+}
+
+/// `render` at `settings.render_scale` of `width` x `height`, upscaled back so
+/// the frame, `depth_buffer` and `sample_counts` always match the window.
+#[allow(clippy::too_many_arguments)]
+pub fn render_scaled(
+    width: i32,
+    height: i32,
+    scene: &Scene,
+    camera: &Camera,
+    light: &Light,
+    texture_manager: &TextureManager,
+    settings: &RenderSettings,
+    depth_buffer: &mut [f32],
+    sample_counts: &mut [u32],
+    stats: &mut RenderStats,
+) -> Vec<Vector3> {
+    let mut pixels = vec![Vector3::zero(); (width * height) as usize];
+    render_scaled_into(&mut pixels, width, height, scene, camera, light, texture_manager, settings, depth_buffer, sample_counts, None, None, stats);
+    pixels
+}
+
+/// `render_scaled` into `pixels`, the window-sized frame reused from the last
+/// call, filling `gbuffer` too if given. Below full scale the traced frame
+/// still gets buffers of its own, and `on_tile` is never called, as its
+/// blocks would not line up with the window.
+#[allow(clippy::too_many_arguments)]
+pub fn render_scaled_into(
+    pixels: &mut [Vector3],
+    width: i32,
+    height: i32,
+    scene: &Scene,
+    camera: &Camera,
+    light: &Light,
+    texture_manager: &TextureManager,
+    settings: &RenderSettings,
+    depth_buffer: &mut [f32],
+    sample_counts: &mut [u32],
+    gbuffer: Option<&mut GBuffer>,
+    on_tile: Option<&TileSink>,
+    stats: &mut RenderStats,
+) {
+    let trace_width = ((width as f32 * settings.render_scale).round() as i32).clamp(1, width);
+    let trace_height = ((height as f32 * settings.render_scale).round() as i32).clamp(1, height);
+    if (trace_width, trace_height) == (width, height) {
+        return render_into(
+            pixels, width, height, scene, camera, light, texture_manager, settings, depth_buffer, sample_counts, gbuffer, on_tile, stats,
+        );
+    }
+    let trace_len = (trace_width * trace_height) as usize;
+    let mut traced = vec![Vector3::zero(); trace_len];
+    let mut trace_depth = vec![f32::INFINITY; trace_len];
+    let mut trace_counts = vec![1; trace_len];
+    let mut trace_gbuffer = gbuffer.is_some().then(|| GBuffer::new(trace_len));
+    render_into(
+        &mut traced, trace_width, trace_height, scene, camera, light, texture_manager, settings, &mut trace_depth, &mut trace_counts,
+        trace_gbuffer.as_mut(), None, stats,
+    );
+    upscale(&trace_depth, trace_width, trace_height, depth_buffer, width, height);
+    upscale(&trace_counts, trace_width, trace_height, sample_counts, width, height);
+    upscale(&traced, trace_width, trace_height, pixels, width, height);
+    if let (Some(gbuffer), Some(traced)) = (gbuffer, &trace_gbuffer) {
+        upscale(&traced.normal, trace_width, trace_height, &mut gbuffer.normal, width, height);
+        upscale(&traced.albedo, trace_width, trace_height, &mut gbuffer.albedo, width, height);
+        upscale(&traced.object_id, trace_width, trace_height, &mut gbuffer.object_id, width, height);
+    }
+}
+
+/// The sun for a point in the day/night cycle, dimmed by the weather.
+pub fn sun_light(time_of_day: f32, sun_factor: f32) -> Light {
+    let sun_angle = time_of_day;
+    let sun_height = sun_angle.sin() * 15.0 + 5.0;
+    let sun_distance = 20.0;
+    let sun_x = sun_angle.cos() * sun_distance;
+    let sun_z = sun_angle.sin() * sun_distance * 0.5;
+
+    let day_intensity = (sun_angle.sin() * 0.5 + 0.5).max(0.2);
+    let sun_color = if sun_angle.sin() > 0.0 {
+        Vector3::new(1.0, 0.95, 0.8)  // Day
+    } else {
+        Vector3::new(0.4, 0.4, 0.8)   // Night
+    };
+
+    Light::new(
+        Vector3::new(sun_x, sun_height, sun_z),
+        sun_color,
+        day_intensity * sun_factor
+    )
+}
+
+
+/// Dedicated pool so the render never takes more than the requested cores.
+pub fn render_pool(threads: usize) -> rayon::ThreadPool {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("render-{}", i))
+        .build()
+        .expect("Could not build the render thread pool")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch;
+    use std::alloc::{GlobalAlloc, Layout, System};
+
+    // Counts the allocations of each thread, so tests running in parallel do
+    // not see each other's
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            // SAFETY: the caller upholds `GlobalAlloc::alloc`'s contract, passed on unchanged
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            // SAFETY: as for `alloc`
+            unsafe { System.realloc(ptr, layout, new_size) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            // SAFETY: as for `alloc`
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Allocations `f` makes on this thread.
+    fn allocations_in(f: impl FnOnce()) -> u64 {
+        let before = ALLOCATIONS.with(Cell::get);
+        f();
+        ALLOCATIONS.with(Cell::get) - before
+    }
+
+    // Frame size for the allocation tests
+    const FRAME_WIDTH: i32 = 64;
+    const FRAME_HEIGHT: i32 = 48;
+
+    fn stone() -> Arc<Material> {
+        Arc::new(Material { diffuse: Vector3::new(0.5, 0.5, 0.5), albedo: [0.9, 0.1], ..Material::black() })
+    }
+
+    fn torch() -> Arc<Material> {
+        Arc::new(Material { emission: Vector3::new(2.0, 1.5, 0.5), ..Material::black() })
+    }
+
+    // The top face of the floor block at the origin, under a torch at (0, 3, 0)
+    fn floor_hit(scene: &Scene, settings: &RenderSettings) -> Intersect {
+        let ray = TracerRay::new(Vector3::new(0.2, 0.9, 0.1), Vector3::new(0.0, -1.0, 0.0));
+        let hit = scene.intersect_nearest(&ray, f32::INFINITY, settings, |_, _| true).expect("the floor is below");
+        assert_eq!(hit.normal, Vector3::new(0.0, 1.0, 0.0));
+        hit
+    }
+
+    fn torch_shadow(scene: &Scene, settings: &RenderSettings) -> f32 {
+        let hit = floor_hit(scene, settings);
+        let light = &scene.emissive_lights[0];
+        let emitter = Some(&scene.objects[scene.emissive_indices[0]]);
+        cast_shadow(&hit, light, &ToLight::new(&hit.point, light).unwrap(), emitter, scene, settings)
+    }
+
+    fn assert_finite(color: Vector3) {
+        assert!(color.x.is_finite() && color.y.is_finite() && color.z.is_finite(), "color {:?}", color);
+    }
+
+    #[test]
+    fn zero_ray_direction_shades_to_a_finite_color() {
+        let settings = RenderSettings::default();
+        let scene = Scene::new(vec![Cube::new(Vector3::new(0.0, 0.0, -3.0), 1.0, stone())]);
+        let camera = Camera::new(Vector3::zero(), Vector3::new(0.0, 0.0, -3.0), Vector3::up());
+        let textures = TextureManager::new();
+
+        let direction = sanitize_direction(camera.basis_change(&Vector3::zero()), camera.forward);
+        assert_eq!(direction, camera.forward);
+        let nan = Vector3::new(f32::NAN, 0.0, -1.0);
+        assert_eq!(sanitize_direction(camera.basis_change(&nan), camera.forward), camera.forward);
+
+        let light = sun_light(0.0, 1.0);
+        let color = cast_ray(&camera.eye, &direction, 0, &mut ShadeContext::new(&scene, &light, &textures, &settings));
+        assert_finite(color);
+        // Along forward, so it lands on the block rather than the sky
+        let hit = closest_hit(&camera.eye, &direction, &scene, &settings, AlphaTest::solid());
+        assert!(hit.is_intersecting);
+    }
+
+    #[test]
+    fn emissive_block_does_not_shadow_its_own_light() {
+        let settings = RenderSettings::default();
+        assert!(settings.shadow_blocker_samples > 0, "the soft shadow path is the one under test");
+        let scene = Scene::new(vec![
+            Cube::new(Vector3::zero(), 1.0, stone()),
+            Cube::new(Vector3::new(0.0, 3.0, 0.0), 1.0, torch()),
+        ]);
+        assert_eq!(scene.emissive_lights.len(), 1);
+        // No blockers: fully lit, however large the light
+        assert_eq!(torch_shadow(&scene, &settings), 0.0);
+
+        let hard = RenderSettings { shadow_blocker_samples: 0, ..settings };
+        assert_eq!(torch_shadow(&scene, &hard), 0.0);
+    }
+
+    #[test]
+    fn block_between_torch_and_floor_casts_a_shadow() {
+        let settings = RenderSettings::default();
+        let scene = Scene::new(vec![
+            Cube::new(Vector3::zero(), 1.0, stone()),
+            Cube::new(Vector3::new(0.0, 1.6, 0.0), 1.0, stone()),
+            Cube::new(Vector3::new(0.0, 3.0, 0.0), 1.0, torch()),
+        ]);
+        let shadow = torch_shadow(&scene, &settings);
+        assert!(shadow > 0.0 && shadow <= SHADOW_STRENGTH, "shadow {}", shadow);
+    }
+
+    #[test]
+    fn light_right_on_the_hit_is_left_out() {
+        let settings = RenderSettings::default();
+        let textures = TextureManager::new();
+        let mut scene = Scene::new(vec![Cube::new(Vector3::zero(), 1.0, stone())]);
+        let hit = floor_hit(&scene, &settings);
+        let eye = Vector3::new(0.2, 3.0, 0.1);
+        let sun = sun_light(1.0, 1.0);
+
+        let lit = local_lighting(&eye, &hit, 0, &mut ShadeContext::new(&scene, &sun, &textures, &settings));
+        scene.transient_lights.push(Light::new(hit.point, Vector3::one(), 2.0));
+        assert_eq!(local_lighting(&eye, &hit, 0, &mut ShadeContext::new(&scene, &sun, &textures, &settings)), lit);
+        // The sun itself on the hit, with nothing else to light it
+        let on_the_hit = Light::new(hit.point, Vector3::one(), 1.0);
+        let mut context = ShadeContext::new(&scene, &on_the_hit, &textures, &settings);
+        assert_eq!(local_lighting(&eye, &hit, 0, &mut context), Vector3::zero());
+        assert!(ToLight::new(&hit.point, &on_the_hit).is_none());
+    }
+
+    // Three unit blocks along +x at x = 0, 3 and 6
+    fn row_of_blocks() -> Scene {
+        Scene::new((0..3).map(|i| Cube::new(Vector3::new(3.0 * i as f32, 0.0, 0.0), 1.0, stone())).collect())
+    }
+
+    #[test]
+    fn trace_closest_and_trace_any_agree_on_a_known_scene() {
+        let settings = RenderSettings::default();
+        let scene = row_of_blocks();
+        let origin = Vector3::new(-5.0, 0.2, 0.1);
+        let along_x = Vector3::new(1.0, 0.0, 0.0);
+
+        let hit = trace_closest(&origin, &along_x, &scene, &settings).expect("the first block is straight ahead");
+        assert_eq!(hit.distance, 4.5);
+        assert_eq!(hit.normal, Vector3::new(-1.0, 0.0, 0.0));
+        assert_eq!(hit.point, Vector3::new(-0.5, 0.2, 0.1));
+        assert!(!trace_any(&origin, &along_x, 4.4, &scene, &settings));
+        assert!(trace_any(&origin, &along_x, 4.6, &scene, &settings));
+
+        // Between the first and second block
+        let gap = Vector3::new(1.0, 0.2, 0.1);
+        assert_eq!(trace_closest(&gap, &along_x, &scene, &settings).map(|hit| hit.distance), Some(1.5));
+        assert!(!trace_any(&gap, &along_x, 1.4, &scene, &settings));
+
+        // Past the last block, or off to the side: nothing
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        assert!(trace_closest(&origin, &up, &scene, &settings).is_none());
+        assert!(!trace_any(&origin, &up, f32::INFINITY, &scene, &settings));
+        let beyond = Vector3::new(7.0, 0.0, 0.0);
+        assert!(trace_closest(&beyond, &along_x, &scene, &settings).is_none());
+        assert!(!trace_any(&beyond, &along_x, f32::INFINITY, &scene, &settings));
+    }
+
+    #[test]
+    fn ray_starting_inside_a_block_hits_its_far_face() {
+        let settings = RenderSettings::default();
+        let scene = row_of_blocks();
+        let inside = Vector3::new(3.1, 0.2, 0.1);
+        let along_x = Vector3::new(1.0, 0.0, 0.0);
+
+        // The exit face of the block it starts in, with the normal pointing out
+        let hit = trace_closest(&inside, &along_x, &scene, &settings).expect("the block it is in");
+        assert!((hit.distance - 0.4).abs() < 1e-5, "distance {}", hit.distance);
+        assert_eq!(hit.normal, Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(hit.point.x, 3.5);
+        assert!(!trace_any(&inside, &along_x, 0.3, &scene, &settings));
+        assert!(trace_any(&inside, &along_x, 0.5, &scene, &settings));
+
+        // Backwards it leaves through the near face, before the first block
+        let hit = trace_closest(&inside, &-along_x, &scene, &settings).expect("the block it is in");
+        assert_eq!(hit.normal, Vector3::new(-1.0, 0.0, 0.0));
+        assert_eq!(hit.point.x, 2.5);
+    }
+
+    fn water() -> Arc<Material> {
+        Arc::new(Material {
+            diffuse: Vector3::new(0.2, 0.4, 0.9),
+            transparency: 0.8,
+            refractive_index: 1.33,
+            ..Material::black()
+        })
+    }
+
+    #[test]
+    fn eye_inside_a_block_sees_the_inside_of_its_faces() {
+        let settings = RenderSettings::default();
+        let textures = TextureManager::new();
+        let scene = Scene::new(vec![Cube::new(Vector3::zero(), 1.0, stone())]);
+        let eye = Vector3::new(0.1, 0.2, -0.1);
+        assert!(matches!(eye_medium(&scene, &eye), EyeMedium::Embedded(_)));
+        assert!(matches!(eye_medium(&scene, &Vector3::new(0.0, 2.0, 0.0)), EyeMedium::Air));
+
+        let light = sun_light(0.0, 1.0);
+        let mut context = ShadeContext::new(&scene, &light, &textures, &settings);
+        for direction in [Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.6, -0.8, 0.0), Vector3::new(-1.0, 1.0, 1.0).normalized()] {
+            let medium = eye_medium(&scene, &eye);
+            let (color, hit) = trace_primary(&eye, &direction, AlphaTest::solid(), medium, &mut context);
+            assert_finite(color);
+            let (distance, normal) = (hit.distance, hit.normal);
+            // The face the ray reaches, at the distance to it, facing back at the eye
+            let exit = scene.objects[0].ray_intersect(&eye, &direction);
+            assert!(exit.is_intersecting);
+            assert_eq!(distance, exit.distance);
+            assert_eq!(normal, -exit.normal);
+            assert!(normal.dot(direction) < 0.0, "normal {:?} for {:?}", normal, direction);
+        }
+    }
+
+    #[test]
+    fn eye_under_water_sees_through_to_the_first_real_surface() {
+        let settings = RenderSettings::default();
+        let textures = TextureManager::new();
+        // Three water blocks along -z and a stone wall after them
+        let mut cubes: Vec<Cube> = (0..3).map(|i| Cube::new(Vector3::new(0.0, 0.0, -(i as f32)), 1.0, water())).collect();
+        cubes.push(Cube::new(Vector3::new(0.0, 0.0, -3.0), 1.0, stone()));
+        let scene = Scene::new(cubes);
+        let eye = Vector3::new(0.1, 0.1, 0.2);
+        let medium = eye_medium(&scene, &eye);
+        let EyeMedium::Submerged(block) = medium else { panic!("the eye is in water") };
+        let forward = Vector3::new(0.0, 0.0, -1.0);
+
+        // Past the boundaries between water blocks, measured from the eye
+        let wall = leave_medium(&eye, &forward, &scene, &settings, AlphaTest::solid());
+        assert!(wall.is_intersecting);
+        assert!((wall.distance - 2.7).abs() < 1e-4, "distance {}", wall.distance);
+        assert_eq!(wall.normal, Vector3::new(0.0, 0.0, 1.0));
+
+        let light = sun_light(0.0, 1.0);
+        let (color, hit) = trace_primary(&eye, &forward, AlphaTest::solid(), medium, &mut ShadeContext::new(&scene, &light, &textures, &settings));
+        assert_finite(color);
+        let distance = hit_distance(&hit);
+        assert_eq!(distance, wall.distance);
+        // Absorption dims red, the channel water lets through least, more than blue
+        let white = Vector3::new(1.0, 1.0, 1.0);
+        let absorbed = absorb(white, distance, &block.material);
+        assert!(absorbed.x < absorbed.z && absorbed.z < 1.0, "{:?}", absorbed);
+        assert_eq!(absorb(white, 0.0, &block.material), white);
+    }
+
+    #[test]
+    fn lighting_from_many_emissive_blocks_allocates_nothing_per_frame() {
+        let settings = RenderSettings::default();
+        let textures = TextureManager::new();
+        // A stone floor with more torches around it than `NEAREST_EMISSIVE`
+        let mut cubes: Vec<Cube> = (-4..4)
+            .flat_map(|x| (-4..4).map(move |z| (x, z)))
+            .map(|(x, z)| Cube::new(Vector3::new(x as f32, 0.0, z as f32), 1.0, stone()))
+            .collect();
+        cubes.extend((0..8).map(|i| {
+            let angle = i as f32 * std::f32::consts::TAU / 8.0;
+            Cube::new(Vector3::new(angle.cos() * 3.0, 2.0, angle.sin() * 3.0), 1.0, torch())
+        }));
+        let mut scene = Scene::new(cubes);
+        scene.transient_lights.push(Light::new(Vector3::new(0.0, 4.0, 0.0), Vector3::new(1.0, 0.5, 0.2), 2.0));
+        assert!(scene.emissive_lights.len() > NEAREST_EMISSIVE);
+        let camera = Camera::new(Vector3::new(0.0, 8.0, 6.0), Vector3::zero(), Vector3::up());
+        let light = sun_light(1.0, 1.0);
+
+        let frame = || {
+            let mut context = ShadeContext::new(&scene, &light, &textures, &settings);
+            for y in 0..FRAME_HEIGHT {
+                for x in 0..FRAME_WIDTH {
+                    let direction = camera.ray_direction(x as f32, y as f32, FRAME_WIDTH, FRAME_HEIGHT);
+                    assert_finite(cast_ray(&camera.eye, &direction, 0, &mut context));
+                }
+            }
+        };
+        // The first frame sets up thread-locals and grows the candidate
+        // buffer to fit every torch; the next must not allocate
+        let growths = scratch::growths();
+        frame();
+        assert!(scratch::growths() > growths, "the torches were never gathered");
+        assert_eq!(allocations_in(frame), 0);
+    }
+
+    // Linear colors of `golden_frame` as little-endian f32 red, green and
+    // blue per pixel, by rows
+    const GOLDEN: &[u8] = include_bytes!("../testdata/golden_small_32x24.bin");
+    const GOLDEN_WIDTH: i32 = 32;
+    const GOLDEN_HEIGHT: i32 = 24;
+
+    /// The first view of the small benchmark scene, textured, at the default
+    /// settings: every block material, torches, glass and water.
+    fn golden_frame(settings: &RenderSettings) -> Vec<Vector3> {
+        let mut textures = TextureManager::new();
+        for path in crate::textures::BLOCK_TEXTURES {
+            textures.load_cpu_texture_or_placeholder(path);
+        }
+        let scene = crate::worldgen::benchmark_scene(crate::worldgen::BenchTier::Small);
+        let light = crate::bench::bench_light(&scene);
+        let view = &scene.bookmarks[0];
+        let camera = Camera::new(view.eye, view.center, Vector3::up());
+        let pixels = (GOLDEN_WIDTH * GOLDEN_HEIGHT) as usize;
+        let (mut depth, mut counts, mut stats) = (vec![0.0; pixels], vec![0; pixels], RenderStats::default());
+        render(GOLDEN_WIDTH, GOLDEN_HEIGHT, &scene, &camera, &light, &textures, settings, &mut depth, &mut counts, &mut stats)
+    }
+
+    // Refactors of the tracer must leave every pixel the same to the bit. A
+    // change meant to alter the image writes the new reference with
+    // RAYTRACER_BLESS=1 set
+    #[test]
+    fn benchmark_view_renders_the_same_to_the_bit() {
+        let bytes: Vec<u8> = golden_frame(&RenderSettings::default()).iter().flat_map(|c| [c.x, c.y, c.z]).flat_map(f32::to_le_bytes).collect();
+        if std::env::var_os("RAYTRACER_BLESS").is_some() {
+            let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/golden_small_32x24.bin");
+            std::fs::write(&path, &bytes).expect("write the golden frame");
+            return;
+        }
+        assert_eq!(bytes.len(), GOLDEN.len());
+        let differing: Vec<usize> = (0..bytes.len() / 12).filter(|i| bytes[i * 12..][..12] != GOLDEN[i * 12..][..12]).collect();
+        assert!(
+            differing.is_empty(),
+            "{} of {} pixels differ from the golden frame, the first at ({}, {})",
+            differing.len(),
+            bytes.len() / 12,
+            differing[0] % GOLDEN_WIDTH as usize,
+            differing[0] / GOLDEN_WIDTH as usize
+        );
+    }
+
+    // Packets are off by default, so the golden frame alone would not cover them
+    #[test]
+    fn packets_render_the_golden_frame_too() {
+        let single = golden_frame(&RenderSettings::default());
+        let packets = golden_frame(&RenderSettings { packet_traversal: true, ..RenderSettings::default() });
+        let bits = |frame: &[Vector3]| -> Vec<u32> { frame.iter().flat_map(|c| [c.x, c.y, c.z]).map(f32::to_bits).collect() };
+        assert!(bits(&packets) == bits(&single), "packets and single rays render different frames");
+    }
+
+    #[test]
+    fn nearest_emissive_keeps_the_nearest_first_and_the_first_of_a_tie() {
+        // Pairs of lights equally far from the origin, one right on it
+        let positions = [(3.0, 0.0), (0.0, 1.0), (-3.0, 0.0), (0.0, 0.0), (2.0, 0.0), (0.0, -1.0), (0.0, 2.0), (5.0, 5.0)];
+        let lights: Vec<Light> = positions.iter().map(|&(x, z)| Light::new(Vector3::new(x, 0.0, z), Vector3::one(), 1.0)).collect();
+        let mut nearest = Vec::new();
+        nearest_emissive(&lights, &Vector3::zero(), &mut nearest);
+        let indices: Vec<usize> = nearest.iter().map(|&(_, index)| index).collect();
+        assert_eq!(indices, [1, 5, 4, 6, 0]);
+
+        // Fewer lights than it keeps: all of them but the one on the point
+        nearest.clear();
+        nearest_emissive(&lights[..4], &Vector3::zero(), &mut nearest);
+        let indices: Vec<usize> = nearest.iter().map(|&(_, index)| index).collect();
+        assert_eq!(indices, [1, 0, 2]);
+    }
+
+    #[test]
+    fn weighted_blended_glass_allocates_nothing_per_frame() {
+        let settings = RenderSettings { transparency: TransparencyMode::WeightedBlended, ..RenderSettings::default() };
+        let textures = TextureManager::new();
+        let glass = Arc::new(Material {
+            diffuse: Vector3::new(0.8, 0.9, 1.0),
+            transparency: 0.7,
+            refractive_index: 1.5,
+            ..Material::black()
+        });
+        // Four glass panes one behind the other in front of a stone wall
+        let mut cubes: Vec<Cube> = (0..4)
+            .map(|i| Cube::with_extent(Vector3::new(0.0, 0.0, -(i as f32) * 1.5), Vector3::new(6.0, 6.0, 0.2), glass.clone()))
+            .collect();
+        cubes.push(Cube::with_extent(Vector3::new(0.0, 0.0, -8.0), Vector3::new(10.0, 10.0, 1.0), stone()));
+        let scene = Scene::new(cubes);
+        let camera = Camera::new(Vector3::new(0.0, 0.0, 6.0), Vector3::new(0.0, 0.0, -8.0), Vector3::up());
+        let light = sun_light(1.0, 1.0);
+        let medium = eye_medium(&scene, &camera.eye);
+
+        let frame = || {
+            // Dropping the context checks every buffer was given back
+            let mut context = ShadeContext::new(&scene, &light, &textures, &settings);
+            for y in 0..FRAME_HEIGHT {
+                for x in 0..FRAME_WIDTH {
+                    let direction = camera.ray_direction(x as f32, y as f32, FRAME_WIDTH, FRAME_HEIGHT);
+                    let (color, _) = trace_primary(&camera.eye, &direction, AlphaTest::solid(), medium, &mut context);
+                    assert_finite(color);
+                }
+            }
+        };
+        // The first frame grows this thread's scratch buffer; the next reuses it
+        let growths = scratch::growths();
+        frame();
+        assert!(scratch::growths() > growths, "the panes were never gathered");
+        assert_eq!(allocations_in(frame), 0);
+    }
+
+    #[test]
+    fn culling_faint_emissive_blocks_changes_no_channel_by_a_step() {
+        let textures = TextureManager::new();
+        // A floor lit by one bright torch and a ring of dim ones further out
+        let mut cubes: Vec<Cube> = (-6..6)
+            .flat_map(|x| (-6..6).map(move |z| (x, z)))
+            .map(|(x, z)| Cube::new(Vector3::new(x as f32, 0.0, z as f32), 1.0, stone()))
+            .collect();
+        cubes.push(Cube::new(Vector3::new(0.0, 3.0, 0.0), 1.0, torch()));
+        // Dim enough that from the far side of the floor they fall under the threshold
+        let dim = Arc::new(Material { emission: Vector3::new(0.005, 0.004, 0.0015), ..Material::black() });
+        cubes.extend((0..12).map(|i| {
+            let angle = i as f32 * std::f32::consts::TAU / 12.0;
+            Cube::new(Vector3::new(angle.cos() * 5.0, 1.0 + (i % 3) as f32, angle.sin() * 5.0), 1.0, dim.clone())
+        }));
+        let scene = Scene::new(cubes);
+        let camera = Camera::new(Vector3::new(0.0, 10.0, 9.0), Vector3::zero(), Vector3::up());
+        let light = sun_light(-1.0, 0.2);
+
+        let frame = |settings: &RenderSettings| {
+            let mut context = ShadeContext::new(&scene, &light, &textures, settings);
+            let rays = rays_traced_here();
+            let colors: Vec<Vector3> = (0..FRAME_HEIGHT)
+                .flat_map(|y| (0..FRAME_WIDTH).map(move |x| (x, y)))
+                .map(|(x, y)| {
+                    let direction = camera.ray_direction(x as f32, y as f32, FRAME_WIDTH, FRAME_HEIGHT);
+                    cast_ray(&camera.eye, &direction, 0, &mut context)
+                })
+                .collect();
+            (colors, rays_traced_here() - rays)
+        };
+        let (exact, all_rays) = frame(&RenderSettings { emissive_cull_epsilon: 0.0, ..RenderSettings::default() });
+        let (culled, culled_rays) = frame(&RenderSettings { emissive_cull_epsilon: 1.0 / 4080.0, ..RenderSettings::default() });
+
+        assert!(culled_rays < all_rays, "nothing was culled ({} rays both times)", all_rays);
+        let worst = exact
+            .iter()
+            .zip(&culled)
+            .map(|(a, b)| (a.x - b.x).abs().max((a.y - b.y).abs()).max((a.z - b.z).abs()))
+            .fold(0.0f32, f32::max);
+        // Some light really was dropped, but never a whole 8-bit step
+        assert!(worst > 0.0, "culling changed nothing");
+        assert!(worst < 1.0 / 255.0, "largest channel difference {}", worst);
+    }
+
+    #[test]
+    fn dispersive_glass_splits_an_edge_seen_through_it_into_colors() {
+        // Deep enough to come out of the slab again
+        let settings = RenderSettings { max_depth: 4, ..RenderSettings::default() };
+        let textures = TextureManager::new();
+        let white = Arc::new(Material { emission: Vector3::one(), ..Material::black() });
+        // A glass slab over a floor that turns from white to black at x = 0
+        let scene_with = |abbe_number| {
+            let glass = Material { transparency: 1.0, refractive_index: 1.5, abbe_number, ..Material::black() };
+            Scene::new(vec![
+                Cube::with_extent(Vector3::new(-5.0, 0.0, 0.0), Vector3::new(10.0, 1.0, 20.0), white.clone()),
+                Cube::with_extent(Vector3::new(5.0, 0.0, 0.0), Vector3::new(10.0, 1.0, 20.0), Material::black()),
+                Cube::with_extent(Vector3::new(0.0, 2.0, 0.0), Vector3::new(20.0, 1.0, 20.0), glass),
+            ])
+        };
+        let (plain, dispersive) = (scene_with(0.0), scene_with(10.0));
+        let eye = Vector3::new(-4.0, 6.0, 0.0);
+        let light = sun_light(1.0, 1.0);
+
+        let mut widest_fringe = 0.0f32;
+        for i in 0..400 {
+            let direction = (Vector3::new(-2.0 + i as f32 * 0.01, 0.5, 0.0) - eye).normalized();
+            let before = cast_ray(&eye, &direction, 0, &mut ShadeContext::new(&plain, &light, &textures, &settings));
+            let after = cast_ray(&eye, &direction, 0, &mut ShadeContext::new(&dispersive, &light, &textures, &settings));
+            // Green refracts by the material's own index, as without dispersion
+            assert!((after.y - before.y).abs() < 1e-3, "green {} vs {}", after.y, before.y);
+            // Red and blue cross the edge at different rays
+            widest_fringe = widest_fringe.max(((after - before).x - (after - before).z).abs());
+            assert_eq!(DISPERSION_CHANNEL.with(Cell::get), None);
+        }
+        assert!(widest_fringe > 0.5, "widest fringe {}", widest_fringe);
+    }
+
+    #[test]
+    fn a_split_ray_refracts_by_its_channels_index() {
+        let glass = Material { transparency: 1.0, refractive_index: 1.5168, abbe_number: 64.17, ..Material::black() };
+        let plain = Material { abbe_number: 0.0, ..glass.clone() };
+        assert_eq!(ray_refractive_index(&glass), 1.5168);
+        for (channel, ior) in rgb_iors(1.5168, 64.17).into_iter().enumerate() {
+            DISPERSION_CHANNEL.with(|split| split.set(Some(channel)));
+            assert_eq!(ray_refractive_index(&glass), ior);
+            assert_eq!(ray_refractive_index(&plain), 1.5168);
+        }
+        DISPERSION_CHANNEL.with(|split| split.set(None));
+    }
+}
@@ -0,0 +1,56 @@
+// sampling.rs
+use raylib::prelude::*;
+
+const GOLDEN_ANGLE: f32 = 2.399_963;
+
+/// Punto `index` de `count` sobre el disco unitario (espiral de Vogel).
+/// Es determinista, así que no introduce ruido entre frames.
+pub fn vogel_disk(index: u32, count: u32) -> (f32, f32) {
+    let radius = ((index as f32 + 0.5) / count as f32).sqrt();
+    let theta = index as f32 * GOLDEN_ANGLE;
+    (radius * theta.cos(), radius * theta.sin())
+}
+
+/// Dos vectores unitarios perpendiculares a `normal` y entre sí.
+pub fn tangent_basis(normal: &Vector3) -> (Vector3, Vector3) {
+    let helper = if normal.x.abs() > 0.9 {
+        Vector3::new(0.0, 1.0, 0.0)
+    } else {
+        Vector3::new(1.0, 0.0, 0.0)
+    };
+    let tangent = normal.cross(helper).normalized();
+    let bitangent = normal.cross(tangent);
+    (tangent, bitangent)
+}
+
+/// Punto `index` de `count` repartido por igual en [0, 1)²: la primera
+/// coordenada estratificada, la segunda por el ángulo áureo.
+pub fn lattice_point(index: u32, count: u32) -> (f32, f32) {
+    let u = (index as f32 + 0.5) / count as f32;
+    let v = (index as f32 * GOLDEN_ANGLE / std::f32::consts::TAU).fract();
+    (u, v)
+}
+
+/// Normal de microfaceta de la distribución GGX alrededor de `normal`, con
+/// alpha = `roughness`². `(u, v)` en [0, 1)² eligen el punto; (0, _) devuelve
+/// `normal`.
+pub fn ggx_microfacet(normal: &Vector3, roughness: f32, u: f32, v: f32) -> Vector3 {
+    let alpha = roughness * roughness;
+    let tan2_theta = alpha * alpha * u / (1.0 - u).max(1e-6);
+    let cos_theta = 1.0 / (1.0 + tan2_theta).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = std::f32::consts::TAU * v;
+    let (tangent, bitangent) = tangent_basis(normal);
+    (tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + *normal * cos_theta).normalized()
+}
+
+/// Desplazamiento del subpíxel `index` de `count` en una rejilla estratificada,
+/// dentro de [-0.5, 0.5) y centrado en el píxel.
+pub fn subpixel_offset(index: u32, count: u32) -> (f32, f32) {
+    let side = (count as f32).sqrt().ceil() as u32;
+    let (column, row) = (index % side, index / side);
+    (
+        (column as f32 + 0.5) / side as f32 - 0.5,
+        (row as f32 + 0.5) / side as f32 - 0.5,
+    )
+}
@@ -0,0 +1,1185 @@
+// scene.rs
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use bvh::bvh::BVH;
+use log::debug;
+use raylib::prelude::*;
+use crate::accel::{visit_bvh, RopeBvh, TracerRay, SAH_BUCKETS};
+use crate::camera::CameraBookmark;
+use crate::chunks::StreamedChunk;
+use crate::clouds::CloudLayer;
+use crate::cube::{compare_slabs, Cube, CubeBounds, SlabHit};
+use crate::light::Light;
+use crate::material::{Material, TintSource};
+use crate::profile::{self, Counter};
+use std::sync::Arc;
+use crate::ray_intersect::Intersect;
+use crate::settings::RenderSettings;
+use crate::shadow_map::ShadowMap;
+
+/// Número de capas de render; cada una se alterna con su tecla numérica.
+pub const LAYER_COUNT: u8 = 9;
+
+/// Grupo de cubos con nombre que se puede ocultar entero.
+#[derive(Debug, Clone)]
+pub struct ObjectGroup {
+    /// Nombre con el que se muestra en el menú de grupos.
+    pub name: Arc<str>,
+    /// Falso mientras el grupo está oculto.
+    pub visible: bool,
+}
+
+/// Construye la lista de cubos de una escena etiquetando cada uno con el grupo
+/// y la capa actuales.
+#[derive(Default)]
+pub struct SceneBuilder {
+    objects: Vec<Cube>,
+    group: Option<Arc<str>>,
+    layer: u8,
+}
+
+impl SceneBuilder {
+    /// Constructor vacío, sin grupo y en la capa 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Los cubos que se añadan a partir de aquí pertenecen a `name`.
+    pub fn group(&mut self, name: &str) -> &mut Self {
+        self.group = Some(name.into());
+        self
+    }
+
+    /// Los cubos que se añadan a partir de aquí van a la capa `layer`.
+    pub fn layer(&mut self, layer: u8) -> &mut Self {
+        self.layer = layer.min(LAYER_COUNT - 1);
+        self
+    }
+
+    /// Añade `cube` con el grupo y la capa actuales.
+    pub fn push(&mut self, cube: Cube) -> &mut Self {
+        self.objects.push(cube.in_group(self.group.clone()).on_layer(self.layer));
+        self
+    }
+
+    /// Los cubos añadidos, en orden.
+    pub fn finish(self) -> Vec<Cube> {
+        self.objects
+    }
+}
+
+/// Todo lo que se traza cada frame: los cubos estáticos con su BVH y las capas
+/// dinámicas (nubes, chunks en streaming) que se intersectan aparte para poder
+/// cambiar sin reconstruir.
+pub struct Scene {
+    /// Solo los visibles: el BVH y los emisivos se construyen sobre estos.
+    pub objects: Vec<Cube>,
+    /// Cubos de grupos o capas ocultos, fuera del BVH.
+    pub hidden: Vec<Cube>,
+    /// Grupos con nombre, en el orden en que aparecieron.
+    pub groups: Vec<ObjectGroup>,
+    hidden_layers: u16,     // bit `i` = capa `i` oculta
+    /// BVH sobre `objects`.
+    pub bvh: BVH,
+    /// El mismo BVH aplanado con enlaces para recorrerlo sin pila.
+    pub ropes: RopeBvh,
+    // Límites de `objects`, en el mismo orden: lo que prueba el recorrido sin
+    // tocar el resto del cubo. Se rehacen con el BVH.
+    bounds: Vec<CubeBounds>,
+    /// Índices en `objects` de los cubos que emiten luz.
+    pub emissive_indices: Vec<usize>,
+    /// Una luz por cubo emisivo, en el mismo orden, con radio de medio bloque
+    /// y sin caída: esa depende de los ajustes y se pone al iluminar.
+    pub emissive_lights: Vec<Light>,
+    /// Materiales con nombre, en orden, uno por nombre; los cubos que los usan
+    /// comparten el mismo `Arc`.
+    pub materials: Vec<Arc<Material>>,
+    /// Capa de nubes, si hay.
+    pub clouds: Option<CloudLayer>,
+    /// Terreno cargado en streaming alrededor de la cámara.
+    pub chunks: Vec<StreamedChunk>,
+    /// Marcadores de cámara de la escena.
+    pub bookmarks: Vec<CameraBookmark>,
+    /// Luces breves, p. ej. el destello de una explosión.
+    pub transient_lights: Vec<Light>,
+    /// Celdas de la rejilla que cambiaron desde la última vez que se recogieron
+    /// para las simulaciones (bloques que caen, agua).
+    pub edited_cells: Vec<(i32, i32, i32)>,
+    /// Mapa de sombras del sol, si está activado; lo mantiene
+    /// `shadow_map::update_sun_shadow`.
+    pub sun_shadow: Option<ShadowMap>,
+    // Número del BVH actual, distinto en cada construcción
+    revision: u64,
+    // Cambia con cada cambio que se ve en la imagen; ver `generation`
+    generation: u64,
+    // Lo que tardó en construirse el BVH actual
+    build_time: Duration,
+    // Cubetas SAH con las que se construye el BVH; ver `set_sah_buckets`
+    sah_buckets: usize,
+    // `objects` se editó a mano y el BVH ya no le corresponde
+    geometry_changed: bool,
+}
+
+impl Scene {
+    /// Escena con `objects` visibles y el BVH construido.
+    pub fn new(mut objects: Vec<Cube>) -> Self {
+        let (bvh, ropes, bounds, build_time) = build_bvh(&mut objects, SAH_BUCKETS);
+        let emissive_indices = emissive_indices(&objects);
+        let emissive_lights = emissive_lights(&objects, &emissive_indices);
+
+        // Biblioteca en orden de primera aparición
+        let mut materials: Vec<Arc<Material>> = Vec::new();
+        for material in share_materials(&mut objects) {
+            if material.name.is_some() && !materials.iter().any(|m| m.name == material.name) {
+                materials.push(material);
+            }
+        }
+
+        let mut groups: Vec<ObjectGroup> = Vec::new();
+        for cube in &objects {
+            if let Some(name) = &cube.group {
+                if !groups.iter().any(|g| g.name == *name) {
+                    groups.push(ObjectGroup { name: name.clone(), visible: true });
+                }
+            }
+        }
+
+        Scene {
+            objects,
+            hidden: Vec::new(),
+            groups,
+            hidden_layers: 0,
+            bvh,
+            ropes,
+            bounds,
+            emissive_indices,
+            emissive_lights,
+            materials,
+            clouds: None,
+            chunks: Vec::new(),
+            bookmarks: Vec::new(),
+            transient_lights: Vec::new(),
+            edited_cells: Vec::new(),
+            sun_shadow: None,
+            revision: next_revision(),
+            generation: next_revision(),
+            build_time,
+            sah_buckets: SAH_BUCKETS,
+            geometry_changed: false,
+        }
+    }
+
+    /// Cubos visibles y ocultos, p. ej. para guardar la escena completa.
+    pub fn all_objects(&self) -> impl Iterator<Item = &Cube> {
+        self.objects.iter().chain(self.hidden.iter())
+    }
+
+    fn is_visible(&self, cube: &Cube) -> bool {
+        if !self.layer_visible(cube.layer) {
+            return false;
+        }
+        match &cube.group {
+            Some(name) => self.groups.iter().find(|g| g.name == *name).is_none_or(|g| g.visible),
+            None => true,
+        }
+    }
+
+    /// Indica si la capa `layer` se dibuja.
+    pub fn layer_visible(&self, layer: u8) -> bool {
+        layer >= LAYER_COUNT || self.hidden_layers & (1 << layer) == 0
+    }
+
+    /// Muestra u oculta una capa entera; igual que con los grupos, los cubos
+    /// ocultos salen del BVH y de la lista de emisivos.
+    pub fn set_layer_visible(&mut self, layer: u8, visible: bool) {
+        if layer >= LAYER_COUNT || self.layer_visible(layer) == visible {
+            return;
+        }
+        self.hidden_layers ^= 1 << layer;
+        self.apply_visibility();
+    }
+
+    /// Muestra u oculta un grupo. Los cubos ocultos salen del BVH y dejan de
+    /// emitir luz; volver a mostrarlo los devuelve tal cual.
+    pub fn set_group_visible(&mut self, index: usize, visible: bool) {
+        if self.groups[index].visible == visible {
+            return;
+        }
+        self.groups[index].visible = visible;
+        self.apply_visibility();
+    }
+
+    /// Reparte los cubos entre `objects` y `hidden` según la visibilidad actual.
+    fn apply_visibility(&mut self) {
+        let mut all = std::mem::take(&mut self.objects);
+        all.append(&mut self.hidden);
+        let (visible, hidden): (Vec<Cube>, Vec<Cube>) = all.into_iter().partition(|cube| self.is_visible(cube));
+        self.objects = visible;
+        self.hidden = hidden;
+        self.rebuild();
+    }
+
+    /// Cubo estático más cercano en la dirección del rayo, con su índice en `objects`.
+    pub fn pick(&self, ray_origin: &Vector3, ray_direction: &Vector3) -> Option<(usize, Intersect)> {
+        let ray = TracerRay::new(*ray_origin, *ray_direction);
+        self.intersect_nearest_static(&ray, f32::INFINITY, &RenderSettings::default())
+    }
+
+    /// Añade un cubo y reconstruye el BVH.
+    pub fn add_cube(&mut self, cube: Cube) {
+        self.edited_cells.extend(cube.grid_cell());
+        self.objects.push(cube);
+        self.rebuild();
+    }
+
+    /// Quita el cubo `index` y reconstruye el BVH.
+    pub fn remove_cube(&mut self, index: usize) -> Cube {
+        let cube = self.objects.remove(index);
+        self.edited_cells.extend(cube.grid_cell());
+        self.rebuild();
+        cube
+    }
+
+    /// Quita varios cubos con un solo rebuild. `indices` debe estar ordenado y sin repetidos.
+    pub fn remove_cubes(&mut self, indices: &[usize]) -> Vec<Cube> {
+        let mut removed = Vec::with_capacity(indices.len());
+        // De atrás hacia delante para que los índices pendientes sigan valiendo
+        for &index in indices.iter().rev() {
+            removed.push(self.objects.remove(index));
+        }
+        self.edited_cells.extend(removed.iter().filter_map(Cube::grid_cell));
+        removed.reverse();
+        self.rebuild();
+        removed
+    }
+
+    /// Añade varios cubos con un solo rebuild.
+    pub fn add_cubes(&mut self, cubes: impl IntoIterator<Item = Cube>) {
+        let start = self.objects.len();
+        self.objects.extend(cubes);
+        self.edited_cells.extend(self.objects[start..].iter().filter_map(Cube::grid_cell));
+        self.rebuild();
+    }
+
+    /// Traslada cubos (índice, desplazamiento) con un solo rebuild.
+    pub fn move_cubes(&mut self, moves: &[(usize, Vector3)]) {
+        for &(index, offset) in moves {
+            let cube = &mut self.objects[index];
+            self.edited_cells.extend(cube.grid_cell());
+            cube.min_bounds += offset;
+            cube.max_bounds += offset;
+            self.edited_cells.extend(cube.grid_cell());
+        }
+        self.rebuild();
+    }
+
+    /// Cambia la caja del cubo `index` (mover y escalar a la vez) y reconstruye el BVH.
+    pub fn set_bounds(&mut self, index: usize, min_bounds: Vector3, max_bounds: Vector3) {
+        let cube = &mut self.objects[index];
+        self.edited_cells.extend(cube.grid_cell());
+        cube.min_bounds = min_bounds;
+        cube.max_bounds = max_bounds;
+        self.edited_cells.extend(cube.grid_cell());
+        self.rebuild();
+    }
+
+    /// Índice actual en `objects` del cubo con identificador `id`; None si
+    /// se quitó o está oculto.
+    pub fn index_of(&self, id: u64) -> Option<usize> {
+        self.objects.iter().position(|c| c.id() == id)
+    }
+
+    /// Índice del cubo centrado exactamente en `center`.
+    pub fn cube_at(&self, center: &Vector3) -> Option<usize> {
+        self.objects.iter().position(|c| (c.min_bounds + c.max_bounds) * 0.5 == *center)
+    }
+
+    /// Índice de un cubo con los mismos límites que `cube`.
+    pub fn find_cube(&self, cube: &Cube) -> Option<usize> {
+        self.objects
+            .iter()
+            .position(|c| c.min_bounds == cube.min_bounds && c.max_bounds == cube.max_bounds)
+    }
+
+    /// Construye el BVH con `sah_buckets` cubetas SAH por división
+    /// (`RenderSettings::bvh_sah_buckets`), rehaciéndolo solo si cambian. Los
+    /// chunks en streaming se construyen siempre con las del crate.
+    pub fn set_sah_buckets(&mut self, sah_buckets: u32) {
+        if self.sah_buckets != sah_buckets as usize {
+            self.sah_buckets = sah_buckets as usize;
+            self.rebuild();
+        }
+    }
+
+    /// Reconstruye el BVH tras editar `objects`.
+    fn rebuild(&mut self) {
+        (self.bvh, self.ropes, self.bounds, self.build_time) = build_bvh(&mut self.objects, self.sah_buckets);
+        self.emissive_indices = emissive_indices(&self.objects);
+        self.emissive_lights = emissive_lights(&self.objects, &self.emissive_indices);
+        self.revision = next_revision();
+        self.geometry_changed = false;
+        self.touch();
+    }
+
+    /// Cambia cada vez que se construye el BVH, también entre escenas: lo
+    /// calculado sobre `objects` con otro número ya no vale.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Lo que tardó en construirse el BVH actual, aplanado incluido.
+    pub fn bvh_build_time(&self) -> Duration {
+        self.build_time
+    }
+
+    /// Cambia con todo lo que altera la imagen de la escena: cada BVH nuevo,
+    /// cada material editado y cada chunk que entra o sale. Con el mismo
+    /// número, y la misma cámara y luz, la escena se ve igual.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Avisa de un cambio visible hecho sin pasar por los métodos de la
+    /// escena, p. ej. en `chunks`.
+    pub fn touch(&mut self) {
+        self.generation = next_revision();
+    }
+
+    /// Avisa de que `objects` se editó directamente, sin `add_cube`,
+    /// `move_cubes` y compañía, que ya reconstruyen el BVH por su cuenta. El
+    /// BVH se reconstruye en el siguiente `rebuild_if_changed`.
+    pub fn mark_geometry_changed(&mut self) {
+        self.geometry_changed = true;
+    }
+
+    /// Reconstruye el BVH si se llamó a `mark_geometry_changed` desde la última
+    /// vez; devuelve si lo hizo.
+    pub fn rebuild_if_changed(&mut self) -> bool {
+        if !self.geometry_changed {
+            return false;
+        }
+        self.rebuild();
+        true
+    }
+
+    /// Posición en la biblioteca del material llamado `name`.
+    pub fn material_index(&self, name: &str) -> Option<usize> {
+        self.materials.iter().position(|m| m.name.as_deref() == Some(name))
+    }
+
+    /// Modifica un material de la biblioteca y lo aplica a todos los cubos que lo usan.
+    pub fn update_material(&mut self, index: usize, edit: impl FnOnce(&mut Material)) {
+        // Copia propia si los cubos la comparten; luego pasan a la nueva
+        edit(Arc::make_mut(&mut self.materials[index]));
+        let material = &self.materials[index];
+        for cube in self.objects.iter_mut().chain(self.chunks.iter_mut().flat_map(|c| c.objects.iter_mut())) {
+            if cube.material.name == material.name {
+                cube.material = Arc::clone(material);
+            }
+        }
+        // La emisión puede haber cambiado
+        self.emissive_indices = emissive_indices(&self.objects);
+        self.emissive_lights = emissive_lights(&self.objects, &self.emissive_indices);
+        self.touch();
+    }
+
+    /// Sustituye los materiales con nombre de `cubes` por los de la biblioteca,
+    /// p. ej. en chunks generados con la paleta original.
+    pub fn adopt_materials(&self, cubes: &mut [Cube]) {
+        for cube in cubes {
+            if let Some(material) = self.materials.iter().find(|m| m.name.is_some() && m.name == cube.material.name) {
+                cube.material = Arc::clone(material);
+            }
+        }
+    }
+
+    /// Cubos visibles que emiten luz.
+    pub fn emissive_objects(&self) -> impl Iterator<Item = &Cube> {
+        self.emissive_indices.iter().map(move |&i| &self.objects[i])
+    }
+
+    /// Llama a `visit` con cada cubo candidato para un rayo, con el recorrido
+    /// que indiquen los ajustes y sin reservar memoria. Para en cuanto `visit`
+    /// corta, p. ej. al primer bloqueador de un rayo de sombra.
+    pub fn visit<'a>(
+        &'a self,
+        ray: &TracerRay,
+        settings: &RenderSettings,
+        mut visit: impl FnMut(&'a Cube) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let visit_index = |index: usize| {
+            profile::count(Counter::CubeTests, 1);
+            visit(&self.objects[index])
+        };
+        if settings.stackless_traversal {
+            self.ropes.visit_leaves(ray, settings.bvh_leaf_size as usize, visit_index)
+        } else {
+            visit_bvh(&self.bvh, ray, visit_index)
+        }
+    }
+
+    /// Como `visit`, pero con el índice en `objects` y solo los límites de cada
+    /// candidato, sin leer el cubo: quien lo llama consulta `objects` para el
+    /// impacto que acepta.
+    pub fn visit_bounds(
+        &self,
+        ray: &TracerRay,
+        settings: &RenderSettings,
+        mut visit: impl FnMut(usize, &CubeBounds) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let visit_index = |index: usize| {
+            profile::count(Counter::CubeTests, 1);
+            visit(index, &self.bounds[index])
+        };
+        if settings.stackless_traversal {
+            self.ropes.visit_leaves(ray, settings.bvh_leaf_size as usize, visit_index)
+        } else {
+            visit_bvh(&self.bvh, ray, visit_index)
+        }
+    }
+
+    /// Impacto más cercano del rayo antes de `max_distance`, entre los cubos
+    /// del BVH y las capas dinámicas (nubes, chunks), o None si escapa.
+    /// `accept` decide sobre cada cubo que queda más cerca que el mejor hasta
+    /// entonces, p. ej. con el recorte alfa; las capas dinámicas cuentan
+    /// siempre. Es la consulta de impacto más cercano de todo el trazador:
+    /// un tipo de geometría nuevo se añade aquí y en `intersect_any`.
+    pub fn intersect_nearest(
+        &self,
+        ray: &TracerRay,
+        max_distance: f32,
+        settings: &RenderSettings,
+        mut accept: impl FnMut(&Cube, &SlabHit) -> bool,
+    ) -> Option<Intersect> {
+        let mut nearest = None;
+        let _ = self.visit_bounds(ray, settings, |index, bounds| {
+            self.keep_nearer(&mut nearest, index, bounds, ray, max_distance, &mut accept);
+            ControlFlow::Continue(())
+        });
+        self.nearest_with_dynamic(nearest, ray, max_distance)
+    }
+
+    /// `intersect_nearest` entre los cubos de `candidates` (índices en
+    /// `objects`) en lugar de recorrer el BVH, p. ej. los que ya reunió un
+    /// paquete de rayos. Las capas dinámicas se prueban igual.
+    pub fn intersect_nearest_among(
+        &self,
+        ray: &TracerRay,
+        max_distance: f32,
+        candidates: impl IntoIterator<Item = usize>,
+        mut accept: impl FnMut(&Cube, &SlabHit) -> bool,
+    ) -> Option<Intersect> {
+        let mut nearest = None;
+        for index in candidates {
+            profile::count(Counter::CubeTests, 1);
+            self.keep_nearer(&mut nearest, index, &self.bounds[index], ray, max_distance, &mut accept);
+        }
+        self.nearest_with_dynamic(nearest, ray, max_distance)
+    }
+
+    /// Cubo estático más cercano antes de `max_distance`, con su índice en
+    /// `objects`; sin capas dinámicas ni recorte alfa.
+    pub fn intersect_nearest_static(
+        &self,
+        ray: &TracerRay,
+        max_distance: f32,
+        settings: &RenderSettings,
+    ) -> Option<(usize, Intersect)> {
+        let mut nearest = None;
+        let _ = self.visit_bounds(ray, settings, |index, bounds| {
+            self.keep_nearer(&mut nearest, index, bounds, ray, max_distance, &mut |_, _| true);
+            ControlFlow::Continue(())
+        });
+        nearest.map(|(index, slab)| (index, self.objects[index].hit(ray, &slab)))
+    }
+
+    /// Si algo corta el rayo antes de `max_distance`. `blocks` decide sobre
+    /// cada cubo que el rayo cruza en ese tramo, en el orden del recorrido, y
+    /// la búsqueda para en el primero que bloquea; los chunks y las nubes
+    /// bloquean siempre. Más barato que `intersect_nearest` para sombras y
+    /// visibilidad.
+    pub fn intersect_any(
+        &self,
+        ray: &TracerRay,
+        max_distance: f32,
+        settings: &RenderSettings,
+        mut blocks: impl FnMut(&Cube) -> bool,
+    ) -> Occlusion {
+        let blocked = self.visit_bounds(ray, settings, |index, bounds| {
+            if bounds.slab(ray).is_some_and(|hit| hit.distance < max_distance) && blocks(&self.objects[index]) {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        if blocked.is_break() || self.dynamic_occluder_distance(&ray.origin, &ray.dir, max_distance).is_some() {
+            Occlusion::Blocked
+        } else {
+            Occlusion::Clear
+        }
+    }
+
+    // Cambia `nearest` por el cubo `index` si el rayo lo corta antes y
+    // `accept` lo admite. Hasta entonces solo se leen los límites.
+    fn keep_nearer(
+        &self,
+        nearest: &mut Option<(usize, SlabHit)>,
+        index: usize,
+        bounds: &CubeBounds,
+        ray: &TracerRay,
+        max_distance: f32,
+        accept: &mut impl FnMut(&Cube, &SlabHit) -> bool,
+    ) {
+        let Some(hit) = bounds.slab(ray).filter(|hit| hit.distance < max_distance) else {
+            return;
+        };
+        let closer = nearest.as_ref().is_none_or(|(best_index, best)| {
+            compare_slabs((bounds, &hit), (&self.bounds[*best_index], best), &ray.dir).is_lt()
+        });
+        if closer && accept(&self.objects[index], &hit) {
+            *nearest = Some((index, hit));
+        }
+    }
+
+    // El impacto completo de `nearest`, o el de una capa dinámica si queda antes
+    fn nearest_with_dynamic(&self, nearest: Option<(usize, SlabHit)>, ray: &TracerRay, max_distance: f32) -> Option<Intersect> {
+        let intersect = nearest.map_or_else(Intersect::empty, |(index, slab)| self.objects[index].hit(ray, &slab));
+        let dynamic = self.dynamic_hits(&ray.origin, &ray.dir).filter(|hit| hit.distance < max_distance);
+        let intersect = nearer_of(intersect, dynamic);
+        intersect.is_intersecting.then_some(intersect)
+    }
+
+    /// Devuelve el impacto más cercano entre `intersect` (del BVH) y las capas dinámicas.
+    pub fn with_dynamic_hits(&self, ray_origin: &Vector3, ray_direction: &Vector3, intersect: Intersect) -> Intersect {
+        nearer_of(intersect, self.dynamic_hits(ray_origin, ray_direction))
+    }
+
+    /// Distancia al oclusor dinámico más cercano, si está antes de `max_distance`.
+    pub fn dynamic_occluder_distance(&self, ray_origin: &Vector3, ray_direction: &Vector3, max_distance: f32) -> Option<f32> {
+        self.dynamic_hits(ray_origin, ray_direction)
+            .map(|hit| hit.distance)
+            .filter(|&distance| distance < max_distance)
+            .min_by(f32::total_cmp)
+    }
+
+    fn dynamic_hits<'a>(&'a self, ray_origin: &'a Vector3, ray_direction: &'a Vector3) -> impl Iterator<Item = Intersect> + 'a {
+        let clouds = self.clouds.iter().filter_map(move |clouds| clouds.intersect(ray_origin, ray_direction));
+        let chunks = self.chunks.iter().filter_map(move |chunk| chunk.intersect(ray_origin, ray_direction));
+        clouds.chain(chunks)
+    }
+
+    /// Indica si el punto está dentro de algún cubo. Cualquier cubo que lo contenga
+    /// también contiene el origen de un rayo que sale de ahí, así que basta con
+    /// recorrer el BVH con un rayo cualquiera.
+    pub fn is_solid_at(&self, point: &Vector3) -> bool {
+        self.cube_containing(point).is_some()
+    }
+
+    /// Un cubo visible o de un chunk que contiene el punto, si hay alguno; ver
+    /// `is_solid_at`.
+    pub fn cube_containing(&self, point: &Vector3) -> Option<&Cube> {
+        let ray = TracerRay::new(*point, Vector3::new(0.0, -1.0, 0.0));
+        let mut found = None;
+        let _ = self.ropes.visit(&ray, |index| {
+            let cube = &self.objects[index];
+            if cube.contains(point) {
+                found = Some(cube);
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        });
+        found.or_else(|| self.chunks.iter().find_map(|chunk| chunk.cube_containing(point)))
+    }
+}
+
+/// Lo que encontró un rayo de `Scene::intersect_any`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Occlusion {
+    /// Nada lo corta antes de la distancia máxima.
+    Clear,
+    /// Algo lo corta; la búsqueda paró ahí.
+    Blocked,
+}
+
+impl Occlusion {
+    /// Verdadero si algo corta el rayo.
+    pub fn is_blocked(self) -> bool {
+        self == Occlusion::Blocked
+    }
+}
+
+// `intersect` o el más cercano de `hits` si queda antes
+fn nearer_of(intersect: Intersect, hits: impl Iterator<Item = Intersect>) -> Intersect {
+    hits.fold(intersect, |nearest, hit| {
+        if !nearest.is_intersecting || hit.distance < nearest.distance {
+            hit
+        } else {
+            nearest
+        }
+    })
+}
+
+/// Hace que los cubos con materiales iguales compartan un solo `Arc`, aunque
+/// se crearan con copias sueltas; devuelve los distintos en orden de primera
+/// aparición. Las escenas usan pocos materiales, así que basta una lista.
+fn share_materials(objects: &mut [Cube]) -> Vec<Arc<Material>> {
+    let mut distinct: Vec<Arc<Material>> = Vec::new();
+    for cube in objects {
+        match distinct.iter().find(|&m| Arc::ptr_eq(m, &cube.material) || **m == *cube.material) {
+            Some(shared) => cube.material = Arc::clone(shared),
+            None => distinct.push(Arc::clone(&cube.material)),
+        }
+    }
+    distinct
+}
+
+// Contador global, para que dos escenas nunca compartan número
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(0);
+
+fn next_revision() -> u64 {
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// BVH de `objects` con `sah_buckets` cubetas SAH, construido en paralelo, su
+/// versión aplanada, los límites de cada cubo y lo que tardaron; el tiempo va
+/// también al log de depuración.
+fn build_bvh(objects: &mut [Cube], sah_buckets: usize) -> (BVH, RopeBvh, Vec<CubeBounds>, Duration) {
+    let start = Instant::now();
+    let bvh = crate::accel::build_bvh_with(objects, sah_buckets);
+    let ropes = RopeBvh::from_bvh(&bvh);
+    let bounds = objects.iter().map(Cube::bounds).collect();
+    let build_time = start.elapsed();
+    debug!("BVH over {} cubes built in {:.2}ms", objects.len(), build_time.as_secs_f64() * 1000.0);
+    (bvh, ropes, bounds, build_time)
+}
+
+fn emissive_lights(objects: &[Cube], indices: &[usize]) -> Vec<Light> {
+    indices
+        .iter()
+        .map(|&i| {
+            let cube = &objects[i];
+            let emission = cube.material.emission;
+            let size = cube.max_bounds.x - cube.min_bounds.x;
+            Light::new((cube.min_bounds + cube.max_bounds) * 0.5, emission.normalized(), emission.length()).with_radius(size * 0.5)
+        })
+        .collect()
+}
+
+fn emissive_indices(objects: &[Cube]) -> Vec<usize> {
+    objects
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.material.emission.dot(c.material.emission) > 0.0)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Tolerancia al comparar campos en coma flotante en `diff`.
+pub const DIFF_EPSILON: f32 = 1e-4;
+
+/// Un elemento que cambió entre dos escenas.
+#[derive(Debug, Clone)]
+pub enum Change<T> {
+    /// Solo está en `b`.
+    Added(T),
+    /// Solo está en `a`.
+    Removed(T),
+    /// Está en las dos con valores distintos: (antes, después).
+    Modified(T, T),
+}
+
+/// Lo que `diff` compara de un cubo.
+#[derive(Debug, Clone)]
+pub struct CubeSummary {
+    /// Centro del cubo; es la clave de la comparación.
+    pub center: Vector3,
+    /// Lado del cubo.
+    pub size: f32,
+    /// Nombre del material, o `?` si no tiene.
+    pub material: String,
+}
+
+/// Un cubo emisivo visto como luz.
+#[derive(Debug, Clone)]
+pub struct LightSummary {
+    /// Centro del bloque; es la clave de la comparación.
+    pub center: Vector3,
+    /// Luz emitida.
+    pub emission: Vector3,
+}
+
+/// Diferencias entre dos escenas, de `a` a `b`.
+#[derive(Debug, Default)]
+pub struct SceneDiff {
+    /// Cubos añadidos, quitados o cambiados.
+    pub cubes: Vec<Change<CubeSummary>>,
+    /// Bloques emisivos añadidos, quitados o cambiados.
+    pub lights: Vec<Change<LightSummary>>,
+    /// Marcadores añadidos, quitados o movidos.
+    pub cameras: Vec<Change<CameraBookmark>>,
+    /// Materiales de la biblioteca añadidos, quitados o cambiados.
+    pub materials: Vec<Change<Arc<Material>>>,
+}
+
+impl SceneDiff {
+    /// Verdadero si las dos escenas son iguales.
+    pub fn is_empty(&self) -> bool {
+        self.cubes.is_empty() && self.lights.is_empty() && self.cameras.is_empty() && self.materials.is_empty()
+    }
+}
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() <= DIFF_EPSILON
+}
+
+fn close_vec(a: &Vector3, b: &Vector3) -> bool {
+    close(a.x, b.x) && close(a.y, b.y) && close(a.z, b.z)
+}
+
+/// Posición en la rejilla como clave; cuantizada para que el ruido de coma
+/// flotante no separe dos cubos en el mismo sitio.
+fn position_key(center: &Vector3) -> (i64, i64, i64) {
+    let q = |v: f32| (v / DIFF_EPSILON).round() as i64;
+    (q(center.x), q(center.y), q(center.z))
+}
+
+fn summarize(cube: &Cube) -> CubeSummary {
+    CubeSummary {
+        center: (cube.min_bounds + cube.max_bounds) * 0.5,
+        size: cube.max_bounds.x - cube.min_bounds.x,
+        material: cube.material.name.as_deref().unwrap_or("?").to_string(),
+    }
+}
+
+/// Campos del material que cambian entre `a` y `b`.
+pub fn material_field_changes(a: &Material, b: &Material) -> Vec<&'static str> {
+    let tint_matches = match (&a.tint, &b.tint) {
+        (None, None) => true,
+        (Some(TintSource::Noise { palette: pa, scale: sa }), Some(TintSource::Noise { palette: pb, scale: sb })) => {
+            close(*sa, *sb) && pa.len() == pb.len() && pa.iter().zip(pb.iter()).all(|(x, y)| close_vec(x, y))
+        }
+        _ => false,
+    };
+    let checks = [
+        ("diffuse", close_vec(&a.diffuse, &b.diffuse)),
+        ("albedo", close(a.albedo[0], b.albedo[0]) && close(a.albedo[1], b.albedo[1])),
+        ("specular", close(a.specular, b.specular)),
+        ("reflectivity", close(a.reflectivity, b.reflectivity)),
+        ("transparency", close(a.transparency, b.transparency)),
+        ("refractive_index", close(a.refractive_index, b.refractive_index)),
+        ("roughness", close(a.roughness, b.roughness)),
+        ("refraction_roughness", close(a.refraction_roughness, b.refraction_roughness)),
+        ("abbe_number", close(a.abbe_number, b.abbe_number)),
+        ("texture", a.texture == b.texture),
+        ("normal_map", a.normal_map_id == b.normal_map_id),
+        ("emission", close_vec(&a.emission, &b.emission)),
+        ("tint", tint_matches),
+        ("falls", a.falls == b.falls),
+        ("alpha_cutout", a.alpha_cutout == b.alpha_cutout),
+        ("texture_fit", a.texture_fit == b.texture_fit),
+    ];
+    checks.iter().filter(|(_, same)| !same).map(|(field, _)| *field).collect()
+}
+
+/// Compara dos escenas: cubos por posición, luces (cubos emisivos), marcadores
+/// de cámara por nombre y materiales de la biblioteca por nombre.
+pub fn diff(a: &Scene, b: &Scene) -> SceneDiff {
+    let mut result = SceneDiff::default();
+
+    let index = |scene: &Scene| -> BTreeMap<(i64, i64, i64), CubeSummary> {
+        scene.all_objects().map(|cube| {
+            let summary = summarize(cube);
+            (position_key(&summary.center), summary)
+        }).collect()
+    };
+    let (before, after) = (index(a), index(b));
+    for (key, old) in &before {
+        match after.get(key) {
+            None => result.cubes.push(Change::Removed(old.clone())),
+            Some(new) if !close(old.size, new.size) || old.material != new.material => {
+                result.cubes.push(Change::Modified(old.clone(), new.clone()));
+            }
+            Some(_) => {}
+        }
+    }
+    for (key, new) in &after {
+        if !before.contains_key(key) {
+            result.cubes.push(Change::Added(new.clone()));
+        }
+    }
+
+    let lights = |scene: &Scene| -> BTreeMap<(i64, i64, i64), LightSummary> {
+        scene.emissive_objects().map(|cube| {
+            let center = (cube.min_bounds + cube.max_bounds) * 0.5;
+            (position_key(&center), LightSummary { center, emission: cube.material.emission })
+        }).collect()
+    };
+    let (before, after) = (lights(a), lights(b));
+    for (key, old) in &before {
+        match after.get(key) {
+            None => result.lights.push(Change::Removed(old.clone())),
+            Some(new) if !close_vec(&old.emission, &new.emission) => {
+                result.lights.push(Change::Modified(old.clone(), new.clone()));
+            }
+            Some(_) => {}
+        }
+    }
+    for (key, new) in &after {
+        if !before.contains_key(key) {
+            result.lights.push(Change::Added(new.clone()));
+        }
+    }
+
+    for old in &a.bookmarks {
+        match b.bookmarks.iter().find(|bookmark| bookmark.name == old.name) {
+            None => result.cameras.push(Change::Removed(old.clone())),
+            Some(new) if !close_vec(&old.eye, &new.eye) || !close_vec(&old.center, &new.center) => {
+                result.cameras.push(Change::Modified(old.clone(), new.clone()));
+            }
+            Some(_) => {}
+        }
+    }
+    for new in &b.bookmarks {
+        if !a.bookmarks.iter().any(|bookmark| bookmark.name == new.name) {
+            result.cameras.push(Change::Added(new.clone()));
+        }
+    }
+
+    for old in &a.materials {
+        match b.materials.iter().find(|material| material.name == old.name) {
+            None => result.materials.push(Change::Removed(old.clone())),
+            Some(new) if !material_field_changes(old, new).is_empty() => {
+                result.materials.push(Change::Modified(old.clone(), new.clone()));
+            }
+            Some(_) => {}
+        }
+    }
+    for new in &b.materials {
+        if !a.materials.iter().any(|material| material.name == new.name) {
+            result.materials.push(Change::Added(new.clone()));
+        }
+    }
+
+    result
+}
+
+fn fmt_point(v: &Vector3) -> String {
+    format!("({:.2}, {:.2}, {:.2})", v.x, v.y, v.z)
+}
+
+impl fmt::Display for SceneDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+        if !self.materials.is_empty() {
+            writeln!(f, "Materials:")?;
+            for change in &self.materials {
+                match change {
+                    Change::Added(m) => writeln!(f, "  + {}", m.name.as_deref().unwrap_or("?"))?,
+                    Change::Removed(m) => writeln!(f, "  - {}", m.name.as_deref().unwrap_or("?"))?,
+                    Change::Modified(old, new) => writeln!(
+                        f,
+                        "  ~ {} ({})",
+                        old.name.as_deref().unwrap_or("?"),
+                        material_field_changes(old, new).join(", ")
+                    )?,
+                }
+            }
+        }
+        if !self.cubes.is_empty() {
+            writeln!(f, "Cubes:")?;
+            for change in &self.cubes {
+                match change {
+                    Change::Added(c) => writeln!(f, "  + {} {} size {}", fmt_point(&c.center), c.material, c.size)?,
+                    Change::Removed(c) => writeln!(f, "  - {} {} size {}", fmt_point(&c.center), c.material, c.size)?,
+                    Change::Modified(old, new) => writeln!(
+                        f,
+                        "  ~ {} {} size {} -> {} size {}",
+                        fmt_point(&old.center), old.material, old.size, new.material, new.size
+                    )?,
+                }
+            }
+        }
+        if !self.lights.is_empty() {
+            writeln!(f, "Lights:")?;
+            for change in &self.lights {
+                match change {
+                    Change::Added(l) => writeln!(f, "  + {} emission {}", fmt_point(&l.center), fmt_point(&l.emission))?,
+                    Change::Removed(l) => writeln!(f, "  - {} emission {}", fmt_point(&l.center), fmt_point(&l.emission))?,
+                    Change::Modified(old, new) => writeln!(
+                        f,
+                        "  ~ {} emission {} -> {}",
+                        fmt_point(&old.center), fmt_point(&old.emission), fmt_point(&new.emission)
+                    )?,
+                }
+            }
+        }
+        if !self.cameras.is_empty() {
+            writeln!(f, "Cameras:")?;
+            for change in &self.cameras {
+                match change {
+                    Change::Added(c) => writeln!(f, "  + {} eye {} center {}", c.name, fmt_point(&c.eye), fmt_point(&c.center))?,
+                    Change::Removed(c) => writeln!(f, "  - {}", c.name)?,
+                    Change::Modified(old, new) => writeln!(
+                        f,
+                        "  ~ {} eye {} -> {}, center {} -> {}",
+                        old.name, fmt_point(&old.eye), fmt_point(&new.eye), fmt_point(&old.center), fmt_point(&new.center)
+                    )?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accel::traverse_packet;
+    use crate::cube::compare_hits;
+    use crate::noise::hash3;
+    use crate::ray_intersect::RayIntersect;
+
+    fn stone() -> Arc<Material> {
+        Arc::new(Material { diffuse: Vector3::new(0.5, 0.5, 0.5), ..Material::black() })
+    }
+
+    // Valor en [-1, 1] de la muestra `i`, eje `axis`
+    fn random(i: i32, axis: i32, seed: u32) -> f32 {
+        hash3(i, axis, 0, seed) * 2.0 - 1.0
+    }
+
+    // Cubos de tamaños variados dentro de [-10, 10]³, algunos solapados
+    fn random_scene(count: i32) -> Scene {
+        let material = stone();
+        let cubes = (0..count)
+            .map(|i| {
+                let center = Vector3::new(random(i, 0, 1), random(i, 1, 1), random(i, 2, 1)) * 10.0;
+                Cube::new(center, 0.25 + hash3(i, 3, 0, 1) * 1.5, material.clone())
+            })
+            .collect();
+        Scene::new(cubes)
+    }
+
+    // Rayo `i` desde fuera de la escena hacia un punto dentro de ella
+    fn random_ray(i: i32) -> TracerRay {
+        let origin = Vector3::new(random(i, 0, 2), random(i, 1, 2), random(i, 2, 2)).normalized() * 30.0;
+        let target = Vector3::new(random(i, 0, 3), random(i, 1, 3), random(i, 2, 3)) * 10.0;
+        TracerRay::new(origin, (target - origin).normalized())
+    }
+
+    // El impacto más cercano probando todos los cubos uno a uno
+    fn brute_force(scene: &Scene, ray: &TracerRay) -> Option<Intersect> {
+        scene
+            .objects
+            .iter()
+            .map(|cube| (cube, cube.ray_intersect(&ray.origin, &ray.dir)))
+            .filter(|(_, hit)| hit.is_intersecting)
+            .min_by(|a, b| compare_hits((a.0, &a.1), (b.0, &b.1), &ray.dir))
+            .map(|(_, hit)| hit)
+    }
+
+    fn assert_same_hit(expected: &Option<Intersect>, found: &Option<Intersect>, ray: &TracerRay) {
+        match (expected, found) {
+            (None, None) => {}
+            (Some(expected), Some(found)) => {
+                assert_eq!(expected.distance, found.distance, "{:?}", ray);
+                assert_eq!(expected.normal, found.normal, "{:?}", ray);
+                assert_eq!(expected.point, found.point, "{:?}", ray);
+            }
+            _ => panic!("brute force {:?}, BVH {:?} for {:?}", expected.is_some(), found.is_some(), ray),
+        }
+    }
+
+    #[test]
+    fn bvh_nearest_hit_matches_brute_force() {
+        let scene = random_scene(400);
+        let settings = RenderSettings::default();
+        let mut hits = 0;
+        for i in 0..5000 {
+            let ray = random_ray(i);
+            let expected = brute_force(&scene, &ray);
+            hits += usize::from(expected.is_some());
+            assert_same_hit(&expected, &scene.intersect_nearest(&ray, f32::INFINITY, &settings, |_, _| true), &ray);
+        }
+        assert!(hits > 1000, "only {} of the rays hit anything", hits);
+    }
+
+    #[test]
+    fn packet_traversal_matches_single_rays() {
+        let scene = random_scene(400);
+        let settings = RenderSettings::default();
+        let mut candidates = Vec::new();
+        for packet in 0..300 {
+            // Rayos coherentes: un origen y direcciones vecinas, como un bloque de píxeles
+            let origin = Vector3::new(random(packet, 0, 4), random(packet, 1, 4), random(packet, 2, 4)).normalized() * 30.0;
+            let target = Vector3::new(random(packet, 0, 5), random(packet, 1, 5), random(packet, 2, 5)) * 8.0;
+            let directions: Vec<Vector3> = (0..16)
+                .map(|lane| {
+                    let offset = Vector3::new((lane % 4) as f32, (lane / 4) as f32, 0.0) * 0.3;
+                    (target + offset - origin).normalized()
+                })
+                .collect();
+            traverse_packet(&scene.bvh, &origin, &directions, &mut candidates);
+            for (lane, direction) in directions.iter().enumerate() {
+                let ray = TracerRay::new(origin, *direction);
+                let lane_candidates = candidates.iter().filter(|(_, mask)| mask & (1 << lane) != 0).map(|(index, _)| *index);
+                let packed = scene.intersect_nearest_among(&ray, f32::INFINITY, lane_candidates, |_, _| true);
+                let single = scene.intersect_nearest(&ray, f32::INFINITY, &settings, |_, _| true);
+                assert_same_hit(&single, &packed, &ray);
+                assert_same_hit(&brute_force(&scene, &ray), &packed, &ray);
+            }
+        }
+    }
+
+    fn named(name: &str, diffuse: f32) -> Arc<Material> {
+        Arc::new(Material { name: Some(Arc::from(name)), diffuse: Vector3::new(diffuse, diffuse, diffuse), ..Material::black() })
+    }
+
+    fn torch(name: &str, emission: f32) -> Arc<Material> {
+        Arc::new(Material { emission: Vector3::new(emission, emission * 0.8, 0.3), ..Material::clone(&named(name, 1.0)) })
+    }
+
+    fn bookmark(name: &str, x: f32) -> CameraBookmark {
+        CameraBookmark { name: name.to_string(), eye: Vector3::new(x, 2.0, 8.0), center: Vector3::zero() }
+    }
+
+    // Tres bloques de piedra, una antorcha, dos marcadores y su biblioteca
+    fn diff_base() -> Scene {
+        let (stone, lamp) = (named("stone", 0.5), torch("torch", 4.0));
+        let mut scene = Scene::new(vec![
+            Cube::new(Vector3::new(0.0, 0.0, 0.0), 1.0, stone.clone()),
+            Cube::new(Vector3::new(1.0, 0.0, 0.0), 1.0, stone.clone()),
+            Cube::new(Vector3::new(2.0, 0.0, 0.0), 1.0, stone.clone()),
+            Cube::new(Vector3::new(0.0, 1.0, 0.0), 1.0, lamp.clone()),
+        ]);
+        scene.materials = vec![stone, lamp];
+        scene.bookmarks = vec![bookmark("front", 0.0), bookmark("side", 5.0)];
+        scene
+    }
+
+    fn rebuilt(scene: &Scene, edit: impl FnOnce(&mut Vec<Cube>)) -> Scene {
+        let mut objects: Vec<Cube> = scene.all_objects().cloned().collect();
+        edit(&mut objects);
+        let mut result = Scene::new(objects);
+        result.materials = scene.materials.clone();
+        result.bookmarks = scene.bookmarks.clone();
+        result
+    }
+
+    fn kinds<T>(changes: &[Change<T>]) -> Vec<&'static str> {
+        let mut kinds: Vec<&'static str> = changes
+            .iter()
+            .map(|change| match change {
+                Change::Added(_) => "added",
+                Change::Removed(_) => "removed",
+                Change::Modified(..) => "modified",
+            })
+            .collect();
+        kinds.sort_unstable();
+        kinds
+    }
+
+    #[test]
+    fn diff_of_identical_scenes_is_empty() {
+        let scene = diff_base();
+        let result = diff(&scene, &rebuilt(&scene, |_| {}));
+        assert!(result.is_empty());
+        assert_eq!(result.to_string(), "No differences\n");
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_modified_cubes() {
+        let a = diff_base();
+        let glass = named("glass", 0.9);
+        let b = rebuilt(&a, |objects| {
+            let center = |cube: &Cube| (cube.min_bounds + cube.max_bounds) * 0.5;
+            objects.retain(|cube| center(cube).x != 2.0);
+            let moved = objects.iter().position(|cube| center(cube) == Vector3::new(1.0, 0.0, 0.0)).unwrap();
+            objects[moved] = Cube::new(Vector3::new(1.0, 0.0, 0.0), 1.0, glass.clone());
+            objects.push(Cube::new(Vector3::new(0.0, 0.0, 3.0), 1.0, glass));
+        });
+        let result = diff(&a, &b);
+        assert_eq!(kinds(&result.cubes), ["added", "modified", "removed"]);
+        assert!(result.lights.is_empty() && result.cameras.is_empty() && result.materials.is_empty());
+        let Some(Change::Modified(old, new)) = result.cubes.iter().find(|c| matches!(c, Change::Modified(..))) else {
+            unreachable!()
+        };
+        assert_eq!((old.material.as_str(), new.material.as_str()), ("stone", "glass"));
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_modified_lights() {
+        let a = diff_base();
+        let b = rebuilt(&a, |objects| {
+            let lamp = objects.iter().position(|cube| cube.material.emission != Vector3::zero()).unwrap();
+            objects[lamp] = Cube::new(Vector3::new(0.0, 1.0, 0.0), 1.0, torch("torch", 8.0));
+            objects.push(Cube::new(Vector3::new(2.0, 1.0, 0.0), 1.0, torch("torch", 4.0)));
+        });
+        let c = rebuilt(&a, |objects| objects.retain(|cube| cube.material.emission == Vector3::zero()));
+        assert_eq!(kinds(&diff(&a, &b).lights), ["added", "modified"]);
+        assert_eq!(kinds(&diff(&a, &c).lights), ["removed"]);
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_moved_cameras() {
+        let a = diff_base();
+        let mut b = rebuilt(&a, |_| {});
+        b.bookmarks = vec![bookmark("front", 1.0), bookmark("top", 0.0)];
+        let result = diff(&a, &b);
+        assert_eq!(kinds(&result.cameras), ["added", "modified", "removed"]);
+        assert!(result.cubes.is_empty() && result.lights.is_empty() && result.materials.is_empty());
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_modified_materials() {
+        let a = diff_base();
+        let mut b = rebuilt(&a, |_| {});
+        b.materials = vec![named("stone", 0.25), named("glass", 0.9)];
+        let result = diff(&a, &b);
+        assert_eq!(kinds(&result.materials), ["added", "modified", "removed"]);
+        let Some(Change::Modified(old, new)) = result.materials.iter().find(|c| matches!(c, Change::Modified(..))) else {
+            unreachable!()
+        };
+        assert_eq!(material_field_changes(old, new), ["diffuse"]);
+        assert!(result.to_string().contains("~ stone (diffuse)"));
+    }
+
+    #[test]
+    fn coplanar_hits_pick_the_same_cube_whatever_the_build_order() {
+        // Dos bloques solapados con la cara de arriba en el mismo plano y dos
+        // bloques de suelo que comparten una arista
+        let cubes = vec![
+            Cube::with_extent(Vector3::new(0.0, 0.0, 0.0), Vector3::new(2.0, 1.0, 2.0), named("wide", 0.2)),
+            Cube::new(Vector3::new(0.5, 0.0, 0.5), 1.0, named("small", 0.4)),
+            Cube::new(Vector3::new(5.0, 0.0, 0.0), 1.0, named("left", 0.6)),
+            Cube::new(Vector3::new(6.0, 0.0, 0.0), 1.0, named("right", 0.8)),
+        ];
+        let down = Vector3::new(0.0, -1.0, 0.0);
+        let rays = [
+            (TracerRay::new(Vector3::new(0.6, 3.0, 0.7), down), "wide"),
+            (TracerRay::new(Vector3::new(5.5, 3.0, 0.2), down), "left"),
+            (TracerRay::new(Vector3::new(4.0, 3.0, 0.0), Vector3::new(1.5, -2.5, 0.0).normalized()), "left"),
+        ];
+        for rotation in 0..cubes.len() {
+            let mut order = cubes.clone();
+            order.rotate_left(rotation);
+            if rotation % 2 == 1 {
+                order.reverse();
+            }
+            let scene = Scene::new(order);
+            for stackless_traversal in [false, true] {
+                let settings = RenderSettings { stackless_traversal, ..RenderSettings::default() };
+                for (ray, expected) in &rays {
+                    let hit = scene.intersect_nearest(ray, f32::INFINITY, &settings, |_, _| true).expect("every ray hits");
+                    assert_eq!(hit.material.name.as_deref(), Some(*expected), "rotation {} ray {:?}", rotation, ray);
+                }
+            }
+        }
+    }
+}
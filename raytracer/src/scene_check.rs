@@ -0,0 +1,281 @@
+// scene_check.rs
+// Estadísticas y comprobaciones de una escena: cuántos cubos, emisivos y
+// materiales tiene, cómo quedó el BVH y cuánta memoria ocupan las texturas, y
+// los cubos que seguramente son un error: repetidos en la misma posición,
+// solapados con otro o perdidos lejos del origen. Se resume en el log al
+// arrancar y se imprime entero con `--validate`.
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use bvh::bvh::BVHNode;
+use log::{info, warn};
+use raylib::prelude::*;
+
+use crate::assets::Assets;
+use crate::cli::SceneOptions;
+use crate::config::Config;
+use crate::cube::Cube;
+use crate::scene::Scene;
+use crate::textures::{SkyboxTextures, TextureManager, BLOCK_TEXTURES};
+
+/// Coordenada más lejana del origen que se considera razonable.
+pub const SANE_RANGE: f32 = 10_000.0;
+
+// Dos cubos se solapan si comparten más que esta profundidad en cada eje;
+// tocarse por una cara no cuenta
+const OVERLAP_EPSILON: f32 = 1e-3;
+
+// Cubos más grandes que esto no van a la rejilla; se comparan con todos
+const LARGE_CUBE: f32 = 16.0;
+
+// Problemas de cada tipo que se listan antes de resumir el resto
+const LISTED_PROBLEMS: usize = 20;
+
+/// Lo que encuentra `SceneReport::new` en una escena.
+#[derive(Debug, Default)]
+pub struct SceneReport {
+    /// Cubos visibles.
+    pub cubes: usize,
+    /// Cubos de grupos o capas ocultos.
+    pub hidden: usize,
+    /// Cubos emisivos entre los visibles.
+    pub emissive: usize,
+    /// Materiales de la biblioteca.
+    pub materials: usize,
+    /// Marcadores de cámara.
+    pub bookmarks: usize,
+    /// Nodos del BVH, hojas incluidas.
+    pub bvh_nodes: usize,
+    /// Profundidad máxima del BVH.
+    pub bvh_depth: u32,
+    /// Milisegundos que tardó en construirse el BVH.
+    pub bvh_build_ms: f64,
+    /// Bytes de las texturas en CPU y en GPU, si se cargaron.
+    pub texture_bytes: Option<(usize, usize)>,
+    /// Texturas reducidas al cargarlas y bytes de CPU que se ahorraron.
+    pub downscaled: (usize, usize),
+    /// Centros de los cubos repetidos: mismo centro y mismo tamaño que otro.
+    pub duplicates: Vec<Vector3>,
+    /// Centros de cada par de cubos que se solapan sin ser repetidos.
+    pub overlaps: Vec<(Vector3, Vector3)>,
+    /// Centros de los cubos fuera de `SANE_RANGE`, con tamaño no positivo o
+    /// con coordenadas que no son números.
+    pub out_of_range: Vec<Vector3>,
+}
+
+fn center(cube: &Cube) -> Vector3 {
+    (cube.min_bounds + cube.max_bounds) * 0.5
+}
+
+/// Esquinas cuantizadas como clave, para que el ruido de coma flotante no
+/// separe dos cubos repetidos.
+fn bounds_key(cube: &Cube) -> [i64; 6] {
+    let q = |v: f32| (v / OVERLAP_EPSILON).round() as i64;
+    let (a, b) = (cube.min_bounds, cube.max_bounds);
+    [q(a.x), q(a.y), q(a.z), q(b.x), q(b.y), q(b.z)]
+}
+
+fn overlaps(a: &Cube, b: &Cube) -> bool {
+    let axis = |a_min: f32, a_max: f32, b_min: f32, b_max: f32| a_min.max(b_min) + OVERLAP_EPSILON < a_max.min(b_max);
+    axis(a.min_bounds.x, a.max_bounds.x, b.min_bounds.x, b.max_bounds.x)
+        && axis(a.min_bounds.y, a.max_bounds.y, b.min_bounds.y, b.max_bounds.y)
+        && axis(a.min_bounds.z, a.max_bounds.z, b.min_bounds.z, b.max_bounds.z)
+}
+
+fn is_sane(cube: &Cube) -> bool {
+    let (a, b) = (cube.min_bounds, cube.max_bounds);
+    [a.x, a.y, a.z, b.x, b.y, b.z].iter().all(|v| v.is_finite() && v.abs() <= SANE_RANGE)
+        && b.x > a.x && b.y > a.y && b.z > a.z
+}
+
+/// Pares de cubos solapados, por índice en `cubes`. Cada cubo entra en las
+/// celdas de lado 1 que toca y solo se comparan los que comparten celda.
+fn overlapping_pairs(cubes: &[&Cube]) -> Vec<(usize, usize)> {
+    let mut grid: HashMap<(i32, i32, i32), Vec<usize>> = HashMap::new();
+    let mut large = Vec::new();
+    for (index, cube) in cubes.iter().enumerate() {
+        let extent = cube.max_bounds - cube.min_bounds;
+        if extent.x.max(extent.y).max(extent.z) > LARGE_CUBE {
+            large.push(index);
+            continue;
+        }
+        let low = |v: f32| (v + OVERLAP_EPSILON).floor() as i32;
+        let high = |v: f32| (v - OVERLAP_EPSILON).floor() as i32;
+        for x in low(cube.min_bounds.x)..=high(cube.max_bounds.x) {
+            for y in low(cube.min_bounds.y)..=high(cube.max_bounds.y) {
+                for z in low(cube.min_bounds.z)..=high(cube.max_bounds.z) {
+                    grid.entry((x, y, z)).or_default().push(index);
+                }
+            }
+        }
+    }
+
+    let mut pairs = HashSet::new();
+    for cell in grid.values() {
+        for (i, &a) in cell.iter().enumerate() {
+            for &b in &cell[i + 1..] {
+                if overlaps(cubes[a], cubes[b]) {
+                    pairs.insert((a.min(b), a.max(b)));
+                }
+            }
+        }
+    }
+    for &a in &large {
+        for b in 0..cubes.len() {
+            if a != b && overlaps(cubes[a], cubes[b]) {
+                pairs.insert((a.min(b), a.max(b)));
+            }
+        }
+    }
+    let mut pairs: Vec<(usize, usize)> = pairs.into_iter().collect();
+    pairs.sort_unstable();
+    pairs
+}
+
+impl SceneReport {
+    /// Cuenta y comprueba los cubos de `scene`, visibles y ocultos.
+    pub fn new(scene: &Scene) -> Self {
+        let mut report = SceneReport {
+            cubes: scene.objects.len(),
+            hidden: scene.hidden.len(),
+            emissive: scene.emissive_indices.len(),
+            materials: scene.materials.len(),
+            bookmarks: scene.bookmarks.len(),
+            bvh_nodes: scene.bvh.nodes.len(),
+            bvh_build_ms: scene.bvh_build_time().as_secs_f64() * 1000.0,
+            ..Self::default()
+        };
+        report.bvh_depth = scene
+            .bvh
+            .nodes
+            .iter()
+            .map(|node| match node {
+                BVHNode::Leaf { depth, .. } | BVHNode::Node { depth, .. } => *depth,
+            })
+            .max()
+            .unwrap_or(0);
+
+        // Los cubos fuera de rango no se comparan: sus celdas no tienen sentido
+        let mut seen = HashSet::new();
+        let mut checked = Vec::new();
+        for cube in scene.all_objects() {
+            if !is_sane(cube) {
+                report.out_of_range.push(center(cube));
+            } else if !seen.insert(bounds_key(cube)) {
+                report.duplicates.push(center(cube));
+            } else {
+                checked.push(cube);
+            }
+        }
+        report.overlaps = overlapping_pairs(&checked)
+            .into_iter()
+            .map(|(a, b)| (center(checked[a]), center(checked[b])))
+            .collect();
+        report
+    }
+
+    /// Añade la memoria de las texturas cargadas en `textures`.
+    pub fn with_textures(mut self, textures: &TextureManager) -> Self {
+        self.texture_bytes = Some((textures.cpu_memory_bytes(), textures.gpu_memory_bytes()));
+        self.downscaled = textures.downscale_savings();
+        self
+    }
+
+    /// Número de cubos con algún problema.
+    pub fn problems(&self) -> usize {
+        self.duplicates.len() + self.overlaps.len() + self.out_of_range.len()
+    }
+
+    /// Resume el informe en el log: una línea de estadísticas y un aviso por
+    /// cada tipo de problema.
+    pub fn log(&self, source: &str) {
+        info!(
+            "Scene from {}: {} cubes ({} hidden), {} materials, {} emissive blocks, {} bookmarks, BVH of {} nodes, depth {}, built in {:.1}ms",
+            source, self.cubes, self.hidden, self.materials, self.emissive, self.bookmarks, self.bvh_nodes, self.bvh_depth, self.bvh_build_ms
+        );
+        let counts = [
+            (self.duplicates.len(), "duplicate cubes"),
+            (self.overlaps.len(), "pairs of overlapping cubes"),
+            (self.out_of_range.len(), "cubes out of range"),
+        ];
+        for (count, what) in counts {
+            if count > 0 {
+                warn!("Scene has {} {}; run with --validate to list them", count, what);
+            }
+        }
+    }
+}
+
+fn fmt_point(v: &Vector3) -> String {
+    format!("({:.2}, {:.2}, {:.2})", v.x, v.y, v.z)
+}
+
+fn mib(bytes: usize) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+fn write_list<T>(f: &mut fmt::Formatter<'_>, title: &str, items: &[T], line: impl Fn(&T) -> String) -> fmt::Result {
+    if items.is_empty() {
+        return Ok(());
+    }
+    writeln!(f, "{} ({}):", title, items.len())?;
+    for item in items.iter().take(LISTED_PROBLEMS) {
+        writeln!(f, "  {}", line(item))?;
+    }
+    if items.len() > LISTED_PROBLEMS {
+        writeln!(f, "  ... and {} more", items.len() - LISTED_PROBLEMS)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for SceneReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Cubes:      {} ({} hidden)", self.cubes, self.hidden)?;
+        writeln!(f, "Emissive:   {}", self.emissive)?;
+        writeln!(f, "Materials:  {}", self.materials)?;
+        writeln!(f, "Bookmarks:  {}", self.bookmarks)?;
+        writeln!(f, "BVH:        {} nodes, depth {}, built in {:.1} ms", self.bvh_nodes, self.bvh_depth, self.bvh_build_ms)?;
+        if let Some((cpu, gpu)) = self.texture_bytes {
+            writeln!(f, "Textures:   {:.1} MiB on the CPU, {:.1} MiB on the GPU", mib(cpu), mib(gpu))?;
+            let (count, saved) = self.downscaled;
+            if count > 0 {
+                writeln!(f, "            {} downscaled on load, {:.1} MiB saved", count, mib(saved))?;
+            }
+        }
+        write_list(f, "Duplicate cubes", &self.duplicates, fmt_point)?;
+        write_list(f, "Overlapping cubes", &self.overlaps, |(a, b)| format!("{} and {}", fmt_point(a), fmt_point(b)))?;
+        write_list(f, "Cubes out of range", &self.out_of_range, fmt_point)?;
+        if self.problems() == 0 {
+            writeln!(f, "No problems found")?;
+        }
+        Ok(())
+    }
+}
+
+/// `--validate`: carga la escena de arranque y sus texturas, imprime el informe
+/// y devuelve el código de salida: 0 sin problemas, 1 con problemas, 2 si la
+/// escena no se pudo cargar.
+pub fn run_validation(options: &SceneOptions, config: &Config) -> i32 {
+    let scene = match options.startup_scene(&options.world_settings()) {
+        Ok(scene) => scene,
+        Err(error) => {
+            eprintln!("error: {}", error.report());
+            return 2;
+        }
+    };
+    // Sin ventana solo hay copias en CPU
+    let mut textures = TextureManager::with_assets(Assets::new(&config.assets)).with_max_size(config.assets.max_texture_size);
+    for path in BLOCK_TEXTURES {
+        textures.load_cpu_texture_or_placeholder(path);
+    }
+    if let Err(error) = textures.load_cpu_skybox(SkyboxTextures::default_faces()) {
+        warn!("Using the procedural sky: {}", error.report());
+    }
+    for path in scene.materials.iter().filter_map(|m| m.texture.as_deref()) {
+        textures.load_cpu_texture_or_placeholder(path);
+    }
+
+    let report = SceneReport::new(&scene).with_textures(&textures);
+    print!("{}", report);
+    if report.problems() == 0 { 0 } else { 1 }
+}
@@ -0,0 +1,326 @@
+// scene_file.rs
+// Escena en disco (RON): materiales con nombre una sola vez, cubos que los
+// referencian por nombre y marcadores de cámara. Los materiales pueden venir
+// de la biblioteca de presets (ver `presets`); al guardar se escriben completos.
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::CameraBookmark;
+use crate::cube::Cube;
+use crate::error::Error;
+use crate::material::{Material, TextureFit, TintSource};
+use crate::presets::{PresetLibrary, PresetOverride};
+use crate::scene::{Scene, LAYER_COUNT};
+
+/// Archivo de escena por defecto, relativo al directorio de trabajo.
+pub const SCENE_FILE: &str = "scene.ron";
+
+/// Contenido de un archivo de escena.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SceneFile {
+    /// Archivo de presets a importar, relativo al de la escena; sin él, los
+    /// de serie.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset_file: Option<String>,
+    /// Materiales hechos a partir de un preset, cambiando algunos campos.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub presets: Vec<PresetOverride>,
+    /// Biblioteca de materiales; cada nombre aparece una sola vez y manda
+    /// sobre los presets del mismo nombre.
+    #[serde(default)]
+    pub materials: Vec<MaterialEntry>,
+    /// Cubos, que nombran su material.
+    pub cubes: Vec<CubeEntry>,
+    /// Marcadores de cámara.
+    #[serde(default)]
+    pub bookmarks: Vec<BookmarkEntry>,
+}
+
+/// Material con nombre tal como se guarda.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaterialEntry {
+    /// Nombre por el que lo referencian los cubos.
+    pub name: String,
+    /// Color difuso, en [0, 1].
+    pub diffuse: [f32; 3],
+    /// Peso de la parte difusa y de la especular.
+    pub albedo: [f32; 2],
+    /// Exponente especular.
+    pub specular: f32,
+    /// Fracción de luz reflejada.
+    #[serde(default)]
+    pub reflectivity: f32,
+    /// Fracción de luz que atraviesa la superficie.
+    #[serde(default)]
+    pub transparency: f32,
+    /// Índice de refracción (1 = sin desviación).
+    #[serde(default = "default_refractive_index")]
+    pub refractive_index: f32,
+    /// Rugosidad (0 = pulida), para el reflejo y la refracción.
+    #[serde(default)]
+    pub roughness: f32,
+    /// Rugosidad solo de la refracción (vidrio esmerilado); 0 = `roughness`.
+    #[serde(default)]
+    pub refraction_roughness: f32,
+    /// Número de Abbe de la dispersión; 0 = sin dispersión.
+    #[serde(default)]
+    pub abbe_number: f32,
+    /// Ruta de la textura difusa.
+    #[serde(default)]
+    pub texture: Option<String>,
+    /// Ruta del mapa de normales.
+    #[serde(default)]
+    pub normal_map: Option<String>,
+    /// Luz emitida; distinta de cero convierte el bloque en fuente de luz.
+    #[serde(default)]
+    pub emission: [f32; 3],
+    /// Tinte por bloque sacado de una paleta con ruido.
+    #[serde(default)]
+    pub tint: Option<TintEntry>,
+    /// Cae si no tiene nada debajo (arena, grava).
+    #[serde(default)]
+    pub falls: bool,
+    /// Recorta los texeles transparentes de la textura (hojas).
+    #[serde(default)]
+    pub alpha_cutout: bool,
+    /// Cómo cubre la textura una cara de otra proporción: `stretch` o `preserve`.
+    #[serde(default)]
+    pub texture_fit: TextureFit,
+}
+
+fn default_refractive_index() -> f32 {
+    1.0
+}
+
+/// Tinte con ruido guardado.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TintEntry {
+    /// Colores entre los que se interpola.
+    pub palette: Vec<[f32; 3]>,
+    /// Tamaño del ruido, en bloques.
+    pub scale: f32,
+}
+
+/// Cubo guardado.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CubeEntry {
+    /// Centro del cubo.
+    pub center: [f32; 3],
+    /// Lado del cubo.
+    pub size: f32,
+    /// Lados (ancho, alto, fondo) de una caja que no es un cubo; manda sobre `size`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extent: Option<[f32; 3]>,
+    /// Nombre del material en la biblioteca.
+    pub material: String,
+    /// Grupo de visibilidad al que pertenece.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Capa de render (0 a LAYER_COUNT - 1).
+    #[serde(default, skip_serializing_if = "is_default_layer")]
+    pub layer: u8,
+}
+
+fn is_default_layer(layer: &u8) -> bool {
+    *layer == 0
+}
+
+/// Marcador de cámara guardado.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BookmarkEntry {
+    /// Nombre que se muestra y que acepta `--camera`.
+    pub name: String,
+    /// Posición de la cámara.
+    pub eye: [f32; 3],
+    /// Punto al que mira.
+    pub center: [f32; 3],
+}
+
+fn to_vector(v: [f32; 3]) -> Vector3 {
+    Vector3::new(v[0], v[1], v[2])
+}
+
+fn to_array(v: Vector3) -> [f32; 3] {
+    [v.x, v.y, v.z]
+}
+
+impl MaterialEntry {
+    /// Entrada para `material` guardado como `name`.
+    pub fn from_material(name: &str, material: &Material) -> Self {
+        MaterialEntry {
+            name: name.to_string(),
+            diffuse: to_array(material.diffuse),
+            albedo: material.albedo,
+            specular: material.specular,
+            reflectivity: material.reflectivity,
+            transparency: material.transparency,
+            refractive_index: material.refractive_index,
+            roughness: material.roughness,
+            refraction_roughness: material.refraction_roughness,
+            abbe_number: material.abbe_number,
+            texture: material.texture.clone(),
+            normal_map: material.normal_map_id.clone(),
+            emission: to_array(material.emission),
+            tint: material.tint.as_ref().map(|tint| match tint {
+                TintSource::Noise { palette, scale } => TintEntry {
+                    palette: palette.iter().map(|c| to_array(*c)).collect(),
+                    scale: *scale,
+                },
+            }),
+            falls: material.falls,
+            alpha_cutout: material.alpha_cutout,
+            texture_fit: material.texture_fit,
+        }
+    }
+
+    /// Material descrito por la entrada.
+    pub fn to_material(&self) -> Material {
+        let mut material = Material::new(
+            to_vector(self.diffuse),
+            self.albedo,
+            self.specular,
+            self.reflectivity,
+            self.transparency,
+            self.refractive_index,
+            self.texture.clone(),
+            self.normal_map.clone(),
+            to_vector(self.emission),
+        )
+        .with_name(&self.name);
+        material.tint = self.tint.as_ref().map(|tint| TintSource::Noise {
+            palette: tint.palette.iter().map(|c| to_vector(*c)).collect::<Vec<_>>().into(),
+            scale: tint.scale,
+        });
+        material.falls = self.falls;
+        material.alpha_cutout = self.alpha_cutout;
+        material.texture_fit = self.texture_fit;
+        material.roughness = self.roughness;
+        material.refraction_roughness = self.refraction_roughness;
+        material.abbe_number = self.abbe_number;
+        material
+    }
+}
+
+impl SceneFile {
+    /// Instantánea de la escena. Los chunks en streaming no se guardan: se regeneran.
+    pub fn from_scene(scene: &Scene) -> Self {
+        let materials = scene
+            .materials
+            .iter()
+            .filter_map(|m| m.name.as_deref().map(|name| MaterialEntry::from_material(name, m)))
+            .collect();
+        let cubes = scene
+            .all_objects()
+            .filter_map(|cube| {
+                let name = cube.material.name.as_deref()?;
+                let extent = cube.max_bounds - cube.min_bounds;
+                let uniform = extent.x == extent.y && extent.x == extent.z;
+                Some(CubeEntry {
+                    center: to_array((cube.min_bounds + cube.max_bounds) * 0.5),
+                    size: extent.x,
+                    extent: (!uniform).then(|| to_array(extent)),
+                    material: name.to_string(),
+                    group: cube.group.as_deref().map(str::to_string),
+                    layer: cube.layer,
+                })
+            })
+            .collect();
+        let bookmarks = scene
+            .bookmarks
+            .iter()
+            .map(|b| BookmarkEntry { name: b.name.clone(), eye: to_array(b.eye), center: to_array(b.center) })
+            .collect();
+        SceneFile { preset_file: None, presets: Vec::new(), materials, cubes, bookmarks }
+    }
+
+    /// `into_scene_with` con los presets de serie.
+    pub fn into_scene(self) -> Result<Scene, String> {
+        self.into_scene_with(&PresetLibrary::builtin())
+    }
+
+    /// Construye la escena importando de `library` los presets que declara o
+    /// que nombran sus cubos; falla si un cubo nombra un material que no
+    /// existe o una capa fuera de rango.
+    pub fn into_scene_with(self, library: &PresetLibrary) -> Result<Scene, String> {
+        let mut entries: Vec<MaterialEntry> = Vec::new();
+        for preset in &self.presets {
+            upsert(&mut entries, preset.resolve(library)?);
+        }
+        for entry in self.materials {
+            upsert(&mut entries, entry);
+        }
+        // Presets que los cubos nombran sin declararlos, en orden de aparición
+        let mut imported: HashSet<&str> = HashSet::new();
+        for cube in &self.cubes {
+            let name = cube.material.as_str();
+            if imported.insert(name) && !entries.iter().any(|m| m.name == name) {
+                if let Some(preset) = library.get(name) {
+                    entries.push(preset.clone());
+                }
+            }
+        }
+        let materials: Vec<Arc<Material>> = entries.iter().map(|entry| Arc::new(entry.to_material())).collect();
+        let lookup = |name: &str| materials.iter().find(|m| m.name.as_deref() == Some(name));
+
+        let mut objects = Vec::with_capacity(self.cubes.len());
+        for (i, entry) in self.cubes.iter().enumerate() {
+            let material = lookup(&entry.material)
+                .ok_or_else(|| format!("cube {} uses unknown material '{}'", i, entry.material))?;
+            if entry.layer >= LAYER_COUNT {
+                return Err(format!("cube {} is on layer {}, the last layer is {}", i, entry.layer, LAYER_COUNT - 1));
+            }
+            let group = entry.group.as_deref().map(Arc::from);
+            let extent = entry.extent.map_or(Vector3::new(entry.size, entry.size, entry.size), to_vector);
+            objects.push(
+                Cube::with_extent(to_vector(entry.center), extent, Arc::clone(material))
+                    .in_group(group)
+                    .on_layer(entry.layer),
+            );
+        }
+
+        let mut scene = Scene::new(objects);
+        // El orden del archivo manda, e incluye materiales que ningún cubo usa
+        scene.materials = materials;
+        scene.bookmarks = self
+            .bookmarks
+            .into_iter()
+            .map(|b| CameraBookmark { name: b.name, eye: to_vector(b.eye), center: to_vector(b.center) })
+            .collect();
+        Ok(scene)
+    }
+}
+
+/// Sustituye el material con el nombre de `entry`, o lo añade al final.
+fn upsert(entries: &mut Vec<MaterialEntry>, entry: MaterialEntry) {
+    match entries.iter_mut().find(|m| m.name == entry.name) {
+        Some(existing) => *existing = entry,
+        None => entries.push(entry),
+    }
+}
+
+/// Lee una escena RON de `path`, con su archivo de presets si nombra uno.
+pub fn load_scene(path: impl AsRef<Path>) -> Result<Scene, Error> {
+    let path = path.as_ref();
+    let parse_error = |message: String| Error::SceneParse { path: path.to_path_buf(), message };
+    let text = fs::read_to_string(path).map_err(Error::io(path))?;
+    let file: SceneFile = ron::from_str(&text).map_err(|e| parse_error(e.to_string()))?;
+    let library = match &file.preset_file {
+        Some(presets) => PresetLibrary::load(&path.parent().unwrap_or(Path::new("")).join(presets))?,
+        None => PresetLibrary::builtin(),
+    };
+    file.into_scene_with(&library).map_err(parse_error)
+}
+
+/// Guarda `scene` como RON en `path`.
+pub fn save_scene(scene: &Scene, path: impl AsRef<Path>) -> Result<(), String> {
+    let path = path.as_ref();
+    let text = ron::ser::to_string_pretty(&SceneFile::from_scene(scene), ron::ser::PrettyConfig::default())
+        .map_err(|e| e.to_string())?;
+    fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
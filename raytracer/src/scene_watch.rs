@@ -0,0 +1,148 @@
+// scene_watch.rs
+// Recarga en caliente del archivo de escena. Unas veces por segundo se mira la
+// fecha de modificación del archivo; cuando cambia, un hilo de fondo lo vuelve a
+// leer y construye la escena con su BVH, y el hilo principal solo tiene que
+// cambiarla por la actual. Si el archivo no se puede leer, la escena en pantalla
+// no se toca.
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::scene::Scene;
+use crate::scene_file::{load_scene, SceneFile};
+
+// Cada cuánto se mira la fecha del archivo
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Qué hacer cuando el archivo cambia y la escena tiene ediciones sin guardar.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReloadEdits {
+    /// Preguntar en el HUD.
+    Ask,
+    /// Cargar el archivo y perder las ediciones.
+    Discard,
+    /// Quedarse con las ediciones e ignorar el cambio.
+    Keep,
+}
+
+/// Resultado de una recarga.
+pub enum Reload {
+    /// Escena nueva lista para sustituir a la actual.
+    Ready(Scene),
+    /// Escena nueva, pero la actual tiene ediciones que se perderían.
+    Conflict(Scene),
+    /// El archivo no se pudo leer; la escena actual sigue como está.
+    Failed(Error),
+}
+
+/// Huella del contenido guardable de la escena, para saber si dos escenas
+/// guardarían el mismo archivo.
+pub fn fingerprint(scene: &Scene) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    // El RON recoge todo lo que se guarda; los chunks en streaming no cuentan
+    ron::to_string(&SceneFile::from_scene(scene)).unwrap_or_default().hash(&mut hasher);
+    hasher.finish()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Vigila un archivo de escena y lo recarga en segundo plano cuando cambia.
+pub struct SceneWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+    loading: Option<Receiver<Result<(Scene, u64), Error>>>,
+    // Huella de la escena tal como se cargó o se guardó por última vez
+    baseline: u64,
+}
+
+impl SceneWatcher {
+    /// Vigila `path`, con `scene` como la escena cargada de él.
+    pub fn new(path: &Path, scene: &Scene) -> Self {
+        SceneWatcher {
+            path: path.to_path_buf(),
+            modified: modified(path),
+            last_check: Instant::now(),
+            loading: None,
+            baseline: fingerprint(scene),
+        }
+    }
+
+    /// Archivo vigilado.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Recarga el archivo ya, haya cambiado o no.
+    pub fn request(&mut self) {
+        if self.loading.is_some() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let path = self.path.clone();
+        thread::spawn(move || {
+            let result = load_scene(&path).map(|scene| {
+                let print = fingerprint(&scene);
+                (scene, print)
+            });
+            let _ = sender.send(result);
+        });
+        self.loading = Some(receiver);
+    }
+
+    /// Mira si el archivo cambió y recoge la recarga en curso. `current` es la
+    /// escena en pantalla, para saber si tiene ediciones sin guardar. None si
+    /// no hay nada nuevo, o si el archivo guarda justo la escena actual (p. ej.
+    /// porque se acaba de guardar desde el editor).
+    pub fn poll(&mut self, current: &Scene) -> Option<Reload> {
+        if let Some(receiver) = &self.loading {
+            let result = match receiver.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {
+                    self.loading = None;
+                    return None;
+                }
+            };
+            self.loading = None;
+            let (scene, incoming) = match result {
+                Ok(loaded) => loaded,
+                Err(error) => return Some(Reload::Failed(error)),
+            };
+            let now = fingerprint(current);
+            if incoming == now {
+                self.baseline = incoming;
+                return None;
+            }
+            return Some(if now == self.baseline { Reload::Ready(scene) } else { Reload::Conflict(scene) });
+        }
+
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return None;
+        }
+        self.last_check = Instant::now();
+        let stamp = modified(&self.path);
+        if stamp.is_some() && stamp != self.modified {
+            debug!("{} changed, reloading", self.path.display());
+            self.modified = stamp;
+            self.request();
+        }
+        None
+    }
+
+    /// Registra `scene` como la escena recién cargada del archivo.
+    pub fn accept(&mut self, scene: &Scene) {
+        self.baseline = fingerprint(scene);
+    }
+}
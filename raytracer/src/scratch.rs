@@ -0,0 +1,117 @@
+// scratch.rs
+// Reusable buffers for per-ray temporaries, one pool per render thread. Each
+// parallel job of a render takes its thread's pool into the `ShadeContext` it
+// hands down the shading functions, and puts it back when the job is done. A
+// ray that needs a list, such as the transparent hits of weighted-blended
+// transparency or the emissive lights nearest a hit, takes an empty buffer
+// from the pool and gives it back cleared when it is done, so once a thread's
+// buffers have grown to fit it traces without allocating. Buffers are handed
+// out as a stack, so a ray traced while another holds a buffer gets its own.
+// Like the ray counters, the pools live in thread-locals and nothing on the
+// hot path is shared; only a buffer that has to grow touches the shared count.
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::ray_intersect::Intersect;
+
+// Times any thread's scratch buffer had to grow, since the program started
+static GROWTHS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // This thread's pool while no `ShadeContext` holds it
+    static POOL: RefCell<ScratchPool> = RefCell::new(ScratchPool::default());
+}
+
+/// The free buffers of one thread.
+#[derive(Default)]
+pub struct ScratchPool {
+    intersects: Vec<Vec<Intersect>>,
+    // Squared distances and indices of candidate lights
+    candidates: Vec<Vec<(f32, usize)>>,
+    // Buffers handed out and not given back yet
+    taken: usize,
+}
+
+/// A buffer taken from a `ScratchPool`, empty when taken; give it back to the
+/// pool it came from once done with it.
+pub struct Buffer<T> {
+    items: Vec<T>,
+    // Capacity when taken, to tell whether it grew
+    capacity: usize,
+}
+
+impl<T> Buffer<T> {
+    fn new(items: Vec<T>) -> Self {
+        debug_assert!(items.is_empty(), "scratch buffer given back with items in it");
+        Buffer { capacity: items.capacity(), items }
+    }
+
+    // The buffer emptied for the next ray, counting it if it grew
+    fn finish(mut self) -> Vec<T> {
+        if self.items.capacity() != self.capacity {
+            GROWTHS.fetch_add(1, Ordering::Relaxed);
+        }
+        self.items.clear();
+        self.items
+    }
+}
+
+impl<T> Deref for Buffer<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Vec<T> {
+        &self.items
+    }
+}
+
+impl<T> DerefMut for Buffer<T> {
+    fn deref_mut(&mut self) -> &mut Vec<T> {
+        &mut self.items
+    }
+}
+
+impl ScratchPool {
+    /// Takes this thread's pool; an empty one if it is already taken.
+    pub fn take() -> Self {
+        POOL.take()
+    }
+
+    /// Puts a pool taken with `take` back for the next job on this thread.
+    /// Checks, in debug builds, that every buffer taken from it was given back.
+    pub fn give_back(self) {
+        debug_assert!(self.taken == 0 || std::thread::panicking(), "scratch buffer not given back");
+        POOL.set(self);
+    }
+
+    /// An empty `Intersect` buffer; see `give_back_intersects`.
+    pub fn take_intersects(&mut self) -> Buffer<Intersect> {
+        self.taken += 1;
+        Buffer::new(self.intersects.pop().unwrap_or_default())
+    }
+
+    /// Puts a buffer from `take_intersects` back, cleared.
+    pub fn give_back_intersects(&mut self, buffer: Buffer<Intersect>) {
+        self.taken -= 1;
+        self.intersects.push(buffer.finish());
+    }
+
+    /// An empty buffer of (squared distance, index) light candidates; see
+    /// `give_back_candidates`.
+    pub fn take_candidates(&mut self) -> Buffer<(f32, usize)> {
+        self.taken += 1;
+        Buffer::new(self.candidates.pop().unwrap_or_default())
+    }
+
+    /// Puts a buffer from `take_candidates` back, cleared.
+    pub fn give_back_candidates(&mut self, buffer: Buffer<(f32, usize)>) {
+        self.taken -= 1;
+        self.candidates.push(buffer.finish());
+    }
+}
+
+/// Times a scratch buffer had to allocate or grow, over every thread since
+/// the program started. Unchanged over a frame means it allocated nothing.
+pub fn growths() -> u64 {
+    GROWTHS.load(Ordering::Relaxed)
+}
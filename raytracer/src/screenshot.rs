@@ -0,0 +1,207 @@
+// screenshot.rs
+// PNG export of rendered frames. Bracketing re-tone-maps the float frame that
+// was already rendered; nothing is traced again. Screenshots copy the displayed
+// pixels and encode them on a background thread, next to a JSON file with what
+// is needed to render the same view again; the PNG carries the same JSON in a
+// tEXt chunk, so it still describes itself once copied away from the file.
+// The PNGs go through an `ExportQueue`; the JSON file is small enough to
+// write on the spot.
+use std::fs;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use raylib::prelude::*;
+use serde::Serialize;
+
+use crate::camera::{Camera, FovAxis};
+use crate::config::QualityConfig;
+use crate::error::Error;
+use crate::framebuffer::{Backpressure, ExportFormat, ExportPixels, ExportQueue, Framebuffer};
+use crate::settings::RenderSettings;
+
+/// Directory screenshots are written to, created on the first one.
+pub const SCREENSHOT_DIR: &str = "screenshots";
+
+/// Screenshots that can wait to be encoded before `ScreenshotSaver::save`
+/// waits for one to finish.
+const SCREENSHOT_QUEUE: usize = 8;
+
+/// Exposure offsets, in stops, of an exposure bracket.
+pub const BRACKET_STOPS: [f32; 3] = [-2.0, 0.0, 2.0];
+
+/// Writes `width` x `height` pixels, row by row from the top, as a PNG.
+pub fn save_png(path: &str, pixels: &[Color], width: i32, height: i32) -> Result<(), String> {
+    if pixels.len() != (width * height) as usize {
+        return Err(format!("{}: expected {}x{} pixels, got {}", path, width, height, pixels.len()));
+    }
+    write_png(Path::new(path), pixels, width, height, &[]).map_err(|error| error.report())
+}
+
+/// Writes `width` x `height` pixels, row by row from the top, as an 8-bit
+/// RGBA PNG, with each `(keyword, text)` of `text` as a tEXt chunk. A file
+/// that cannot be created or written is an `Error::ImageEncode`.
+pub fn write_png(path: &Path, pixels: &[Color], width: i32, height: i32, text: &[(&str, String)]) -> Result<(), Error> {
+    assert_eq!(pixels.len(), (width * height) as usize, "the pixels do not fill the image");
+    let encode_error = |source| Error::ImageEncode { path: path.to_path_buf(), source };
+    let file = fs::File::create(path).map_err(|error| encode_error(png::EncodingError::IoError(error)))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, value) in text {
+        encoder.add_text_chunk(keyword.to_string(), value.clone()).map_err(encode_error)?;
+    }
+    let bytes: Vec<u8> = pixels.iter().flat_map(|c| [c.r, c.g, c.b, c.a]).collect();
+    let mut writer = encoder.write_header().map_err(encode_error)?;
+    writer.write_image_data(&bytes).map_err(encode_error)?;
+    writer.finish().map_err(encode_error)
+}
+
+/// Writes `<prefix>_-2ev.png`, `<prefix>_+0ev.png` and `<prefix>_+2ev.png` from
+/// the pre-tonemap frame. Returns the paths written.
+pub fn export_bracket(prefix: &str, frame: &Framebuffer, settings: &RenderSettings) -> Result<Vec<String>, String> {
+    let mut written = Vec::with_capacity(BRACKET_STOPS.len());
+    for stops in BRACKET_STOPS {
+        let path = format!("{}_{:+}ev.png", prefix, stops);
+        let pixels = frame.resolve(settings, stops.exp2());
+        save_png(&path, &pixels, frame.width(), frame.height())?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// What a screenshot showed, written next to it as `shot_*.json`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShotMetadata {
+    /// Image width in pixels.
+    pub width: i32,
+    /// Image height in pixels.
+    pub height: i32,
+    /// Camera position.
+    pub eye: [f32; 3],
+    /// Point the camera looks at.
+    pub center: [f32; 3],
+    /// Field of view in degrees, along `fov_axis`.
+    pub fov: f32,
+    /// Axis `fov` is measured on.
+    pub fov_axis: FovAxis,
+    /// Day/night cycle angle in radians.
+    pub time_of_day: f32,
+    /// Seed of the generated caves, if the scene has them.
+    pub seed: Option<u32>,
+    /// Render settings, in the form of the `[quality]` section.
+    pub quality: QualityConfig,
+}
+
+impl ShotMetadata {
+    /// The state behind the current frame.
+    pub fn capture(
+        camera: &Camera,
+        width: i32,
+        height: i32,
+        time_of_day: f32,
+        seed: Option<u32>,
+        settings: &RenderSettings,
+    ) -> Self {
+        ShotMetadata {
+            width,
+            height,
+            eye: [camera.eye.x, camera.eye.y, camera.eye.z],
+            center: [camera.center.x, camera.center.y, camera.center.z],
+            fov: camera.fov.to_degrees(),
+            fov_axis: camera.fov_axis,
+            time_of_day,
+            seed,
+            quality: QualityConfig::from(settings),
+        }
+    }
+
+    /// tEXt chunks that make a PNG describe itself: the program that wrote
+    /// it, and this metadata as JSON under `Comment`.
+    pub fn png_text(&self) -> Vec<(&'static str, String)> {
+        let json = serde_json::to_string(self).expect("screenshot metadata always serializes");
+        vec![("Software", format!("raytracer {}", env!("CARGO_PKG_VERSION"))), ("Comment", json)]
+    }
+}
+
+/// Writes screenshots without holding up the frame: `save` queues the pixels
+/// for an `ExportQueue` and `poll` reports each one once it is on disk.
+/// Dropping the saver waits for the queued ones to be written.
+pub struct ScreenshotSaver {
+    queue: ExportQueue,
+    // Shots that failed before reaching the queue
+    failed: Vec<Error>,
+    // Timestamp of the last shot and the suffix the next one in the same
+    // second gets
+    last_stem: Option<(String, u32)>,
+}
+
+impl Default for ScreenshotSaver {
+    fn default() -> Self {
+        ScreenshotSaver {
+            queue: ExportQueue::new(2, SCREENSHOT_QUEUE, Backpressure::Block),
+            failed: Vec::new(),
+            last_stem: None,
+        }
+    }
+}
+
+impl ScreenshotSaver {
+    /// Writes the JSON file of `screenshots/shot_YYYYMMDD_HHMMSS.png` and
+    /// queues the PNG. A second shot within the same second gets a `_2`,
+    /// `_3`, ... suffix.
+    pub fn save(&mut self, pixels: Vec<Color>, metadata: ShotMetadata) {
+        // Picked here rather than on a thread, so two shots still being
+        // written cannot both claim the same name
+        let path = self.unused_shot_path(Path::new(SCREENSHOT_DIR));
+        match write_sidecar(&path, &metadata) {
+            Ok(()) => {
+                let format = ExportFormat::Png(metadata.png_text());
+                self.queue.enqueue(ExportPixels::Colors(pixels), metadata.width, metadata.height, path, format);
+            }
+            Err(error) => self.failed.push(error),
+        }
+    }
+
+    /// Screenshots queued or being written.
+    pub fn pending(&self) -> usize {
+        self.queue.pending()
+    }
+
+    fn unused_shot_path(&mut self, dir: &Path) -> PathBuf {
+        let stem = chrono::Local::now().format("shot_%Y%m%d_%H%M%S").to_string();
+        let mut index = match &self.last_stem {
+            Some((last, next)) if *last == stem => *next,
+            _ => 1,
+        };
+        let name = |index: u32| match index {
+            1 => format!("{}.png", stem),
+            _ => format!("{}_{}.png", stem, index),
+        };
+        while dir.join(name(index)).exists() {
+            index += 1;
+        }
+        let path = dir.join(name(index));
+        self.last_stem = Some((stem, index + 1));
+        path
+    }
+
+    /// A screenshot that finished since the last call: its PNG path, or why it failed.
+    pub fn poll(&mut self) -> Option<Result<PathBuf, Error>> {
+        self.failed.pop().map(Err).or_else(|| self.queue.poll())
+    }
+
+    /// Waits for every queued screenshot to be written and returns the
+    /// results `poll` has not given yet.
+    pub fn finish(self) -> Vec<Result<PathBuf, Error>> {
+        self.failed.into_iter().map(Err).chain(self.queue.finish()).collect()
+    }
+}
+
+fn write_sidecar(path: &Path, metadata: &ShotMetadata) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(Error::io(dir))?;
+    }
+    let sidecar = path.with_extension("json");
+    let json = serde_json::to_string_pretty(metadata).expect("screenshot metadata always serializes");
+    fs::write(&sidecar, json).map_err(Error::io(&sidecar))
+}
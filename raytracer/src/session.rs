@@ -0,0 +1,138 @@
+// session.rs
+// What the interactive window leaves behind when it closes: a summary on the
+// console and the camera and scene in `last_session.json`, which `[session]
+// restore` reads on the next launch. A panic hook writes the same file with the
+// panic message, from whatever the last frame stored. Writing the dump never
+// panics; a failure is reported and ignored.
+use std::fs;
+use std::io::Write;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use log::{error, info, warn};
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{Camera, CameraBookmark};
+
+/// Where the dump goes, in the working directory.
+pub const SESSION_FILE: &str = "last_session.json";
+
+/// The state written to `last_session.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionState {
+    /// `--scene` of the run, if one was given; otherwise the next launch
+    /// picks its scene as usual.
+    pub scene: Option<PathBuf>,
+    /// Camera position.
+    pub eye: [f32; 3],
+    /// Point the camera looks at.
+    pub center: [f32; 3],
+    /// Sun angle of the day/night cycle, in radians.
+    pub time_of_day: f32,
+    /// Frames rendered.
+    pub frames: u64,
+    /// Frames rendered per second of the run, pauses included.
+    pub average_fps: f64,
+    /// Edits made, undone ones included.
+    pub edits: usize,
+    /// The panic message, if the run ended in one.
+    pub panic: Option<String>,
+}
+
+impl SessionState {
+    /// Reads `path`. None, with a warning, if it is missing or unreadable.
+    pub fn load(path: &Path) -> Option<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) => {
+                warn!("Not restoring the last session: {}: {}", path.display(), error);
+                return None;
+            }
+        };
+        match serde_json::from_str(&text) {
+            Ok(state) => Some(state),
+            Err(error) => {
+                warn!("Not restoring the last session: {}: {}", path.display(), error);
+                None
+            }
+        }
+    }
+
+    /// The saved camera as a bookmark to `Camera::go_to`.
+    pub fn camera(&self) -> CameraBookmark {
+        let [x, y, z] = self.eye;
+        let [cx, cy, cz] = self.center;
+        CameraBookmark { name: "Last session".to_string(), eye: Vector3::new(x, y, z), center: Vector3::new(cx, cy, cz) }
+    }
+
+    /// The console summary: frames, average frame rate and edits.
+    pub fn summary(&self) -> String {
+        format!("{} frames rendered, {:.1} FPS on average, {} edits made", self.frames, self.average_fps, self.edits)
+    }
+
+    // Errors are returned as text so the panic hook can report them without
+    // building anything that might panic itself
+    fn write(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|error| error.to_string())?;
+        fs::write(path, json).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+}
+
+/// The dump of the running session, updated every frame.
+pub struct SessionDump {
+    path: PathBuf,
+    state: Arc<Mutex<SessionState>>,
+    started: Instant,
+}
+
+impl SessionDump {
+    /// Starts the dump for a run of `scene` and installs the panic hook that
+    /// writes it.
+    pub fn new(path: &Path, scene: Option<PathBuf>) -> Self {
+        let state = Arc::new(Mutex::new(SessionState { scene, ..SessionState::default() }));
+        let hook_state = Arc::clone(&state);
+        let hook_path = path.to_path_buf();
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            // try_lock: the panic may have happened mid-update with the lock held
+            if let Ok(mut state) = hook_state.try_lock() {
+                state.panic = Some(info.to_string());
+                let mut stderr = std::io::stderr();
+                let _ = writeln!(stderr, "{}", state.summary());
+                if let Err(error) = state.write(&hook_path) {
+                    let _ = writeln!(stderr, "Could not write the session dump: {}", error);
+                }
+            }
+            previous(info);
+        }));
+        SessionDump { path: path.to_path_buf(), state, started: Instant::now() }
+    }
+
+    /// Stores this frame's camera, sun angle and counts.
+    pub fn update(&self, camera: &Camera, time_of_day: f32, frames: u64, edits: usize) {
+        let seconds = self.started.elapsed().as_secs_f64();
+        if let Ok(mut state) = self.state.lock() {
+            state.eye = [camera.eye.x, camera.eye.y, camera.eye.z];
+            state.center = [camera.center.x, camera.center.y, camera.center.z];
+            state.time_of_day = time_of_day;
+            state.frames = frames;
+            state.average_fps = if seconds > 0.0 { frames as f64 / seconds } else { 0.0 };
+            state.edits = edits;
+        }
+    }
+
+    /// Prints the summary and writes the dump, on a normal exit.
+    pub fn finish(&self) {
+        let Ok(state) = self.state.lock() else {
+            return;
+        };
+        let _ = writeln!(std::io::stdout(), "{}", state.summary());
+        match state.write(&self.path) {
+            Ok(()) => info!("Saved the session to {}", self.path.display()),
+            Err(error) => error!("Could not write the session dump: {}", error),
+        }
+    }
+}
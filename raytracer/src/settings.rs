@@ -0,0 +1,319 @@
+// settings.rs
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How primary rays resolve transparent surfaces.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TransparencyMode {
+    /// Refraction rays through every transparent hit. Accurate; the default.
+    Recursive,
+    /// Fast preview: weighted-blended order-independent transparency. Transparent
+    /// hits in front of the opaque surface are accumulated with a depth weight and
+    /// composited over it, with no refraction and no sorting.
+    WeightedBlended,
+}
+
+impl TransparencyMode {
+    /// Label shown in the HUD.
+    pub fn name(self) -> &'static str {
+        match self {
+            TransparencyMode::Recursive => "Recursive",
+            TransparencyMode::WeightedBlended => "Fast (OIT)",
+        }
+    }
+}
+
+/// How the primary pass hands out its pixels to the render threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Schedule {
+    /// One job per band of packet rows, which rayon splits between the threads
+    /// as they run out of work; the original.
+    #[default]
+    Bands,
+    /// The same bands, but never split below a quarter of a thread's share
+    /// (`with_min_len`), for fewer and larger jobs.
+    GroupedBands,
+    /// A shared queue of small square tiles, nearest the middle of the frame
+    /// first, that each thread takes from until it is empty. The middle is
+    /// usually the expensive part (glass, water), so it starts first and the
+    /// cheap sky at the borders fills in the end of the frame.
+    CenterOut,
+}
+
+impl Schedule {
+    /// Every policy, for the benchmark to sweep.
+    pub const ALL: [Schedule; 3] = [Schedule::Bands, Schedule::GroupedBands, Schedule::CenterOut];
+
+    /// Label shown in the HUD, the settings panel and the benchmark.
+    pub fn name(self) -> &'static str {
+        match self {
+            Schedule::Bands => "Bands",
+            Schedule::GroupedBands => "Grouped bands",
+            Schedule::CenterOut => "Center-out tiles",
+        }
+    }
+
+    /// The next one, for keys and panels that cycle through them.
+    pub fn next(self) -> Self {
+        match self {
+            Schedule::Bands => Schedule::GroupedBands,
+            Schedule::GroupedBands => Schedule::CenterOut,
+            Schedule::CenterOut => Schedule::Bands,
+        }
+    }
+}
+
+/// Operator that maps HDR radiance to [0, 1] for display.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToneMapping {
+    /// Clip at 1.0; the original look.
+    Clamp,
+    /// Extended Reinhard with `white_point` as the value that maps to white.
+    Reinhard,
+}
+
+/// Transfer function that encodes the tone-mapped values into the 8-bit frame,
+/// for the window and every exported PNG alike.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputTransfer {
+    /// Values written as they are; the original output. Viewers read the
+    /// bytes as sRGB, so midtones come out darker than the radiance.
+    #[default]
+    Raw,
+    /// The sRGB curve, with its linear segment near black.
+    Srgb,
+    /// A plain 1/2.2 power.
+    Gamma22,
+    /// The BT.709 camera curve that video pipelines expect.
+    Bt709,
+}
+
+impl OutputTransfer {
+    /// Encodes a display value; the input is clamped to [0, 1].
+    pub fn encode(self, value: f32) -> f32 {
+        let v = value.clamp(0.0, 1.0);
+        match self {
+            OutputTransfer::Raw => v,
+            OutputTransfer::Srgb if v <= 0.003_130_8 => v * 12.92,
+            OutputTransfer::Srgb => 1.055 * v.powf(1.0 / 2.4) - 0.055,
+            OutputTransfer::Gamma22 => v.powf(1.0 / 2.2),
+            OutputTransfer::Bt709 if v < 0.018 => v * 4.5,
+            OutputTransfer::Bt709 => 1.099 * v.powf(0.45) - 0.099,
+        }
+    }
+
+    /// Label shown in the HUD and the settings panel.
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputTransfer::Raw => "Raw",
+            OutputTransfer::Srgb => "sRGB",
+            OutputTransfer::Gamma22 => "Gamma 2.2",
+            OutputTransfer::Bt709 => "BT.709",
+        }
+    }
+
+    /// The next one, for keys and panels that cycle through them.
+    pub fn next(self) -> Self {
+        match self {
+            OutputTransfer::Raw => OutputTransfer::Srgb,
+            OutputTransfer::Srgb => OutputTransfer::Gamma22,
+            OutputTransfer::Gamma22 => OutputTransfer::Bt709,
+            OutputTransfer::Bt709 => OutputTransfer::Raw,
+        }
+    }
+}
+
+/// Gradient sky shown when no skybox textures are loaded. Colors blend
+/// continuously from the horizon toward the zenith above and the ground below.
+#[derive(Debug, Clone)]
+pub struct ProceduralSky {
+    /// Color looking straight down, below the horizon.
+    pub ground_color: Vector3,
+    /// Color at the horizon.
+    pub horizon_color: Vector3,
+    /// Color looking straight up.
+    pub zenith_color: Vector3,
+    /// How fast the horizon color fades with elevation; higher gives a thinner haze band.
+    pub horizon_sharpness: f32,
+    /// Brightness once the sun is below the horizon, as a fraction of daylight.
+    pub night_brightness: f32,
+}
+
+impl Default for ProceduralSky {
+    fn default() -> Self {
+        ProceduralSky {
+            ground_color: Vector3::new(0.32, 0.3, 0.28),
+            horizon_color: Vector3::new(0.78, 0.84, 0.92),
+            zenith_color: Vector3::new(0.22, 0.42, 0.85),
+            horizon_sharpness: 4.0,
+            night_brightness: 0.12,
+        }
+    }
+}
+
+impl ProceduralSky {
+    /// Sky color in `direction`. `sun_elevation` is the sine of the sun's
+    /// height (1 at noon, negative at night) and dims the whole gradient.
+    pub fn sample(&self, direction: Vector3, sun_elevation: f32) -> Vector3 {
+        let length = direction.length();
+        let y = if length > 0.0 { direction.y / length } else { 0.0 };
+        // Both sides start from the horizon color at y = 0, so there is no seam
+        let blend = 1.0 - (-y.abs() * self.horizon_sharpness.max(0.0)).exp();
+        let far = if y >= 0.0 { self.zenith_color } else { self.ground_color };
+        let color = self.horizon_color + (far - self.horizon_color) * blend;
+
+        let t = ((sun_elevation + 0.2) / 0.5).clamp(0.0, 1.0);
+        let daylight = t * t * (3.0 - 2.0 * t);
+        let night = self.night_brightness.clamp(0.0, 1.0);
+        color * (night + (1.0 - night) * daylight)
+    }
+}
+
+/// Quality knobs for the renderer that used to be hardcoded in `main.rs`.
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    /// Shadow rays used to estimate the average occluder distance (PCSS blocker search).
+    pub shadow_blocker_samples: u32,
+    /// Shadow rays averaged across the estimated penumbra once a blocker was found.
+    pub shadow_filter_samples: u32,
+    /// Take the sun's shadows from an orthographic depth map of the static
+    /// cubes instead of tracing shadow rays; see `shadow_map`.
+    pub sun_shadow_map: bool,
+    /// Texels along each side of the sun's shadow map.
+    pub shadow_map_resolution: u32,
+    /// Trace primary rays in square pixel packets sharing one BVH traversal.
+    /// Off by default: on the benchmark scenes packets are no faster than
+    /// one ray at a time.
+    pub packet_traversal: bool,
+    /// Walk the flattened rope BVH instead of the crate's recursive traversal.
+    pub stackless_traversal: bool,
+    /// SAH buckets per split when the scene's BVH is built, from 2 to 32; 6
+    /// builds the tree the `bvh` crate builds. `Scene::set_sah_buckets`
+    /// rebuilds the tree when it changes.
+    pub bvh_sah_buckets: u32,
+    /// Cubes the rope traversal treats as one leaf: a subtree with at most
+    /// this many is tested cube by cube once its box is hit, skipping the
+    /// boxes inside. 1 tests every box; the stack traversal and primary ray
+    /// packets always do.
+    pub bvh_leaf_size: u32,
+    /// Exponential distance fog applied to primary hits (0.0 disables it).
+    pub fog_density: f32,
+    /// Color distant geometry fades to.
+    pub fog_color: Vector3,
+    /// Scale the local (diffuse + specular) term by what reflection and
+    /// transparency leave over, so a material never returns more than it receives.
+    pub energy_conserving: bool,
+    /// How transparent materials are traced.
+    pub transparency: TransparencyMode,
+    /// Trace the reflection and refraction rays of camera hits for one pixel
+    /// of each 2x2 quad only, and give the others the average of those on the
+    /// same surface (material, depth and normal); pixels with none nearby
+    /// trace their own. Direct lighting stays at full resolution.
+    pub half_res_secondary: bool,
+    /// Rays per pixel on geometry edges found in the first pass; 1 disables
+    /// the edge supersampling pass.
+    pub edge_samples: u32,
+    /// Operator that maps HDR radiance to display colors.
+    pub tone_mapping: ToneMapping,
+    /// Linear multiplier applied before tone mapping.
+    pub exposure: f32,
+    /// Radiance that Reinhard maps to pure white; highlights roll off below it.
+    pub white_point: f32,
+    /// Encoding of the tone-mapped values in the 8-bit output.
+    pub output_transfer: OutputTransfer,
+    /// Up to one 8-bit step of fixed per-pixel noise added before the
+    /// encoded values are rounded, so smooth gradients such as the sky do not
+    /// band.
+    pub dither: bool,
+    /// Worker threads in the render pool; 1 traces serially.
+    pub threads: usize,
+    /// How the primary pass splits the frame between the threads.
+    pub schedule: Schedule,
+    /// Reflection and refraction bounces before a ray returns the sky.
+    pub max_depth: u32,
+    /// Reflection and refraction rays one camera ray may spawn over all its
+    /// bounces; further bounces return the sky instead. 0 leaves it unlimited.
+    pub bounce_budget: u32,
+    /// Fraction of the window resolution the CPU traces at; the frame is
+    /// upscaled to the window for display.
+    pub render_scale: f32,
+    /// Sky color seen by rays that hit nothing.
+    pub sky: ProceduralSky,
+    /// Hits farther than this from their ray's origin take their color from
+    /// the 4x4 mini-mip of their texture instead of a texel: out there a texel
+    /// is smaller than a pixel and only adds noise. 0 turns it off.
+    pub texture_lod_distance: f32,
+    /// Soft minimum distance of an emissive block's light, in multiples of the
+    /// block's size. Keeps surfaces right next to a torch from blowing out.
+    pub emissive_soft_radius: f32,
+    /// Emissive blocks that could add less than this to any color channel of
+    /// a hit, even unshadowed and shining head on, are skipped along with
+    /// their shadow ray. Each skipped block changes the hit by at most this
+    /// much; 0 traces every nearby block.
+    pub emissive_cull_epsilon: f32,
+    /// Sine of the sun's height in the day/night cycle, updated every frame.
+    pub sun_elevation: f32,
+    /// Glow around pixels brighter than `bloom_threshold`.
+    pub bloom: bool,
+    /// Radiance luminance a pixel must exceed to glow. A diffuse surface
+    /// reflects at most the sunlight falling on it, 1.0, so only emissive
+    /// blocks cross it.
+    pub bloom_threshold: f32,
+    /// How much of the blurred glow is added back.
+    pub bloom_intensity: f32,
+    /// Blur radius of the glow in pixels.
+    pub bloom_radius: u32,
+    /// Count rays, BVH node visits and cube tests and time the stages of each
+    /// frame; see `profile`. Off, the counters cost next to nothing.
+    pub profile: bool,
+    /// Trace the shadow of each nearby emissive block once per block face and
+    /// frame, at the face's center, and reuse it for every primary hit on the
+    /// face. Much cheaper with many torches, but shadows no longer vary across
+    /// a face; reflections still trace their own.
+    pub emissive_shadow_cache: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            shadow_blocker_samples: 4,
+            shadow_filter_samples: 8,
+            sun_shadow_map: false,
+            shadow_map_resolution: 1024,
+            packet_traversal: false,
+            stackless_traversal: true,
+            bvh_sah_buckets: 6,
+            bvh_leaf_size: 1,
+            fog_density: 0.0,
+            fog_color: Vector3::new(0.6, 0.63, 0.68),
+            energy_conserving: false,
+            transparency: TransparencyMode::Recursive,
+            half_res_secondary: false,
+            edge_samples: 4,
+            tone_mapping: ToneMapping::Clamp,
+            exposure: 1.0,
+            white_point: 4.0,
+            output_transfer: OutputTransfer::Raw,
+            dither: true,
+            threads: num_cpus::get(),
+            schedule: Schedule::Bands,
+            max_depth: 1,
+            bounce_budget: 64,
+            render_scale: 1.0,
+            sky: ProceduralSky::default(),
+            texture_lod_distance: 0.0,
+            emissive_soft_radius: 1.5,
+            emissive_cull_epsilon: 0.0,
+            sun_elevation: 1.0,
+            bloom: true,
+            bloom_threshold: 1.0,
+            bloom_intensity: 0.6,
+            bloom_radius: 6,
+            profile: false,
+            emissive_shadow_cache: false,
+        }
+    }
+}
@@ -0,0 +1,255 @@
+// settings_panel.rs
+// In-window panel listing the render settings with their current values. While
+// it is open the orbit keys move the selection (up/down) and change the
+// selected value (left/right), and the camera stays still; while it is closed
+// it reads no keys but its own toggle. Changes apply to the live
+// `RenderSettings` from the next frame on.
+use raylib::prelude::*;
+
+use crate::input::{Action, KeyBindings};
+use crate::settings::{RenderSettings, ToneMapping, TransparencyMode};
+
+const EDGE_SAMPLE_STEPS: [u32; 4] = [1, 4, 9, 16];
+
+/// One row: a label, how to show the value and how to step it.
+struct Entry {
+    label: &'static str,
+    value: fn(&RenderSettings) -> String,
+    // Steps the value by `direction` (-1 or 1)
+    adjust: fn(&mut RenderSettings, i32),
+}
+
+fn on_off(value: bool) -> String {
+    if value { "on" } else { "off" }.to_string()
+}
+
+fn step_f32(value: &mut f32, direction: i32, step: f32, min: f32, max: f32) {
+    // Rounded to the step so repeated presses do not drift
+    *value = ((*value / step).round() + direction as f32).clamp(min / step, max / step) * step;
+}
+
+fn step_u32(value: &mut u32, direction: i32, min: u32, max: u32) {
+    *value = (*value as i64 + direction as i64).clamp(min as i64, max as i64) as u32;
+}
+
+const ENTRIES: &[Entry] = &[
+    Entry {
+        label: "Edge samples",
+        value: |s| s.edge_samples.to_string(),
+        adjust: |s, d| {
+            let index = EDGE_SAMPLE_STEPS.iter().position(|&n| n >= s.edge_samples).unwrap_or(EDGE_SAMPLE_STEPS.len() - 1) as i32;
+            s.edge_samples = EDGE_SAMPLE_STEPS[(index + d).clamp(0, EDGE_SAMPLE_STEPS.len() as i32 - 1) as usize];
+        },
+    },
+    Entry { label: "Max depth", value: |s| s.max_depth.to_string(), adjust: |s, d| step_u32(&mut s.max_depth, d, 0, 16) },
+    Entry {
+        label: "Half-res reflections",
+        value: |s| on_off(s.half_res_secondary),
+        adjust: |s, _| s.half_res_secondary = !s.half_res_secondary,
+    },
+    Entry {
+        label: "Render scale",
+        value: |s| format!("{:.1}", s.render_scale),
+        adjust: |s, d| step_f32(&mut s.render_scale, d, 0.1, 0.1, 1.0),
+    },
+    Entry {
+        label: "Shadow blocker rays",
+        value: |s| s.shadow_blocker_samples.to_string(),
+        adjust: |s, d| step_u32(&mut s.shadow_blocker_samples, d, 1, 32),
+    },
+    Entry {
+        label: "Shadow filter rays",
+        value: |s| s.shadow_filter_samples.to_string(),
+        adjust: |s, d| step_u32(&mut s.shadow_filter_samples, d, 1, 64),
+    },
+    Entry {
+        label: "Sun shadow map",
+        value: |s| on_off(s.sun_shadow_map),
+        adjust: |s, _| s.sun_shadow_map = !s.sun_shadow_map,
+    },
+    Entry {
+        label: "Packet traversal",
+        value: |s| on_off(s.packet_traversal),
+        adjust: |s, _| s.packet_traversal = !s.packet_traversal,
+    },
+    Entry {
+        label: "BVH SAH buckets",
+        value: |s| s.bvh_sah_buckets.to_string(),
+        adjust: |s, d| step_u32(&mut s.bvh_sah_buckets, d, 2, 32),
+    },
+    Entry {
+        label: "BVH leaf size",
+        value: |s| s.bvh_leaf_size.to_string(),
+        adjust: |s, d| step_u32(&mut s.bvh_leaf_size, d, 1, 16),
+    },
+    Entry {
+        label: "Scheduling",
+        value: |s| s.schedule.name().to_string(),
+        adjust: |s, _| s.schedule = s.schedule.next(),
+    },
+    Entry {
+        label: "Energy conserving",
+        value: |s| on_off(s.energy_conserving),
+        adjust: |s, _| s.energy_conserving = !s.energy_conserving,
+    },
+    Entry {
+        label: "Glass",
+        value: |s| s.transparency.name().to_string(),
+        adjust: |s, _| {
+            s.transparency = match s.transparency {
+                TransparencyMode::Recursive => TransparencyMode::WeightedBlended,
+                TransparencyMode::WeightedBlended => TransparencyMode::Recursive,
+            }
+        },
+    },
+    Entry {
+        label: "Tone mapping",
+        value: |s| match s.tone_mapping {
+            ToneMapping::Clamp => "Clamp".to_string(),
+            ToneMapping::Reinhard => "Reinhard".to_string(),
+        },
+        adjust: |s, _| {
+            s.tone_mapping = match s.tone_mapping {
+                ToneMapping::Clamp => ToneMapping::Reinhard,
+                ToneMapping::Reinhard => ToneMapping::Clamp,
+            }
+        },
+    },
+    Entry {
+        label: "Exposure",
+        value: |s| format!("{:.1}", s.exposure),
+        adjust: |s, d| step_f32(&mut s.exposure, d, 0.1, 0.1, 8.0),
+    },
+    Entry {
+        label: "White point",
+        value: |s| format!("{:.1}", s.white_point),
+        adjust: |s, d| step_f32(&mut s.white_point, d, 0.5, 0.5, 32.0),
+    },
+    Entry {
+        label: "Output transfer",
+        value: |s| s.output_transfer.name().to_string(),
+        adjust: |s, _| s.output_transfer = s.output_transfer.next(),
+    },
+    Entry { label: "Dither", value: |s| on_off(s.dither), adjust: |s, _| s.dither = !s.dither },
+    Entry {
+        label: "Texture LOD distance",
+        value: |s| if s.texture_lod_distance > 0.0 { format!("{:.0}", s.texture_lod_distance) } else { "off".to_string() },
+        adjust: |s, d| step_f32(&mut s.texture_lod_distance, d, 8.0, 0.0, 256.0),
+    },
+    Entry {
+        label: "Block light radius",
+        value: |s| format!("{:.2}", s.emissive_soft_radius),
+        adjust: |s, d| step_f32(&mut s.emissive_soft_radius, d, 0.25, 0.0, 8.0),
+    },
+    Entry {
+        label: "Block light cull",
+        value: |s| if s.emissive_cull_epsilon > 0.0 { format!("{:.3}", s.emissive_cull_epsilon) } else { "off".to_string() },
+        adjust: |s, d| step_f32(&mut s.emissive_cull_epsilon, d, 0.001, 0.0, 0.05),
+    },
+    Entry {
+        label: "Block light cache",
+        value: |s| on_off(s.emissive_shadow_cache),
+        adjust: |s, _| s.emissive_shadow_cache = !s.emissive_shadow_cache,
+    },
+    Entry { label: "Bloom", value: |s| on_off(s.bloom), adjust: |s, _| s.bloom = !s.bloom },
+    Entry {
+        label: "Bloom threshold",
+        value: |s| format!("{:.1}", s.bloom_threshold),
+        adjust: |s, d| step_f32(&mut s.bloom_threshold, d, 0.1, 0.0, 16.0),
+    },
+    Entry {
+        label: "Bloom intensity",
+        value: |s| format!("{:.1}", s.bloom_intensity),
+        adjust: |s, d| step_f32(&mut s.bloom_intensity, d, 0.1, 0.0, 4.0),
+    },
+    Entry {
+        label: "Bloom radius",
+        value: |s| s.bloom_radius.to_string(),
+        adjust: |s, d| step_u32(&mut s.bloom_radius, d, 0, 64),
+    },
+    Entry { label: "Profile", value: |s| on_off(s.profile), adjust: |s, _| s.profile = !s.profile },
+];
+
+/// The settings panel and whether its changes go back to the config file.
+pub struct SettingsPanel {
+    /// True while the panel is shown.
+    pub open: bool,
+    /// Write the quality settings to the config file on exit; the last row of
+    /// the panel.
+    pub save_on_exit: bool,
+    selected: usize,
+    changed: bool,
+}
+
+impl SettingsPanel {
+    /// A closed panel; `save_on_exit` is the starting value of its last row.
+    pub fn new(save_on_exit: bool) -> Self {
+        SettingsPanel { open: false, save_on_exit, selected: 0, changed: false }
+    }
+
+    /// True once any value was changed from the panel.
+    pub fn changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Handles this frame's keys. Only the toggle is read while closed.
+    pub fn handle_input(&mut self, window: &RaylibHandle, keys: &KeyBindings, settings: &mut RenderSettings) {
+        if keys.pressed(window, Action::SettingsPanel) {
+            self.open = !self.open;
+        }
+        if !self.open {
+            return;
+        }
+        // The rows, then the save-on-exit row
+        let rows = ENTRIES.len() + 1;
+        if keys.pressed(window, Action::OrbitDown) {
+            self.selected = (self.selected + 1) % rows;
+        }
+        if keys.pressed(window, Action::OrbitUp) {
+            self.selected = (self.selected + rows - 1) % rows;
+        }
+        let direction = if keys.pressed(window, Action::OrbitRight) {
+            1
+        } else if keys.pressed(window, Action::OrbitLeft) {
+            -1
+        } else {
+            return;
+        };
+        match ENTRIES.get(self.selected) {
+            Some(entry) => {
+                (entry.adjust)(settings, direction);
+                self.changed = true;
+            }
+            None => self.save_on_exit = !self.save_on_exit,
+        }
+    }
+
+    /// Draws the panel at (x, y) when open.
+    pub fn draw(&self, d: &mut impl RaylibDraw, keys: &KeyBindings, settings: &RenderSettings, x: i32, y: i32) {
+        if !self.open {
+            return;
+        }
+        let row_height = 16;
+        let height = 48 + row_height * (ENTRIES.len() as i32 + 1);
+        d.draw_rectangle(x, y, 280, height, Color::new(0, 0, 0, 190));
+        d.draw_text(&format!("Settings ({}: close)", keys.settings_panel), x + 8, y + 8, 16, Color::WHITE);
+        let save = on_off(self.save_on_exit);
+        let rows = ENTRIES
+            .iter()
+            .map(|entry| (entry.label, (entry.value)(settings)))
+            .chain(std::iter::once(("Save on exit", save)));
+        for (i, (label, value)) in rows.enumerate() {
+            let row_y = y + 30 + row_height * i as i32;
+            let color = if i == self.selected { Color::YELLOW } else { Color::LIGHTGRAY };
+            d.draw_text(label, x + 8, row_y, 14, color);
+            d.draw_text(&value, x + 190, row_y, 14, color);
+        }
+        d.draw_text(
+            &format!(
+                "{}/{}: Select | {}/{}: Change",
+                keys.orbit_up, keys.orbit_down, keys.orbit_left, keys.orbit_right
+            ),
+            x + 8, y + height - 16, 12, Color::GRAY,
+        );
+    }
+}
@@ -0,0 +1,201 @@
+// shadow_map.rs
+// Sun shadows from a depth map instead of a shadow ray per shaded point. The
+// map is an orthographic view of the static cubes along the sun's direction,
+// traced once through the BVH whenever the sun or the geometry changes; every
+// shaded point then looks itself up in it, filtering a 3x3 block of texels
+// (PCF) to soften the edge. The sun of the day/night cycle is a point light
+// about 20 blocks from the origin, so the map treats it as directional, as seen
+// from the middle of the scene; far from there its shadows lean slightly
+// differently from the traced ones. The day/night cycle moves the sun a little
+// every frame, so the map is only retraced once it has drifted by more than
+// `MAX_DRIFT_DEGREES`. Streamed chunks and clouds move without a
+// rebuild, so they are not in the map and are still tested with a ray.
+// A depth cannot say how much light got through, so glass and water shadow
+// like stone in the map. Other lights always trace their shadows.
+
+use raylib::prelude::*;
+use rayon::prelude::*;
+
+use crate::accel::TracerRay;
+use crate::light::Light;
+use crate::ray_intersect::Intersect;
+use crate::render::SHADOW_STRENGTH;
+use crate::sampling::tangent_basis;
+use crate::scene::Scene;
+use crate::settings::RenderSettings;
+
+// Texels on each side of the looked-up one that are averaged
+const PCF_RADIUS: i32 = 1;
+// Depth slack, in texels; grows on surfaces the sun grazes
+const DEPTH_BIAS: f32 = 1.0;
+// How far the lookup moves off the surface along its normal, in texels
+const NORMAL_OFFSET: f32 = 1.0;
+// How far the sun may move, seen from the scene's center, before the map is
+// traced again
+const MAX_DRIFT_DEGREES: f32 = 0.5;
+
+/// Depth of the first static cube along the sun's direction, per texel.
+pub struct ShadowMap {
+    // The geometry it was built for, and where its direction is taken from
+    revision: u64,
+    center: Vector3,
+    resolution: u32,
+    // Towards the sun, and the two axes of the map across it
+    direction: Vector3,
+    axis_u: Vector3,
+    axis_v: Vector3,
+    // Map coordinates of texel (0, 0)'s corner, the side of a texel and the
+    // plane the depths are measured from, all along the axes above
+    min_u: f32,
+    min_v: f32,
+    texel: f32,
+    top: f32,
+    depths: Vec<f32>,
+}
+
+impl ShadowMap {
+    /// Traces a `resolution` x `resolution` map of the static cubes of `scene`
+    /// towards `light`. None for a scene without cubes, or a light at its center.
+    pub fn build(scene: &Scene, light: &Light, resolution: u32, settings: &RenderSettings) -> Option<Self> {
+        let mut cubes = scene.objects.iter();
+        let first = cubes.next()?;
+        let (min, max) = cubes.fold((first.min_bounds, first.max_bounds), |(min, max), cube| {
+            (
+                Vector3::new(min.x.min(cube.min_bounds.x), min.y.min(cube.min_bounds.y), min.z.min(cube.min_bounds.z)),
+                Vector3::new(max.x.max(cube.max_bounds.x), max.y.max(cube.max_bounds.y), max.z.max(cube.max_bounds.z)),
+            )
+        });
+        let center = (min + max) * 0.5;
+        let direction = direction_from(center, light)?;
+        let (axis_u, axis_v) = tangent_basis(&direction);
+
+        // The box's corners on the map axes give the area to cover
+        let corners = (0..8).map(|i| {
+            Vector3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        });
+        let mut range = [(f32::INFINITY, f32::NEG_INFINITY); 3];
+        for corner in corners {
+            for (axis, (low, high)) in [axis_u, axis_v, direction].iter().zip(range.iter_mut()) {
+                let coordinate = corner.dot(*axis);
+                *low = low.min(coordinate);
+                *high = high.max(coordinate);
+            }
+        }
+        let [(min_u, max_u), (min_v, max_v), (_, max_d)] = range;
+        let resolution = resolution.max(1);
+        let texel = (max_u - min_u).max(max_v - min_v).max(1e-3) / resolution as f32;
+        let top = max_d + 1.0;
+
+        let mut depths = vec![f32::INFINITY; (resolution * resolution) as usize];
+        depths.par_chunks_mut(resolution as usize).enumerate().for_each(|(row, depths)| {
+            let v = min_v + (row as f32 + 0.5) * texel;
+            for (column, depth) in depths.iter_mut().enumerate() {
+                let u = min_u + (column as f32 + 0.5) * texel;
+                let origin = axis_u * u + axis_v * v + direction * top;
+                *depth = first_hit(scene, &origin, &-direction, settings);
+            }
+        });
+
+        Some(ShadowMap {
+            revision: scene.revision(),
+            center,
+            resolution,
+            direction,
+            axis_u,
+            axis_v,
+            min_u,
+            min_v,
+            texel,
+            top,
+            depths,
+        })
+    }
+
+    /// True if the map was built for the current BVH of `scene` and
+    /// `resolution`, and can stand in for `light`.
+    pub fn is_current(&self, scene: &Scene, light: &Light, resolution: u32) -> bool {
+        self.revision == scene.revision() && self.resolution == resolution.max(1) && self.casts_for(light)
+    }
+
+    /// True if `light` is within `MAX_DRIFT_DEGREES` of the direction the
+    /// map was traced along.
+    pub fn casts_for(&self, light: &Light) -> bool {
+        direction_from(self.center, light)
+            .is_some_and(|direction| direction.dot(self.direction) >= MAX_DRIFT_DEGREES.to_radians().cos())
+    }
+
+    /// Shadow intensity of `light` at `intersect`, on the same scale as the
+    /// traced shadows: 0 lit, `SHADOW_STRENGTH` fully shadowed.
+    pub fn shadow(&self, intersect: &Intersect, light: &Light, scene: &Scene) -> f32 {
+        let facing = intersect.normal.dot(self.direction);
+        // Turned away from the sun: its own cube is in the way
+        if facing <= 0.0 {
+            return SHADOW_STRENGTH;
+        }
+
+        let point = intersect.point + intersect.normal * (self.texel * NORMAL_OFFSET);
+        let depth = self.top - point.dot(self.direction);
+        let slope = (1.0 - facing * facing).sqrt() / facing.max(0.2);
+        let bias = self.texel * DEPTH_BIAS * (1.0 + slope);
+        let s = ((point.dot(self.axis_u) - self.min_u) / self.texel).floor() as i32;
+        let t = ((point.dot(self.axis_v) - self.min_v) / self.texel).floor() as i32;
+        let size = self.resolution as i32;
+        let mut occluded = 0;
+        let mut taps = 0;
+        for dt in -PCF_RADIUS..=PCF_RADIUS {
+            for ds in -PCF_RADIUS..=PCF_RADIUS {
+                let (x, y) = (s + ds, t + dt);
+                taps += 1;
+                // Outside the map nothing static is between the point and the sun
+                if x < 0 || y < 0 || x >= size || y >= size {
+                    continue;
+                }
+                if depth > self.depths[(y * size + x) as usize] + bias {
+                    occluded += 1;
+                }
+            }
+        }
+        let mut fraction = occluded as f32 / taps as f32;
+
+        if fraction < 1.0 {
+            let origin = intersect.point + intersect.normal * 0.001;
+            let to_light = light.position - origin;
+            let distance = to_light.length();
+            if scene.dynamic_occluder_distance(&origin, &(to_light / distance), distance).is_some() {
+                fraction = 1.0;
+            }
+        }
+        SHADOW_STRENGTH * fraction
+    }
+}
+
+// Unit vector from `center` towards `light`; None if the light sits on it
+fn direction_from(center: Vector3, light: &Light) -> Option<Vector3> {
+    let to_light = light.position - center;
+    (to_light.length() >= 1e-3).then(|| to_light.normalized())
+}
+
+/// Distance to the first static cube along the ray, or infinity.
+fn first_hit(scene: &Scene, origin: &Vector3, direction: &Vector3, settings: &RenderSettings) -> f32 {
+    let ray = TracerRay::new(*origin, *direction);
+    scene.intersect_nearest_static(&ray, f32::INFINITY, settings).map_or(f32::INFINITY, |(_, hit)| hit.distance)
+}
+
+/// Builds, rebuilds or drops `scene.sun_shadow` to match `sun` and
+/// `settings`. Returns whether a map was traced.
+pub fn update_sun_shadow(scene: &mut Scene, sun: &Light, settings: &RenderSettings) -> bool {
+    if !settings.sun_shadow_map {
+        scene.sun_shadow = None;
+        return false;
+    }
+    let resolution = settings.shadow_map_resolution;
+    if scene.sun_shadow.as_ref().is_some_and(|map| map.is_current(scene, sun, resolution)) {
+        return false;
+    }
+    scene.sun_shadow = ShadowMap::build(scene, sun, resolution, settings);
+    scene.sun_shadow.is_some()
+}
@@ -0,0 +1,106 @@
+// simd.rs
+// The per-lane loops of primary ray packets, eight lanes at a time with `wide`;
+// built with `--features simd`. Two loops: rotating the cached camera-space
+// directions into the world, and the slab test of a BVH node against every ray
+// of a packet, which at a leaf is the test against the cube itself. Each lane
+// does the same float operations in the same order as the scalar code, so the
+// directions are bit for bit the same and every cube the scalar test reaches
+// is reached here too. A lane with a direction component of 0 has an infinite
+// inverse, which the vector test cannot settle, so it falls back to the scalar
+// test. Shading stays scalar: once the lanes part ways into different
+// materials there is nothing left to share.
+use bvh::aabb::AABB;
+use raylib::prelude::*;
+use wide::{f32x8, CmpGt};
+
+use crate::accel::MAX_PACKET_RAYS;
+use crate::camera::{is_valid_direction, Camera};
+
+const LANES: usize = 8;
+const BLOCKS: usize = MAX_PACKET_RAYS / LANES;
+
+/// Inverse directions of a packet's rays, eight lanes to a vector.
+pub struct PacketLanes {
+    origin: [f32; 3],
+    inverse: [[f32x8; BLOCKS]; 3],
+    blocks: usize,
+    // Lanes whose inverse direction is not finite
+    scalar: u32,
+}
+
+impl PacketLanes {
+    /// Lanes for rays from `origin` along `directions`, at most `MAX_PACKET_RAYS`.
+    pub fn new(origin: &Vector3, directions: &[Vector3]) -> Self {
+        let mut inverse = [[[0.0f32; LANES]; BLOCKS]; 3];
+        let mut scalar = 0u32;
+        for (lane, d) in directions.iter().enumerate() {
+            for (axis, value) in [d.x, d.y, d.z].into_iter().enumerate() {
+                let inv = 1.0 / value;
+                if !inv.is_finite() {
+                    scalar |= 1 << lane;
+                }
+                inverse[axis][lane / LANES][lane % LANES] = inv;
+            }
+        }
+        PacketLanes {
+            origin: [origin.x, origin.y, origin.z],
+            inverse: inverse.map(|axis| axis.map(f32x8::new)),
+            blocks: directions.len().div_ceil(LANES),
+            scalar,
+        }
+    }
+
+    /// Lanes `hit_mask` leaves to the scalar test.
+    pub fn scalar_lanes(&self) -> u32 {
+        self.scalar
+    }
+
+    /// Bit `i` set when lane `i` of `active` hits `aabb` in front of the
+    /// origin. Scalar lanes are never set.
+    pub fn hit_mask(&self, aabb: &AABB, active: u32) -> u32 {
+        let min = [aabb.min.x, aabb.min.y, aabb.min.z];
+        let max = [aabb.max.x, aabb.max.y, aabb.max.z];
+        let mut mask = 0u32;
+        for block in 0..self.blocks {
+            let lanes = (active >> (block * LANES)) & 0xff;
+            if lanes == 0 {
+                continue;
+            }
+            let mut near = f32x8::ZERO;
+            let mut far = f32x8::splat(f32::INFINITY);
+            for axis in 0..3 {
+                let inv = self.inverse[axis][block];
+                let t1 = f32x8::splat(min[axis] - self.origin[axis]) * inv;
+                let t2 = f32x8::splat(max[axis] - self.origin[axis]) * inv;
+                near = near.max(t1.min(t2));
+                far = far.min(t1.max(t2));
+            }
+            let missed = near.cmp_gt(far).move_mask() as u32;
+            mask |= (lanes & !missed) << (block * LANES);
+        }
+        mask & !self.scalar
+    }
+}
+
+/// `Camera::basis_change` of every direction in `camera_space`, written to the
+/// same positions of `world`.
+pub fn rotate_to_world(camera: &Camera, camera_space: &[Vector3], world: &mut [Vector3]) {
+    for (chunk, out) in camera_space.chunks(LANES).zip(world.chunks_mut(LANES)) {
+        let mut p = [[0.0f32; LANES]; 3];
+        for (lane, v) in chunk.iter().enumerate() {
+            p[0][lane] = v.x;
+            p[1][lane] = v.y;
+            p[2][lane] = v.z;
+        }
+        let [px, py, pz] = p.map(f32x8::new);
+        let row = |right: f32, up: f32, forward: f32| {
+            (px * f32x8::splat(right) + py * f32x8::splat(up) - pz * f32x8::splat(forward)).to_array()
+        };
+        let x = row(camera.right.x, camera.up.x, camera.forward.x);
+        let y = row(camera.right.y, camera.up.y, camera.forward.y);
+        let z = row(camera.right.z, camera.up.z, camera.forward.z);
+        for (lane, (out, v)) in out.iter_mut().zip(chunk).enumerate() {
+            *out = if is_valid_direction(v) { Vector3::new(x[lane], y[lane], z[lane]) } else { camera.forward };
+        }
+    }
+}
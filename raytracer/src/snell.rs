@@ -1,47 +1,453 @@
 use raylib::prelude::*;
 
-pub fn reflect(incident: &Vector3, normal: &Vector3) -> Vector3 {
-    *incident - *normal * 2.0 * incident.dot(*normal)
-}
+use crate::sampling::ggx_microfacet;
 
-pub fn refract(incident: &Vector3, normal: &Vector3, refractive_index: f32) -> Vector3 {
-    // Implementation of Snell's Law for refraction.
-    // It calculates the direction of a ray as it passes from one medium to another.
+/// Wavelengths in nanometers of the red, green and blue channels for
+/// `rgb_iors`: the Fraunhofer C, d and F lines that glass catalogs quote
+/// indices and Abbe numbers at, so the green index is the catalog's n_d.
+pub const RGB_WAVELENGTHS_NM: [f32; 3] = [656.27, 587.56, 486.13];
 
-    // `cosi` is the cosine of the angle between the incident ray and the normal.
-    // We clamp it to the [-1, 1] range to avoid floating point errors.
-    let mut cosi = incident.dot(*normal).max(-1.0).min(1.0);
+// Squared length below which a vector has no direction to speak of, e.g. a
+// normal decoded from a flat normal map texel
+const MIN_LENGTH_SQ: f32 = 1e-12;
 
-    // `etai` is the refractive index of the medium the ray is currently in.
-    // `etat` is the refractive index of the medium the ray is entering.
-    // `n` is the normal vector, which may be flipped depending on the ray's direction.
-    let mut etai = 1.0; // Assume we are in Air (or vacuum) initially
-    let mut etat = refractive_index;
-    let mut n = *normal;
+// How far a squared length may be from 1 and still count as a unit vector;
+// a length within 1e-5 of 1
+const UNIT_TOLERANCE: f32 = 2e-5;
 
-    if cosi > 0.0 {
-        // The ray is inside the medium (e.g., glass) and going out into the air.
-        // We need to swap the refractive indices.
-        std::mem::swap(&mut etai, &mut etat);
-        // We also flip the normal so it points away from the medium.
-        n = -n;
+/// `v` scaled to unit length, or `None` if it is too short, infinite or NaN.
+/// Vectors already within rounding of unit length come back as they are.
+fn unit(v: Vector3) -> Option<Vector3> {
+    let length_sq = v.dot(v);
+    if !(length_sq > MIN_LENGTH_SQ && length_sq.is_finite()) {
+        return None;
+    }
+    if (length_sq - 1.0).abs() <= UNIT_TOLERANCE {
+        Some(v)
     } else {
-        // The ray is outside the medium and going in.
-        // We need a positive cosine for the calculation, so we negate it.
-        cosi = -cosi;
+        Some(v / length_sq.sqrt())
     }
+}
 
-    // `eta` is the ratio of the refractive indices (n1 / n2).
-    let eta = etai / etat;
-    // `k` is a term derived from Snell's law that helps determine if total internal reflection occurs.
-    let k = 1.0 - eta * eta * (1.0 - cosi * cosi);
+/// Mirror direction of `incident` about `normal`, of unit length. Neither
+/// needs to be unit length; a `normal` too short to have a direction leaves
+/// the ray as it was. An `incident` without a direction, too short, infinite
+/// or NaN, goes off along `normal`, or straight up if that has none either,
+/// so the result is always a unit vector.
+pub fn reflect(incident: &Vector3, normal: &Vector3) -> Vector3 {
+    let Some(i) = unit(*incident) else {
+        return unit(*normal).unwrap_or(Vector3::up());
+    };
+    let Some(n) = unit(*normal) else {
+        return i;
+    };
+    let cos_i = i.dot(n).clamp(-1.0, 1.0);
+    unit(i - n * 2.0 * cos_i).unwrap_or(i)
+}
 
+/// Indices of refraction `(eta_i, eta_t)` on either side of a surface of a
+/// medium with index `refractive_index` in air, for a ray along `incident`:
+/// the one it travels in, then the one it enters. A ray along `normal` is
+/// leaving the medium.
+pub fn media(incident: &Vector3, normal: &Vector3, refractive_index: f32) -> (f32, f32) {
+    if incident.dot(*normal) > 0.0 {
+        (refractive_index, 1.0)
+    } else {
+        (1.0, refractive_index)
+    }
+}
+
+/// Direction `incident` takes after crossing from a medium with index `eta_i`
+/// into one with index `eta_t`, by Snell's law, of unit length; `None` on
+/// total internal reflection. `normal` may face either side of the surface,
+/// and neither vector needs to be unit length. Equal indices, or a `normal`
+/// too short to have a direction, leave the ray going straight on. An
+/// `incident` without a direction has nowhere to go: `None`, as on total
+/// internal reflection.
+pub fn refract(incident: &Vector3, normal: &Vector3, eta_i: f32, eta_t: f32) -> Option<Vector3> {
+    let i = unit(*incident)?;
+    let Some(mut n) = unit(*normal) else {
+        return Some(i);
+    };
+    if eta_i == eta_t {
+        return Some(i);
+    }
+
+    // The formula wants the normal against the ray and a positive cosine
+    let mut cos_i = -i.dot(n).clamp(-1.0, 1.0);
+    if cos_i < 0.0 {
+        cos_i = -cos_i;
+        n = -n;
+    }
+
+    let eta = eta_i / eta_t;
+    // cos² of the refracted angle; negative past the critical angle
+    let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
     if k < 0.0 {
-        // If k is negative, it means total internal reflection has occurred.
-        // There is no refracted ray, so we return None.
-        Vector3::zero()
+        return None;
+    }
+    Some(unit(i * eta + n * (eta * cos_i - k.sqrt())).unwrap_or(i))
+}
+
+/// `refract` through a microfacet drawn from the GGX distribution of
+/// `roughness` around `normal`, for frosted glass; `sample` in [0, 1)² picks
+/// the facet, as in `ggx_microfacet`. A facet tilted far enough to send the
+/// ray back out of the side it came from refracts through `normal` instead.
+/// Returns the direction and the unit facet it went through, whose angle to
+/// the ray is the one the Fresnel term wants. At roughness 0 this is `refract`
+/// exactly, through `normal`. A `normal` too short to have a direction lets
+/// the ray straight on, as through a facet met head on.
+pub fn refract_rough(
+    incident: &Vector3,
+    normal: &Vector3,
+    eta_i: f32,
+    eta_t: f32,
+    roughness: f32,
+    sample: (f32, f32),
+) -> Option<(Vector3, Vector3)> {
+    let Some(n) = unit(*normal) else {
+        let i = unit(*incident)?;
+        return Some((i, -i));
+    };
+    let through_normal = || refract(incident, &n, eta_i, eta_t).map(|direction| (direction, n));
+    if roughness <= 0.0 {
+        return through_normal();
+    }
+    let Some(facet) = unit(ggx_microfacet(&n, roughness, sample.0, sample.1)) else {
+        return through_normal();
+    };
+    let direction = refract(incident, &facet, eta_i, eta_t)?;
+    if direction.dot(n) * incident.dot(n) > 0.0 {
+        Some((direction, facet))
     } else {
-        // If k is non-negative, we can calculate the direction of the refracted ray.
-        *incident * eta + n * (eta * cosi - k.sqrt())
+        through_normal()
+    }
+}
+
+/// Schlick's approximation of the reflectance at an angle whose cosine to the
+/// normal is `cos_theta`, from the reflectance `f0` head-on. Cheap, and close
+/// to `fresnel_dielectric` going into a denser medium; it knows nothing of
+/// total internal reflection, so for rays leaving one pass the cosine of the
+/// refracted angle instead.
+pub fn fresnel_schlick(cos_theta: f32, f0: f32) -> f32 {
+    let m = (1.0 - cos_theta.abs().min(1.0)).max(0.0);
+    f0 + (1.0 - f0) * m.powi(5)
+}
+
+/// Fraction of unpolarized light reflected where a medium with index `eta_i`
+/// meets one with index `eta_t`, from the exact Fresnel equations; the rest,
+/// `1.0 - fresnel_dielectric(..)`, is transmitted. `cos_i` is the cosine
+/// between the ray and the normal, of either sign. Past the critical angle,
+/// and exactly at it, where the refracted ray would run along the surface,
+/// all of the light is reflected.
+pub fn fresnel_dielectric(cos_i: f32, eta_i: f32, eta_t: f32) -> f32 {
+    // Nothing to reflect off, even grazing, where the terms below are 0 / 0
+    if eta_i == eta_t {
+        return 0.0;
+    }
+    let cos_i = cos_i.abs().min(1.0);
+    let sin_t = eta_i / eta_t * (1.0 - cos_i * cos_i).max(0.0).sqrt();
+    if sin_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin_t * sin_t).max(0.0).sqrt();
+    let rs = (eta_t * cos_i - eta_i * cos_t) / (eta_t * cos_i + eta_i * cos_t);
+    let rp = (eta_i * cos_i - eta_t * cos_t) / (eta_i * cos_i + eta_t * cos_t);
+    (rs * rs + rp * rp) / 2.0
+}
+
+/// Index of refraction at `wavelength_nm` of a glass with index `base_ior` at
+/// the d line (587.56 nm) and Abbe number `abbe_number`, from the two-term
+/// Cauchy equation n = A + B / λ². B is fitted so that n_F - n_C is
+/// (n_d - 1) / V, which is what the Abbe number measures; BK7 (n_d 1.5168,
+/// V 64.17) gives 1.5224 at 486 nm and 1.5143 at 656 nm. An Abbe number
+/// that is not positive and finite means no dispersion: `base_ior` at
+/// every wavelength.
+pub fn ior_for_wavelength(base_ior: f32, abbe_number: f32, wavelength_nm: f32) -> f32 {
+    if !(abbe_number > 0.0 && abbe_number.is_finite()) {
+        return base_ior;
+    }
+    let [c, d, f] = RGB_WAVELENGTHS_NM.map(|nm| 1.0 / (nm * 1e-3).powi(2));
+    let b = (base_ior - 1.0) / (abbe_number * (f - c));
+    let a = base_ior - b * d;
+    a + b / (wavelength_nm * 1e-3).powi(2)
+}
+
+/// Indices of refraction of the red, green and blue channels, at
+/// `RGB_WAVELENGTHS_NM`, of a glass with index `base_ior` and Abbe number
+/// `abbe_number`; see `ior_for_wavelength`.
+pub fn rgb_iors(base_ior: f32, abbe_number: f32) -> [f32; 3] {
+    RGB_WAVELENGTHS_NM.map(|nm| ior_for_wavelength(base_ior, abbe_number, nm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::hash3;
+
+    // Indices a ray meets in the scenes: air, water, glass, diamond
+    const IORS: [f32; 4] = [1.0, 1.333, 1.5, 2.42];
+
+    /// A unit direction for case `i`, spread over the sphere.
+    fn direction(i: i32, seed: u32) -> Vector3 {
+        let z = hash3(i, 0, 0, seed) * 2.0 - 1.0;
+        let phi = hash3(i, 1, 0, seed) * std::f32::consts::TAU;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        Vector3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    /// Transmitted power from the Fresnel transmission coefficients, worked
+    /// out apart from `fresnel_dielectric`, whose rest is `1 - R` by
+    /// definition.
+    fn transmitted(cos_i: f32, eta_i: f32, eta_t: f32) -> f32 {
+        let sin_t = eta_i / eta_t * (1.0 - cos_i * cos_i).sqrt();
+        if sin_t >= 1.0 {
+            return 0.0;
+        }
+        let cos_t = (1.0 - sin_t * sin_t).sqrt();
+        let ts = 2.0 * eta_i * cos_i / (eta_i * cos_i + eta_t * cos_t);
+        let tp = 2.0 * eta_i * cos_i / (eta_t * cos_i + eta_i * cos_t);
+        eta_t * cos_t / (eta_i * cos_i) * (ts * ts + tp * tp) / 2.0
+    }
+
+    #[test]
+    fn refracting_back_returns_the_original_direction() {
+        for i in 0..10_000 {
+            let (incident, normal) = (direction(i, 1), direction(i, 2));
+            let (eta_i, eta_t) = (IORS[i as usize % 4], IORS[(i as usize / 4) % 4]);
+            let Some(through) = refract(&incident, &normal, eta_i, eta_t) else {
+                continue;
+            };
+            let back = refract(&-through, &normal, eta_t, eta_i).expect("the way back is never past the critical angle");
+            assert!((back + incident).length() < 1e-3, "{:?} came back as {:?} through {} / {}", incident, -back, eta_i, eta_t);
+        }
+    }
+
+    #[test]
+    fn reflected_and_transmitted_power_add_up_to_one() {
+        for eta_i in IORS {
+            for eta_t in IORS {
+                for step in 0..=100 {
+                    let cos_i = step as f32 / 100.0;
+                    let reflected = fresnel_dielectric(cos_i, eta_i, eta_t);
+                    assert!((0.0..=1.0).contains(&reflected), "R = {} at cos {} for {} / {}", reflected, cos_i, eta_i, eta_t);
+                    if cos_i > 0.0 && eta_i != eta_t {
+                        let total = reflected + transmitted(cos_i, eta_i, eta_t);
+                        assert!(total <= 1.0 + 1e-4, "R + T = {} at cos {} for {} / {}", total, cos_i, eta_i, eta_t);
+                        assert!(total >= 1.0 - 1e-4, "R + T = {} at cos {} for {} / {}", total, cos_i, eta_i, eta_t);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn past_the_critical_angle_nothing_is_transmitted() {
+        let normal = Vector3::up();
+        for (eta_i, eta_t) in [(1.5_f32, 1.0_f32), (1.333, 1.0), (2.42, 1.5)] {
+            let critical = (eta_t / eta_i).asin();
+            for degrees in [0.5_f32, 2.0, 10.0, 30.0] {
+                let angle = (critical + degrees.to_radians()).min(std::f32::consts::FRAC_PI_2);
+                // Coming down onto the surface from inside the denser medium
+                let incident = Vector3::new(angle.sin(), -angle.cos(), 0.0);
+                assert_eq!(refract(&incident, &normal, eta_i, eta_t), None, "{} degrees past the critical angle of {} / {}", degrees, eta_i, eta_t);
+                assert_eq!(fresnel_dielectric(angle.cos(), eta_i, eta_t), 1.0);
+            }
+            // Just short of it the ray still gets out, grazing
+            let angle = critical - 0.5_f32.to_radians();
+            let incident = Vector3::new(angle.sin(), -angle.cos(), 0.0);
+            let through = refract(&incident, &normal, eta_i, eta_t).expect("short of the critical angle");
+            assert!(through.y < 0.0 && through.y > -0.2, "{:?}", through);
+        }
+    }
+
+    #[test]
+    fn schlick_tracks_the_exact_reflectance_into_a_denser_medium() {
+        for &eta_t in &IORS[1..] {
+            let f0 = ((1.0 - eta_t) / (1.0 + eta_t)).powi(2);
+            // Head on and grazing it is exact; near grazing it is close
+            assert!((fresnel_schlick(1.0, f0) - fresnel_dielectric(1.0, 1.0, eta_t)).abs() < 1e-6, "head on into {}", eta_t);
+            assert!((fresnel_schlick(0.0, f0) - fresnel_dielectric(0.0, 1.0, eta_t)).abs() < 1e-6, "grazing into {}", eta_t);
+            let near_grazing = fresnel_schlick(0.01, f0) - fresnel_dielectric(0.01, 1.0, eta_t);
+            assert!(near_grazing.abs() < 0.02, "near grazing into {}: off by {}", eta_t, near_grazing);
+            // In between it never strays by more than the approximation's known error
+            for i in 0..=100 {
+                let cos_i = i as f32 / 100.0;
+                let error = fresnel_schlick(cos_i, f0) - fresnel_dielectric(cos_i, 1.0, eta_t);
+                assert!(error.abs() < 0.08, "cosine {} into {}: off by {}", cos_i, eta_t, error);
+            }
+        }
+    }
+
+    #[test]
+    fn rough_refraction_returns_the_facet_it_went_through() {
+        let normal = Vector3::up();
+        let (mut tilted, mut fallbacks) = (0, 0);
+        for i in 0..10_000 {
+            let incident = direction(i, 5);
+            let (eta_i, eta_t) = media(&incident, &normal, 1.5);
+            let sample = (hash3(i, 0, 1, 6), hash3(i, 1, 1, 6));
+            let Some((through, facet)) = refract_rough(&incident, &normal, eta_i, eta_t, 0.4, sample) else {
+                continue;
+            };
+            assert_eq!(refract(&incident, &facet, eta_i, eta_t), Some(through));
+            // Onward to the other side of the surface, whichever facet it took
+            assert!(through.dot(normal) * incident.dot(normal) > 0.0);
+            if facet == normal {
+                fallbacks += 1;
+            } else {
+                tilted += 1;
+            }
+        }
+        assert!(tilted > 9 * fallbacks, "{} tilted facets, {} through the normal", tilted, fallbacks);
+        // Smooth glass refracts through the normal itself
+        let incident = direction(0, 5);
+        let (eta_i, eta_t) = media(&incident, &normal, 1.5);
+        let smooth = refract(&incident, &normal, eta_i, eta_t).map(|through| (through, normal));
+        assert_eq!(refract_rough(&incident, &normal, eta_i, eta_t, 0.0, (0.3, 0.7)), smooth);
+    }
+
+    #[test]
+    fn rough_lobes_split_the_light_without_making_or_losing_any() {
+        let normal = Vector3::up();
+        for roughness in [0.2, 0.5] {
+            // Onto glass from above, and out of it from below, where facets
+            // past the critical angle reflect everything
+            for (below, degrees) in [(false, 0.0_f32), (false, 45.0), (false, 80.0), (true, 0.0), (true, 30.0), (true, 40.0)] {
+                let angle = degrees.to_radians();
+                let incident = Vector3::new(angle.sin(), if below { angle.cos() } else { -angle.cos() }, 0.0);
+                let (eta_i, eta_t) = media(&incident, &normal, 1.5);
+                let (mut reflected, mut transmitted) = (0.0, 0.0);
+                const SAMPLES: i32 = 4096;
+                for i in 0..SAMPLES {
+                    let sample = (hash3(i, 0, 2, 7), hash3(i, 1, 2, 7));
+                    let Some((through, facet)) = refract_rough(&incident, &normal, eta_i, eta_t, roughness, sample) else {
+                        reflected += 1.0;
+                        continue;
+                    };
+                    // The reflected share from the Fresnel term, the
+                    // transmitted one from the transmission coefficients at
+                    // the angle the ray really left the facet at
+                    let (cos_i, cos_t) = (incident.dot(facet).abs(), through.dot(facet).abs());
+                    reflected += fresnel_dielectric(incident.dot(facet), eta_i, eta_t);
+                    let ts = 2.0 * eta_i * cos_i / (eta_i * cos_i + eta_t * cos_t);
+                    let tp = 2.0 * eta_i * cos_i / (eta_t * cos_i + eta_i * cos_t);
+                    transmitted += eta_t * cos_t / (eta_i * cos_i) * (ts * ts + tp * tp) / 2.0;
+                }
+                let (reflected, transmitted) = (reflected / SAMPLES as f32, transmitted / SAMPLES as f32);
+                let total = reflected + transmitted;
+                let case = format!("{} degrees from {} at roughness {}", degrees, if below { "inside" } else { "outside" }, roughness);
+                assert!(total <= 1.0 + 1e-4, "R + T = {} at {}", total, case);
+                assert!(total >= 1.0 - 1e-3, "R + T = {} at {}", total, case);
+                assert!(reflected > 0.0 && transmitted > 0.0, "R = {}, T = {} at {}", reflected, transmitted, case);
+            }
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn bk7_indices_match_the_catalog() {
+        let (n_d, abbe_number) = (1.5168, 64.17);
+        // Catalog values at the F, d and C lines
+        assert!((ior_for_wavelength(n_d, abbe_number, 587.56) - n_d).abs() < 1e-5);
+        assert!((ior_for_wavelength(n_d, abbe_number, 486.13) - 1.52238).abs() < 2e-4);
+        assert!((ior_for_wavelength(n_d, abbe_number, 656.27) - 1.51432).abs() < 2e-4);
+        let [red, green, blue] = rgb_iors(n_d, abbe_number);
+        assert!((green - n_d).abs() < 1e-5);
+        assert!(((n_d - 1.0) / (blue - red) - abbe_number).abs() < 0.1, "Abbe number {}", (n_d - 1.0) / (blue - red));
+        // Normal dispersion: the index falls across the visible range, and
+        // less at the red end
+        let indices: Vec<f32> = (400..=700).step_by(10).map(|nm| ior_for_wavelength(n_d, abbe_number, nm as f32)).collect();
+        assert!(indices.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(indices[0] - indices[1] > indices[29] - indices[30]);
+    }
+
+    #[test]
+    fn no_abbe_number_means_no_dispersion() {
+        for abbe_number in [0.0, -3.0, f32::INFINITY, f32::NAN] {
+            assert_eq!(rgb_iors(1.5, abbe_number), [1.5; 3]);
+        }
+        // A lower Abbe number spreads the colors more
+        let spread = |abbe_number| {
+            let [red, _, blue] = rgb_iors(1.6, abbe_number);
+            blue - red
+        };
+        assert!(spread(30.0) > spread(64.0) && spread(64.0) > 0.0);
+    }
+
+    #[test]
+    fn reflection_keeps_the_length_and_the_angle() {
+        for i in 0..10_000 {
+            let (incident, normal) = (direction(i, 3), direction(i, 4));
+            let reflected = reflect(&incident, &normal);
+            assert!((reflected.length() - 1.0).abs() < 1e-5, "|{:?}| = {}", reflected, reflected.length());
+            assert!((reflected.dot(normal) + incident.dot(normal)).abs() < 1e-5);
+            // A longer incident reflects the same way
+            assert!((reflect(&(incident * 7.0), &normal) - reflected).length() < 1e-5);
+        }
+    }
+
+    /// A vector for fuzz case `i`: most are unit directions at any length
+    /// from 1e-8 to 1e8, some lie just off `normal`'s plane (grazing), and
+    /// the rest have no direction at all: zero, tiny, infinite or NaN.
+    fn fuzz_vector(i: i32, seed: u32, normal: &Vector3) -> Vector3 {
+        let v = direction(i, seed);
+        match i % 16 {
+            0 => Vector3::zero(),
+            1 => v * 1e-7,
+            2 => Vector3::new(f32::NAN, v.y, v.z),
+            3 => Vector3::new(f32::INFINITY, v.y, v.z),
+            4..=7 => {
+                let along = hash3(i, 2, 0, seed) * 2e-3 - 1e-3;
+                v - *normal * (v.dot(*normal) - along)
+            }
+            _ => v * 10f32.powf(hash3(i, 3, 0, seed) * 16.0 - 8.0),
+        }
+    }
+
+    fn assert_unit(v: Vector3, what: &str) {
+        assert!(v.x.is_finite() && v.y.is_finite() && v.z.is_finite(), "{}: {:?}", what, v);
+        assert!((v.length() - 1.0).abs() <= 1e-4, "{}: |{:?}| = {}", what, v, v.length());
+    }
+
+    #[test]
+    fn reflect_and_refract_always_give_unit_vectors() {
+        for i in 0..1_000_000 {
+            // One normal in five is as degenerate as the incidents, in cases
+            // of its own
+            let normal = if i % 5 == 0 { fuzz_vector(i / 5, 7, &Vector3::up()) } else { direction(i, 7) };
+            let incident = fuzz_vector(i, 9, &normal);
+            let (eta_i, eta_t) = (IORS[i as usize % 4], IORS[(i as usize / 4) % 4]);
+
+            assert_unit(reflect(&incident, &normal), "reflect");
+            match refract(&incident, &normal, eta_i, eta_t) {
+                Some(through) => assert_unit(through, "refract"),
+                // Only past the critical angle, or with nowhere to go
+                None => assert!(eta_i > eta_t || unit(incident).is_none(), "{:?} through {:?}", incident, normal),
+            }
+            if let Some((through, facet)) = refract_rough(&incident, &normal, eta_i, eta_t, 0.3, (hash3(i, 0, 2, 10), hash3(i, 1, 2, 10))) {
+                assert_unit(through, "refract_rough");
+                assert_unit(facet, "refract_rough's facet");
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_inputs_have_a_defined_result() {
+        let normal = Vector3::new(0.0, 2.0, 0.0);
+        let incident = Vector3::new(1.0, -1.0, 0.0);
+        for nothing in [Vector3::zero(), Vector3::one() * 1e-9, Vector3::new(f32::NAN, 0.0, 0.0), Vector3::new(0.0, f32::INFINITY, 0.0)] {
+            // No incident direction: off along the normal, or up without one
+            assert_eq!(reflect(&nothing, &normal), Vector3::up());
+            assert_eq!(reflect(&nothing, &Vector3::new(0.0, 0.0, -3.0)), Vector3::new(0.0, 0.0, -1.0));
+            assert_eq!(reflect(&nothing, &nothing), Vector3::up());
+            assert_eq!(refract(&nothing, &normal, 1.0, 1.5), None);
+            // No normal: the ray goes on as it was
+            assert_unit(reflect(&incident, &nothing), "reflect");
+            assert!((reflect(&incident, &nothing) - incident / 2f32.sqrt()).length() < 1e-6);
+            assert!((refract(&incident, &nothing, 1.0, 1.5).unwrap() - incident / 2f32.sqrt()).length() < 1e-6);
+        }
+        // Equal indices go straight through exactly, even grazing
+        let grazing = Vector3::new(1.0, -1e-6, 0.0);
+        assert_eq!(refract(&grazing, &normal, 1.333, 1.333), Some(grazing));
+    }
+}
@@ -1,14 +1,108 @@
 // textures.rs
+use log::{info, warn};
 use raylib::prelude::*;
 use std::collections::HashMap;
 
+use crate::assets::Assets;
+use crate::error::Error;
+use crate::settings::RenderSettings;
+
+/// Side of the checkerboard drawn in place of a texture that failed to load.
+const PLACEHOLDER_SIZE: i32 = 16;
+
 struct CpuTexture {
     width: i32,
     height: i32,
     pixels: Vec<Vector3>, // Normalized RGB values
+    alpha: Vec<f32>,      // Coverage in [0, 1], same order as `pixels`
+    stats: TextureStats,
+}
+
+/// Side of `TextureStats::mini_mip`.
+pub const MINI_MIP_SIZE: usize = 4;
+
+/// Averages of a texture's CPU copy, worked out once when it loads, for
+/// shading that wants its overall color rather than one texel. Every texel
+/// counts the same, whatever its alpha.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureStats {
+    /// Mean color of the whole texture.
+    pub average: Vector3,
+    /// Mean color of each row, top first.
+    pub row_averages: Vec<Vector3>,
+    /// Mean color of each column, left first.
+    pub column_averages: Vec<Vector3>,
+    /// The texture box-filtered down to `MINI_MIP_SIZE` texels a side, row by
+    /// row from the top. A side shorter than that repeats its texels.
+    pub mini_mip: [Vector3; MINI_MIP_SIZE * MINI_MIP_SIZE],
+}
+
+impl TextureStats {
+    fn of(width: i32, height: i32, pixels: &[Vector3]) -> Self {
+        // Sums in f64, so a large texture does not lose its last rows to rounding
+        let mean = |sum: [f64; 3], count: usize| {
+            let count = count.max(1) as f64;
+            Vector3::new((sum[0] / count) as f32, (sum[1] / count) as f32, (sum[2] / count) as f32)
+        };
+        let add = |sum: &mut [f64; 3], color: &Vector3| {
+            sum[0] += color.x as f64;
+            sum[1] += color.y as f64;
+            sum[2] += color.z as f64;
+        };
+        let (columns, rows) = (width.max(0) as usize, height.max(0) as usize);
+        let mut row_sums = vec![[0.0; 3]; rows];
+        let mut column_sums = vec![[0.0; 3]; columns];
+        for (index, color) in pixels.iter().enumerate().take(rows * columns) {
+            add(&mut row_sums[index / columns], color);
+            add(&mut column_sums[index % columns], color);
+        }
+        let total = row_sums.iter().fold([0.0; 3], |total, row| [total[0] + row[0], total[1] + row[1], total[2] + row[2]]);
+        let mini_mip = std::array::from_fn(|cell| {
+            let mut sum = [0.0; 3];
+            let mut count = 0;
+            for y in box_span(cell / MINI_MIP_SIZE, MINI_MIP_SIZE, rows) {
+                for x in box_span(cell % MINI_MIP_SIZE, MINI_MIP_SIZE, columns) {
+                    if let Some(color) = pixels.get(y * columns + x) {
+                        add(&mut sum, color);
+                        count += 1;
+                    }
+                }
+            }
+            mean(sum, count)
+        });
+        TextureStats {
+            average: mean(total, rows * columns),
+            row_averages: row_sums.into_iter().map(|sum| mean(sum, columns)).collect(),
+            column_averages: column_sums.into_iter().map(|sum| mean(sum, rows)).collect(),
+            mini_mip,
+        }
+    }
+
+    /// The mini-mip texel under (u, v), both in [0, 1].
+    pub fn mini_mip_at(&self, u: f32, v: f32) -> Vector3 {
+        let cell = |t: f32| ((t * MINI_MIP_SIZE as f32) as usize).min(MINI_MIP_SIZE - 1);
+        self.mini_mip[cell(v) * MINI_MIP_SIZE + cell(u)]
+    }
+
+    fn memory_bytes(&self) -> usize {
+        (self.row_averages.len() + self.column_averages.len() + self.mini_mip.len() + 1) * std::mem::size_of::<Vector3>()
+    }
+}
+
+/// Source texels [start, end) that output texel `i` of `out` covers along a
+/// side of `size`; at least one, even when `out` is larger than `size`.
+fn box_span(i: usize, out: usize, size: usize) -> std::ops::Range<usize> {
+    let start = i * size / out;
+    let end = ((i + 1) * size / out).max(start + 1);
+    start..end
 }
 
 impl CpuTexture {
+    fn new(width: i32, height: i32, pixels: Vec<Vector3>, alpha: Vec<f32>) -> Self {
+        let stats = TextureStats::of(width, height, &pixels);
+        CpuTexture { width, height, pixels, alpha, stats }
+    }
+
     fn from_image(image: &Image) -> Self {
         // Safe: Raylib handles pixel format internally
         let colors = image.get_image_data(); // Vec<Color>
@@ -22,75 +116,290 @@ impl CpuTexture {
                 )
             })
             .collect();
+        let alpha = colors.iter().map(|c| c.a as f32 / 255.0).collect();
+
+        CpuTexture::new(image.width, image.height, pixels, alpha)
+    }
 
-        CpuTexture {
-            width: image.width,
-            height: image.height,
-            pixels,
+    fn memory_bytes(&self) -> usize {
+        self.pixels.len() * std::mem::size_of::<Vector3>() + self.alpha.len() * std::mem::size_of::<f32>() + self.stats.memory_bytes()
+    }
+
+    /// Box-filtered down so neither side exceeds `max_size`, keeping the
+    /// aspect ratio; textures already within it are returned unchanged.
+    fn downscaled(self, max_size: i32) -> Self {
+        let longest = self.width.max(self.height);
+        if longest <= max_size {
+            return self;
         }
+        let scale = max_size as f32 / longest as f32;
+        let width = ((self.width as f32 * scale).round() as i32).clamp(1, max_size);
+        let height = ((self.height as f32 * scale).round() as i32).clamp(1, max_size);
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        let mut alpha = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let mut color = Vector3::zero();
+                let mut coverage = 0.0;
+                let mut count = 0.0;
+                for sy in box_span(y as usize, height as usize, self.height as usize) {
+                    for sx in box_span(x as usize, width as usize, self.width as usize) {
+                        let index = sy * self.width as usize + sx;
+                        color += self.pixels[index];
+                        coverage += self.alpha[index];
+                        count += 1.0;
+                    }
+                }
+                pixels.push(color / count);
+                alpha.push(coverage / count);
+            }
+        }
+        CpuTexture::new(width, height, pixels, alpha)
     }
 }
 
+/// Textures of the block materials, loaded at startup.
+pub const BLOCK_TEXTURES: [&str; 11] = [
+    "assets/grass.png",
+    "assets/glass.png",
+    "assets/magma.png",
+    "assets/diamond_ore.png",
+    "assets/oak.png",
+    "assets/wood_planks.png",
+    "assets/stone.png",
+    "assets/obsidian.png",
+    "assets/water.png",
+    "assets/leaves.png",
+    "assets/dirt.png",
+];
+
+/// Textures by path: a CPU copy for the tracer and, when a window is open, a GPU copy for raylib.
+#[derive(Default)]
 pub struct TextureManager {
     cpu_textures: HashMap<String, CpuTexture>,
     textures: HashMap<String, Texture2D>, // Store GPU textures for rendering
     skybox_textures: Option<SkyboxTextures>,
+    assets: Assets,
+    // Longest side of a CPU copy; None keeps every texture at full size
+    max_size: Option<i32>,
+    // Textures downscaled and the CPU bytes that saved
+    downscaled: (usize, usize),
 }
 
+/// Paths of the six skybox faces.
 #[derive(Clone)]
 pub struct SkyboxTextures {
+    /// +Z face.
     pub front: String,
+    /// -Z face.
     pub back: String,
+    /// -X face.
     pub left: String,
+    /// +X face.
     pub right: String,
+    /// +Y face.
     pub top: String,
+    /// -Y face.
     pub bottom: String,
 }
 
+impl SkyboxTextures {
+    /// The faces in `assets/skybox/`.
+    pub fn default_faces() -> Self {
+        SkyboxTextures {
+            front: "assets/skybox/front.png".to_string(),
+            back: "assets/skybox/back.png".to_string(),
+            left: "assets/skybox/left.png".to_string(),
+            right: "assets/skybox/right.png".to_string(),
+            top: "assets/skybox/top.png".to_string(),
+            bottom: "assets/skybox/bottom.png".to_string(),
+        }
+    }
+
+    fn faces(&self) -> [&str; 6] {
+        [&self.front, &self.back, &self.left, &self.right, &self.top, &self.bottom]
+    }
+}
+
 impl TextureManager {
+    /// An empty manager searching the default asset directories.
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Reads textures whose paths start with `assets/` through `assets`.
+    pub fn with_assets(assets: Assets) -> Self {
+        TextureManager { assets, ..Self::default() }
+    }
+
+    /// Box-filters the CPU copy of any texture loaded from now on down to
+    /// `max_size` texels on its longest side. The GPU copy stays at full size.
+    pub fn with_max_size(mut self, max_size: u32) -> Self {
+        self.max_size = Some(max_size.min(i32::MAX as u32) as i32);
+        self
+    }
+
+    // The CPU copy of `image`, downscaled to `max_size`
+    fn cpu_copy(&mut self, image: &Image) -> CpuTexture {
+        let full = CpuTexture::from_image(image);
+        let Some(max_size) = self.max_size else {
+            return full;
+        };
+        let full_bytes = full.memory_bytes();
+        let reduced = full.downscaled(max_size);
+        if reduced.memory_bytes() < full_bytes {
+            self.downscaled.0 += 1;
+            self.downscaled.1 += full_bytes - reduced.memory_bytes();
+        }
+        reduced
+    }
+
+    // Textures keep their `assets/...` path as their key, so scene files do
+    // not depend on where the assets live
+    fn load_image(&self, path: &str) -> Result<Image, Error> {
+        let (bytes, file) = self.assets.read(path)?;
+        let extension = file.extension().map_or("png".into(), |ext| ext.to_string_lossy().to_lowercase());
+        Image::load_image_from_mem(&format!(".{}", extension), &bytes)
+            .map_err(|_| Error::ImageDecode { path: file })
+    }
+
+    /// Magenta and black checkerboard, bright enough to spot in a frame.
+    fn placeholder_image() -> Image {
+        Image::gen_image_checked(PLACEHOLDER_SIZE, PLACEHOLDER_SIZE, 2, 2, Color::MAGENTA, Color::BLACK)
+    }
+
+    /// Loads only the CPU copy the tracer samples, without a window or GPU
+    /// upload; enough for headless rendering.
+    pub fn load_cpu_texture(&mut self, path: &str) -> Result<(), Error> {
+        if self.cpu_textures.contains_key(path) {
+            return Ok(());
+        }
+        let image = self.load_image(path)?;
+        let cpu_texture = self.cpu_copy(&image);
+        self.cpu_textures.insert(path.to_string(), cpu_texture);
+        Ok(())
+    }
+
+    /// `load_cpu_texture`, but a missing or undecodable image is reported and
+    /// replaced by a checkerboard so the scene still renders.
+    pub fn load_cpu_texture_or_placeholder(&mut self, path: &str) {
+        if let Err(error) = self.load_cpu_texture(path) {
+            warn!("Drawing a placeholder: {}", error.report());
+            self.cpu_textures.insert(path.to_string(), CpuTexture::from_image(&Self::placeholder_image()));
+        }
+    }
+
+    /// Loads the CPU copies of the skybox faces; see `load_cpu_texture`. The
+    /// skybox is only used once every face has loaded.
+    pub fn load_cpu_skybox(&mut self, skybox: SkyboxTextures) -> Result<(), Error> {
+        for face in skybox.faces() {
+            self.load_cpu_texture(face)?;
+        }
+        self.skybox_textures = Some(skybox);
+        Ok(())
+    }
+
+    /// Loads `path` once, both as a raylib texture and as a CPU copy.
     pub fn load_texture(
         &mut self,
         rl: &mut RaylibHandle,
         thread: &RaylibThread,
         path: &str,
-    ) {
+    ) -> Result<(), Error> {
         if self.textures.contains_key(path) {
-            return;
+            return Ok(());
         }
 
-        let image = Image::load_image(path)
-            .unwrap_or_else(|_| panic!("Failed to load image {}", path));
+        let image = self.load_image(path)?;
+        self.insert_texture(rl, thread, path, &image)
+    }
 
+    /// `load_texture`, but a missing or undecodable image is reported and
+    /// replaced by a checkerboard. Only a failed GPU upload is an error.
+    pub fn load_texture_or_placeholder(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        path: &str,
+    ) -> Result<(), Error> {
+        match self.load_texture(rl, thread, path) {
+            Err(error) if error.is_missing_asset() => {
+                warn!("Drawing a placeholder: {}", error.report());
+                self.insert_texture(rl, thread, path, &Self::placeholder_image())
+            }
+            result => result,
+        }
+    }
+
+    fn insert_texture(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        path: &str,
+        image: &Image,
+    ) -> Result<(), Error> {
         let texture = rl
-            .load_texture_from_image(thread, &image)
-            .unwrap_or_else(|_| panic!("Failed to load texture {}", path));
+            .load_texture_from_image(thread, image)
+            .map_err(|_| Error::GpuUpload { name: path.to_string() })?;
 
-        let cpu_texture = CpuTexture::from_image(&image);
+        let cpu_texture = self.cpu_copy(image);
 
         self.cpu_textures.insert(path.to_string(), cpu_texture);
         self.textures.insert(path.to_string(), texture);
+        Ok(())
     }
 
+    /// Loads the skybox faces with `load_texture`; used only once every face
+    /// has loaded.
     pub fn load_skybox(
         &mut self,
         rl: &mut RaylibHandle,
         thread: &RaylibThread,
         skybox: SkyboxTextures,
-    ) {
-        self.load_texture(rl, thread, &skybox.front);
-        self.load_texture(rl, thread, &skybox.back);
-        self.load_texture(rl, thread, &skybox.left);
-        self.load_texture(rl, thread, &skybox.right);
-        self.load_texture(rl, thread, &skybox.top);
-        self.load_texture(rl, thread, &skybox.bottom);
+    ) -> Result<(), Error> {
+        for face in skybox.faces() {
+            self.load_texture(rl, thread, face)?;
+        }
         self.skybox_textures = Some(skybox);
+        Ok(())
+    }
+
+    /// Bytes held by the CPU copies: a color and an alpha value per texel.
+    pub fn cpu_memory_bytes(&self) -> usize {
+        self.cpu_textures.values().map(CpuTexture::memory_bytes).sum()
+    }
+
+    /// Textures whose CPU copy was downscaled on load, and the CPU bytes that
+    /// saved.
+    pub fn downscale_savings(&self) -> (usize, usize) {
+        self.downscaled
+    }
+
+    /// Bytes of the GPU copies, at four bytes per texel; zero without a window.
+    pub fn gpu_memory_bytes(&self) -> usize {
+        self.textures.values().map(|texture| texture.width() as usize * texture.height() as usize * 4).sum()
+    }
+
+    /// Logs how many textures are loaded, their memory and where the sky comes from.
+    pub fn log_summary(&self) {
+        let sky = if self.skybox_textures.is_some() { "skybox" } else { "procedural sky" };
+        let megabytes = (self.cpu_memory_bytes() + self.gpu_memory_bytes()) as f64 / (1024.0 * 1024.0);
+        let dirs: Vec<String> = self.assets.dirs().iter().map(|dir| dir.display().to_string()).collect();
+        info!(
+            "Loaded {} textures ({:.1} MiB) searching {}, {}",
+            self.cpu_textures.len(), megabytes, dirs.join(", "), sky
+        );
+        let (count, saved) = self.downscaled;
+        if count > 0 {
+            info!(
+                "Downscaled {} textures to {} texels, saving {:.1} MiB",
+                count, self.max_size.unwrap_or(0), saved as f64 / (1024.0 * 1024.0)
+            );
+        }
     }
 
-    pub fn sample_skybox(&self, direction: Vector3) -> Vector3 {
+    /// Sky color in `direction`: the skybox when loaded, else the procedural sky.
+    pub fn sample_skybox(&self, direction: Vector3, settings: &RenderSettings) -> Vector3 {
         if let Some(ref skybox) = self.skybox_textures {
             // Mapear la dirección a las caras del cubo
             let abs_x = direction.x.abs();
@@ -142,7 +451,9 @@ impl TextureManager {
             let u = u.max(0.0).min(1.0);
             let v = v.max(0.0).min(1.0);
             
-            let cpu_texture = self.cpu_textures.get(texture_path).unwrap();
+            let Some(cpu_texture) = self.cpu_textures.get(texture_path.as_str()) else {
+                return settings.sky.sample(direction, settings.sun_elevation);
+            };
             let tx = (u * (cpu_texture.width - 1) as f32) as u32;
             let ty = (v * (cpu_texture.height - 1) as f32) as u32;
             
@@ -154,25 +465,11 @@ impl TextureManager {
             }
         } else {
             // Fallback a sky procedural si no hay skybox
-            let d = direction.normalized();
-            let t = (d.y + 1.0) * 0.5;
-            let green = Vector3::new(0.1, 0.6, 0.2);
-            let white = Vector3::new(1.0, 1.0, 1.0);
-            let blue = Vector3::new(0.3, 0.5, 1.0);
-            if t < 0.54 {
-                let k = t / 0.55;
-                green * (1.0 - k) + white * k
-            } else if t < 0.55 {
-                white
-            } else if t < 0.8 {
-                let k = (t - 0.55) / (0.25);
-                white * (1.0 - k) + blue * k
-            } else {
-                blue
-            }
+            settings.sky.sample(direction, settings.sun_elevation)
         }
     }
 
+    /// Texel (tx, ty) of `path`, clamped to the texture; white if it is not loaded.
     pub fn get_pixel_color(
         &self,
         path: &str,
@@ -198,6 +495,17 @@ impl TextureManager {
         }
     }
 
+    /// Coverage of the texel under (u, v) in [0, 1]; opaque if `path` is not loaded.
+    pub fn alpha_at(&self, path: &str, u: f32, v: f32) -> f32 {
+        let Some(cpu_texture) = self.cpu_textures.get(path) else {
+            return 1.0;
+        };
+        let x = ((u * cpu_texture.width as f32) as i32).clamp(0, cpu_texture.width - 1);
+        let y = ((v * cpu_texture.height as f32) as i32).clamp(0, cpu_texture.height - 1);
+        cpu_texture.alpha.get((y * cpu_texture.width + x) as usize).copied().unwrap_or(1.0)
+    }
+
+    /// The raylib texture for `path`, if it was loaded with a window.
     pub fn get_texture(
         &self,
         path: &str,
@@ -205,6 +513,25 @@ impl TextureManager {
         self.textures.get(path)
     }
 
+    /// Píxeles en CPU de una textura cargada: (ancho, alto, píxeles por filas).
+    pub fn texture_pixels(&self, path: &str) -> Option<(i32, i32, &[Vector3])> {
+        self.cpu_textures
+            .get(path)
+            .map(|t| (t.width, t.height, t.pixels.as_slice()))
+    }
+
+    /// Averages and mini-mip of the CPU copy of `path`, worked out when it
+    /// loaded; None if it is not loaded.
+    pub fn texture_stats(&self, path: &str) -> Option<&TextureStats> {
+        self.cpu_textures.get(path).map(|t| &t.stats)
+    }
+
+    /// The loaded skybox faces.
+    pub fn skybox(&self) -> Option<&SkyboxTextures> {
+        self.skybox_textures.as_ref()
+    }
+
+    /// Tangent-space normal at (tx, ty) of a normal map, or None if it is not loaded.
     pub fn get_normal_from_map(
         &self,
         path: &str,
@@ -237,12 +564,100 @@ impl TextureManager {
     }
 }
 
-impl Default for TextureManager {
-    fn default() -> Self {
-        TextureManager {
-            cpu_textures: HashMap::new(),
-            textures: HashMap::new(),
-            skybox_textures: None,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // Each texel its own color, far from any other
+    fn known_image(width: i32, height: i32) -> Vec<Vector3> {
+        (0..width * height).map(|i| {
+            let (x, y) = ((i % width) as f32, (i / width) as f32);
+            Vector3::new(x / width as f32, y / height as f32, (x * 7.0 + y * 13.0) % 5.0 / 5.0)
+        }).collect()
+    }
+
+    fn mean_of(pixels: impl Iterator<Item = Vector3>) -> Vector3 {
+        let (sum, count) = pixels.fold((Vector3::zero(), 0), |(sum, count), color| (sum + color, count + 1));
+        sum / count as f32
+    }
+
+    fn assert_close(a: Vector3, b: Vector3, what: &str) {
+        assert!((a - b).length() < 1e-5, "{}: {:?} vs {:?}", what, a, b);
+    }
+
+    #[test]
+    fn stats_match_brute_force_on_an_uneven_image() {
+        // Neither side a multiple of MINI_MIP_SIZE, so the buckets differ in size
+        let (width, height) = (10, 7);
+        let pixels = known_image(width, height);
+        let at = |x: usize, y: usize| pixels[y * width as usize + x];
+        let stats = TextureStats::of(width, height, &pixels);
+
+        assert_close(stats.average, mean_of(pixels.iter().copied()), "average");
+        assert_eq!((stats.row_averages.len(), stats.column_averages.len()), (7, 10));
+        for (y, row) in stats.row_averages.iter().enumerate() {
+            assert_close(*row, mean_of((0..10).map(|x| at(x, y))), &format!("row {}", y));
+        }
+        for (x, column) in stats.column_averages.iter().enumerate() {
+            assert_close(*column, mean_of((0..7).map(|y| at(x, y))), &format!("column {}", x));
+        }
+        // Bucket edges at i * size / 4, rounded down: 0, 2, 5, 7, 10 across and 0, 1, 3, 5, 7 down
+        let columns = [0..2, 2..5, 5..7, 7..10];
+        let rows = [0..1, 1..3, 3..5, 5..7];
+        for (cy, ys) in rows.iter().enumerate() {
+            for (cx, xs) in columns.iter().enumerate() {
+                let expected = mean_of(ys.clone().flat_map(|y| xs.clone().map(move |x| (x, y))).map(|(x, y)| at(x, y)));
+                assert_close(stats.mini_mip[cy * MINI_MIP_SIZE + cx], expected, &format!("mini-mip ({}, {})", cx, cy));
+            }
+        }
+        assert_eq!(stats.mini_mip_at(0.99, 0.99), stats.mini_mip[15]);
+        assert_eq!(stats.mini_mip_at(0.0, 0.3), stats.mini_mip[4]);
+    }
+
+    #[test]
+    fn a_texture_smaller_than_the_mini_mip_repeats_its_texels() {
+        let pixels = known_image(3, 2);
+        let stats = TextureStats::of(3, 2, &pixels);
+        // Across: texels 0, 0, 1, 2; down: rows 0, 0, 1, 1
+        for (cy, y) in [0, 0, 1, 1].into_iter().enumerate() {
+            for (cx, x) in [0, 0, 1, 2].into_iter().enumerate() {
+                assert_eq!(stats.mini_mip[cy * MINI_MIP_SIZE + cx], pixels[y * 3 + x], "mini-mip ({}, {})", cx, cy);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_texture_is_asset_not_found() {
+        let mut textures = TextureManager::new();
+        let error = textures.load_cpu_texture("assets/no_such_texture.png").unwrap_err();
+        match &error {
+            Error::AssetNotFound { path, searched } => {
+                assert_eq!(path, "assets/no_such_texture.png");
+                assert!(!searched.is_empty() && searched.iter().all(|file| file.ends_with("no_such_texture.png")), "{:?}", searched);
+            }
+            other => panic!("expected AssetNotFound, got {:?}", other),
         }
+        // Recoverable: the scene still gets something to sample
+        assert!(error.is_missing_asset());
+        textures.load_cpu_texture_or_placeholder("assets/no_such_texture.png");
+        let (width, height, _) = textures.texture_pixels("assets/no_such_texture.png").expect("the placeholder");
+        assert_eq!((width, height), (PLACEHOLDER_SIZE, PLACEHOLDER_SIZE));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn corrupt_png_is_image_decode() {
+        // A real PNG's signature and header, then noise where the pixels should be
+        let mut bytes = crate::assets::embedded("assets/stone.png").expect("an embedded texture")[..33].to_vec();
+        bytes.extend((0..64u8).map(|i| i.wrapping_mul(97)));
+        let file = std::env::temp_dir().join(format!("raytracer-corrupt-{}.png", std::process::id()));
+        fs::write(&file, &bytes).unwrap();
+        let error = TextureManager::new().load_cpu_texture(file.to_str().unwrap()).unwrap_err();
+        fs::remove_file(&file).unwrap();
+        match &error {
+            Error::ImageDecode { path } => assert_eq!(path, &file),
+            other => panic!("expected ImageDecode, got {:?}", other),
+        }
+        assert!(error.is_missing_asset());
+    }
+}
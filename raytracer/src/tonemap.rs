@@ -0,0 +1,86 @@
+// tonemap.rs
+// HDR radiance to displayable 8-bit color. `render` produces linear floats;
+// this is the only place they are squeezed into [0, 1] and encoded with the
+// output transfer function. A frame can be dithered on the way to 8 bits: a
+// fixed per-pixel offset of up to one step, added after the transfer and
+// before rounding, breaks the bands a slow gradient like the sky otherwise
+// shows. The offset depends only on the pixel, so a still view resolves to the
+// same bytes every time.
+use rayon::prelude::*;
+use raylib::prelude::*;
+
+use crate::material::vector3_to_color;
+use crate::settings::{OutputTransfer, RenderSettings, ToneMapping};
+
+/// Extended Reinhard, `x * (1 + x / w²) / (1 + x)`: maps `white_point` to 1.0,
+/// so highlights roll off below it and clip only above it.
+fn reinhard(x: f32, white_point: f32) -> f32 {
+    let w2 = (white_point * white_point).max(1e-6);
+    x * (1.0 + x / w2) / (1.0 + x)
+}
+
+/// Tone maps one HDR color and encodes it with `settings.output_transfer`.
+/// `exposure_scale` multiplies the settings' exposure, e.g. 4.0 for +2 stops.
+pub fn tonemap(color: Vector3, settings: &RenderSettings, exposure_scale: f32) -> Vector3 {
+    let exposed = color * (settings.exposure * exposure_scale);
+    let mapped = match settings.tone_mapping {
+        ToneMapping::Clamp => exposed,
+        ToneMapping::Reinhard => Vector3::new(
+            reinhard(exposed.x, settings.white_point),
+            reinhard(exposed.y, settings.white_point),
+            reinhard(exposed.z, settings.white_point),
+        ),
+    };
+    match settings.output_transfer {
+        // Left alone: `vector3_to_color` clips it exactly as it always has
+        OutputTransfer::Raw => mapped,
+        transfer => Vector3::new(transfer.encode(mapped.x), transfer.encode(mapped.y), transfer.encode(mapped.z)),
+    }
+}
+
+/// Maps a linear HDR frame to display colors, with exposure multiplied by `exposure_scale`.
+pub fn tonemap_buffer(hdr: &[Vector3], settings: &RenderSettings, exposure_scale: f32) -> Vec<Color> {
+    let mut colors = vec![Color::BLACK; hdr.len()];
+    tonemap_into(hdr, &mut colors, settings, exposure_scale);
+    colors
+}
+
+/// `tonemap_buffer` into `colors`, which is as long as `hdr`.
+pub fn tonemap_into(hdr: &[Vector3], colors: &mut [Color], settings: &RenderSettings, exposure_scale: f32) {
+    colors.par_iter_mut().zip(hdr.par_iter()).for_each(|(out, &color)| {
+        *out = vector3_to_color(tonemap(color, settings, exposure_scale));
+    });
+}
+
+/// `tonemap_into` for a frame `width` pixels wide, with `dither_noise` of up
+/// to one 8-bit step added to every channel before it is rounded down; on
+/// average that is rounding to nearest with ±0.5 steps of noise.
+pub fn tonemap_dithered_into(hdr: &[Vector3], colors: &mut [Color], width: usize, settings: &RenderSettings, exposure_scale: f32) {
+    colors.par_iter_mut().zip(hdr.par_iter()).enumerate().for_each(|(i, (out, &color))| {
+        *out = tonemap_dithered(color, (i % width) as u32, (i / width) as u32, settings, exposure_scale);
+    });
+}
+
+/// One pixel of `tonemap_dithered_into`: `color` at column `x`, row `y` of the
+/// frame, so a part of it resolves to the same bytes as the whole.
+pub fn tonemap_dithered(color: Vector3, x: u32, y: u32, settings: &RenderSettings, exposure_scale: f32) -> Color {
+    let offset = dither_noise(x, y);
+    let mapped = tonemap(color, settings, exposure_scale);
+    let channel = |c: f32| (c * 255.0 + offset).clamp(0.0, 255.0) as u8;
+    Color::new(channel(mapped.x), channel(mapped.y), channel(mapped.z), 255)
+}
+
+/// Interleaved gradient noise: a fixed value in [0, 1) per pixel, spread
+/// like blue noise, so the dither shows no pattern and does not flicker.
+fn dither_noise(x: u32, y: u32) -> f32 {
+    let v = 0.067_110_56 * x as f32 + 0.005_837_15 * y as f32;
+    (52.982_918 * v.fract()).fract()
+}
+
+/// Copies `colors` into `bytes` as RGBA, the layout of a window texture;
+/// `bytemuck::cast_slice(bytes)` is then the upload.
+pub fn pack_rgba(colors: &[Color], bytes: &mut [[u8; 4]]) {
+    bytes.par_iter_mut().zip(colors.par_iter()).for_each(|(out, color)| {
+        *out = [color.r, color.g, color.b, color.a];
+    });
+}
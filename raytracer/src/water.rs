@@ -0,0 +1,307 @@
+// water.rs
+// Agua que fluye: cada bloque "water" de 1x1x1 es una fuente. El agua cae sin
+// límite (hasta el bloque más bajo de la escena) y, donde tiene suelo debajo, se
+// extiende en horizontal perdiendo un nivel por celda. Los flujos son cajas de
+// altura parcial sin nombre de material, así que no se guardan: son estado
+// derivado de las fuentes. Se recalcula solo alrededor de las celdas editadas,
+// un paso por tick, y al quitar la fuente el flujo se va secando.
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use raylib::prelude::*;
+
+use crate::cube::Cube;
+use crate::material::Material;
+use crate::scene::Scene;
+
+/// Nombre del material de las fuentes.
+pub const WATER_MATERIAL: &str = "water";
+
+// Altura de una celda llena de flujo; algo menos que un bloque para que se vea el borde
+const FULL_FLOW_HEIGHT: f32 = 0.9;
+const MIN_FLOW_HEIGHT: f32 = 0.1;
+// Cambios máximos por tick, para acotar el coste de un frame
+const MAX_CHANGES_PER_TICK: usize = 256;
+
+type Cell = (i32, i32, i32);
+
+/// Alcance y velocidad del flujo de agua.
+#[derive(Debug, Clone)]
+pub struct WaterSettings {
+    /// Celdas que avanza en horizontal desde una fuente.
+    pub spread: u8,
+    /// Segundos por tick de simulación.
+    pub tick_seconds: f32,
+}
+
+impl Default for WaterSettings {
+    fn default() -> Self {
+        WaterSettings { spread: 4, tick_seconds: 0.25 }
+    }
+}
+
+/// Simulación del agua que fluye desde las fuentes.
+pub struct WaterFlow {
+    /// Parámetros del flujo.
+    pub settings: WaterSettings,
+    fallback: Arc<Material>, // si la escena no tiene material "water" en la biblioteca
+    levels: HashMap<Cell, u8>, // celdas con flujo y su nivel (1..=spread)
+    flow_cubes: HashMap<Cell, Cube>,
+    dirty: HashSet<Cell>,
+    accumulator: f32,
+}
+
+fn center_of(cell: Cell) -> Vector3 {
+    Vector3::new(cell.0 as f32, cell.1 as f32, cell.2 as f32)
+}
+
+fn horizontal_neighbors((x, y, z): Cell) -> [Cell; 4] {
+    [(x + 1, y, z), (x - 1, y, z), (x, y, z + 1), (x, y, z - 1)]
+}
+
+impl WaterFlow {
+    /// Sin flujo; `fallback` se usa si la escena no tiene material "water".
+    pub fn new(settings: WaterSettings, fallback: Arc<Material>) -> Self {
+        WaterFlow {
+            settings,
+            fallback,
+            levels: HashMap::new(),
+            flow_cubes: HashMap::new(),
+            dirty: HashSet::new(),
+            accumulator: 0.0,
+        }
+    }
+
+    /// Celdas con agua en movimiento.
+    pub fn flowing_cells(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Dormida cuando no hay ninguna celda pendiente de revisar.
+    pub fn is_idle(&self) -> bool {
+        self.dirty.is_empty()
+    }
+
+    /// Celdas cuyo nivel puede depender de `cell`: ella, sus vecinas
+    /// horizontales, la de debajo (caída) y las vecinas de la de encima (que la
+    /// usan como suelo).
+    fn mark_around(&mut self, (x, y, z): Cell) {
+        self.dirty.insert((x, y, z));
+        self.dirty.insert((x, y - 1, z));
+        self.dirty.extend(horizontal_neighbors((x, y, z)));
+        self.dirty.extend(horizontal_neighbors((x, y + 1, z)));
+    }
+
+    /// Celdas tocadas por ediciones desde el último frame.
+    pub fn mark_edited(&mut self, cells: &[Cell]) {
+        for &cell in cells {
+            self.mark_around(cell);
+        }
+    }
+
+    /// Avanza los ticks que tocan en `dt` y coloca o quita los bloques de flujo.
+    pub fn update(&mut self, dt: f32, scene: &mut Scene) {
+        if self.dirty.is_empty() {
+            self.accumulator = 0.0;
+            return;
+        }
+        self.accumulator += dt;
+        if self.accumulator >= self.settings.tick_seconds {
+            self.accumulator = 0.0;
+            self.tick(scene);
+        }
+    }
+
+    fn tick(&mut self, scene: &mut Scene) {
+        let blocks: HashMap<Cell, usize> = scene
+            .objects
+            .iter()
+            .enumerate()
+            .filter_map(|(index, cube)| cube.grid_cell().map(|cell| (cell, index)))
+            .collect();
+        let Some(floor) = blocks.keys().map(|(_, y, _)| *y).min() else {
+            self.dirty.clear();
+            return;
+        };
+
+        let mut pending: Vec<Cell> = self.dirty.drain().collect();
+        pending.sort_by_key(|&(x, y, z)| (y, x, z));
+
+        // Todas las decisiones usan el estado del inicio del tick
+        let mut changes: Vec<(Cell, u8)> = Vec::new();
+        for (i, &cell) in pending.iter().enumerate() {
+            if changes.len() == MAX_CHANGES_PER_TICK {
+                // El resto queda para el próximo tick
+                self.dirty.extend(&pending[i..]);
+                break;
+            }
+            if cell.1 < floor || blocks.contains_key(&cell) {
+                continue;
+            }
+            let current = self.levels.get(&cell).copied().unwrap_or(0);
+            if current == 0 && scene.is_solid_at(&center_of(cell)) {
+                continue; // otra geometría (antorchas, chunks)
+            }
+            let desired = self.desired_level(cell, &blocks, scene);
+            if desired != current {
+                changes.push((cell, desired));
+            }
+        }
+        if changes.is_empty() {
+            return;
+        }
+
+        let mut stale: Vec<usize> = changes
+            .iter()
+            .filter_map(|(cell, _)| self.flow_cubes.remove(cell))
+            .filter_map(|cube| scene.find_cube(&cube))
+            .collect();
+        stale.sort_unstable();
+        stale.dedup();
+        if !stale.is_empty() {
+            scene.remove_cubes(&stale);
+        }
+
+        // La biblioteca manda, para que el flujo siga las ediciones del material.
+        // Sin nombre: no entra en la biblioteca ni en los archivos guardados
+        let mut water = match scene.material_index(WATER_MATERIAL) {
+            Some(index) => Material::clone(&scene.materials[index]),
+            None => Material::clone(&self.fallback),
+        };
+        water.name = None;
+        let water = Arc::new(water);
+        let mut added = Vec::new();
+        for (cell, level) in changes {
+            self.mark_around(cell);
+            if level == 0 {
+                self.levels.remove(&cell);
+                continue;
+            }
+            self.levels.insert(cell, level);
+            let cube = self.flow_cube(cell, level, &water);
+            self.flow_cubes.insert(cell, cube.clone());
+            added.push(cube);
+        }
+        if !added.is_empty() {
+            scene.add_cubes(added);
+        }
+    }
+
+    /// Nivel de fuente o flujo en `cell`; 0 si no hay agua.
+    fn level_at(&self, cell: Cell, blocks: &HashMap<Cell, usize>, scene: &Scene) -> u8 {
+        match blocks.get(&cell) {
+            Some(&index) if scene.objects[index].material.name.as_deref() == Some(WATER_MATERIAL) => {
+                self.settings.spread.saturating_add(1)
+            }
+            Some(_) => 0,
+            None => self.levels.get(&cell).copied().unwrap_or(0),
+        }
+    }
+
+    /// Indica si el agua de la celda de encima puede apoyarse aquí en vez de caer.
+    fn supports(&self, cell: Cell, blocks: &HashMap<Cell, usize>, scene: &Scene) -> bool {
+        !self.levels.contains_key(&cell) && (blocks.contains_key(&cell) || scene.is_solid_at(&center_of(cell)))
+    }
+
+    fn desired_level(&self, cell: Cell, blocks: &HashMap<Cell, usize>, scene: &Scene) -> u8 {
+        let (x, y, z) = cell;
+        // Cae sin perder fuerza
+        if self.level_at((x, y + 1, z), blocks, scene) > 0 {
+            return self.settings.spread;
+        }
+        // Se extiende desde vecinas que no pueden caer
+        horizontal_neighbors(cell)
+            .into_iter()
+            .filter(|&(nx, ny, nz)| self.supports((nx, ny - 1, nz), blocks, scene))
+            .map(|neighbor| self.level_at(neighbor, blocks, scene).saturating_sub(1))
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn flow_cube(&self, cell: Cell, level: u8, water: &Arc<Material>) -> Cube {
+        let full = self.settings.spread.saturating_add(1) as f32;
+        let height = (FULL_FLOW_HEIGHT * level as f32 / full).max(MIN_FLOW_HEIGHT);
+        let floor = cell.1 as f32 - 0.5;
+        let mut cube = Cube::new(center_of(cell), 1.0, Arc::clone(water));
+        cube.min_bounds.y = floor;
+        cube.max_bounds.y = floor + height;
+        cube
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(name: &str) -> Arc<Material> {
+        Arc::new(Material { name: Some(Arc::from(name)), ..Material::black() })
+    }
+
+    // Suelo de piedra en `y` para x en `xs` y z en -8..=8
+    fn floor(y: i32, xs: std::ops::RangeInclusive<i32>, stone: &Arc<Material>) -> Vec<Cube> {
+        xs.flat_map(|x| (-8..=8).map(move |z| (x, z)))
+            .map(|(x, z)| Cube::new(center_of((x, y, z)), 1.0, Arc::clone(stone)))
+            .collect()
+    }
+
+    // Fuente en (0, 1, 0) y ticks hasta que el flujo se duerme
+    fn settle(mut objects: Vec<Cube>) -> WaterFlow {
+        objects.push(Cube::new(center_of((0, 1, 0)), 1.0, named(WATER_MATERIAL)));
+        let mut scene = Scene::new(objects);
+        let settings = WaterSettings::default();
+        let tick = settings.tick_seconds;
+        let mut water = WaterFlow::new(settings, named("fallback"));
+        water.mark_edited(&[(0, 1, 0)]);
+        for _ in 0..1000 {
+            if water.is_idle() {
+                return water;
+            }
+            water.update(tick, &mut scene);
+        }
+        panic!("water flow did not settle");
+    }
+
+    #[test]
+    fn water_on_a_flat_plane_spreads_in_a_diamond() {
+        let stone = named("stone");
+        let water = settle(floor(0, -8..=8, &stone));
+        let spread = water.settings.spread as i32;
+        for x in -8..=8_i32 {
+            for z in -8..=8_i32 {
+                let distance = x.abs() + z.abs();
+                let expected = if distance == 0 || distance > spread { 0 } else { (spread + 1 - distance) as u8 };
+                assert_eq!(water.levels.get(&(x, 1, z)).copied().unwrap_or(0), expected, "cell ({}, 1, {})", x, z);
+            }
+        }
+        assert_eq!(water.flowing_cells(), (2 * spread * (spread + 1)) as usize);
+    }
+
+    #[test]
+    fn water_falls_off_a_cliff_edge_without_losing_strength() {
+        let stone = named("stone");
+        // Meseta en y = 0 hasta x = 1 y suelo bajo en y = -5
+        let mut objects = floor(0, -8..=1, &stone);
+        objects.extend(floor(-5, -8..=8, &stone));
+        let water = settle(objects);
+        let spread = water.settings.spread;
+        let level = |cell: Cell| water.levels.get(&cell).copied().unwrap_or(0);
+
+        // Pasa el borde una celda y cae en vez de seguir por el aire
+        assert_eq!(level((1, 1, 0)), spread);
+        assert_eq!(level((2, 1, 0)), spread - 1);
+        assert_eq!(level((3, 1, 0)), 0);
+        for y in -4..=0 {
+            assert_eq!(level((2, y, 0)), spread, "falling column at y = {}", y);
+        }
+        // Abajo se extiende desde la columna, no desde la fuente
+        assert_eq!(level((3, -4, 0)), spread - 1);
+        // Cada celda que asoma por el borde tiene su propia columna
+        for z in -8..=8 {
+            let spilled = level((2, 1, z)) > 0;
+            assert_eq!(level((2, -4, z)) == spread, spilled, "column at z = {}", z);
+        }
+        assert_eq!(level((2 + spread as i32, -4, 0)), 0);
+        // Nada por debajo del bloque más bajo
+        assert!(water.levels.keys().all(|&(_, y, _)| y >= -4));
+    }
+}
@@ -0,0 +1,208 @@
+// weather.rs
+// Lluvia y nieve como partículas simuladas en CPU y dibujadas encima de la imagen
+// trazada, con test de profundidad contra el buffer de distancias del rayo primario.
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::camera::Camera;
+use crate::scene::Scene;
+
+/// Tiempo que hace; cambia la luz del sol, la niebla y las partículas.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Weather {
+    /// Despejado.
+    Clear,
+    /// Lluvia.
+    Rain,
+    /// Nieve.
+    Snow,
+}
+
+impl Weather {
+    /// El siguiente en el ciclo despejado, lluvia, nieve.
+    pub fn next(self) -> Self {
+        match self {
+            Weather::Clear => Weather::Rain,
+            Weather::Rain => Weather::Snow,
+            Weather::Snow => Weather::Clear,
+        }
+    }
+
+    /// Nombre para el HUD.
+    pub fn name(self) -> &'static str {
+        match self {
+            Weather::Clear => "Clear",
+            Weather::Rain => "Rain",
+            Weather::Snow => "Snow",
+        }
+    }
+
+    /// Factor que se aplica a la intensidad del sol.
+    pub fn sun_factor(self) -> f32 {
+        match self {
+            Weather::Clear => 1.0,
+            Weather::Rain => 0.5,
+            Weather::Snow => 0.75,
+        }
+    }
+
+    /// Densidad de la niebla exponencial.
+    pub fn fog_density(self) -> f32 {
+        match self {
+            Weather::Clear => 0.0,
+            Weather::Rain => 0.02,
+            Weather::Snow => 0.012,
+        }
+    }
+
+    fn particle_count(self) -> usize {
+        match self {
+            Weather::Clear => 0,
+            Weather::Rain => 400,
+            Weather::Snow => 300,
+        }
+    }
+}
+
+struct Particle {
+    position: Vector3,
+    velocity: Vector3,
+    phase: f32,
+}
+
+// Las partículas viven en una caja alrededor del punto que mira la cámara
+const SPAWN_HALF_EXTENT: f32 = 12.0;
+const SPAWN_HEIGHT: f32 = 14.0;
+const GROUND_Y: f32 = -0.5;
+const MAX_SPAWN_ATTEMPTS: usize = 8;
+
+/// Tiempo actual y sus partículas.
+pub struct WeatherSystem {
+    /// Tiempo actual; para cambiarlo usar `cycle` o `set`.
+    pub weather: Weather,
+    particles: Vec<Particle>,
+    rng_state: u32,
+}
+
+impl WeatherSystem {
+    /// Despejado, sin partículas.
+    pub fn new() -> Self {
+        WeatherSystem {
+            weather: Weather::Clear,
+            particles: Vec::new(),
+            rng_state: 0x9e37_79b9,
+        }
+    }
+
+    /// Pasa al siguiente tiempo y descarta las partículas.
+    pub fn cycle(&mut self) {
+        self.weather = self.weather.next();
+        self.particles.clear();
+    }
+
+    /// Cambia a `weather`; las partículas solo se descartan si de verdad cambia.
+    pub fn set(&mut self, weather: Weather) {
+        if self.weather != weather {
+            self.weather = weather;
+            self.particles.clear();
+        }
+    }
+
+    // xorshift32: determinista y suficiente para repartir partículas
+    fn random(&mut self) -> f32 {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng_state = x;
+        x as f32 / u32::MAX as f32
+    }
+
+    fn spawn(&mut self, center: Vector3, scene: &Scene, anywhere_in_column: bool) -> Particle {
+        let mut position = center;
+        for _ in 0..MAX_SPAWN_ATTEMPTS {
+            let x = center.x + (self.random() * 2.0 - 1.0) * SPAWN_HALF_EXTENT;
+            let z = center.z + (self.random() * 2.0 - 1.0) * SPAWN_HALF_EXTENT;
+            let top = center.y + SPAWN_HEIGHT;
+            let y = if anywhere_in_column {
+                GROUND_Y + self.random() * (top - GROUND_Y)
+            } else {
+                top
+            };
+            position = Vector3::new(x, y, z);
+            if !scene.is_solid_at(&position) {
+                break;
+            }
+        }
+
+        let velocity = match self.weather {
+            Weather::Rain => Vector3::new(0.8, -14.0, 0.3),
+            _ => Vector3::new(0.0, -1.5 - self.random(), 0.0),
+        };
+        Particle { position, velocity, phase: self.random() * std::f32::consts::TAU }
+    }
+
+    /// Mueve las partículas y genera las que falten alrededor de lo que mira la cámara.
+    pub fn update(&mut self, dt: f32, camera: &Camera, scene: &Scene) {
+        let target = self.weather.particle_count();
+        let center = camera.center;
+        while self.particles.len() < target {
+            let particle = self.spawn(center, scene, true);
+            self.particles.push(particle);
+        }
+
+        for i in 0..self.particles.len() {
+            let particle = &mut self.particles[i];
+            let mut velocity = particle.velocity;
+            if self.weather == Weather::Snow {
+                // balanceo lateral de los copos
+                particle.phase += dt * 2.0;
+                velocity.x += particle.phase.sin() * 0.6;
+                velocity.z += particle.phase.cos() * 0.4;
+            }
+            particle.position += velocity * dt;
+
+            let position = particle.position;
+            let outside = (position.x - center.x).abs() > SPAWN_HALF_EXTENT
+                || (position.z - center.z).abs() > SPAWN_HALF_EXTENT;
+            if position.y < GROUND_Y || outside || scene.is_solid_at(&position) {
+                self.particles[i] = self.spawn(center, scene, false);
+            }
+        }
+    }
+
+    /// Dibuja las partículas visibles. `depth` es la distancia del rayo primario
+    /// por píxel: una partícula detrás de la geometría no se dibuja.
+    pub fn draw(&self, d: &mut impl RaylibDraw, camera: &Camera, depth: &[f32], width: i32, height: i32) {
+        for particle in &self.particles {
+            let Some((x, y, distance)) = camera.project(particle.position, width, height) else {
+                continue;
+            };
+            let (px, py) = (x as i32, y as i32);
+            if px < 0 || py < 0 || px >= width || py >= height {
+                continue;
+            }
+            if distance > depth[(py * width + px) as usize] {
+                continue;
+            }
+
+            match self.weather {
+                Weather::Rain => {
+                    let tail = particle.position - particle.velocity * 0.04;
+                    if let Some((tx, ty, _)) = camera.project(tail, width, height) {
+                        d.draw_line(px, py, tx as i32, ty as i32, Color::new(170, 190, 220, 160));
+                    }
+                }
+                _ => {
+                    let size = if distance < 8.0 { 3 } else { 2 };
+                    d.draw_rectangle(px, py, size, size, Color::new(245, 245, 255, 220));
+                }
+            }
+        }
+    }
+}
+
+impl Default for WeatherSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,368 @@
+// world.rs
+// Mundo en binario: cabecera mágica + versión y después bincode comprimido con
+// zstd. Los bloques de 1x1x1 se guardan por chunks con una paleta local y los
+// índices empaquetados en bits; el resto de cubos (antorchas, etc.) van sueltos.
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use bincode::Options;
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::CameraBookmark;
+use crate::chunks::{ChunkPos, CHUNK_SIZE};
+use crate::cube::Cube;
+use crate::light::Light;
+use crate::material::Material;
+use crate::scene::{Scene, LAYER_COUNT};
+use crate::scene_file::MaterialEntry;
+
+/// Archivo de mundo por defecto, relativo al directorio de trabajo.
+pub const WORLD_FILE: &str = "world.mcw";
+
+const MAGIC: &[u8; 8] = b"MCRTWRLD";
+const VERSION: u32 = 8;
+const ZSTD_LEVEL: i32 = 3;
+const AIR: u32 = 0;
+
+#[derive(Serialize, Deserialize)]
+struct WorldData {
+    materials: Vec<MaterialEntry>,
+    groups: Vec<String>,
+    chunks: Vec<ChunkRecord>,
+    loose: Vec<LooseCube>,
+    bookmarks: Vec<BookmarkRecord>,
+    camera: BookmarkRecord,
+    lights: Vec<LightRecord>,
+    time_of_day: f32,
+}
+
+/// Bloques de un chunk entre `min_y` y `min_y + height`. `palette[i - 1]` es la
+/// terna (material, grupo, capa) global del índice local `i`; el 0 es aire. El
+/// grupo 0 es "sin grupo" y `g` es `groups[g - 1]`.
+#[derive(Serialize, Deserialize)]
+struct ChunkRecord {
+    x: i32,
+    z: i32,
+    min_y: i32,
+    height: u32,
+    palette: Vec<Block>,
+    bits: u8,
+    data: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LooseCube {
+    center: [f32; 3],
+    extent: [f32; 3],
+    material: u32,
+    group: u32,
+    layer: u8,
+}
+
+type Block = (u32, u32, u8); // (material, grupo, capa)
+
+#[derive(Serialize, Deserialize)]
+struct BookmarkRecord {
+    name: String,
+    eye: [f32; 3],
+    center: [f32; 3],
+}
+
+#[derive(Serialize, Deserialize)]
+struct LightRecord {
+    position: [f32; 3],
+    color: [f32; 3],
+    intensity: f32,
+    radius: f32,
+}
+
+/// Lo que devuelve `load_binary`: la escena más el estado de la sesión guardada.
+pub struct WorldSnapshot {
+    /// La escena.
+    pub scene: Scene,
+    /// Posición de la cámara al guardar.
+    pub camera: CameraBookmark,
+    /// Luces puestas por el usuario.
+    pub lights: Vec<Light>,
+    /// Ángulo del ciclo día/noche.
+    pub time_of_day: f32,
+}
+
+fn to_array(v: Vector3) -> [f32; 3] {
+    [v.x, v.y, v.z]
+}
+
+fn to_vector(v: [f32; 3]) -> Vector3 {
+    Vector3::new(v[0], v[1], v[2])
+}
+
+impl BookmarkRecord {
+    fn from_bookmark(bookmark: &CameraBookmark) -> Self {
+        BookmarkRecord { name: bookmark.name.clone(), eye: to_array(bookmark.eye), center: to_array(bookmark.center) }
+    }
+
+    fn into_bookmark(self) -> CameraBookmark {
+        CameraBookmark { name: self.name, eye: to_vector(self.eye), center: to_vector(self.center) }
+    }
+}
+
+fn bits_for(entries: usize) -> u8 {
+    // Índices 0..entries, incluido el aire
+    let mut bits = 1;
+    while (1usize << bits) < entries {
+        bits += 1;
+    }
+    bits
+}
+
+fn cell_index(local_x: i32, y: i32, local_z: i32) -> usize {
+    ((y * CHUNK_SIZE + local_z) * CHUNK_SIZE + local_x) as usize
+}
+
+impl ChunkRecord {
+    fn pack(pos: ChunkPos, blocks: &[((i32, i32, i32), Block)]) -> Self {
+        let min_y = blocks.iter().map(|((_, y, _), _)| *y).min().unwrap_or(0);
+        let max_y = blocks.iter().map(|((_, y, _), _)| *y).max().unwrap_or(0);
+        let height = (max_y - min_y + 1) as u32;
+
+        let mut palette: Vec<Block> = Vec::new();
+        let mut cells = vec![AIR; (CHUNK_SIZE * CHUNK_SIZE) as usize * height as usize];
+        for ((x, y, z), material) in blocks {
+            let local = match palette.iter().position(|m| m == material) {
+                Some(i) => i as u32 + 1,
+                None => {
+                    palette.push(*material);
+                    palette.len() as u32
+                }
+            };
+            let index = cell_index(x - pos.x * CHUNK_SIZE, y - min_y, z - pos.z * CHUNK_SIZE);
+            cells[index] = local;
+        }
+
+        let bits = bits_for(palette.len() + 1);
+        let per_word = 64 / bits as usize;
+        let mut data = vec![0u64; cells.len().div_ceil(per_word)];
+        for (i, cell) in cells.iter().enumerate() {
+            data[i / per_word] |= (*cell as u64) << ((i % per_word) * bits as usize);
+        }
+
+        ChunkRecord { x: pos.x, z: pos.z, min_y, height, palette, bits, data }
+    }
+
+    /// Desempaqueta los bloques, comprobando que el registro sea coherente.
+    fn unpack(&self, material_count: usize, group_count: usize, out: &mut Vec<((i32, i32, i32), Block)>) -> Result<(), String> {
+        let context = |message: &str| format!("chunk ({}, {}): {}", self.x, self.z, message);
+        if self.bits == 0 || self.bits > 32 || self.bits < bits_for(self.palette.len() + 1) {
+            return Err(context("invalid bit width"));
+        }
+        if let Some((material, _, _)) = self.palette.iter().find(|(m, _, _)| *m as usize >= material_count) {
+            return Err(context(&format!("unknown material {}", material)));
+        }
+        if let Some((_, group, _)) = self.palette.iter().find(|(_, g, _)| *g as usize > group_count) {
+            return Err(context(&format!("unknown group {}", group)));
+        }
+        if let Some((_, _, layer)) = self.palette.iter().find(|(_, _, l)| *l >= LAYER_COUNT) {
+            return Err(context(&format!("unknown layer {}", layer)));
+        }
+        let cell_count = (CHUNK_SIZE * CHUNK_SIZE) as usize * self.height as usize;
+        let per_word = 64 / self.bits as usize;
+        if self.data.len() != cell_count.div_ceil(per_word) {
+            return Err(context("block data has the wrong length"));
+        }
+
+        let mask = (1u64 << self.bits) - 1;
+        for i in 0..cell_count {
+            let local = ((self.data[i / per_word] >> ((i % per_word) * self.bits as usize)) & mask) as usize;
+            if local == AIR as usize {
+                continue;
+            }
+            let block = *self.palette.get(local - 1).ok_or_else(|| context("palette index out of range"))?;
+            let i = i as i32;
+            let local_x = i % CHUNK_SIZE;
+            let local_z = (i / CHUNK_SIZE) % CHUNK_SIZE;
+            let y = i / (CHUNK_SIZE * CHUNK_SIZE);
+            out.push(((self.x * CHUNK_SIZE + local_x, self.min_y + y, self.z * CHUNK_SIZE + local_z), block));
+        }
+        Ok(())
+    }
+}
+
+/// Guarda los cubos estáticos de la escena (no los chunks en streaming, que se
+/// regeneran), la biblioteca de materiales, la cámara y las luces.
+pub fn save_binary(
+    path: impl AsRef<Path>,
+    scene: &Scene,
+    camera: &CameraBookmark,
+    lights: &[Light],
+    time_of_day: f32,
+) -> Result<(), String> {
+    let path = path.as_ref();
+    let material_ids: HashMap<&str, u32> = scene
+        .materials
+        .iter()
+        .enumerate()
+        .filter_map(|(i, m)| m.name.as_deref().map(|name| (name, i as u32)))
+        .collect();
+
+    let groups: Vec<String> = scene.groups.iter().map(|g| g.name.to_string()).collect();
+    let group_id = |cube: &Cube| -> u32 {
+        cube.group
+            .as_deref()
+            .and_then(|name| groups.iter().position(|g| g == name))
+            .map_or(0, |i| i as u32 + 1)
+    };
+
+    let mut by_chunk: HashMap<ChunkPos, Vec<((i32, i32, i32), Block)>> = HashMap::new();
+    let mut occupied = HashSet::new();
+    let mut loose = Vec::new();
+    for cube in scene.all_objects() {
+        let Some(&material) = cube.material.name.as_deref().and_then(|name| material_ids.get(name)) else {
+            continue; // sin nombre no hay entrada en la paleta
+        };
+        match cube.grid_cell() {
+            // Un segundo cubo en la misma celda no cabe en la rejilla: va suelto
+            Some(cell) if occupied.insert(cell) => {
+                let pos = ChunkPos { x: cell.0.div_euclid(CHUNK_SIZE), z: cell.2.div_euclid(CHUNK_SIZE) };
+                by_chunk.entry(pos).or_default().push((cell, (material, group_id(cube), cube.layer)));
+            }
+            _ => loose.push(LooseCube {
+                center: to_array((cube.min_bounds + cube.max_bounds) * 0.5),
+                extent: to_array(cube.max_bounds - cube.min_bounds),
+                material,
+                group: group_id(cube),
+                layer: cube.layer,
+            }),
+        }
+    }
+
+    let mut chunk_positions: Vec<ChunkPos> = by_chunk.keys().copied().collect();
+    chunk_positions.sort_by_key(|pos| (pos.z, pos.x));
+    let data = WorldData {
+        materials: scene
+            .materials
+            .iter()
+            .map(|m| MaterialEntry::from_material(m.name.as_deref().unwrap_or(""), m))
+            .collect(),
+        groups,
+        chunks: chunk_positions.iter().map(|pos| ChunkRecord::pack(*pos, &by_chunk[pos])).collect(),
+        loose,
+        bookmarks: scene.bookmarks.iter().map(BookmarkRecord::from_bookmark).collect(),
+        camera: BookmarkRecord::from_bookmark(camera),
+        lights: lights
+            .iter()
+            .map(|l| LightRecord { position: to_array(l.position), color: to_array(l.color), intensity: l.intensity, radius: l.radius })
+            .collect(),
+        time_of_day,
+    };
+
+    let encoded = bincode::DefaultOptions::new().serialize(&data).map_err(|e| e.to_string())?;
+    let compressed = zstd::encode_all(encoded.as_slice(), ZSTD_LEVEL).map_err(|e| e.to_string())?;
+    let mut bytes = Vec::with_capacity(MAGIC.len() + 4 + compressed.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&compressed);
+    fs::write(path, bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Lee un mundo escrito por `save_binary`.
+pub fn load_binary(path: impl AsRef<Path>) -> Result<WorldSnapshot, String> {
+    let path = path.as_ref();
+    let error = |message: String| format!("{}: {}", path.display(), message);
+    let bytes = fs::read(path).map_err(|e| error(e.to_string()))?;
+
+    let header = MAGIC.len() + 4;
+    if bytes.len() < header || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(error("not a world file".to_string()));
+    }
+    let version = u32::from_le_bytes(bytes[MAGIC.len()..header].try_into().unwrap());
+    if version != VERSION {
+        return Err(error(format!("unsupported world version {} (expected {})", version, VERSION)));
+    }
+
+    let encoded = zstd::decode_all(&bytes[header..]).map_err(|e| error(format!("corrupt data: {}", e)))?;
+    // The limit keeps a corrupt length prefix from asking for more than the data holds
+    let data: WorldData = bincode::DefaultOptions::new()
+        .with_limit(encoded.len() as u64)
+        .deserialize(&encoded)
+        .map_err(|e| error(format!("corrupt data: {}", e)))?;
+
+    let materials: Vec<Arc<Material>> = data.materials.iter().map(|entry| Arc::new(entry.to_material())).collect();
+    let groups: Vec<Arc<str>> = data.groups.iter().map(|name| Arc::from(name.as_str())).collect();
+    // 0 es "sin grupo"; los registros ya se validaron contra `groups.len()`
+    let group = |id: u32| id.checked_sub(1).map(|i| groups[i as usize].clone());
+    let mut blocks = Vec::new();
+    for chunk in &data.chunks {
+        chunk.unpack(materials.len(), groups.len(), &mut blocks).map_err(error)?;
+    }
+    let mut objects = Vec::with_capacity(blocks.len() + data.loose.len());
+    for ((x, y, z), (material, group_id, layer)) in blocks {
+        let center = Vector3::new(x as f32, y as f32, z as f32);
+        let cube = Cube::new(center, 1.0, Arc::clone(&materials[material as usize]));
+        objects.push(cube.in_group(group(group_id)).on_layer(layer));
+    }
+    for cube in &data.loose {
+        let material = materials
+            .get(cube.material as usize)
+            .ok_or_else(|| error(format!("unknown material {}", cube.material)))?;
+        if cube.group as usize > groups.len() {
+            return Err(error(format!("unknown group {}", cube.group)));
+        }
+        if cube.layer >= LAYER_COUNT {
+            return Err(error(format!("unknown layer {}", cube.layer)));
+        }
+        let loaded = Cube::with_extent(to_vector(cube.center), to_vector(cube.extent), Arc::clone(material));
+        objects.push(loaded.in_group(group(cube.group)).on_layer(cube.layer));
+    }
+
+    let mut scene = Scene::new(objects);
+    scene.materials = materials;
+    scene.bookmarks = data.bookmarks.into_iter().map(BookmarkRecord::into_bookmark).collect();
+    Ok(WorldSnapshot {
+        scene,
+        camera: data.camera.into_bookmark(),
+        lights: data
+            .lights
+            .iter()
+            .map(|l| Light::new(to_vector(l.position), to_vector(l.color), l.intensity).with_radius(l.radius))
+            .collect(),
+        time_of_day: data.time_of_day,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene_file::save_scene;
+    use crate::worldgen::{demo_scene, WorldGenSettings};
+
+    #[test]
+    fn binary_save_is_much_smaller_than_ron() {
+        let settings = WorldGenSettings { clouds: None, terrain: None, ..WorldGenSettings::default() };
+        let scene = demo_scene(&settings);
+        let camera = CameraBookmark { name: "test".to_string(), eye: Vector3::new(0.0, 5.0, 10.0), center: Vector3::zero() };
+        let dir = std::env::temp_dir().join(format!("raytracer-world-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let binary = dir.join("demo.mcw");
+        let text = dir.join("demo.ron");
+        save_binary(&binary, &scene, &camera, &[], 0.0).unwrap();
+        save_scene(&scene, &text).unwrap();
+
+        let binary_size = fs::metadata(&binary).unwrap().len();
+        let text_size = fs::metadata(&text).unwrap().len();
+        // Lo mismo se lee del binario que se guardó
+        let loaded = load_binary(&binary).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded.scene.all_objects().count(), scene.all_objects().count());
+        assert!(
+            binary_size * 20 < text_size,
+            "binary {} bytes vs RON {} bytes for {} cubes",
+            binary_size,
+            text_size,
+            scene.all_objects().count()
+        );
+    }
+}
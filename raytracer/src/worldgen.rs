@@ -0,0 +1,846 @@
+// worldgen.rs
+// Construcción de la escena de demostración y parámetros de generación.
+use raylib::prelude::*;
+use std::collections::HashSet;
+use std::f32::consts::PI;
+use std::sync::Arc;
+use crate::camera::CameraBookmark;
+use crate::clouds::{CloudLayer, CloudSettings};
+use crate::cube::Cube;
+use crate::explosion::TNT_MATERIAL;
+use crate::material::{Material, TextureFit, TintSource};
+use crate::chunks::{ChunkPos, CHUNK_SIZE};
+use crate::noise::{hash3, ridged_noise_3d, value_noise_2d};
+use crate::scene::{Scene, SceneBuilder};
+
+/// Qué genera la escena de demostración.
+pub struct WorldGenSettings {
+    /// Nubes; None = cielo sin nubes.
+    pub clouds: Option<CloudSettings>,           // None = cielo sin nubes
+    /// Piedra, cuevas y vetas; None = solo la capa de suelo.
+    pub underground: Option<UndergroundSettings>, // None = solo la capa de suelo
+    /// Terreno en streaming; None = sin terreno alrededor de la demo.
+    pub terrain: Option<TerrainSettings>,         // None = sin terreno alrededor de la demo
+}
+
+impl Default for WorldGenSettings {
+    fn default() -> Self {
+        WorldGenSettings {
+            clouds: Some(CloudSettings::default()),
+            underground: Some(UndergroundSettings::default()),
+            terrain: Some(TerrainSettings::default()),
+        }
+    }
+}
+
+/// Terreno por chunks que rodea la escena de demostración y se genera en streaming.
+#[derive(Debug, Clone)]
+pub struct TerrainSettings {
+    /// Radio en chunks alrededor de la cámara.
+    pub view_distance: i32,
+    /// Desnivel máximo en bloques respecto al suelo.
+    pub height_scale: f32,
+    /// Frecuencia del ruido de altura, por bloque.
+    pub noise_scale: f32,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        TerrainSettings {
+            view_distance: 2,
+            height_scale: 4.0,
+            noise_scale: 0.08,
+        }
+    }
+}
+
+/// Subsuelo de la demo: capas de piedra con cuevas y vetas de mineral.
+#[derive(Debug, Clone)]
+pub struct UndergroundSettings {
+    /// Capas de piedra bajo el suelo.
+    pub depth: i32,
+    /// 0.0 = sin cuevas, 1.0 = muy hueco.
+    pub cave_density: f32,
+    /// Vetas por bloque de piedra (aprox.)
+    pub ore_rarity: f32,
+    /// Semilla de las cuevas y las vetas.
+    pub seed: u32,
+}
+
+impl Default for UndergroundSettings {
+    fn default() -> Self {
+        UndergroundSettings {
+            depth: 5,
+            cave_density: 0.4,
+            ore_rarity: 0.015,
+            seed: 1337,
+        }
+    }
+}
+
+// El suelo ocupa x, z en [-MAP_HALF, MAP_HALF] a la altura SURFACE_Y
+const MAP_HALF: i32 = 8;
+const SURFACE_Y: i32 = -1;
+const CAVE_SCALE: f32 = 0.22;
+const CAVE_VERTICAL_SQUASH: f32 = 1.6; // cuevas más anchas que altas
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Block {
+    Air,
+    Stone,
+    Diamond,
+    Magma,
+}
+
+/// Volumen de bloques bajo el suelo, desde SURFACE_Y - 1 hacia abajo.
+struct BlockGrid {
+    depth: i32,
+    blocks: Vec<Block>,
+}
+
+impl BlockGrid {
+    const SIDE: i32 = 2 * MAP_HALF + 1;
+
+    fn filled(depth: i32) -> Self {
+        BlockGrid { depth, blocks: vec![Block::Stone; (Self::SIDE * Self::SIDE * depth) as usize] }
+    }
+
+    fn index(&self, x: i32, y: i32, z: i32) -> Option<usize> {
+        let layer = SURFACE_Y - 1 - y;
+        if x.abs() > MAP_HALF || z.abs() > MAP_HALF || layer < 0 || layer >= self.depth {
+            return None;
+        }
+        Some(((layer * Self::SIDE + z + MAP_HALF) * Self::SIDE + x + MAP_HALF) as usize)
+    }
+
+    fn get(&self, x: i32, y: i32, z: i32) -> Block {
+        self.index(x, y, z).map_or(Block::Air, |i| self.blocks[i])
+    }
+
+    fn set(&mut self, x: i32, y: i32, z: i32, block: Block) {
+        if let Some(i) = self.index(x, y, z) {
+            self.blocks[i] = block;
+        }
+    }
+
+    fn cells(&self) -> impl Iterator<Item = (i32, i32, i32)> + '_ {
+        (0..self.depth).flat_map(|layer| {
+            (-MAP_HALF..=MAP_HALF).flat_map(move |z| {
+                (-MAP_HALF..=MAP_HALF).map(move |x| (x, SURFACE_Y - 1 - layer, z))
+            })
+        })
+    }
+}
+
+// xorshift32, igual que en weather.rs: mismas semillas, mismas vetas
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    fn range(&mut self, lo: i32, hi: i32) -> i32 {
+        lo + (self.next() % (hi - lo + 1) as u32) as i32
+    }
+
+    fn chance(&mut self, p: f32) -> bool {
+        (self.next() as f32 / u32::MAX as f32) < p
+    }
+}
+
+/// Un túnel aparece donde dos ruidos "ridged" independientes superan el umbral
+/// a la vez: la intersección de dos láminas es un tubo.
+fn carve_caves(grid: &mut BlockGrid, settings: &UndergroundSettings) {
+    if settings.cave_density <= 0.0 {
+        return;
+    }
+    let threshold = 1.0 - settings.cave_density.min(1.0) * 0.5;
+    let cells: Vec<_> = grid.cells().collect();
+    for (x, y, z) in cells {
+        let (nx, ny, nz) = (x as f32 * CAVE_SCALE, y as f32 * CAVE_SCALE * CAVE_VERTICAL_SQUASH, z as f32 * CAVE_SCALE);
+        let a = ridged_noise_3d(nx, ny, nz, settings.seed);
+        let b = ridged_noise_3d(nx, ny, nz, settings.seed ^ 0x5bd1_e995);
+        if a > threshold && b > threshold {
+            grid.set(x, y, z, Block::Air);
+        }
+    }
+}
+
+/// Vetas pequeñas de diamante y magma, solo a partir de dos bloques bajo el suelo.
+fn place_ores(grid: &mut BlockGrid, settings: &UndergroundSettings) {
+    if grid.depth < 2 {
+        return;
+    }
+    let mut rng = Rng(settings.seed | 1);
+    let stone = grid.blocks.iter().filter(|b| **b == Block::Stone).count();
+    let veins = (stone as f32 * settings.ore_rarity.max(0.0)).round() as usize;
+    let deepest = SURFACE_Y - grid.depth;
+
+    for _ in 0..veins {
+        let mut x = rng.range(-MAP_HALF, MAP_HALF);
+        let mut y = rng.range(deepest, SURFACE_Y - 2);
+        let mut z = rng.range(-MAP_HALF, MAP_HALF);
+        // El magma solo aparece en la mitad inferior
+        let deep = y <= SURFACE_Y - 1 - grid.depth / 2;
+        let ore = if deep && rng.chance(0.4) { Block::Magma } else { Block::Diamond };
+
+        for _ in 0..rng.range(2, 5) {
+            if grid.get(x, y, z) == Block::Stone {
+                grid.set(x, y, z, ore);
+            }
+            match rng.range(0, 5) {
+                0 => x += 1,
+                1 => x -= 1,
+                2 => y += 1,
+                3 => y -= 1,
+                4 => z += 1,
+                _ => z -= 1,
+            }
+        }
+    }
+}
+
+/// Busca la celda de cueva con el pasillo horizontal más largo y mira a lo largo de él.
+fn cave_bookmark(grid: &BlockGrid) -> Option<CameraBookmark> {
+    let directions = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+    let mut best: Option<(i32, i32, (i32, i32, i32), (i32, i32))> = None;
+
+    for (x, y, z) in grid.cells() {
+        if grid.get(x, y, z) != Block::Air {
+            continue;
+        }
+        for (dx, dz) in directions {
+            let mut run = 0;
+            while grid.index(x + dx * (run + 1), y, z + dz * (run + 1)).is_some()
+                && grid.get(x + dx * (run + 1), y, z + dz * (run + 1)) == Block::Air
+            {
+                run += 1;
+            }
+            let distance = x * x + z * z;
+            let better = match best {
+                None => true,
+                Some((best_run, best_distance, ..)) => {
+                    run > best_run || (run == best_run && distance < best_distance)
+                }
+            };
+            if better {
+                best = Some((run, distance, (x, y, z), (dx, dz)));
+            }
+        }
+    }
+
+    best.map(|(run, _, (x, y, z), (dx, dz))| {
+        let eye = Vector3::new(x as f32, y as f32, z as f32);
+        let look = run.max(1) as f32;
+        CameraBookmark {
+            name: "Cave".to_string(),
+            eye,
+            center: eye + Vector3::new(dx as f32 * look, 0.0, dz as f32 * look),
+        }
+    })
+}
+
+// Altura del terreno en una columna. Junto a la demo se aplana hasta SURFACE_Y
+// para que el borde del suelo empalme sin escalones.
+fn terrain_height(x: i32, z: i32, settings: &TerrainSettings) -> i32 {
+    let edge_distance = (x.abs().max(z.abs()) - MAP_HALF) as f32;
+    let blend = ((edge_distance - 1.0) / 8.0).clamp(0.0, 1.0);
+    let n = value_noise_2d(x as f32 * settings.noise_scale + 100.0, z as f32 * settings.noise_scale + 100.0);
+    SURFACE_Y + ((n * 2.0 - 1.0) * settings.height_scale * blend).round() as i32
+}
+
+fn in_demo_area(x: i32, z: i32) -> bool {
+    x.abs() <= MAP_HALF && z.abs() <= MAP_HALF
+}
+
+/// Bloques de un chunk de terreno. Solo se generan los visibles: la cima de cada
+/// columna y lo que quede al descubierto por una columna vecina más baja. Como la
+/// altura es una función pura, los vecinos de otros chunks se consultan sin cargarlos.
+pub fn generate_chunk(pos: ChunkPos, settings: &TerrainSettings, palette: &BlockPalette) -> Vec<Cube> {
+    let mut cubes = Vec::new();
+    for local_z in 0..CHUNK_SIZE {
+        for local_x in 0..CHUNK_SIZE {
+            let x = pos.x * CHUNK_SIZE + local_x;
+            let z = pos.z * CHUNK_SIZE + local_z;
+            if in_demo_area(x, z) {
+                continue;
+            }
+            let height = terrain_height(x, z, settings);
+            let lowest_neighbour = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                .iter()
+                .map(|(dx, dz)| {
+                    if in_demo_area(x + dx, z + dz) {
+                        SURFACE_Y
+                    } else {
+                        terrain_height(x + dx, z + dz, settings)
+                    }
+                })
+                .min()
+                .unwrap_or(height);
+
+            for y in (lowest_neighbour + 1).min(height)..=height {
+                let material = if y == height {
+                    &palette.grass
+                } else if y >= height - 2 {
+                    &palette.dirt
+                } else {
+                    &palette.stone
+                };
+                cubes.push(Cube::new(Vector3::new(x as f32, y as f32, z as f32), 1.0, material.clone()));
+            }
+        }
+    }
+    cubes
+}
+
+/// Quita los bloques opacos de 1x1x1 rodeados por opacos en sus seis caras:
+/// ningún rayo que empiece fuera de ellos puede alcanzarlos.
+pub fn cull_hidden_blocks(objects: Vec<Cube>) -> Vec<Cube> {
+    let grid_cell = |cube: &Cube| cube.grid_cell().filter(|_| cube.material.transparency <= 0.0);
+
+    let opaque: HashSet<(i32, i32, i32)> = objects.iter().filter_map(grid_cell).collect();
+    let neighbours = [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)];
+
+    objects
+        .into_iter()
+        .filter(|cube| match grid_cell(cube) {
+            Some((x, y, z)) => !neighbours
+                .iter()
+                .all(|(dx, dy, dz)| opaque.contains(&(x + dx, y + dy, z + dz))),
+            None => true,
+        })
+        .collect()
+}
+
+/// Los materiales de los bloques del mundo; cada uno se comparte entre todos
+/// los cubos que lo usan.
+pub struct BlockPalette {
+    /// Vidrio.
+    pub glass: Arc<Material>,
+    /// Vidrio esmerilado: el reflejo nítido, lo de detrás borroso.
+    pub frosted_glass: Arc<Material>,
+    /// Agua; cada bloque es una fuente.
+    pub water: Arc<Material>,
+    /// Mineral de diamante.
+    pub diamond_ore: Arc<Material>,
+    /// Obsidiana.
+    pub obsidian: Arc<Material>,
+    /// Magma, emisivo.
+    pub magma: Arc<Material>,
+    /// Tierra.
+    pub dirt: Arc<Material>,
+    /// Hierba, teñida con ruido.
+    pub grass: Arc<Material>,
+    /// Hojas, teñidas con ruido.
+    pub leaves: Arc<Material>,
+    /// Tronco de roble.
+    pub oak: Arc<Material>,
+    /// Tablones de madera.
+    pub wood_planks: Arc<Material>,
+    /// Piedra.
+    pub stone: Arc<Material>,
+    /// Antorcha, emisiva.
+    pub torch: Arc<Material>,
+    /// TNT, explota al encenderla.
+    pub tnt: Arc<Material>,
+    /// Arena, cae.
+    pub sand: Arc<Material>,
+    /// Grava, cae.
+    pub gravel: Arc<Material>,
+}
+
+impl BlockPalette {
+    /// Paleta con los materiales por defecto.
+    pub fn new() -> Self {
+        let zero_emission = Vector3::zero();
+
+        // Biome tint: lush green to olive across the map
+        let biome_tint = TintSource::Noise {
+            palette: vec![
+                Vector3::new(0.85, 1.1, 0.8),
+                Vector3::new(1.0, 1.0, 0.9),
+                Vector3::new(1.1, 0.95, 0.55),
+            ].into(),
+            scale: 0.08,
+        };
+
+        // Material 1: Glass (refraction + reflection)
+        let glass = Material {
+            diffuse: Vector3::new(0.9, 0.95, 1.0), albedo: [0.1, 5.0], specular: 125.0, reflectivity: 0.15,
+            transparency: 0.85, refractive_index: 1.5, roughness: 0.0, refraction_roughness: 0.0, abbe_number: 0.0, texture: Some("assets/glass.png".to_string()),
+            normal_map_id: None, emission: zero_emission, tint: None, falls: false, alpha_cutout: false, texture_fit: TextureFit::Stretch, name: Some("glass".into()),
+        };
+
+        // Material 1b: Frosted glass, the glass with a blurred refraction
+        let frosted_glass = Material {
+            diffuse: Vector3::new(0.92, 0.95, 0.97), refraction_roughness: 0.35, name: Some("frosted_glass".into()),
+            ..glass.clone()
+        };
+
+        // Material 2: Water (refraction + reflection)
+        let water = Material {
+            diffuse: Vector3::new(0.0, 0.4, 0.8), albedo: [0.5, 0.5], specular: 40.0, reflectivity: 0.2,
+            transparency: 0.7, refractive_index: 1.33, roughness: 0.0, refraction_roughness: 0.0, abbe_number: 0.0, texture: Some("assets/water.png".to_string()),
+            normal_map_id: None, emission: zero_emission, tint: None, falls: false, alpha_cutout: false, texture_fit: TextureFit::Stretch, name: Some("water".into()),
+        };
+
+        // Material 3: Diamond Ore (reflection)
+        let diamond_ore = Material {
+            diffuse: Vector3::new(0.4, 0.6, 0.7), albedo: [0.6, 0.4], specular: 80.0, reflectivity: 0.3,
+            transparency: 0.0, refractive_index: 2.4, roughness: 0.0, refraction_roughness: 0.0, abbe_number: 0.0, texture: Some("assets/diamond_ore.png".to_string()),
+            normal_map_id: None, emission: zero_emission, tint: None, falls: false, alpha_cutout: false, texture_fit: TextureFit::Stretch, name: Some("diamond_ore".into()),
+        };
+
+        // Material 4: Obsidian (reflection)
+        let obsidian = Material {
+            diffuse: Vector3::new(0.1, 0.05, 0.15), albedo: [0.7, 0.3], specular: 50.0, reflectivity: 0.25,
+            transparency: 0.0, refractive_index: 1.0, roughness: 0.0, refraction_roughness: 0.0, abbe_number: 0.0, texture: Some("assets/obsidian.png".to_string()),
+            normal_map_id: None, emission: zero_emission, tint: None, falls: false, alpha_cutout: false, texture_fit: TextureFit::Stretch, name: Some("obsidian".into()),
+        };
+
+        // Material 5: Magma (emissive)
+        let magma = Material {
+            diffuse: Vector3::new(1.0, 0.3, 0.0), albedo: [0.9, 0.1], specular: 50.0, reflectivity: 0.0,
+            transparency: 0.0, refractive_index: 1.0, roughness: 0.0, refraction_roughness: 0.0, abbe_number: 0.0, texture: Some("assets/magma.png".to_string()),
+            normal_map_id: None, emission: Vector3::new(1.5, 0.5, 0.1), tint: None, falls: false, alpha_cutout: false, texture_fit: TextureFit::Stretch, name: Some("magma".into()),
+        };
+
+        // Material 6: Dirt
+        let dirt = Material {
+            diffuse: Vector3::new(0.4, 0.26, 0.13), albedo: [0.9, 0.1], specular: 1.0, reflectivity: 0.0,
+            transparency: 0.0, refractive_index: 1.0, roughness: 0.0, refraction_roughness: 0.0, abbe_number: 0.0, texture: Some("assets/dirt.png".to_string()),
+            normal_map_id: None, emission: zero_emission, tint: None, falls: false, alpha_cutout: false, texture_fit: TextureFit::Stretch, name: Some("dirt".into()),
+        };
+
+        // Material 7: Grass
+        let grass = Material {
+            diffuse: Vector3::new(0.2, 0.6, 0.2), albedo: [0.8, 0.2], specular: 2.0, reflectivity: 0.0,
+            transparency: 0.0, refractive_index: 1.0, roughness: 0.0, refraction_roughness: 0.0, abbe_number: 0.0, texture: Some("assets/grass.png".to_string()),
+            normal_map_id: None, emission: zero_emission, tint: Some(biome_tint.clone()), falls: false, alpha_cutout: false, texture_fit: TextureFit::Stretch, name: Some("grass".into()),
+        };
+
+        // Material 8: Leaves
+        let leaves = Material {
+            diffuse: Vector3::new(0.1, 0.5, 0.1), albedo: [0.7, 0.3], specular: 3.0, reflectivity: 0.0,
+            transparency: 0.0, refractive_index: 1.2, roughness: 0.0, refraction_roughness: 0.0, abbe_number: 0.0, texture: Some("assets/leaves.png".to_string()),
+            normal_map_id: None, emission: zero_emission, tint: Some(biome_tint.clone()), falls: false, alpha_cutout: true, texture_fit: TextureFit::Stretch, name: Some("leaves".into()),
+        };
+
+        // Material 9: Oak
+        let oak = Material {
+            diffuse: Vector3::new(0.6, 0.4, 0.2), albedo: [0.85, 0.15], specular: 5.0, reflectivity: 0.0,
+            transparency: 0.0, refractive_index: 1.0, roughness: 0.0, refraction_roughness: 0.0, abbe_number: 0.0, texture: Some("assets/oak.png".to_string()),
+            normal_map_id: None, emission: zero_emission, tint: None, falls: false, alpha_cutout: false, texture_fit: TextureFit::Stretch, name: Some("oak".into()),
+        };
+
+        // Material 10: Wood Planks
+        let wood_planks = Material {
+            diffuse: Vector3::new(0.6, 0.4, 0.2), albedo: [0.85, 0.15], specular: 5.0, reflectivity: 0.0,
+            transparency: 0.0, refractive_index: 1.0, roughness: 0.0, refraction_roughness: 0.0, abbe_number: 0.0, texture: Some("assets/wood_planks.png".to_string()),
+            normal_map_id: None, emission: zero_emission, tint: None, falls: false, alpha_cutout: false, texture_fit: TextureFit::Stretch, name: Some("wood_planks".into()),
+        };
+
+        // Material 11: Stone
+        let stone = Material {
+            diffuse: Vector3::new(0.5, 0.5, 0.5), albedo: [0.8, 0.2], specular: 8.0, reflectivity: 0.0,
+            transparency: 0.0, refractive_index: 0.5, roughness: 0.0, refraction_roughness: 0.0, abbe_number: 0.0, texture: Some("assets/stone.png".to_string()),
+            normal_map_id: None, emission: zero_emission, tint: None, falls: false, alpha_cutout: false, texture_fit: TextureFit::Stretch, name: Some("stone".into()),
+        };
+
+        // Material 12: Torch (emissive - lights up scene)
+        let torch = Material {
+            diffuse: Vector3::new(1.0, 0.8, 0.3), albedo: [0.3, 0.1], specular: 10.0, reflectivity: 0.0,
+            transparency: 0.0, refractive_index: 1.0, roughness: 0.0, refraction_roughness: 0.0, abbe_number: 0.0, texture: None,
+            normal_map_id: None, emission: Vector3::new(2.0, 1.5, 0.5), tint: None, falls: false, alpha_cutout: false, texture_fit: TextureFit::Stretch, name: Some("torch".into()),
+        };
+
+        // Material 13: TNT (explota al encenderla, ver explosion.rs)
+        let tnt = Material {
+            diffuse: Vector3::new(0.85, 0.15, 0.1), albedo: [0.9, 0.1], specular: 4.0, reflectivity: 0.0,
+            transparency: 0.0, refractive_index: 1.0, roughness: 0.0, refraction_roughness: 0.0, abbe_number: 0.0, texture: None,
+            normal_map_id: None, emission: zero_emission, tint: None, falls: false, alpha_cutout: false, texture_fit: TextureFit::Stretch, name: Some(TNT_MATERIAL.into()),
+        };
+
+        // Material 14: Sand (cae)
+        let sand = Material {
+            diffuse: Vector3::new(0.86, 0.8, 0.55), albedo: [0.9, 0.1], specular: 2.0, reflectivity: 0.0,
+            transparency: 0.0, refractive_index: 1.0, roughness: 0.0, refraction_roughness: 0.0, abbe_number: 0.0, texture: None,
+            normal_map_id: None, emission: zero_emission, tint: None, falls: true, alpha_cutout: false, texture_fit: TextureFit::Stretch, name: Some("sand".into()),
+        };
+
+        // Material 15: Gravel (cae)
+        let gravel = Material {
+            diffuse: Vector3::new(0.52, 0.49, 0.47), albedo: [0.9, 0.1], specular: 3.0, reflectivity: 0.0,
+            transparency: 0.0, refractive_index: 1.0, roughness: 0.0, refraction_roughness: 0.0, abbe_number: 0.0, texture: None,
+            normal_map_id: None, emission: zero_emission, tint: None, falls: true, alpha_cutout: false, texture_fit: TextureFit::Stretch, name: Some("gravel".into()),
+        };
+
+        BlockPalette {
+            glass: Arc::new(glass), frosted_glass: Arc::new(frosted_glass), water: Arc::new(water), diamond_ore: Arc::new(diamond_ore),
+            obsidian: Arc::new(obsidian), magma: Arc::new(magma), dirt: Arc::new(dirt), grass: Arc::new(grass),
+            leaves: Arc::new(leaves), oak: Arc::new(oak), wood_planks: Arc::new(wood_planks), stone: Arc::new(stone),
+            torch: Arc::new(torch), tnt: Arc::new(tnt), sand: Arc::new(sand), gravel: Arc::new(gravel),
+        }
+    }
+}
+
+impl BlockPalette {
+    /// Todos los materiales, en el orden de los campos.
+    pub fn materials(&self) -> [&Arc<Material>; 16] {
+        [
+            &self.glass, &self.frosted_glass, &self.water, &self.diamond_ore, &self.obsidian, &self.magma, &self.dirt, &self.grass,
+            &self.leaves, &self.oak, &self.wood_planks, &self.stone, &self.torch, &self.tnt, &self.sand, &self.gravel,
+        ]
+    }
+}
+
+impl Default for BlockPalette {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Escena de demostración: casa, árboles, antorchas y el subsuelo que pida `settings`.
+pub fn demo_scene(settings: &WorldGenSettings) -> Scene {
+    let BlockPalette {
+        glass, frosted_glass, water, diamond_ore, obsidian, magma, dirt,
+        grass, leaves, oak, wood_planks, stone, torch, tnt, sand, gravel,
+    } = BlockPalette::new();
+
+    // Capas (teclas 1-5): 0 terreno, 1 construcciones, 2 tejado, 3 árboles, 4 antorchas
+    let mut objects = SceneBuilder::new();
+
+    // Optimized ground - smaller but still complex
+    objects.group("ground").layer(0);
+    for x in -MAP_HALF..=MAP_HALF {
+        for z in -MAP_HALF..=MAP_HALF {
+            let dist_sq = x*x + z*z;
+            let mat = if dist_sq < 16 { grass.clone() }
+                     else if dist_sq < 49 { dirt.clone() }
+                     else { stone.clone() };
+            objects.push(Cube::new(Vector3::new(x as f32, SURFACE_Y as f32, z as f32), 1.0, mat));
+        }
+    }
+
+    // House with glass windows
+    objects.group("house").layer(1);
+    for x in -5..=-2 {
+        for z in -7..=-4 {
+            for y in 0..=3 {
+                if y == 0 || x == -5 || x == -2 || z == -7 || z == -4 {
+                    let mat = if y == 0 { stone.clone() } else { wood_planks.clone() };
+                    objects.push(Cube::new(Vector3::new(x as f32, y as f32, z as f32), 1.0, mat));
+                }
+            }
+        }
+    }
+
+    // Glass windows, with a frosted bathroom one beside the first to compare
+    objects.push(Cube::new(Vector3::new(-3.0, 2.0, -7.0), 1.0, glass.clone()));
+    objects.push(Cube::new(Vector3::new(-4.0, 2.0, -7.0), 1.0, frosted_glass));
+    objects.push(Cube::new(Vector3::new(-4.0, 2.0, -4.0), 1.0, glass.clone()));
+
+    // Roof
+    objects.group("roof").layer(2);
+    for x in -6..=0 {
+        for z in -8..=-3 {
+            objects.push(Cube::new(Vector3::new(x as f32, 4.0, z as f32), 1.0, oak.clone()));
+        }
+    }
+
+    // Tower with diamond on top
+    objects.group("tower").layer(1);
+    for y in 0..=6 {
+        objects.push(Cube::new(Vector3::new(5.0, y as f32, -5.0), 1.0, stone.clone()));
+    }
+    objects.push(Cube::new(Vector3::new(5.0, 7.0, -5.0), 1.0, diamond_ore.clone()));
+
+    // Nether portal frame (obsidian)
+    objects.group("portal").layer(1);
+    for y in 0..=3 {
+        objects.push(Cube::new(Vector3::new(-8.0, y as f32, 2.0), 1.0, obsidian.clone()));
+        objects.push(Cube::new(Vector3::new(-8.0, y as f32, 4.0), 1.0, obsidian.clone()));
+    }
+    for z in 2..=4 {
+        objects.push(Cube::new(Vector3::new(-8.0, 0.0, z as f32), 1.0, obsidian.clone()));
+        objects.push(Cube::new(Vector3::new(-8.0, 3.0, z as f32), 1.0, obsidian.clone()));
+    }
+
+    // Magma inside portal (emissive)
+    for y in 1..=2 {
+        objects.push(Cube::new(Vector3::new(-8.0, y as f32, 3.0), 1.0, magma.clone()));
+    }
+
+    // Water pool with stone base
+    objects.group("pool").layer(1);
+    for x in 0..=2 {
+        for z in 0..=2 {
+            objects.push(Cube::new(Vector3::new(x as f32, 0.0, z as f32), 1.0, stone.clone()));
+        }
+    }
+    objects.push(Cube::new(Vector3::new(1.0, 1.0, 1.0), 1.0, water.clone()));
+    objects.push(Cube::new(Vector3::new(1.0, 2.0, 1.0), 1.0, water.clone()));
+
+    // Glass dome around water
+    for angle in 0..8 {
+        let rad = (angle as f32) * PI / 4.0;
+        let x = 1.0 + rad.cos() * 1.5;
+        let z = 1.0 + rad.sin() * 1.5;
+        objects.push(Cube::new(Vector3::new(x, 3.0, z), 0.5, glass.clone()));
+    }
+
+    // Trees (reduced from 8 to 4)
+    objects.group("trees").layer(3);
+    let tree_positions = vec![
+        (7.0, 6.0), (7.0, 2.0),
+        (-6.0, 6.0), (2.0, 7.0),
+    ];
+
+    for (tx, tz) in tree_positions {
+        // Trunk
+        for y in 0..=3 {
+            objects.push(Cube::new(Vector3::new(tx, y as f32, tz), 1.0, oak.clone()));
+        }
+        // Leaves
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                objects.push(Cube::new(
+                    Vector3::new(tx + dx as f32, 4.0, tz + dz as f32),
+                    1.0,
+                    leaves.clone()
+                ));
+            }
+        }
+    }
+
+    // Torches for lighting (emissive objects that cast light)
+    objects.group("torches").layer(4);
+    let torch_positions = vec![
+        (-3.0, 1.0, -3.0), (-3.0, 1.0, -8.0),
+        (5.0, 1.0, -3.0), (5.0, 5.0, -5.0),
+        (-7.0, 1.0, 1.0), (-7.0, 1.0, 5.0),
+    ];
+
+    for (tx, ty, tz) in torch_positions {
+        objects.push(Cube::new(Vector3::new(tx, ty, tz), 0.3, torch.clone()));
+    }
+
+    // Diamond ore showcase
+    objects.group("showcase").layer(1);
+    objects.push(Cube::new(Vector3::new(-1.0, 0.0, 7.0), 1.0, diamond_ore.clone()));
+
+    // Magma showcase (emissive)
+    objects.push(Cube::new(Vector3::new(-1.0, 0.0, -2.0), 1.0, magma.clone()));
+
+    // TNT junto a la piscina: la segunda estalla en cadena
+    objects.group("tnt").layer(1);
+    objects.push(Cube::new(Vector3::new(4.0, 0.0, 3.0), 1.0, tnt.clone()));
+    objects.push(Cube::new(Vector3::new(4.0, 1.0, 3.0), 1.0, tnt));
+
+    // Columna de arena y grava sobre un tablón: al quitar el tablón se desploma
+    objects.group("sand").layer(1);
+    objects.push(Cube::new(Vector3::new(-6.0, 0.0, -1.0), 1.0, wood_planks.clone()));
+    for y in 1..=3 {
+        objects.push(Cube::new(Vector3::new(-6.0, y as f32, -1.0), 1.0, sand.clone()));
+    }
+    objects.push(Cube::new(Vector3::new(-6.0, 4.0, -1.0), 1.0, gravel));
+
+    let mut bookmarks = vec![CameraBookmark {
+        name: "Overview".to_string(),
+        eye: Vector3::new(0.0, 10.0, 13.0),
+        center: Vector3::new(0.0, 2.0, 0.0),
+    }];
+
+    // Cuevas y vetas bajo el suelo; van al final para no desplazar las antorchas
+    // de la lista de emisivos
+    if let Some(underground) = settings.underground.as_ref().filter(|u| u.depth > 0) {
+        let mut grid = BlockGrid::filled(underground.depth);
+        carve_caves(&mut grid, underground);
+        place_ores(&mut grid, underground);
+        bookmarks.extend(cave_bookmark(&grid));
+
+        objects.group("underground").layer(0);
+        for (x, y, z) in grid.cells() {
+            let mat = match grid.get(x, y, z) {
+                Block::Air => continue,
+                Block::Stone => &stone,
+                Block::Diamond => &diamond_ore,
+                Block::Magma => &magma,
+            };
+            objects.push(Cube::new(Vector3::new(x as f32, y as f32, z as f32), 1.0, mat.clone()));
+        }
+    }
+
+    let mut scene = Scene::new(cull_hidden_blocks(objects.finish()));
+    scene.clouds = settings.clouds.as_ref().map(CloudLayer::generate);
+    scene.bookmarks = bookmarks;
+    scene
+}
+
+/// Tamaños de la escena de benchmark. Cada uno da siempre los mismos cubos y
+/// las mismas cámaras, para que los tiempos se puedan comparar entre cambios.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BenchTier {
+    /// ~500 cubos.
+    Small,
+    /// ~10k.
+    Medium,
+    /// ~100k.
+    Large,
+    /// Dos espejos enfrentados: cadenas largas de reflejos, para `max_depth` altos.
+    Mirrors,
+}
+
+impl BenchTier {
+    /// Tamaño por nombre (`small`, `medium`, `large`, `mirrors`), sin distinguir mayúsculas.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "small" => Some(BenchTier::Small),
+            "medium" => Some(BenchTier::Medium),
+            "large" => Some(BenchTier::Large),
+            "mirrors" => Some(BenchTier::Mirrors),
+            _ => None,
+        }
+    }
+
+    /// Nombre que acepta `parse`.
+    pub fn name(self) -> &'static str {
+        match self {
+            BenchTier::Small => "small",
+            BenchTier::Medium => "medium",
+            BenchTier::Large => "large",
+            BenchTier::Mirrors => "mirrors",
+        }
+    }
+
+    // (2h + 1)² columnas de BENCH_DEPTH bloques cada una; la de espejos no es
+    // un terreno
+    fn half_extent(self) -> Option<i32> {
+        match self {
+            BenchTier::Small => Some(5),
+            BenchTier::Medium => Some(25),
+            BenchTier::Large => Some(79),
+            BenchTier::Mirrors => None,
+        }
+    }
+}
+
+const BENCH_DEPTH: i32 = 4;
+const BENCH_SEED: u32 = 9001;
+
+/// Material de la celda: la superficie reparte todos los materiales de la
+/// paleta (emisivos y transparentes incluidos), debajo hay tierra y piedra con vetas.
+fn bench_material(palette: &BlockPalette, (x, y, z): (i32, i32, i32), top: i32) -> &Material {
+    let roll = hash3(x, y, z, BENCH_SEED);
+    if y < top - 1 {
+        return match roll {
+            r if r < 0.03 => &palette.diamond_ore,
+            r if r < 0.05 => &palette.obsidian,
+            _ => &palette.stone,
+        };
+    }
+    if y < top {
+        return &palette.dirt;
+    }
+    match roll {
+        r if r < 0.004 => &palette.torch,
+        r if r < 0.008 => &palette.magma,
+        r if r < 0.05 => &palette.glass,
+        r if r < 0.09 => &palette.water,
+        r if r < 0.11 => &palette.leaves,
+        r if r < 0.13 => &palette.oak,
+        r if r < 0.15 => &palette.wood_planks,
+        r if r < 0.17 => &palette.sand,
+        r if r < 0.18 => &palette.gravel,
+        r if r < 0.19 => &palette.tnt,
+        _ => &palette.grass,
+    }
+}
+
+/// Escena determinista para medir rendimiento: un terreno ondulado sin culling
+/// (el número de cubos no depende del relieve) y cámaras fijas en `bookmarks`;
+/// `BenchTier::Mirrors` da `mirror_scene`.
+pub fn benchmark_scene(tier: BenchTier) -> Scene {
+    let Some(half) = tier.half_extent() else {
+        return mirror_scene();
+    };
+    let palette = BlockPalette::new();
+    let side = (2 * half + 1) as usize;
+    let mut objects = Vec::with_capacity(side * side * BENCH_DEPTH as usize);
+    for x in -half..=half {
+        for z in -half..=half {
+            let top = (value_noise_2d(x as f32 * 0.15, z as f32 * 0.15) * 4.0) as i32;
+            for y in top - BENCH_DEPTH + 1..=top {
+                let material = bench_material(&palette, (x, y, z), top);
+                objects.push(Cube::new(Vector3::new(x as f32, y as f32, z as f32), 1.0, material.clone()));
+            }
+        }
+    }
+
+    let reach = half as f32 * 1.6 + 6.0;
+    let view = |name: &str, eye: Vector3, center: Vector3| CameraBookmark { name: name.to_string(), eye, center };
+    let mut scene = Scene::new(objects);
+    scene.bookmarks = vec![
+        view("Overview", Vector3::new(reach * 0.7, reach * 0.6, reach * 0.7), Vector3::zero()),
+        view("Top down", Vector3::new(0.0, reach * 1.2, 1.0), Vector3::zero()),
+        // Casi rasante: rayos largos que cruzan mucho terreno
+        view("Horizon", Vector3::new(0.0, 6.0, half as f32), Vector3::new(0.0, 2.0, -(half as f32))),
+        view("Close up", Vector3::new(4.0, 7.0, 4.0), Vector3::new(0.0, 2.0, 0.0)),
+    ];
+    scene
+}
+
+// Medio largo del pasillo de espejos y distancia de cada pared al centro
+const MIRROR_HALF_LENGTH: i32 = 10;
+const MIRROR_GAP: i32 = 5;
+
+/// Dos paredes de espejo enfrentadas a lo largo de un pasillo con suelo de
+/// hierba y unos bloques de colores en medio. Un rayo entre las paredes rebota
+/// de una a otra hasta `max_depth`, así que sirve para medir y comprobar las
+/// cadenas de reflejos. Determinista, con cámaras fijas en `bookmarks`.
+pub fn mirror_scene() -> Scene {
+    let palette = BlockPalette::new();
+    let mirror = Arc::new(
+        Material::new(
+            Vector3::new(0.9, 0.92, 0.95), [0.1, 0.4], 200.0, 0.85, 0.0, 1.0, None, None, Vector3::zero(),
+        )
+        .with_name("mirror"),
+    );
+
+    let mut objects = Vec::new();
+    for x in -MIRROR_HALF_LENGTH..=MIRROR_HALF_LENGTH {
+        for z in -MIRROR_GAP..=MIRROR_GAP {
+            objects.push(Cube::new(Vector3::new(x as f32, 0.0, z as f32), 1.0, palette.grass.clone()));
+        }
+        for y in 1..=4 {
+            for z in [-MIRROR_GAP, MIRROR_GAP] {
+                objects.push(Cube::new(Vector3::new(x as f32, y as f32, z as f32), 1.0, mirror.clone()));
+            }
+        }
+    }
+    // Columnas de colores entre los espejos, con una antorcha encima de cada una
+    let columns = [&palette.tnt, &palette.wood_planks, &palette.diamond_ore, &palette.sand, &palette.obsidian];
+    for (i, material) in columns.into_iter().enumerate() {
+        let x = i as f32 * 4.0 - 8.0;
+        let z = if i % 2 == 0 { -1.0 } else { 1.0 };
+        objects.push(Cube::new(Vector3::new(x, 1.0, z), 1.0, material.clone()));
+        objects.push(Cube::new(Vector3::new(x, 2.0, z), 1.0, material.clone()));
+        objects.push(Cube::new(Vector3::new(x, 3.0, z), 1.0, palette.torch.clone()));
+    }
+
+    let view = |name: &str, eye: Vector3, center: Vector3| CameraBookmark { name: name.to_string(), eye, center };
+    let mut scene = Scene::new(objects);
+    scene.bookmarks = vec![
+        // De frente a un espejo: casi cada píxel es una cadena de reflejos
+        view("Facing", Vector3::new(0.0, 2.5, 3.5), Vector3::new(0.0, 2.0, -5.0)),
+        view("Corridor", Vector3::new(-9.0, 3.0, 0.0), Vector3::new(10.0, 2.0, 0.0)),
+        view("Oblique", Vector3::new(6.0, 3.5, 3.0), Vector3::new(-4.0, 2.0, -5.0)),
+    ];
+    scene
+}